@@ -1,9 +1,30 @@
 #[cfg(windows)]
 extern crate winres;
 
+use std::{
+  process::Command,
+  time::{SystemTime, UNIX_EPOCH},
+};
+
 fn main() {
   if cfg!(windows) {
     let res = winres::WindowsResource::new();
     res.compile().unwrap();
   }
+
+  // 编译时间及 Git 提交，由 `GET /info` 返回
+  let timestamp = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_secs())
+    .unwrap_or_default();
+  println!("cargo:rustc-env=BUILD_TIMESTAMP={}", timestamp);
+
+  let commit = Command::new("git")
+    .args(["rev-parse", "--short=12", "HEAD"])
+    .output()
+    .ok()
+    .filter(|output| output.status.success())
+    .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    .unwrap_or_default();
+  println!("cargo:rustc-env=BUILD_COMMIT={}", commit);
 }
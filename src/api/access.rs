@@ -0,0 +1,263 @@
+use std::{net::IpAddr, str::FromStr};
+
+use sha2::{Digest, Sha256};
+
+use super::token::constant_time_eq;
+
+/// 打印机访问策略，为空时允许全部
+#[derive(Debug, Clone, Default)]
+pub struct AccessPolicy {
+  rules: Vec<AccessRule>,
+  key_rules: Vec<KeyAccessRule>,
+}
+
+impl AccessPolicy {
+  pub fn new(rules: Vec<AccessRule>, key_rules: Vec<KeyAccessRule>) -> Self {
+    Self { rules, key_rules }
+  }
+
+  /// 客户端是否可以使用指定的打印机。
+  /// 配置了按密钥的规则时，客户端的 API 密钥必须匹配其中一条规则，且打印机名称匹配该规则的模式，
+  /// 此时不再使用按网段的规则。否则按网段的规则不为空时，客户端必须匹配至少一条规则，
+  /// 且打印机名称匹配其中一条规则的模式
+  pub fn allows(&self, client: &Client, printer: &str) -> bool {
+    if !self.key_rules.is_empty() {
+      let Some(key) = &client.api_key_sha256 else {
+        return false;
+      };
+
+      return self
+        .key_rules
+        .iter()
+        .filter(|rule| constant_time_eq(&rule.key_sha256, key))
+        .any(|rule| matches_any(&rule.printers, printer));
+    }
+
+    if self.rules.is_empty() {
+      return true;
+    }
+
+    let Some(ip) = client.ip else {
+      return false;
+    };
+
+    self
+      .rules
+      .iter()
+      .filter(|rule| rule.network.contains(ip))
+      .any(|rule| matches_any(&rule.printers, printer))
+  }
+}
+
+/// 请求打印机的客户端
+#[derive(Debug, Clone, Default)]
+pub struct Client {
+  pub ip: Option<IpAddr>,
+  /// 请求头 `X-API-Key` 的 SHA-256，保存定时任务时不保存密钥本身
+  pub api_key_sha256: Option<String>,
+}
+
+impl Client {
+  pub fn new(ip: Option<IpAddr>, api_key: Option<&str>) -> Self {
+    Self {
+      ip,
+      api_key_sha256: api_key.map(key_hash),
+    }
+  }
+}
+
+fn key_hash(key: &str) -> String {
+  let hash = Sha256::digest(key);
+  hash.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// 访问规则，格式为 `网段=打印机名称模式,...`，如 `192.168.1.0/24=Label*,Zebra?`
+#[derive(Debug, Clone)]
+pub struct AccessRule {
+  network: IpNetwork,
+  printers: Vec<String>,
+}
+
+impl FromStr for AccessRule {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (network, printers) = s
+      .split_once('=')
+      .ok_or_else(|| format!("Expected <CIDR>=<printer patterns>, got {}", s))?;
+
+    Ok(Self {
+      network: network.trim().parse()?,
+      printers: parse_patterns(printers, s)?,
+    })
+  }
+}
+
+/// 按 API 密钥的访问规则，格式为 `密钥=打印机名称模式,...`，如 `s3cr3t=Label*,Zebra?`。
+/// 密钥本身可以包含 `=`，以最后一个 `=` 分隔
+#[derive(Debug, Clone)]
+pub struct KeyAccessRule {
+  key_sha256: String,
+  printers: Vec<String>,
+}
+
+impl FromStr for KeyAccessRule {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (key, printers) = s
+      .rsplit_once('=')
+      .filter(|(key, _)| !key.is_empty())
+      .ok_or_else(|| "Expected <API key>=<printer patterns>".to_string())?;
+
+    Ok(Self {
+      key_sha256: key_hash(key),
+      printers: parse_patterns(printers, "the API key rule")?,
+    })
+  }
+}
+
+/// 解析逗号分隔的打印机名称模式
+fn parse_patterns(printers: &str, rule: &str) -> Result<Vec<String>, String> {
+  let printers = printers
+    .split(',')
+    .map(str::trim)
+    .filter(|p| !p.is_empty())
+    .map(str::to_string)
+    .collect::<Vec<_>>();
+
+  if printers.is_empty() {
+    return Err(format!("No printer patterns in {}", rule));
+  }
+
+  Ok(printers)
+}
+
+fn matches_any(patterns: &[String], printer: &str) -> bool {
+  patterns.iter().any(|p| wildcard_match(p, printer))
+}
+
+/// 网段，如 `10.0.0.0/8` 或单个地址
+#[derive(Debug, Clone, Copy)]
+struct IpNetwork {
+  addr: IpAddr,
+  prefix: u8,
+}
+
+impl IpNetwork {
+  fn contains(&self, addr: IpAddr) -> bool {
+    // IPv4 客户端可能以 IPv4 映射的 IPv6 地址出现
+    match (self.addr, addr.to_canonical()) {
+      (IpAddr::V4(net), IpAddr::V4(addr)) => {
+        let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+        u32::from(net) & mask == u32::from(addr) & mask
+      }
+      (IpAddr::V6(net), IpAddr::V6(addr)) => {
+        let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+        u128::from(net) & mask == u128::from(addr) & mask
+      }
+      _ => false,
+    }
+  }
+}
+
+impl FromStr for IpNetwork {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (addr, prefix) = match s.split_once('/') {
+      Some((addr, prefix)) => (addr, Some(prefix)),
+      None => (s, None),
+    };
+    let addr = addr
+      .parse::<IpAddr>()
+      .map_err(|_| format!("Invalid IP address: {}", addr))?
+      .to_canonical();
+    let max = if addr.is_ipv4() { 32 } else { 128 };
+    let prefix = match prefix {
+      Some(prefix) => prefix
+        .parse::<u8>()
+        .ok()
+        .filter(|p| *p <= max)
+        .ok_or_else(|| format!("Invalid network prefix: {}", prefix))?,
+      None => max,
+    };
+
+    Ok(Self { addr, prefix })
+  }
+}
+
+/// 通配符匹配，`*` 匹配任意个字符，`?` 匹配一个字符，不区分大小写
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+  let pattern = pattern.to_lowercase().chars().collect::<Vec<_>>();
+  let text = text.to_lowercase().chars().collect::<Vec<_>>();
+  let (mut p, mut t) = (0, 0);
+  let mut star = None;
+
+  while t < text.len() {
+    if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+      p += 1;
+      t += 1;
+    } else if p < pattern.len() && pattern[p] == '*' {
+      star = Some((p, t));
+      p += 1;
+    } else if let Some((sp, st)) = star {
+      // 回溯，让上一个 `*` 多匹配一个字符
+      p = sp + 1;
+      t = st + 1;
+      star = Some((sp, st + 1));
+    } else {
+      return false;
+    }
+  }
+
+  pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn policy(rules: &[&str], key_rules: &[&str]) -> AccessPolicy {
+    AccessPolicy::new(
+      rules.iter().map(|r| r.parse().unwrap()).collect(),
+      key_rules.iter().map(|r| r.parse().unwrap()).collect(),
+    )
+  }
+
+  fn client(ip: &str, api_key: Option<&str>) -> Client {
+    Client::new(ip.parse().ok(), api_key)
+  }
+
+  #[test]
+  fn network_rules_apply_without_key_rules() {
+    let policy = policy(&["192.168.1.0/24=Label*"], &[]);
+    assert!(policy.allows(&client("192.168.1.7", None), "Label 1"));
+    assert!(!policy.allows(&client("192.168.1.7", None), "Office"));
+    assert!(!policy.allows(&client("10.0.0.1", None), "Label 1"));
+    assert!(!policy.allows(&Client::default(), "Label 1"));
+  }
+
+  #[test]
+  fn key_rules_replace_network_rules() {
+    let policy = policy(&["192.168.1.0/24=*"], &["k1=Label*", "k2=Office,Zebra?"]);
+    assert!(policy.allows(&client("10.0.0.1", Some("k1")), "Label 1"));
+    assert!(!policy.allows(&client("10.0.0.1", Some("k1")), "Office"));
+    assert!(policy.allows(&client("10.0.0.1", Some("k2")), "Zebra1"));
+    assert!(!policy.allows(&client("192.168.1.7", None), "Label 1"));
+    assert!(!policy.allows(&client("192.168.1.7", Some("k3")), "Label 1"));
+  }
+
+  #[test]
+  fn key_may_contain_equals_sign() {
+    let policy = policy(&[], &["a2V5===Label*"]);
+    assert!(policy.allows(&client("10.0.0.1", Some("a2V5==")), "Label 1"));
+  }
+
+  #[test]
+  fn rejects_invalid_key_rules() {
+    assert!("=Label*".parse::<KeyAccessRule>().is_err());
+    assert!("key=".parse::<KeyAccessRule>().is_err());
+    assert!("key".parse::<KeyAccessRule>().is_err());
+  }
+}
@@ -0,0 +1,790 @@
+use std::{
+  collections::HashMap,
+  sync::{Arc, LazyLock, Mutex},
+  time::{Duration, Instant},
+};
+
+use anyhow::bail;
+use chrono::Utc;
+use log::{debug, info, trace, warn};
+use poem_openapi::types::ToJSON;
+use winprint::{
+  printer::PrinterDevice,
+  ticket::{
+    document::{
+      reader::ParsableXmlDocument, OwnedName, ParameterDef, ParameterInit,
+      PrintCapabilitiesDocument, PropertyValue, WithProperties, WithScoredProperties, NS_PSF,
+      NS_PSK,
+    },
+    FeatureOptionPack, FeatureOptionPackWithPredefined, FetchPrintCapabilitiesError,
+    PageImageableSize, PageMediaSize, PrintCapabilities, PrintTicketBuilder,
+  },
+};
+
+use super::{
+  drift,
+  fixture::{self, FixtureInfo},
+  locale::Languages,
+  options::ApiOptions,
+  printers::{display_name, select_named_printer},
+  settings::{check_settings_with, default_settings},
+  v1::{
+    CustomPageSize, Dimensions, DriverFeature, DriverFeatureOption, DriverValueType, ImageableArea,
+    LengthUnit, Orientation, PageSize, PageSizeSort, PrinterCapability, PrinterWarmup,
+    WarmupResult,
+  },
+};
+
+use crate::pdf;
+
+/// 打印机能力缓存的有效时间
+pub const CAPABILITY_TTL: Duration = Duration::from_secs(60);
+
+/// 由驱动返回的能力生成打印机能力，不含详细信息
+pub fn printer_capability(
+  cap: &PrintCapabilities,
+  imageable_area_of: Option<&PrinterDevice>,
+  sort: PageSizeSort,
+  languages: &Languages,
+) -> PrinterCapability {
+  PrinterCapability {
+    max_copies: cap.max_copies().map(|cp| cp.0),
+    orientations: get_orientations(cap),
+    page_sizes: get_page_sizes(cap, imageable_area_of, sort, languages),
+    custom_page_size: get_custom_page_size(cap).map(|(_, range)| range),
+    driver_features: Some(get_driver_features(cap)).filter(|f| !f.is_empty()),
+    details: None,
+    state: None,
+  }
+}
+
+/// 获取打印机能力，不含可打印区域，纸张按默认方式排序
+pub fn fetch_capability(
+  printer: &PrinterDevice,
+  languages: &Languages,
+  record: Option<&std::path::Path>,
+  letter_a4_interchange: bool,
+) -> anyhow::Result<PrinterCapability> {
+  let cap = cached_capabilities(printer, record, letter_a4_interchange)?;
+  Ok(printer_capability(
+    &cap,
+    None,
+    PageSizeSort::default(),
+    languages,
+  ))
+}
+
+/// 从驱动获取打印机能力，短时间内重复获取时使用缓存，见 [`cached_or_fetch`]。
+/// `letter_a4_interchange` 用于检查能力变化后的默认打印设置，见 `ApiOptions`
+pub fn cached_capabilities(
+  printer: &PrinterDevice,
+  record: Option<&std::path::Path>,
+  letter_a4_interchange: bool,
+) -> std::result::Result<Arc<PrintCapabilities>, FetchPrintCapabilitiesError> {
+  let key = printer.os_name().to_string_lossy();
+  let (cap, fetched) = cached_or_fetch(&key, || fetch_print_capabilities(printer, record))?;
+
+  if fetched {
+    trace!("Printer {}: {:#?}", printer.name(), cap);
+    detect_capability_changes(printer, &cap, letter_a4_interchange);
+  }
+  Ok(cap)
+}
+
+/// 使用缓存的打印机能力，没有或已过期时调用 `fetch` 查询。同时获取同一台打印机时只查询一次，
+/// 其余的等待并使用该次的结果。查询失败时不缓存，下次重新查询。返回的 bool 表示是否为本次查询的结果
+fn cached_or_fetch<E>(
+  key: &str,
+  fetch: impl FnOnce() -> std::result::Result<PrintCapabilities, E>,
+) -> std::result::Result<(Arc<PrintCapabilities>, bool), E> {
+  let entry = CAPABILITY_CACHE
+    .lock()
+    .unwrap_or_else(|e| e.into_inner())
+    .entry(key.to_string())
+    .or_default()
+    .clone();
+  let mut cached = entry.lock().unwrap_or_else(|e| e.into_inner());
+
+  if let Some((time, cap)) = &*cached {
+    if time.elapsed() < CAPABILITY_TTL {
+      return Ok((cap.clone(), false));
+    }
+  }
+
+  let cap = Arc::new(fetch()?);
+  *cached = Some((Instant::now(), cap.clone()));
+  Ok((cap, true))
+}
+
+/// 删除缓存的打印机能力，下次获取时重新查询驱动
+pub fn invalidate_capabilities(printer: &PrinterDevice) {
+  let key = printer.os_name().to_string_lossy();
+  CAPABILITY_CACHE
+    .lock()
+    .unwrap_or_else(|e| e.into_inner())
+    .remove(key.as_ref());
+}
+
+/// 与保存的快照比较打印机能力，驱动更新等导致变化时记录变化及受影响的默认打印设置
+pub fn detect_capability_changes(
+  printer: &PrinterDevice,
+  cap: &PrintCapabilities,
+  letter_a4_interchange: bool,
+) {
+  let name = display_name(printer);
+  let capability = printer_capability(cap, None, PageSizeSort::default(), &Languages::default());
+  let mut changes = match drift::compare(&name, capability.to_json().unwrap_or_default()) {
+    Ok(Some(changes)) => changes,
+    Ok(None) => return,
+    Err(e) => {
+      warn!(
+        "Failed to save the capability snapshot of printer {}: {:#}",
+        name, e
+      );
+      return;
+    }
+  };
+
+  if let Some(settings) = default_settings().filter(|s| s.printer == name) {
+    changes.affected_settings = check_settings_with(&settings, cap, letter_a4_interchange)
+      .into_iter()
+      .map(|issue| format!("Default settings: {}", issue))
+      .collect();
+  }
+
+  warn!(
+    "Capabilities of printer {} changed: {}",
+    name,
+    changes.summary()
+  );
+  for issue in &changes.affected_settings {
+    warn!("Printer {}: {}", name, issue);
+  }
+  if let Err(e) = drift::record(&changes) {
+    warn!(
+      "Failed to save the capability changes of printer {}: {:#}",
+      name, e
+    );
+  }
+}
+
+/// 从驱动获取打印机能力，`record` 不为空时将驱动返回的 XML 保存到该目录。保存失败不影响获取
+pub fn fetch_print_capabilities(
+  printer: &PrinterDevice,
+  record: Option<&std::path::Path>,
+) -> std::result::Result<PrintCapabilities, FetchPrintCapabilitiesError> {
+  let xml = PrintCapabilities::fetch_xml(printer)?;
+  let document = PrintCapabilitiesDocument::parse_from_bytes(xml.as_slice())
+    .map_err(FetchPrintCapabilitiesError::ParseError)?;
+  let cap = PrintCapabilities { document };
+
+  if let Some(dir) = record {
+    let capability = printer_capability(&cap, None, PageSizeSort::default(), &Languages::default());
+    let info = FixtureInfo {
+      printer: printer.name().to_string(),
+      recorded_at: Utc::now(),
+      capability: capability.to_json().unwrap_or_default(),
+    };
+    match fixture::record(dir, &xml, &info) {
+      Ok(()) => debug!("Recorded capabilities of printer {}", printer.name()),
+      Err(e) => warn!(
+        "Failed to record capabilities of printer {}: {:#}",
+        printer.name(),
+        e
+      ),
+    }
+  }
+
+  Ok(cap)
+}
+
+pub fn get_orientations(cap: &PrintCapabilities) -> Option<Vec<Orientation>> {
+  let mut oriens = Vec::new();
+
+  for ori in cap.page_orientations() {
+    if let Some(ori) = ori.as_predefined_name() {
+      oriens.push(ori.into());
+    }
+  }
+
+  if oriens.is_empty() {
+    None
+  } else {
+    Some(oriens)
+  }
+}
+
+/// 为纸张大小加上以 `units` 表示的尺寸
+pub fn with_units(
+  sizes: Option<Vec<PageSize>>,
+  units: Option<LengthUnit>,
+) -> Option<Vec<PageSize>> {
+  let Some(units) = units else {
+    return sizes;
+  };
+
+  sizes.map(|sizes| {
+    sizes
+      .into_iter()
+      .map(|size| PageSize {
+        dimensions: Some(Dimensions {
+          width: units.micron_to_units(size.width),
+          height: units.micron_to_units(size.height),
+          units,
+        }),
+        ..size
+      })
+      .collect()
+  })
+}
+
+/// 标准纸张的名称及尺寸（微米），按纵向排列
+pub const STANDARD_PAGE_SIZES: &[(&str, u32, u32)] = &[
+  ("A3", 297_000, 420_000),
+  ("A4", 210_000, 297_000),
+  ("A5", 148_000, 210_000),
+  ("A6", 105_000, 148_000),
+  ("A7", 74_000, 105_000),
+  ("B4", 250_000, 353_000),
+  ("B5", 176_000, 250_000),
+  ("B6", 125_000, 176_000),
+  ("JIS B4", 257_000, 364_000),
+  ("JIS B5", 182_000, 257_000),
+  ("Letter", 215_900, 279_400),
+  ("Legal", 215_900, 355_600),
+  ("Folio", 210_000, 330_000),
+  // 北美的 Folio 与 Legal 同宽
+  ("Folio", 215_900, 330_200),
+  ("Tabloid", 279_400, 431_800),
+  ("Executive", 184_150, 266_700),
+  ("Statement", 139_700, 215_900),
+  ("4x6in", 101_600, 152_400),
+  ("4x4in", 101_600, 101_600),
+  ("2x1in", 25_400, 50_800),
+  ("100x150mm", 100_000, 150_000),
+  ("100x100mm", 100_000, 100_000),
+  ("60x40mm", 40_000, 60_000),
+  ("50x30mm", 30_000, 50_000),
+  ("40x30mm", 30_000, 40_000),
+];
+
+/// 可以互相代替的标准纸张，见打印设置的 `allow_letter_a4_interchange`
+pub const INTERCHANGEABLE_PAGE_SIZES: &[(&str, &str)] = &[("Letter", "A4"), ("Legal", "Folio")];
+
+/// 可以代替标准纸张 `name` 的纸张
+pub fn interchangeable_page_size(name: &str) -> Option<&'static str> {
+  INTERCHANGEABLE_PAGE_SIZES.iter().find_map(|&(a, b)| {
+    if a == name {
+      Some(b)
+    } else if b == name {
+      Some(a)
+    } else {
+      None
+    }
+  })
+}
+
+/// 按尺寸识别标准纸张，横向的纸张也能识别
+pub fn standard_page_size(width: u32, height: u32) -> Option<&'static str> {
+  let (short, long) = (width.min(height), width.max(height));
+
+  STANDARD_PAGE_SIZES
+    .iter()
+    .find(|(_, w, h)| {
+      short.abs_diff(*w) <= PAGE_SIZE_TOLERANCE && long.abs_diff(*h) <= PAGE_SIZE_TOLERANCE
+    })
+    .map(|(name, _, _)| *name)
+}
+
+pub fn get_page_sizes(
+  cap: &PrintCapabilities,
+  imageable_area_of: Option<&PrinterDevice>,
+  sort: PageSizeSort,
+  languages: &Languages,
+) -> Option<Vec<PageSize>> {
+  let mut sizes: Vec<_> = cap
+    .page_media_sizes()
+    .filter(|pms| !is_custom_media(pms))
+    .map(|pms| {
+      let s = pms.size();
+      let (width, height) = (s.width_in_micron(), s.height_in_micron());
+      // 安装 Gprinter GP-1134T 打印驱动发现纸张大小有“&#xEB;米”字样，不知道怎么来的
+      let display_name = pms.display_name().map(|s| s.replace("&#xEB;米", "毫米"));
+      PageSize {
+        name: languages.name(keyword(&pms), display_name.as_deref()),
+        width,
+        height,
+        dimensions: None,
+        standard: standard_page_size(width, height).map(str::to_string),
+        imageable_area: imageable_area_of.and_then(|printer| get_imageable_area(printer, pms)),
+        auto_height: None,
+      }
+    })
+    .collect();
+
+  if sort == PageSizeSort::Area {
+    // 驱动返回的顺序及重复项没有意义
+    sizes.sort_by(|a, b| {
+      let area = |s: &PageSize| s.width as u64 * s.height as u64;
+      area(a).cmp(&area(b)).then_with(|| a.name.cmp(&b.name))
+    });
+    sizes.dedup_by(|a, b| a.width == b.width && a.height == b.height && a.name == b.name);
+  }
+
+  if sizes.is_empty() {
+    None
+  } else {
+    Some(sizes)
+  }
+}
+
+pub fn get_imageable_area(printer: &PrinterDevice, pms: PageMediaSize) -> Option<ImageableArea> {
+  match PageImageableSize::try_fetch(printer, pms) {
+    Ok(size) => Some(ImageableArea {
+      origin_x: size.origin.width_in_micron(),
+      origin_y: size.origin.height_in_micron(),
+      width: size.extent.width_in_micron(),
+      height: size.extent.height_in_micron(),
+    }),
+    Err(e) => {
+      trace!("No imageable area: {:#?}", e);
+      None
+    }
+  }
+}
+
+/// 选项的 PrintSchema 关键字，驱动自定义的选项没有关键字
+pub fn keyword(option: &impl FeatureOptionPack) -> Option<&str> {
+  option
+    .option()
+    .name
+    .as_ref()
+    .filter(|name| name.namespace.as_deref() == Some(NS_PSK))
+    .map(|name| name.local_name.as_str())
+}
+
+/// 自定义纸张的宽高由参数决定，而不是固定值
+pub fn is_custom_media(pms: &PageMediaSize) -> bool {
+  media_size_parameter(pms, "MediaSizeWidth").is_some()
+    || media_size_parameter(pms, "MediaSizeHeight").is_some()
+}
+
+pub fn media_size_parameter(pms: &PageMediaSize, name: &str) -> Option<OwnedName> {
+  pms
+    .option()
+    .get_scored_property(name, Some(NS_PSK))
+    .and_then(|x| x.parameter_ref.clone())
+}
+
+pub fn get_parameter_range(cap: &PrintCapabilities, name: &OwnedName) -> Option<(u32, u32)> {
+  let def = cap
+    .document
+    .parameter_defs
+    .iter()
+    .find(|x| x.name == *name)?;
+  let value = |key| {
+    def
+      .get_property(key, Some(NS_PSF))
+      .and_then(|x| x.value.as_ref())
+      .and_then(|x| x.integer())
+      .and_then(|x| u32::try_from(x).ok())
+  };
+
+  Some((value("MinValue")?, value("MaxValue")?))
+}
+
+pub fn get_custom_page_size(cap: &PrintCapabilities) -> Option<(PageMediaSize, CustomPageSize)> {
+  cap.page_media_sizes().find_map(|pms| {
+    let width = media_size_parameter(&pms, "MediaSizeWidth")?;
+    let height = media_size_parameter(&pms, "MediaSizeHeight")?;
+    let (min_width, max_width) = get_parameter_range(cap, &width)?;
+    let (min_height, max_height) = get_parameter_range(cap, &height)?;
+
+    Some((
+      pms,
+      CustomPageSize {
+        min_width,
+        max_width,
+        min_height,
+        max_height,
+      },
+    ))
+  })
+}
+
+/// 以指定宽高填充自定义纸张的参数
+pub fn build_custom_media(
+  mut pms: PageMediaSize,
+  range: &CustomPageSize,
+  width: u32,
+  height: u32,
+) -> anyhow::Result<PageMediaSize> {
+  if !range.contains(width, height) {
+    bail!(
+      "Page size out of range: width must be {}-{} and height {}-{} microns",
+      range.min_width,
+      range.max_width,
+      range.min_height,
+      range.max_height
+    );
+  }
+
+  for (name, value) in [("MediaSizeWidth", width), ("MediaSizeHeight", height)] {
+    let name = media_size_parameter(&pms, name).unwrap();
+    let params = pms.parameters_mut();
+    params.retain(|x| x.name != name);
+    params.push(ParameterInit {
+      name,
+      value: PropertyValue::Integer(value as i32),
+    });
+  }
+
+  Ok(pms)
+}
+
+/// 是否为驱动私有的名称，即不在打印架构的命名空间中
+pub fn is_driver_private(name: &OwnedName) -> bool {
+  name
+    .namespace_ref()
+    .is_some_and(|ns| ns != NS_PSK && ns != NS_PSF)
+}
+
+/// 显示名称
+pub fn display_name_of(item: &impl WithProperties) -> Option<String> {
+  item
+    .get_property("DisplayName", Some(NS_PSK))
+    .and_then(|x| x.value.as_ref())
+    .and_then(|x| x.string())
+    .map(str::to_string)
+}
+
+/// 参数的整数属性，如 `MinValue`
+pub fn parameter_integer(def: &ParameterDef, key: &str) -> Option<i32> {
+  def
+    .get_property(key, Some(NS_PSF))
+    .and_then(|x| x.value.as_ref())
+    .and_then(|x| x.integer())
+}
+
+/// 参数值的类型，按 `DataType` 判断，未声明时为文本
+pub fn parameter_type(def: &ParameterDef) -> DriverValueType {
+  let data_type = def
+    .get_property("DataType", Some(NS_PSF))
+    .and_then(|x| x.value.as_ref())
+    .and_then(|x| x.qualified_name());
+  match data_type {
+    Some(name) if name.local_name == "integer" => DriverValueType::Integer,
+    _ => DriverValueType::String,
+  }
+}
+
+/// 打印机能力中驱动私有的功能及参数
+pub fn get_driver_features(cap: &PrintCapabilities) -> Vec<DriverFeature> {
+  let features = cap
+    .document
+    .features
+    .iter()
+    .filter(|f| is_driver_private(&f.name))
+    .map(|feature| DriverFeature {
+      name: feature.name.local_name.clone(),
+      namespace: feature.name.namespace.clone().unwrap_or_default(),
+      display_name: display_name_of(feature),
+      options: Some(
+        feature
+          .options
+          .iter()
+          .filter_map(|option| {
+            Some(DriverFeatureOption {
+              name: option.name.as_ref()?.local_name.clone(),
+              display_name: display_name_of(option),
+            })
+          })
+          .collect(),
+      ),
+      value_type: None,
+      min_value: None,
+      max_value: None,
+      default_value: None,
+    });
+  let parameters = cap
+    .document
+    .parameter_defs
+    .iter()
+    .filter(|p| is_driver_private(&p.name))
+    .map(|def| DriverFeature {
+      name: def.name.local_name.clone(),
+      namespace: def.name.namespace.clone().unwrap_or_default(),
+      display_name: display_name_of(def),
+      options: None,
+      value_type: Some(parameter_type(def)),
+      min_value: parameter_integer(def, "MinValue"),
+      max_value: parameter_integer(def, "MaxValue"),
+      default_value: def.default_value().map(property_text),
+    });
+  features.chain(parameters).collect()
+}
+
+/// 属性值的文本形式
+pub fn property_text(value: &PropertyValue) -> String {
+  match value {
+    PropertyValue::String(s) => s.clone(),
+    PropertyValue::Integer(i) => i.to_string(),
+    PropertyValue::QName(name) => name.local_name.clone(),
+    PropertyValue::Unknown(_, s) => s.clone(),
+  }
+}
+
+/// 纸张大小的容差（微米），不同驱动报告的同一种纸张的尺寸可能略有差别
+pub const PAGE_SIZE_TOLERANCE: u32 = 1000;
+
+/// 预热 Pdfium、打印机驱动及打印机能力缓存。`printers` 为空时使用选项中的预热打印机，
+/// 也未配置时为全部打印机。回放录制的打印机能力时不查询驱动
+pub fn warm_up(
+  options: &ApiOptions,
+  printers: Option<&[String]>,
+  validate_ticket: bool,
+) -> WarmupResult {
+  let start = Instant::now();
+  let elapsed = |since: Instant| since.elapsed().as_millis() as u64;
+
+  let pdfium_start = Instant::now();
+  let pdfium_error = pdf::init().err().map(|e| format!("{:#}", e));
+  let pdfium = elapsed(pdfium_start);
+
+  let enumerate_start = Instant::now();
+  let devices = match options.replay {
+    Some(_) => Vec::new(),
+    None => PrinterDevice::all().unwrap_or_default(),
+  };
+  let printers_elapsed = elapsed(enumerate_start);
+
+  let wanted = printers
+    .or((!options.warmup_printers.is_empty()).then_some(options.warmup_printers.as_slice()));
+  let names: Vec<String> = match wanted {
+    Some(wanted) => wanted.to_vec(),
+    None => devices.iter().map(display_name).collect(),
+  };
+
+  let printers = names
+    .into_iter()
+    .map(|name| {
+      let printer = match select_named_printer(&devices, &name) {
+        Ok(Some(printer)) => printer,
+        found => {
+          let error = found.err().map_or("No such printer".to_string(), |e| e.msg);
+          return PrinterWarmup {
+            printer: name,
+            capabilities: None,
+            ticket: None,
+            error: Some(error),
+          };
+        }
+      };
+
+      let cap_start = Instant::now();
+      if let Err(e) = cached_capabilities(
+        printer,
+        options.record_fixtures.as_deref(),
+        options.letter_a4_interchange,
+      ) {
+        return PrinterWarmup {
+          printer: name,
+          capabilities: None,
+          ticket: None,
+          error: Some(format!("Failed to get capabilities: {}", e)),
+        };
+      }
+      let capabilities = Some(elapsed(cap_start));
+
+      let mut ticket = None;
+      let mut error = None;
+      if validate_ticket {
+        let ticket_start = Instant::now();
+        match PrintTicketBuilder::new(printer).and_then(|builder| builder.build()) {
+          Ok(_) => ticket = Some(elapsed(ticket_start)),
+          Err(e) => error = Some(format!("Failed to build the default print ticket: {}", e)),
+        }
+      }
+
+      PrinterWarmup {
+        printer: name,
+        capabilities,
+        ticket,
+        error,
+      }
+    })
+    .collect();
+
+  WarmupResult {
+    pdfium,
+    pdfium_error,
+    printers_elapsed,
+    printers,
+    total: elapsed(start),
+  }
+}
+
+/// 启动时预热，见 `POST /admin/warmup`，记录结果
+pub fn warm_up_on_start(options: &ApiOptions) {
+  let result = warm_up(options, None, false);
+
+  if let Some(e) = &result.pdfium_error {
+    warn!("Warm-up: {}", e);
+  }
+  for printer in &result.printers {
+    match &printer.error {
+      Some(e) => warn!("Warm-up of printer {}: {}", printer.printer, e),
+      None => debug!(
+        "Warmed up printer {} in {} ms",
+        printer.printer,
+        printer.capabilities.unwrap_or_default()
+      ),
+    }
+  }
+  info!(
+    "Warmed up {} printers in {} ms",
+    result.printers.len(),
+    result.total
+  );
+}
+
+/// 打印机能力缓存中的一项，正在查询时保持锁定，同时获取同一台打印机时等待同一次查询
+pub type CapabilityEntry = Arc<Mutex<Option<(Instant, Arc<PrintCapabilities>)>>>;
+
+/// 打印机能力缓存，键为系统中的打印机名称
+pub static CAPABILITY_CACHE: LazyLock<Mutex<HashMap<String, CapabilityEntry>>> =
+  LazyLock::new(Default::default);
+
+#[cfg(test)]
+mod tests {
+  use std::sync::atomic::{AtomicUsize, Ordering};
+
+  use super::*;
+
+  /// 录制的 Gprinter GP-1134T 打印机能力，驱动的纸张名称中有“&#xEB;米”，并有重复的纸张
+  fn gprinter() -> PrintCapabilities {
+    let xml = include_bytes!("../../tests/fixtures/Gprinter GP-1134T.xml");
+    let document = PrintCapabilitiesDocument::parse_from_bytes(xml.as_slice()).unwrap();
+    PrintCapabilities { document }
+  }
+
+  fn names(sizes: &[PageSize]) -> Vec<&str> {
+    sizes
+      .iter()
+      .map(|size| size.name.as_deref().unwrap_or_default())
+      .collect()
+  }
+
+  #[test]
+  fn sorts_gprinter_page_sizes_by_area() {
+    let sizes =
+      get_page_sizes(&gprinter(), None, PageSizeSort::Area, &Languages::default()).unwrap();
+
+    assert_eq!(
+      names(&sizes),
+      [
+        "40 x 30 毫米",
+        "2 x 1 英寸",
+        "60 x 40 毫米",
+        "80 x 50 毫米",
+        "100 x 100 毫米",
+        "100 x 150 毫米",
+        "4 x 6 英寸",
+      ]
+    );
+  }
+
+  #[test]
+  fn detects_standard_gprinter_page_sizes() {
+    let sizes =
+      get_page_sizes(&gprinter(), None, PageSizeSort::Area, &Languages::default()).unwrap();
+    let standards = sizes
+      .iter()
+      .map(|size| size.standard.as_deref())
+      .collect::<Vec<_>>();
+
+    assert_eq!(
+      standards,
+      [
+        Some("40x30mm"),
+        Some("2x1in"),
+        Some("60x40mm"),
+        None,
+        Some("100x100mm"),
+        Some("100x150mm"),
+        Some("4x6in"),
+      ]
+    );
+  }
+
+  #[test]
+  fn keeps_the_driver_order_without_sorting() {
+    let sizes =
+      get_page_sizes(&gprinter(), None, PageSizeSort::None, &Languages::default()).unwrap();
+
+    assert_eq!(sizes.len(), 8);
+    assert_eq!(sizes[0].name.as_deref(), Some("4 x 6 英寸"));
+    assert_eq!(sizes[1].name.as_deref(), Some("100 x 150 毫米"));
+    assert_eq!(sizes[6].name.as_deref(), Some("100 x 150 毫米"));
+    assert!(sizes
+      .iter()
+      .all(|size| !size.name.as_deref().unwrap_or_default().contains('&')));
+  }
+
+  #[test]
+  fn names_gprinter_page_sizes_in_english() {
+    let languages = Languages::parse(Some("en-US,en;q=0.9"));
+    let sizes = get_page_sizes(&gprinter(), None, PageSizeSort::None, &languages).unwrap();
+
+    // 只有 PrintSchema 关键字的纸张有英文名称，驱动自定义的纸张仍使用显示名称
+    assert_eq!(sizes[0].name.as_deref(), Some("North America 4x6"));
+    assert_eq!(sizes[1].name.as_deref(), Some("100 x 150 毫米"));
+  }
+
+  #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+  async fn fetches_once_for_concurrent_requests() {
+    let calls = Arc::new(AtomicUsize::new(0));
+
+    let requests = (0..20)
+      .map(|_| {
+        let calls = calls.clone();
+        tokio::task::spawn_blocking(move || {
+          cached_or_fetch("Concurrent test printer", || {
+            calls.fetch_add(1, Ordering::SeqCst);
+            // 查询期间其余请求都已到达
+            std::thread::sleep(Duration::from_millis(200));
+            anyhow::Ok(gprinter())
+          })
+        })
+      })
+      .collect::<Vec<_>>();
+
+    let mut fetched = 0;
+    for request in requests {
+      let (cap, fresh) = request.await.unwrap().unwrap();
+      assert!(cap.max_copies().is_some());
+      fetched += fresh as usize;
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    assert_eq!(fetched, 1);
+  }
+
+  #[test]
+  fn does_not_cache_errors() {
+    let calls = AtomicUsize::new(0);
+    let fetch = |fail: bool| {
+      cached_or_fetch("Failing test printer", || {
+        calls.fetch_add(1, Ordering::SeqCst);
+        if fail {
+          anyhow::bail!("The driver is busy");
+        }
+        Ok(gprinter())
+      })
+    };
+
+    assert!(fetch(true).is_err());
+    assert!(fetch(false).is_ok_and(|(_, fresh)| fresh));
+    assert!(fetch(false).is_ok_and(|(_, fresh)| !fresh));
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+  }
+}
@@ -0,0 +1,282 @@
+use std::{
+  cell::RefCell,
+  collections::{HashMap, VecDeque},
+  fs::{create_dir_all, read_dir, File},
+  io::{self, Write},
+  path::{Path, PathBuf},
+  sync::{LazyLock, Mutex},
+  time::Duration,
+};
+
+use log::warn;
+use serde_json::{json, Value};
+use tokio::{
+  io::{AsyncWriteExt, DuplexStream},
+  runtime::Handle,
+};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use super::{
+  receipt::Receipt,
+  retention::{Artifact, ArtifactStore},
+};
+use crate::storage::write_atomically;
+
+/// 每个任务保留的日志行数，超过时丢弃最早的
+#[cfg(feature = "with-ui")]
+const MAX_LOG_LINES: usize = 500;
+
+/// 发送诊断包时的缓冲区大小
+const STREAM_BUFFER: usize = 64 * 1024;
+
+/// 诊断包中的文件，按此顺序打包，不存在的文件跳过
+const FILES: &[&str] = &[
+  "job.json",
+  "timings.json",
+  "ticket.xml",
+  "capabilities.json",
+  "log.txt",
+];
+
+/// 打印期间记录的诊断信息
+#[derive(Debug, Default)]
+pub struct Capture {
+  /// 合并默认设置后的打印设置
+  pub settings: Option<Value>,
+  /// 提交打印使用的打印票据 XML
+  pub ticket: Option<Vec<u8>>,
+  /// 打印使用的打印机能力，与 `GET /printers/:name` 的结果相同
+  pub capabilities: Option<Value>,
+  /// 各阶段耗时
+  pub timings: Option<Value>,
+  logs: VecDeque<String>,
+}
+
+/// 正在打印的任务的诊断信息，键为任务 ID，任务结束时保存并移除
+static CAPTURES: LazyLock<Mutex<HashMap<String, Capture>>> = LazyLock::new(Default::default);
+
+thread_local! {
+  /// 当前线程正在打印的任务，日志按此记录到任务的诊断信息中
+  static CURRENT: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// 正在记录的任务，释放时当前线程停止记录
+pub struct Recording {
+  previous: Option<String>,
+}
+
+impl Drop for Recording {
+  fn drop(&mut self) {
+    CURRENT.with(|current| *current.borrow_mut() = self.previous.take());
+  }
+}
+
+/// 开始在当前线程记录任务的诊断信息，同一任务换打印机重试时继续记录
+pub fn begin(job_id: &str) -> Recording {
+  CAPTURES
+    .lock()
+    .unwrap_or_else(|e| e.into_inner())
+    .entry(job_id.to_string())
+    .or_default();
+  let previous = CURRENT.with(|current| current.borrow_mut().replace(job_id.to_string()));
+  Recording { previous }
+}
+
+/// 当前线程是否在记录任务的诊断信息
+pub fn is_recording() -> bool {
+  CURRENT.with(|current| current.borrow().is_some())
+}
+
+/// 更新当前线程正在记录的任务的诊断信息，没有记录时忽略
+pub fn record(update: impl FnOnce(&mut Capture)) {
+  CURRENT.with(|current| {
+    let current = current.borrow();
+    let Some(job_id) = current.as_deref() else {
+      return;
+    };
+    let mut captures = CAPTURES.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(capture) = captures.get_mut(job_id) {
+      update(capture);
+    }
+  });
+}
+
+/// 记录一行日志，超过行数上限时丢弃最早的
+#[cfg(feature = "with-ui")]
+pub fn log_line(line: String) {
+  record(|capture| {
+    if capture.logs.len() >= MAX_LOG_LINES {
+      capture.logs.pop_front();
+    }
+    capture.logs.push_back(line);
+  });
+}
+
+/// 保存任务的诊断信息，供支持人员下载。只在配置了保留时间时启用，按保留策略删除
+#[derive(Debug)]
+pub struct JobDiagnostics {
+  dir: PathBuf,
+  /// 保留策略中诊断信息保留的时间，不限制时为空
+  retention: Option<Duration>,
+}
+
+impl JobDiagnostics {
+  pub fn new(dir: PathBuf, retention: Option<Duration>) -> Self {
+    Self { dir, retention }
+  }
+
+  pub fn retention(&self) -> Option<Duration> {
+    self.retention
+  }
+
+  /// 任务的目录，ID 由 `schedule::new_id` 生成，检查格式避免访问目录外的文件
+  fn job_dir(&self, job_id: &str) -> Option<PathBuf> {
+    let valid = job_id.len() == 32 && job_id.chars().all(|c| c.is_ascii_hexdigit());
+    valid.then(|| self.dir.join(job_id))
+  }
+
+  /// 是否保存了任务的诊断信息
+  pub fn contains(&self, job_id: &str) -> bool {
+    self
+      .job_dir(job_id)
+      .is_some_and(|dir| dir.join(FILES[0]).exists())
+  }
+
+  /// 任务结束后保存回执及打印期间记录的诊断信息，失败时只记录警告
+  pub fn save(&self, receipt: &Receipt) {
+    let capture = CAPTURES
+      .lock()
+      .unwrap_or_else(|e| e.into_inner())
+      .remove(&receipt.job_id)
+      .unwrap_or_default();
+
+    if let Err(e) = self.write(receipt, capture) {
+      warn!(
+        "Failed to save the diagnostics of job {}: {:#}",
+        receipt.job_id, e
+      );
+    }
+  }
+
+  fn write(&self, receipt: &Receipt, capture: Capture) -> anyhow::Result<()> {
+    let dir = self
+      .job_dir(&receipt.job_id)
+      .ok_or_else(|| anyhow::anyhow!("Invalid job ID {}", receipt.job_id))?;
+    create_dir_all(&dir)?;
+
+    if let Some(timings) = &capture.timings {
+      write_atomically(
+        &dir.join("timings.json"),
+        serde_json::to_vec_pretty(timings)?,
+      )?;
+    }
+    if let Some(ticket) = &capture.ticket {
+      write_atomically(&dir.join("ticket.xml"), ticket)?;
+    }
+    if let Some(capabilities) = &capture.capabilities {
+      let json = serde_json::to_vec_pretty(capabilities)?;
+      write_atomically(&dir.join("capabilities.json"), json)?;
+    }
+    let logs = capture.logs.iter().fold(String::new(), |mut logs, line| {
+      logs.push_str(line);
+      logs.push('\n');
+      logs
+    });
+    write_atomically(&dir.join("log.txt"), logs)?;
+
+    // 最后写入任务信息，以此判断诊断信息是否完整
+    let job = json!({
+      "receipt": receipt,
+      "settings": capture.settings,
+    });
+    write_atomically(&dir.join(FILES[0]), serde_json::to_vec_pretty(&job)?)?;
+    Ok(())
+  }
+
+  /// 在后台将任务的诊断信息打包为 ZIP 文件，返回读取 ZIP 文件的流。
+  /// 边打包边发送，不在内存中生成整个文件。任务不存在时为空
+  pub fn stream(&self, job_id: &str) -> Option<DuplexStream> {
+    let dir = self.job_dir(job_id).filter(|_| self.contains(job_id))?;
+    let (reader, writer) = tokio::io::duplex(STREAM_BUFFER);
+    let writer = BlockingWriter {
+      inner: writer,
+      handle: Handle::current(),
+    };
+    let job_id = job_id.to_string();
+
+    tokio::task::spawn_blocking(move || {
+      if let Err(e) = write_zip(&dir, writer) {
+        warn!("Failed to send the diagnostics of job {}: {:#}", job_id, e);
+      }
+    });
+    Some(reader)
+  }
+}
+
+/// 每个任务的目录为一项，按目录的修改时间判断新旧
+impl ArtifactStore for JobDiagnostics {
+  fn artifacts(&self) -> anyhow::Result<Vec<Artifact>> {
+    let entries = match read_dir(&self.dir) {
+      Ok(entries) => entries,
+      Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+      Err(e) => return Err(e.into()),
+    };
+
+    Ok(
+      entries
+        .flatten()
+        .filter_map(|entry| {
+          let job_id = entry.file_name().into_string().ok()?;
+          let dir = self.job_dir(&job_id)?;
+          let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+          let size = read_dir(&dir)
+            .ok()?
+            .flatten()
+            .filter_map(|file| file.metadata().ok())
+            .map(|meta| meta.len())
+            .sum();
+          Some(Artifact {
+            id: job_id,
+            modified,
+            size,
+            paths: vec![dir],
+          })
+        })
+        .collect(),
+    )
+  }
+}
+
+/// 依次写入诊断包中的文件，读取时不整个读入内存
+fn write_zip(dir: &Path, writer: impl Write) -> anyhow::Result<()> {
+  let mut zip = ZipWriter::new_stream(writer);
+  let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+  for name in FILES {
+    let mut file = match File::open(dir.join(name)) {
+      Ok(file) => file,
+      Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+      Err(e) => return Err(e.into()),
+    };
+    zip.start_file(*name, options)?;
+    io::copy(&mut file, &mut zip)?;
+  }
+  zip.finish()?.flush()?;
+  Ok(())
+}
+
+/// 在阻塞线程中写入异步流，读取端关闭时写入失败
+struct BlockingWriter {
+  inner: DuplexStream,
+  handle: Handle,
+}
+
+impl Write for BlockingWriter {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.handle.block_on(self.inner.write(buf))
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.handle.block_on(self.inner.flush())
+  }
+}
@@ -0,0 +1,517 @@
+use std::{
+  collections::{HashMap, HashSet},
+  ffi::OsStr,
+  time::Duration,
+};
+
+use anyhow::bail;
+use chrono::{DateTime, FixedOffset, TimeDelta, Utc};
+use log::{debug, error, info, warn};
+use poem::http::StatusCode;
+use poem_openapi::types::{Base64, ParseFromJSON, ToJSON};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tokio::time::MissedTickBehavior;
+use winprint::printer::PrinterDevice;
+
+use super::{
+  access::Client,
+  error::{CodedError, ErrorCode},
+  events::{self, JobEvent, JobStatus},
+  options::ApiOptions,
+  print::{catch_panic, check_page_limit, job_name, print_file, run_post_print_hooks, skips_hooks},
+  printers::select_named_printer,
+  receipt::{self, Outcome, Receipt},
+  schedule::{self, Ending, QueueDepth, QueueLimits, ScheduledJob},
+  settings::{effective_settings, select_pages},
+  token::TokenClaims,
+  v1::{waiting_state, JobState, PrintPayload, PrintResult, PrintTimings},
+};
+
+use crate::{pdf, spooler, status};
+
+/// 最早的等待中任务已等待的秒数
+pub fn oldest_queued_seconds(depth: &QueueDepth) -> Option<u64> {
+  depth
+    .oldest_submitted_at
+    .map(|at| (Utc::now() - at).num_seconds().max(0) as u64)
+}
+
+/// 打印机排队已满的错误，包括排队的任务数、总大小及最早的任务已等待的时间
+pub fn queue_full(printer: &str, depth: &QueueDepth, limits: &QueueLimits) -> CodedError {
+  let limit = |max: Option<u64>| max.map_or("unlimited".to_string(), |max| max.to_string());
+  CodedError::new(
+    StatusCode::SERVICE_UNAVAILABLE,
+    ErrorCode::QueueFull,
+    format!(
+      "The queue of printer {} is full: {} jobs (limit {}), {} bytes (limit {}), the oldest job has waited {} seconds",
+      printer,
+      depth.jobs,
+      limit(limits.max_jobs.map(u64::from)),
+      depth.bytes,
+      limit(limits.max_bytes),
+      oldest_queued_seconds(depth).unwrap_or_default()
+    ),
+  )
+}
+
+/// 检查并保存定时打印任务。使用提交时的打印设置，提前检查文件和页码范围
+pub fn schedule_file(
+  mut payload: PrintPayload,
+  print_at: DateTime<FixedOffset>,
+  options: &ApiOptions,
+  client: &Client,
+  claims: Option<TokenClaims>,
+) -> anyhow::Result<PrintResult> {
+  let mut settings = effective_settings(payload.settings.take(), options, client, claims.as_ref())?;
+  let document_sha256 = payload.document_sha256();
+  // 多个文件时提前合并，保存合并后的文件
+  let mut warnings = Vec::new();
+  let file = payload.take_document(&mut warnings)?;
+  // 打印机长时间不可用时排队的任务不再无限增加
+  let document_size = file.len() as u64;
+  let depth = schedule::depth(&settings.printer);
+  if options.queue_limits.is_exceeded_by(&depth, document_size) {
+    return Err(queue_full(&settings.printer, &depth, &options.queue_limits).into());
+  }
+  let summary = pdf::inspect(&file)?;
+  let count = summary.pages;
+  // 保存时确定名称，默认名称为提交时间而不是打印时间
+  let name = settings
+    .job_name
+    .as_deref()
+    .or(summary.metadata.title.as_deref());
+  settings.job_name = Some(job_name(name));
+  payload.file = Some(Base64(file));
+  let pages = select_pages(&settings, count)?;
+  check_page_limit(claims.as_ref(), &settings, &pages)?;
+
+  let copies = settings.copies.unwrap_or(1) as u32;
+  let id = schedule::new_id();
+  let printer = settings.printer.clone();
+  let ttl_seconds = payload.ttl_seconds.or(options.job_ttl);
+  let held = payload.hold.unwrap_or_default();
+  // 只保存 PIN 的摘要
+  let release_pin_sha256 = payload
+    .release_pin
+    .take()
+    .map(|pin| release_pin_hash(&id, &pin));
+  payload.settings = Some(settings);
+  let job = ScheduledJob {
+    id: id.clone(),
+    print_at,
+    printer: printer.clone(),
+    client: client.ip,
+    api_key_sha256: client.api_key_sha256.clone(),
+    claims,
+    ttl_seconds,
+    payload: payload.to_json().unwrap_or_default(),
+    submitted_at: Some(Utc::now()),
+    document_sha256,
+    total_pages: Some(pages.len() as u32 * copies),
+    held,
+    release_pin_sha256,
+    priority: payload.priority.map(Into::into).unwrap_or_default(),
+    document: Some(summary.metadata),
+    document_size: Some(document_size),
+    interrupted: false,
+  };
+  let state = waiting_state(&job);
+  schedule::add(job)?;
+
+  if let JobState::Held = state {
+    info!("Holding job {} for printer {} until released", id, printer);
+  } else if let JobState::QueuedPaused = state {
+    info!("Queued job {} until printer {} is resumed", id, printer);
+  } else {
+    info!(
+      "Scheduled job {} to print to {} at {}",
+      id, printer, print_at
+    );
+  }
+
+  Ok(PrintResult {
+    printer: None,
+    failed_printers: None,
+    total_pages: Some(pages.len() as u32 * copies),
+    pages,
+    rotate_degrees: None,
+    rotated_pages: None,
+    sheets: None,
+    total_sheets: None,
+    estimated_cost: None,
+    warnings: (!warnings.is_empty()).then_some(warnings),
+    timings: PrintTimings::default(),
+    attempts: 0,
+    retried_errors: None,
+    state: Some(state),
+    job_id: Some(id),
+    print_at: Some(print_at),
+    document: None,
+    ticket: None,
+    deadline_exceeded: None,
+    chunks: None,
+    coverage: None,
+    page_height: None,
+  })
+}
+
+/// 释放暂缓任务前检查打印机此时是否可用，不可用时任务保持暂缓
+pub fn check_release(job: &ScheduledJob) -> std::result::Result<(), CodedError> {
+  let not_ready = |msg: String| {
+    CodedError::new(
+      StatusCode::SERVICE_UNAVAILABLE,
+      ErrorCode::PrinterNotReady,
+      msg,
+    )
+  };
+
+  if status::is_paused() {
+    return Err(CodedError::paused());
+  }
+  if status::is_printer_paused(&job.printer) {
+    return Err(not_ready(format!("Printer {} is paused", job.printer)));
+  }
+
+  let printers = PrinterDevice::all().map_err(|e| {
+    CodedError::new(
+      StatusCode::INTERNAL_SERVER_ERROR,
+      ErrorCode::InternalError,
+      format!("Failed to list printers: {}", e),
+    )
+  })?;
+  let Some(printer) = select_named_printer(&printers, &job.printer)? else {
+    return Err(not_ready(format!("Printer {} is not found", job.printer)));
+  };
+
+  let queue = job
+    .payload
+    .get("queue_if_not_ready")
+    .and_then(Value::as_bool)
+    == Some(true);
+  match spooler::printer_status(printer.os_name()) {
+    Ok(status) if !queue && !status.problems().is_empty() => Err(not_ready(format!(
+      "Printer {} is not ready: {}",
+      job.printer,
+      status.problems().join(", ")
+    ))),
+    Ok(_) => Ok(()),
+    Err(e) => {
+      warn!(
+        "Failed to read the status of printer {}: {}",
+        job.printer, e
+      );
+      Ok(())
+    }
+  }
+}
+
+/// 释放 PIN 的摘要，加入任务 ID 使相同的 PIN 在各任务中的摘要不同
+pub fn release_pin_hash(id: &str, pin: &str) -> String {
+  let hash = Sha256::new()
+    .chain_update(id)
+    .chain_update([0])
+    .chain_update(pin)
+    .finalize();
+  hash.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// 计划时间不晚于当前时间加上此值时立即打印，容忍客户端与服务器的时钟偏差
+pub const SCHEDULE_TOLERANCE: TimeDelta = TimeDelta::seconds(5);
+
+/// 调度的最长等待时间，系统时间被调整后也能及时执行任务
+pub const SCHEDULE_MAX_WAIT: Duration = Duration::from_secs(60);
+
+/// 启动定时打印的调度任务。服务停止期间错过的任务在启动后立即补打，暂停接受任务时推迟执行
+pub fn spawn_scheduler(options: ApiOptions) {
+  tokio::spawn(async move {
+    loop {
+      if !status::is_paused() {
+        let now = Utc::now() + SCHEDULE_TOLERANCE;
+        for printer in schedule::start_draining(now, printer_ready) {
+          let options = options.clone();
+          // 每台打印机依次打印，每次取出优先级最高的任务，之后加入的优先级高的任务也能插队
+          tokio::task::spawn_blocking(move || {
+            drain_printer(&printer, &options);
+            schedule::stop_draining(&printer);
+          });
+        }
+      }
+
+      let wait = schedule::next_due(printer_ready)
+        .and_then(|t| (t - Utc::now()).to_std().ok())
+        .map_or(SCHEDULE_MAX_WAIT, |wait| wait.min(SCHEDULE_MAX_WAIT));
+
+      tokio::select! {
+        _ = schedule::changed() => {}
+        _ = tokio::time::sleep(wait) => {}
+      }
+    }
+  });
+}
+
+/// 查询打印进度的间隔，进度事件最多每秒一次
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 启动跟踪打印进度的任务，按提交时的文档名称在系统打印队列中查找任务
+pub fn spawn_progress_tracker() {
+  tokio::spawn(async {
+    let mut interval = tokio::time::interval(PROGRESS_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+      interval.tick().await;
+      let _ = tokio::task::spawn_blocking(poll_progress).await;
+    }
+  });
+}
+
+/// 读取仍在打印的任务所在打印机的队列并更新进度，已打印的页数变化时通知
+fn poll_progress() {
+  let tracked = schedule::tracked();
+  let queues = tracked
+    .iter()
+    .map(|(_, progress)| progress.printer.clone())
+    .collect::<HashSet<_>>()
+    .into_iter()
+    .filter_map(|printer| match spooler::jobs(OsStr::new(&printer)) {
+      Ok(jobs) => Some((printer, jobs)),
+      Err(e) => {
+        debug!("Failed to read the jobs of printer {}: {}", printer, e);
+        None
+      }
+    })
+    .collect::<HashMap<_, _>>();
+
+  for (id, mut progress) in tracked {
+    let Some(queue) = queues.get(&progress.printer) else {
+      continue;
+    };
+    let before = progress.pages_printed();
+
+    for document in progress.documents.iter_mut().filter(|d| !d.done) {
+      match queue.iter().find(|job| job.document == document.name) {
+        Some(job) if !job.is_done() => {
+          document.pages_printed = job.pages_printed();
+          document.total_pages = job.total_pages();
+        }
+        // 打印完成或已离开队列，驱动报告过总页数时按全部打印
+        _ => {
+          document.pages_printed = document.total_pages.or(document.pages_printed);
+          document.done = true;
+        }
+      }
+    }
+
+    let pages_printed = progress.pages_printed();
+    schedule::update_progress(&id, progress.documents);
+    if pages_printed.is_some() && pages_printed != before {
+      events::publish(JobEvent {
+        pages_printed,
+        ..JobEvent::new(&id, JobStatus::Progress, &progress.printer)
+      });
+    }
+  }
+}
+
+/// 估算排队任务的开始时间至少需要的打印记录数
+pub const ETA_MIN_SAMPLES: usize = 3;
+
+/// 各打印机队列中任务的位置，从 1 开始
+pub fn queue_positions(printers: HashSet<&str>, options: &ApiOptions) -> HashMap<String, u32> {
+  let now = Utc::now() + SCHEDULE_TOLERANCE;
+  printers
+    .into_iter()
+    .flat_map(|printer| {
+      let queue = schedule::queue(printer, now, options.priority_aging);
+      queue.into_iter().zip(1..)
+    })
+    .collect()
+}
+
+/// 队列中第 `position` 个任务预计多少秒后开始打印，打印机正在打印时先等待正在打印的任务。
+/// 打印记录不足或服务、打印机暂停时为空
+pub fn queue_estimate(printer: &str, position: u32, options: &ApiOptions) -> Option<u64> {
+  if status::is_paused() || status::is_printer_paused(printer) {
+    return None;
+  }
+  if options.eta_window == 0 {
+    return None;
+  }
+  let average = status::average_duration(printer, ETA_MIN_SAMPLES.min(options.eta_window))?;
+  let ahead = position.saturating_sub(1) + schedule::is_draining(printer) as u32;
+  Some((average * ahead).as_secs_f64().round() as u64)
+}
+
+/// 通知打印机队列中各任务新的位置及预计的开始时间
+pub fn publish_queue(printer: &str, options: &ApiOptions) {
+  let now = Utc::now() + SCHEDULE_TOLERANCE;
+  for (i, id) in schedule::queue(printer, now, options.priority_aging)
+    .into_iter()
+    .enumerate()
+  {
+    let position = i as u32 + 1;
+    events::publish(JobEvent {
+      queue_position: Some(position),
+      estimated_start_seconds: queue_estimate(printer, position, options),
+      ..JobEvent::new(&id, JobStatus::Scheduled, printer)
+    });
+  }
+}
+
+/// 任务的打印机是否未暂停，暂缓的任务只在过期时执行，不受打印机暂停影响
+pub fn printer_ready(job: &ScheduledJob) -> bool {
+  job.held || !status::is_printer_paused(&job.printer)
+}
+
+/// 依次打印打印机的到期任务，没有到期任务或服务暂停接受任务时结束
+pub fn drain_printer(printer: &str, options: &ApiOptions) {
+  while !status::is_paused() {
+    let now = Utc::now() + SCHEDULE_TOLERANCE;
+    let Some((job, overtaken)) =
+      schedule::take_next(printer, now, options.priority_aging, printer_ready)
+    else {
+      break;
+    };
+
+    // 过期的暂缓任务不打印，不算超过
+    if !job.held {
+      for id in overtaken {
+        debug!("Job {} is overtaken by job {}", id, job.id);
+        events::publish(JobEvent {
+          detail: Some(format!("overtaken by job {}", job.id)),
+          ..JobEvent::new(&id, JobStatus::Overtaken, printer)
+        });
+      }
+    }
+    // 队列变化后通知等待中的任务
+    publish_queue(printer, options);
+    run_scheduled(job, options);
+  }
+}
+
+pub fn run_scheduled(job: ScheduledJob, options: &ApiOptions) {
+  let late = Utc::now() - job.print_at.to_utc();
+
+  // 调度只在暂缓的任务过期时执行它
+  let expired = job
+    .ttl_seconds
+    .filter(|ttl| job.held || late > TimeDelta::seconds(*ttl as i64));
+  if let Some(ttl) = expired {
+    if job.held {
+      warn!(
+        "Held job {} expired, it was not released within {} seconds",
+        job.id, ttl
+      );
+    } else {
+      warn!(
+        "Scheduled job {} expired, it is {} seconds late and the TTL is {} seconds",
+        job.id,
+        late.num_seconds(),
+        ttl
+      );
+    }
+    let skip = skips_hooks(&job.payload);
+    let receipt = schedule::finish(job, Ending::Expired);
+    run_post_print_hooks(&receipt, options, skip);
+    return;
+  }
+
+  if late > TimeDelta::minutes(1) {
+    warn!(
+      "Scheduled job {} is {} minutes late",
+      job.id,
+      late.num_minutes()
+    );
+  }
+
+  let (id, printer) = (job.id.clone(), job.printer.clone());
+  match print_saved(job, options) {
+    Ok(_) => info!("Scheduled job {} printed to {}", id, printer),
+    Err(e) => error!("Scheduled job {} failed: {:#}", id, e),
+  }
+}
+
+/// 打印保存在服务内的定时或暂缓的任务，签发回执并执行打印后命令
+pub fn print_saved(mut job: ScheduledJob, options: &ApiOptions) -> anyhow::Result<PrintResult> {
+  let payload = match PrintPayload::parse_from_json(Some(std::mem::take(&mut job.payload))) {
+    Ok(payload) => payload,
+    Err(e) => {
+      let msg = e.into_message();
+      let receipt = job.receipt(Outcome::Failed, Some(msg.clone()));
+      issue_receipt(&receipt, options);
+      run_post_print_hooks(&receipt, options, false);
+      bail!("Invalid job content: {}", msg);
+    }
+  };
+  let skip_hooks = payload.skip_hooks.unwrap_or_default();
+
+  events::publish(JobEvent::new(&job.id, JobStatus::Printing, &job.printer));
+  let result = catch_panic(|| {
+    print_file(
+      payload,
+      options,
+      &Client {
+        ip: job.client,
+        api_key_sha256: job.api_key_sha256.clone(),
+      },
+      job.claims.as_ref(),
+      None,
+      Some(&job.id),
+    )
+  });
+  status::record(result.is_ok());
+
+  let receipt = match &result {
+    Ok(result) => {
+      let (outcome, detail) = result_outcome(result);
+      Receipt {
+        printer: result.printer.clone().unwrap_or(job.printer.clone()),
+        pages: result.total_pages,
+        sheets: result.total_sheets,
+        ..job.receipt(outcome, detail)
+      }
+    }
+    Err(e) => {
+      #[cfg(feature = "error-reporting")]
+      crate::report::print_failure(Some(&job.printer), e);
+      Receipt {
+        error: error_name(e),
+        ..job.receipt(Outcome::Failed, Some(format!("{:#}", e)))
+      }
+    }
+  };
+  issue_receipt(&receipt, options);
+  run_post_print_hooks(&receipt, options, skip_hooks);
+  result
+}
+
+/// 签发回执，启用诊断信息时同时保存打印期间记录的诊断信息
+pub fn issue_receipt(receipt: &Receipt, options: &ApiOptions) {
+  receipt::issue(receipt);
+  schedule::settle(&receipt.job_id);
+  if let Some(diagnostics) = &options.job_diagnostics {
+    diagnostics.save(receipt);
+  }
+}
+
+/// 错误的类型名称，与响应中的 `error` 相同，用于回执及统计
+pub fn error_name(e: &anyhow::Error) -> Option<String> {
+  let value = e.downcast_ref::<CodedError>()?.code.to_json()?;
+  value.as_str().map(str::to_string)
+}
+
+/// 打印结果对应的回执结果，分部分打印时取消的任务为已取消
+pub fn result_outcome(result: &PrintResult) -> (Outcome, Option<String>) {
+  if !matches!(result.state, Some(JobState::Cancelled)) {
+    return (Outcome::Printed, None);
+  }
+
+  let chunks = result.chunks.as_deref().unwrap_or_default();
+  let spooled = chunks.iter().filter(|chunk| chunk.spooled).count();
+  let detail = format!(
+    "cancelled after {} of {} chunks were spooled",
+    spooled,
+    chunks.len()
+  );
+  (Outcome::Cancelled, Some(detail))
+}
@@ -0,0 +1,181 @@
+use std::{
+  collections::BTreeSet,
+  fs::{create_dir_all, read_to_string},
+  path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::{
+  instance,
+  storage::{file_stem, write_atomically},
+};
+
+/// 保存的打印机能力快照，用于发现驱动更新等导致的变化
+#[derive(Debug, Serialize, Deserialize)]
+struct Snapshot {
+  printer: String,
+  /// `capability` 的 SHA-256
+  sha256: String,
+  captured_at: DateTime<Utc>,
+  /// 由驱动返回的能力得到的 `GET /printers/:name` 结果
+  capability: Value,
+}
+
+/// 打印机能力与上次快照相比的变化
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CapabilityChanges {
+  pub printer: String,
+  /// 上次快照的时间
+  pub previous_at: DateTime<Utc>,
+  /// 发现变化的时间
+  pub detected_at: DateTime<Utc>,
+  pub previous_sha256: String,
+  pub sha256: String,
+  /// 新增的纸张，没有名称的纸张为 `宽x高` 微米
+  pub added_page_sizes: Vec<String>,
+  pub removed_page_sizes: Vec<String>,
+  pub added_orientations: Vec<String>,
+  pub removed_orientations: Vec<String>,
+  /// 最大份数有变化时为变化前后的值
+  pub max_copies: Option<(Option<u64>, Option<u64>)>,
+  /// 引用了已不存在的选项的默认打印设置等
+  #[serde(default)]
+  pub affected_settings: Vec<String>,
+}
+
+impl CapabilityChanges {
+  /// 用于日志的简短说明
+  pub fn summary(&self) -> String {
+    let mut parts = Vec::new();
+    let mut list = |label: &str, items: &[String]| {
+      if !items.is_empty() {
+        parts.push(format!("{} {}", label, items.join(", ")));
+      }
+    };
+    list("added page sizes", &self.added_page_sizes);
+    list("removed page sizes", &self.removed_page_sizes);
+    list("added orientations", &self.added_orientations);
+    list("removed orientations", &self.removed_orientations);
+
+    if let Some((before, after)) = self.max_copies {
+      let show = |n: Option<u64>| n.map_or("none".to_string(), |n| n.to_string());
+      parts.push(format!("max copies {} -> {}", show(before), show(after)));
+    }
+    if parts.is_empty() {
+      parts.push("other capabilities changed".to_string());
+    }
+    parts.join("; ")
+  }
+}
+
+fn snapshot_dir() -> Option<PathBuf> {
+  instance::data_dir().map(|dir| dir.join("capabilities"))
+}
+
+fn write_json(path: &Path, value: &impl Serialize) -> anyhow::Result<()> {
+  let dir = path.parent().unwrap_or(Path::new("."));
+  create_dir_all(dir)?;
+  write_atomically(path, serde_json::to_string_pretty(value)?)?;
+  Ok(())
+}
+
+fn read_json<T: for<'de> Deserialize<'de>>(path: &Path) -> Option<T> {
+  let json = read_to_string(path).ok()?;
+  serde_json::from_str(&json).ok()
+}
+
+fn sha256(value: &Value) -> String {
+  Sha256::digest(value.to_string().as_bytes())
+    .iter()
+    .map(|b| format!("{:02x}", b))
+    .collect()
+}
+
+/// 纸张的名称，没有名称时为尺寸
+fn page_sizes(capability: &Value) -> BTreeSet<String> {
+  let sizes = capability["page_sizes"].as_array();
+  sizes
+    .into_iter()
+    .flatten()
+    .map(|size| match size["name"].as_str() {
+      Some(name) => name.to_string(),
+      None => format!("{}x{}", size["width"], size["height"]),
+    })
+    .collect()
+}
+
+fn orientations(capability: &Value) -> BTreeSet<String> {
+  let orientations = capability["orientations"].as_array();
+  orientations
+    .into_iter()
+    .flatten()
+    .filter_map(|o| o.as_str().map(str::to_string))
+    .collect()
+}
+
+/// `after` 中有而 `before` 中没有的项
+fn added(before: &BTreeSet<String>, after: &BTreeSet<String>) -> Vec<String> {
+  after.difference(before).cloned().collect()
+}
+
+/// 与保存的快照比较并保存新的快照。首次保存或没有变化时为空
+pub fn compare(printer: &str, capability: Value) -> anyhow::Result<Option<CapabilityChanges>> {
+  let dir = snapshot_dir().ok_or_else(|| anyhow::anyhow!("No data directory"))?;
+  let path = dir.join(format!("{}.json", file_stem(printer)));
+  let sha256 = sha256(&capability);
+  let previous = read_json::<Snapshot>(&path);
+
+  if previous.as_ref().is_some_and(|p| p.sha256 == sha256) {
+    return Ok(None);
+  }
+
+  let snapshot = Snapshot {
+    printer: printer.to_string(),
+    sha256,
+    captured_at: Utc::now(),
+    capability,
+  };
+  write_json(&path, &snapshot)?;
+
+  let Some(previous) = previous else {
+    return Ok(None);
+  };
+
+  let (before, after) = (&previous.capability, &snapshot.capability);
+  let (sizes_before, sizes_after) = (page_sizes(before), page_sizes(after));
+  let (orientations_before, orientations_after) = (orientations(before), orientations(after));
+  let (copies_before, copies_after) = (before["max_copies"].as_u64(), after["max_copies"].as_u64());
+
+  Ok(Some(CapabilityChanges {
+    printer: printer.to_string(),
+    previous_at: previous.captured_at,
+    detected_at: snapshot.captured_at,
+    previous_sha256: previous.sha256,
+    sha256: snapshot.sha256,
+    added_page_sizes: added(&sizes_before, &sizes_after),
+    removed_page_sizes: added(&sizes_after, &sizes_before),
+    added_orientations: added(&orientations_before, &orientations_after),
+    removed_orientations: added(&orientations_after, &orientations_before),
+    max_copies: (copies_before != copies_after).then_some((copies_before, copies_after)),
+    affected_settings: Vec::new(),
+  }))
+}
+
+fn changes_filepath(dir: &Path, printer: &str) -> PathBuf {
+  dir.join(format!("{}.changes.json", file_stem(printer)))
+}
+
+/// 保存打印机最近一次的变化，重启后仍可查询
+pub fn record(changes: &CapabilityChanges) -> anyhow::Result<()> {
+  let dir = snapshot_dir().ok_or_else(|| anyhow::anyhow!("No data directory"))?;
+  write_json(&changes_filepath(&dir, &changes.printer), changes)
+}
+
+/// 打印机最近一次的变化，没有发现过变化时为空
+pub fn last_changes(printer: &str) -> Option<CapabilityChanges> {
+  read_json(&changes_filepath(&snapshot_dir()?, printer))
+}
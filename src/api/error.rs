@@ -0,0 +1,298 @@
+use std::any::Any;
+
+use log::{debug, error};
+use poem::{http::StatusCode, IntoResponse};
+use poem_openapi::{
+  payload::Json,
+  types::{ParseFromJSON, ToJSON},
+  Enum, Object,
+};
+
+use super::token::TokenError;
+
+use crate::status;
+
+/// 统一响应
+#[derive(Object)]
+#[oai(skip_serializing_if_is_none, example)]
+pub struct Response<T>
+where
+  T: ParseFromJSON + ToJSON,
+{
+  /// 代码，0 表示成功，非 0 表示失败
+  pub code: i32,
+  /// 错误类型，便于客户端区分处理
+  pub error: Option<ErrorCode>,
+  /// 错误消息
+  pub msg: Option<String>,
+  /// 成功时的数据
+  pub data: Option<T>,
+  /// 被拒绝的请求的隔离 ID，服务启用隔离时才有，见 `GET /admin/quarantine/{id}`
+  pub quarantine_id: Option<String>,
+}
+
+impl<T> Response<T>
+where
+  T: ParseFromJSON + ToJSON + std::fmt::Debug,
+{
+  pub fn ok(data: T) -> Json<Self> {
+    debug!("OK: {:#?}", data);
+
+    Json(Self {
+      code: 0,
+      error: None,
+      msg: None,
+      data: Some(data),
+      quarantine_id: None,
+    })
+  }
+
+  pub fn err(msg: impl ToString) -> Json<Self> {
+    let msg = msg.to_string();
+    debug!("Failed: {}", msg);
+
+    Json(Self {
+      code: 1,
+      error: None,
+      msg: Some(msg),
+      data: None,
+      quarantine_id: None,
+    })
+  }
+
+  pub fn err_with(error: ErrorCode, msg: impl ToString) -> Json<Self> {
+    let msg = msg.to_string();
+    debug!("Failed ({:?}): {}", error, msg);
+
+    Json(Self {
+      code: 1,
+      error: Some(error),
+      msg: Some(msg),
+      data: None,
+      quarantine_id: None,
+    })
+  }
+}
+
+/// 错误类型
+#[derive(Debug, Enum)]
+#[oai(rename_all = "snake_case")]
+pub enum ErrorCode {
+  /// 服务已暂停接受打印任务
+  Paused,
+  /// 客户端无权使用该打印机
+  PrinterForbidden,
+  /// 缺少或错误的 API 密钥
+  Unauthorized,
+  /// 缺少打印令牌
+  TokenRequired,
+  /// 打印令牌格式或签名错误
+  TokenInvalid,
+  /// 打印令牌已过期
+  TokenExpired,
+  /// 打印令牌已使用过
+  TokenReused,
+  /// 打印超出令牌允许的范围
+  TokenOutOfScope,
+  /// 打印机脱机、出错或缺纸等，暂时无法打印
+  PrinterNotReady,
+  /// 缺少模板的必填变量
+  MissingVariables,
+  /// 打印机名称对应多台打印机，需要指定打印机 ID
+  AmbiguousPrinter,
+  /// 找不到指定的打印机
+  PrinterNotFound,
+  /// 打印机不支持请求的打印设置，如纸张大小或方向
+  UnsupportedSettings,
+  /// 操作需要以管理员身份运行服务
+  RequiresAdministrator,
+  /// 处理请求时程序崩溃，见错误信息
+  InternalPanic,
+  /// 路径不存在
+  NotFound,
+  /// 路径不支持该请求方法
+  MethodNotAllowed,
+  /// 请求体过大
+  PayloadTooLarge,
+  /// 请求参数或请求体格式错误，错误信息中包含出错的字段
+  InvalidRequest,
+  /// 服务内部错误
+  InternalError,
+  /// 提交到打印队列前已超过请求的截止时间，任务已取消
+  DeadlineExceeded,
+  /// 不支持请求头 `Content-Encoding` 指定的压缩方式
+  UnsupportedEncoding,
+  /// 打印前命令拒绝了文档，错误信息中包含命令的错误输出
+  RejectedByHook,
+  /// 打印前命令超时未结束，文档未打印
+  HookTimedOut,
+  /// 释放暂缓任务的 PIN 缺失或错误
+  InvalidReleasePin,
+  /// 分块或合并后文件的 SHA-256 与提供的不符，需要重新上传
+  ChecksumMismatch,
+  /// 上传缺少分块，错误信息中列出缺少的分块序号
+  IncompleteUpload,
+  /// 进行中的上传数或总大小已达到上限，需要等待其他上传完成或过期
+  UploadQuotaExceeded,
+  /// 打印机在服务内排队的任务数或总大小已达到上限，错误信息中包括排队的任务数、总大小及最早的任务已等待的时间
+  QueueFull,
+}
+
+/// 带错误类型的错误，处理请求时转换为对应的 HTTP 状态和错误类型
+#[derive(Debug)]
+pub struct CodedError {
+  pub status: StatusCode,
+  pub code: ErrorCode,
+  pub msg: String,
+  /// 被拒绝的请求的隔离 ID
+  pub quarantine_id: Option<String>,
+}
+
+impl CodedError {
+  pub fn new(status: StatusCode, code: ErrorCode, msg: impl ToString) -> Self {
+    Self {
+      status,
+      code,
+      msg: msg.to_string(),
+      quarantine_id: None,
+    }
+  }
+
+  /// 暂停接受打印任务，信息中包括自动恢复的时间及管理员的说明
+  pub fn paused() -> Self {
+    let mut msg = "Printing is paused".to_string();
+    if let Some(pause) = status::pause() {
+      if let Some(resume_at) = pause.resume_at {
+        msg.push_str(&format!(" until {}", resume_at.to_rfc3339()));
+      }
+      if let Some(message) = pause.message {
+        msg.push_str(&format!(": {}", message));
+      }
+    }
+    Self::new(StatusCode::SERVICE_UNAVAILABLE, ErrorCode::Paused, msg)
+  }
+
+  pub fn quarantined(self, quarantine_id: Option<String>) -> Self {
+    Self {
+      quarantine_id,
+      ..self
+    }
+  }
+
+  pub fn forbidden(printer: &str) -> Self {
+    Self::new(
+      StatusCode::FORBIDDEN,
+      ErrorCode::PrinterForbidden,
+      format!("Access to printer {} is not allowed", printer),
+    )
+  }
+
+  pub fn unauthorized() -> Self {
+    Self::new(
+      StatusCode::UNAUTHORIZED,
+      ErrorCode::Unauthorized,
+      "Invalid API key",
+    )
+  }
+
+  pub fn out_of_scope(msg: impl ToString) -> Self {
+    Self::new(StatusCode::FORBIDDEN, ErrorCode::TokenOutOfScope, msg)
+  }
+
+  /// 按状态码转换框架返回的错误，如路径不存在、请求体解析失败等
+  pub fn from_status(status: StatusCode, msg: impl ToString) -> Self {
+    let code = match status {
+      StatusCode::NOT_FOUND => ErrorCode::NotFound,
+      StatusCode::METHOD_NOT_ALLOWED => ErrorCode::MethodNotAllowed,
+      StatusCode::PAYLOAD_TOO_LARGE => ErrorCode::PayloadTooLarge,
+      status if status.is_client_error() => ErrorCode::InvalidRequest,
+      _ => ErrorCode::InternalError,
+    };
+    Self::new(status, code, msg)
+  }
+
+  pub fn deadline_exceeded(msg: impl ToString) -> Self {
+    Self::new(
+      StatusCode::GATEWAY_TIMEOUT,
+      ErrorCode::DeadlineExceeded,
+      msg,
+    )
+  }
+
+  pub fn panic(err: &(dyn Any + Send)) -> Self {
+    let msg = err
+      .downcast_ref::<&str>()
+      .copied()
+      .or_else(|| err.downcast_ref::<String>().map(String::as_str))
+      .unwrap_or("unknown panic");
+    Self::new(
+      StatusCode::INTERNAL_SERVER_ERROR,
+      ErrorCode::InternalPanic,
+      format!("Internal error: {}", msg),
+    )
+  }
+
+  /// 转换为带统一响应的 poem 错误
+  pub fn into_error<T>(self) -> poem::Error
+  where
+    T: ParseFromJSON + ToJSON + std::fmt::Debug,
+    Response<T>: ToJSON,
+  {
+    let mut resp = Response::<T>::err_with(self.code, self.msg);
+    resp.0.quarantine_id = self.quarantine_id;
+    poem::Error::from_response(resp.with_status(self.status).into_response())
+  }
+}
+
+impl std::fmt::Display for CodedError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str(&self.msg)
+  }
+}
+
+impl std::error::Error for CodedError {}
+
+impl From<TokenError> for CodedError {
+  fn from(e: TokenError) -> Self {
+    let (code, msg) = match e {
+      TokenError::Invalid => (ErrorCode::TokenInvalid, "Invalid print token"),
+      TokenError::Expired => (ErrorCode::TokenExpired, "Print token expired"),
+      TokenError::Reused => (ErrorCode::TokenReused, "Print token already used"),
+    };
+    Self::new(StatusCode::UNAUTHORIZED, code, msg)
+  }
+}
+
+impl<T> Response<T>
+where
+  T: ParseFromJSON + ToJSON,
+{
+  pub fn example_of(data: T) -> Self {
+    Self {
+      code: 0,
+      error: None,
+      msg: None,
+      data: Some(data),
+      quarantine_id: None,
+    }
+  }
+}
+
+/// 处理请求时崩溃的响应，仍使用统一响应，状态码为 500
+pub fn panic_response(err: Box<dyn Any + Send>) -> poem::Response {
+  let err = CodedError::panic(&*err);
+  error!("Request panicked: {}", err);
+  err.into_error::<String>().into_response()
+}
+
+/// 将框架返回的错误转换为统一响应，保留状态码。已经是统一响应的错误保持不变
+pub async fn error_response(err: poem::Error) -> poem::Response {
+  if err.is_from_response() {
+    return err.into_response();
+  }
+
+  debug!("Request failed: {}", err);
+  CodedError::from_status(err.status(), &err)
+    .into_error::<String>()
+    .into_response()
+}
@@ -0,0 +1,110 @@
+use std::sync::LazyLock;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use super::{
+  receipt::{Outcome, Receipt},
+  schedule,
+};
+
+/// 未及时接收的事件数超过此数量时，较早的事件被丢弃
+const CAPACITY: usize = 256;
+
+/// 任务的状态
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+  /// 保存为定时任务，包括打印机暂停时排队的任务
+  Scheduled,
+  /// 暂缓打印，等待释放
+  Held,
+  /// 被优先级更高的任务超过，仍在排队
+  Overtaken,
+  /// 开始打印
+  Printing,
+  /// 打印进度变化，见 `pages_printed`，最多每秒一次
+  Progress,
+  Printed,
+  Failed,
+  Expired,
+  Cancelled,
+}
+
+impl From<Outcome> for JobStatus {
+  fn from(outcome: Outcome) -> Self {
+    match outcome {
+      Outcome::Printed => Self::Printed,
+      Outcome::Failed => Self::Failed,
+      Outcome::Expired => Self::Expired,
+      Outcome::Cancelled => Self::Cancelled,
+    }
+  }
+}
+
+/// 任务状态的变化
+#[derive(Debug, Clone, Serialize)]
+pub struct JobEvent {
+  pub job_id: String,
+  pub status: JobStatus,
+  pub printer: String,
+  /// 打印的总页数，结束时才有
+  pub pages: Option<u32>,
+  /// 打印机已打印的页数，打印进度变化时才有
+  pub pages_printed: Option<u32>,
+  /// 失败或取消的原因
+  pub detail: Option<String>,
+  /// 在打印机队列中的位置，从 1 开始，队列变化时才有
+  pub queue_position: Option<u32>,
+  /// 预计多少秒后开始打印，打印记录不足或打印机暂停时为空
+  pub estimated_start_seconds: Option<u64>,
+  pub at: DateTime<Utc>,
+}
+
+impl JobEvent {
+  pub fn new(job_id: &str, status: JobStatus, printer: &str) -> Self {
+    Self {
+      job_id: job_id.to_string(),
+      status,
+      printer: printer.to_string(),
+      pages: None,
+      pages_printed: None,
+      detail: None,
+      queue_position: None,
+      estimated_start_seconds: None,
+      at: Utc::now(),
+    }
+  }
+}
+
+impl From<&Receipt> for JobEvent {
+  fn from(receipt: &Receipt) -> Self {
+    Self {
+      job_id: receipt.job_id.clone(),
+      status: receipt.outcome.into(),
+      printer: receipt.printer.clone(),
+      pages: receipt.pages,
+      pages_printed: None,
+      detail: receipt.detail.clone(),
+      queue_position: None,
+      estimated_start_seconds: None,
+      at: receipt.finished_at,
+    }
+  }
+}
+
+static EVENTS: LazyLock<broadcast::Sender<JobEvent>> =
+  LazyLock::new(|| broadcast::channel(CAPACITY).0);
+
+/// 通知任务状态的变化，没有订阅者时忽略
+pub fn publish(event: JobEvent) {
+  schedule::notify(&event.job_id);
+  let _ = EVENTS.send(event);
+}
+
+/// 订阅之后的任务状态变化
+#[cfg(feature = "mqtt")]
+pub fn subscribe() -> broadcast::Receiver<JobEvent> {
+  EVENTS.subscribe()
+}
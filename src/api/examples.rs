@@ -0,0 +1,859 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use poem_openapi::types::{Base64, Example};
+
+use super::{
+  error::Response,
+  upload,
+  v1::{
+    CapabilityChangeSet, ChunkRange, ConfigBundle, CoverageEstimate, CoveragePayload,
+    CustomPageSize, DailyReport, DefaultSettings, Diagnostics, DocumentInfo, DriverFeature,
+    DriverValueType, ExpiredArtifact, ExtractPayload, ImageType, InkChannels, InstanceInfo, Job,
+    JobPriority, JobReceipt, JobState, LabelElement, LabelElementType, LabelPayload, MergePayload,
+    Orientation, PageCoverage, PageImage, PageSize, PausePayload, PdfFile, PostPrintHook,
+    PostPrintHookState, Pricing, PrintPayload, PrintResult, PrintSettings, PrintTimings,
+    PrintToken, PrinterCapability, PrinterCapabilityResult, PrinterConnectionPayload, PrinterEntry,
+    PrinterFormat, PrinterList, PrinterReport, PrinterState, PrinterWarmup, PublicKey, PurgeResult,
+    QuarantineRecord, QuarantinedDocument, RenderPayload, ReportErrorCount, ReportTotals,
+    RetentionClass, RetentionPreview, ServicePause, SettingsValidation, SpoolerHealth,
+    SpoolerRestart, TableColumn, TablePayload, Template, TemplateField, TemplatePayload,
+    TemplatePrintPayload, TextAlign, TokenRequest, UploadPayload, UploadPrintPayload, UploadStatus,
+    WarmupResult, DEFAULT_COVERAGE_DPI,
+  },
+};
+
+impl Example for PrinterCapability {
+  fn example() -> Self {
+    Self {
+      max_copies: Some(999),
+      orientations: Some(vec![Orientation::Portrait, Orientation::Landscape]),
+      page_sizes: Some(vec![PageSize {
+        name: Some("A4".to_string()),
+        width: 210000,
+        height: 297000,
+        dimensions: None,
+        standard: Some("A4".to_string()),
+        imageable_area: None,
+        auto_height: None,
+      }]),
+      custom_page_size: Some(CustomPageSize {
+        min_width: 10000,
+        max_width: 108000,
+        min_height: 10000,
+        max_height: 2000000,
+      }),
+      driver_features: Some(vec![DriverFeature {
+        name: "Darkness".to_string(),
+        namespace: "http://schemas.example.com/thermal".to_string(),
+        display_name: Some("Darkness".to_string()),
+        options: None,
+        value_type: Some(DriverValueType::Integer),
+        min_value: Some(0),
+        max_value: Some(30),
+        default_value: Some("15".to_string()),
+      }]),
+      details: None,
+      state: None,
+    }
+  }
+}
+
+impl Example for PrintSettings {
+  fn example() -> Self {
+    Self {
+      printer: "Gprinter GP-1134T".to_string(),
+      copies: Some(1),
+      orientation: Some(Orientation::Portrait),
+      page_size: Some(PageSize {
+        width: 100000,
+        height: 150000,
+        ..Default::default()
+      }),
+      ..Default::default()
+    }
+  }
+}
+
+impl Example for PrintPayload {
+  fn example() -> Self {
+    Self {
+      file: Some(Base64(b"%PDF-1.7\n...".to_vec())),
+      settings: Some(PrintSettings::example()),
+      ..Default::default()
+    }
+  }
+}
+
+impl Example for PrintResult {
+  fn example() -> Self {
+    Self {
+      printer: Some("Gprinter GP-1134T".to_string()),
+      failed_printers: None,
+      pages: vec![1, 2, 3],
+      rotate_degrees: None,
+      rotated_pages: None,
+      sheets: None,
+      total_pages: Some(3),
+      total_sheets: Some(3),
+      estimated_cost: None,
+      warnings: None,
+      timings: PrintTimings::default(),
+      attempts: 1,
+      retried_errors: None,
+      state: None,
+      job_id: None,
+      print_at: None,
+      document: None,
+      ticket: None,
+      deadline_exceeded: None,
+      chunks: None,
+      coverage: None,
+      page_height: None,
+    }
+  }
+}
+
+impl Example for Response<String> {
+  fn example() -> Self {
+    Self::example_of("ok".to_string())
+  }
+}
+
+impl Example for Response<Vec<String>> {
+  fn example() -> Self {
+    Self::example_of(vec!["Microsoft Print to PDF".to_string()])
+  }
+}
+
+impl Example for Response<PrinterCapability> {
+  fn example() -> Self {
+    Self::example_of(PrinterCapability::example())
+  }
+}
+
+impl Example for Response<HashMap<String, PrinterCapabilityResult>> {
+  fn example() -> Self {
+    Self::example_of(HashMap::from([
+      (
+        "Gprinter GP-1134T".to_string(),
+        PrinterCapabilityResult {
+          capability: Some(PrinterCapability::example()),
+          error: None,
+        },
+      ),
+      (
+        "Microsoft Print to PDF".to_string(),
+        PrinterCapabilityResult {
+          capability: None,
+          error: Some("The RPC server is unavailable.".to_string()),
+        },
+      ),
+    ]))
+  }
+}
+
+impl Example for Response<PrintSettings> {
+  fn example() -> Self {
+    Self::example_of(PrintSettings::example())
+  }
+}
+
+impl Example for Response<DefaultSettings> {
+  fn example() -> Self {
+    Self::example_of(DefaultSettings {
+      settings: PrintSettings::example(),
+      validation: SettingsValidation {
+        valid: true,
+        issues: Vec::new(),
+      },
+    })
+  }
+}
+
+impl Example for Response<SettingsValidation> {
+  fn example() -> Self {
+    Self::example_of(SettingsValidation {
+      valid: false,
+      issues: vec!["Printer Gprinter GP-1134T is not installed".to_string()],
+    })
+  }
+}
+
+impl Example for Response<ConfigBundle> {
+  fn example() -> Self {
+    Self::example_of(ConfigBundle {
+      schema_version: "1.0".to_string(),
+      default_settings: Some(PrintSettings::example()),
+      templates: Some(Vec::new()),
+    })
+  }
+}
+
+impl Example for TokenRequest {
+  fn example() -> Self {
+    Self {
+      printer: "Gprinter GP-1134T".to_string(),
+      max_pages: Some(10),
+      expires_in: Some(300),
+    }
+  }
+}
+
+impl Example for Response<PrintToken> {
+  fn example() -> Self {
+    Self::example_of(PrintToken {
+      token: "eyJwcmludGVyIjoiR3ByaW50ZXIgR1AtMTEzNFQifQ.c2lnbmF0dXJl".to_string(),
+      expires_at: 1735689600,
+    })
+  }
+}
+
+impl Example for Response<PrintResult> {
+  fn example() -> Self {
+    Self::example_of(PrintResult::example())
+  }
+}
+
+impl Example for PrinterState {
+  fn example() -> Self {
+    Self {
+      paused: false,
+      spooler_paused: Some(false),
+      problems: Some(Vec::new()),
+      queued_jobs: 3,
+      queued_bytes: 1_572_864,
+      oldest_queued_seconds: Some(420),
+      max_queued_jobs: Some(50),
+      max_queued_bytes: Some(536_870_912),
+    }
+  }
+}
+
+impl Example for Response<PrinterState> {
+  fn example() -> Self {
+    Self::example_of(PrinterState::example())
+  }
+}
+
+impl Example for Pricing {
+  fn example() -> Self {
+    Self {
+      currency: "CNY".to_string(),
+      mono_page: 0.1,
+      color_page: 0.5,
+      sheet: 0.05,
+      default_color: false,
+    }
+  }
+}
+
+impl Example for Response<Pricing> {
+  fn example() -> Self {
+    Self::example_of(Pricing::example())
+  }
+}
+
+impl Example for MergePayload {
+  fn example() -> Self {
+    Self {
+      files: vec![
+        Base64(b"%PDF-1.7\n...".to_vec()),
+        Base64(b"%PDF-1.7\n...".to_vec()),
+      ],
+    }
+  }
+}
+
+impl Example for ExtractPayload {
+  fn example() -> Self {
+    Self {
+      file: Base64(b"%PDF-1.7\n...".to_vec()),
+      page_range: "1-3,5".to_string(),
+    }
+  }
+}
+
+impl Example for RenderPayload {
+  fn example() -> Self {
+    Self {
+      file: Base64(b"%PDF-1.7\n...".to_vec()),
+      pages: Some(vec![1, 2]),
+      width: Some(200),
+      format: Some(ImageType::Png),
+    }
+  }
+}
+
+impl Example for Response<Vec<PageImage>> {
+  fn example() -> Self {
+    Self::example_of(vec![PageImage {
+      page: 1,
+      image: Base64(b"\x89PNG...".to_vec()),
+      width: 200,
+      height: 283,
+      page_width: 210000,
+      page_height: 297000,
+    }])
+  }
+}
+
+impl Example for Response<PdfFile> {
+  fn example() -> Self {
+    Self::example_of(PdfFile {
+      file: Base64(b"%PDF-1.7\n...".to_vec()),
+      pages: 2,
+    })
+  }
+}
+
+impl Example for Response<PrinterList> {
+  fn example() -> Self {
+    Self::example_of(PrinterList::Detailed(vec![PrinterEntry {
+      id: "5d41402abc4b2a76".to_string(),
+      name: "HP LaserJet".to_string(),
+      port: "USB001".to_string(),
+      driver: "HP Universal Printing PCL 6".to_string(),
+      location: Some("3rd floor copy room".to_string()),
+      comment: None,
+      shared: false,
+      share_name: None,
+      remote: false,
+      supported_formats: vec![PrinterFormat::Pdf, PrinterFormat::Raw, PrinterFormat::Text],
+      aliases: None,
+    }]))
+  }
+}
+
+impl Example for SpoolerHealth {
+  fn example() -> Self {
+    Self {
+      running: true,
+      state: "running".to_string(),
+      started_at: Some(Utc::now() - TimeDelta::hours(5)),
+      uptime_seconds: Some(5 * 3600),
+    }
+  }
+}
+
+impl Example for Response<Diagnostics> {
+  fn example() -> Self {
+    Self::example_of(Diagnostics {
+      spooler: Some(SpoolerHealth::example()),
+      spooler_error: None,
+      printers: 4,
+      paused: false,
+      printed: 128,
+      failed: 2,
+    })
+  }
+}
+
+impl Example for Response<SpoolerRestart> {
+  fn example() -> Self {
+    Self::example_of(SpoolerRestart {
+      spooler: SpoolerHealth::example(),
+      printers: vec!["Gprinter GP-1134T".to_string()],
+    })
+  }
+}
+
+impl Example for Response<JobReceipt> {
+  fn example() -> Self {
+    Self::example_of(JobReceipt {
+      receipt: r#"{"job_id":"5f0c9b7e2d4a41c8a3e1f6b2c7d8e9f0","document_sha256":"9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08","printer":"Gprinter GP-1134T","pages":3,"submitted_at":"2025-03-01T08:00:00Z","finished_at":"2025-03-01T08:00:01Z","outcome":"printed","detail":null}"#.to_string(),
+      signature: "mR1mF0tq2u7nVd3+8p1H0d1c6Qj8m2wz3V0XkQ9o1b6r0G5c9sH7p2Y4f3E8x6n1a0Jk5t2L9q3W7e4R1u8iBw==".to_string(),
+      post_print_hook: Some(PostPrintHook {
+        state: PostPrintHookState::Succeeded,
+        started_at: DateTime::parse_from_rfc3339("2025-03-01T08:00:01Z").unwrap().to_utc(),
+        finished_at: Some(DateTime::parse_from_rfc3339("2025-03-01T08:00:02Z").unwrap().to_utc()),
+        output: vec!["Drawer opened".to_string()],
+        error: None,
+      }),
+    })
+  }
+}
+
+impl Example for Response<QuarantineRecord> {
+  fn example() -> Self {
+    Self::example_of(QuarantineRecord {
+      id: "Q-1F3A9C2E".to_string(),
+      created_at: DateTime::parse_from_rfc3339("2025-03-12T01:30:00Z")
+        .unwrap()
+        .to_utc(),
+      error: None,
+      reason: "Failed to print: Only PDF files can be printed, the file is png".to_string(),
+      request: serde_json::json!({
+        "file": "<redacted, 2048 bytes>",
+        "settings": { "printer": "Microsoft Print to PDF" }
+      }),
+      files: vec![QuarantinedDocument {
+        size: 2048,
+        sha256: "b7e23ec29af22b0b4e41da31e868d57226121c84c1d2e3f4a5b6c7d8e9f0a1b2".to_string(),
+        content: Some(Base64(b"\x89PNG...".to_vec())),
+      }],
+      omitted: false,
+    })
+  }
+}
+
+impl Example for Response<CapabilityChangeSet> {
+  fn example() -> Self {
+    Self::example_of(CapabilityChangeSet {
+      printer: "Gprinter GP-1134T".to_string(),
+      previous_at: DateTime::parse_from_rfc3339("2025-03-01T08:00:00Z")
+        .unwrap()
+        .to_utc(),
+      detected_at: DateTime::parse_from_rfc3339("2025-03-12T01:30:00Z")
+        .unwrap()
+        .to_utc(),
+      previous_sha256: "4e1f0c3b9a7d2e8f6a5b4c3d2e1f0a9b8c7d6e5f4a3b2c1d0e9f8a7b6c5d4e3f"
+        .to_string(),
+      sha256: "b7e23ec29af22b0b4e41da31e868d57226121c84c1d2e3f4a5b6c7d8e9f0a1b2".to_string(),
+      added_page_sizes: vec!["USER 100x150mm".to_string()],
+      removed_page_sizes: vec!["4x6".to_string()],
+      added_orientations: Vec::new(),
+      removed_orientations: Vec::new(),
+      previous_max_copies: None,
+      max_copies: None,
+      affected_settings: vec!["Default settings: Page size 4x6 is not supported".to_string()],
+    })
+  }
+}
+
+impl Example for Response<DailyReport> {
+  fn example() -> Self {
+    let totals = || ReportTotals {
+      jobs: 42,
+      printed: 40,
+      failed: 2,
+      expired: 0,
+      cancelled: 0,
+      pages: 57,
+      sheets: 57,
+      top_errors: vec![ReportErrorCount {
+        error: "printer_not_ready".to_string(),
+        count: 2,
+      }],
+    };
+    Self::example_of(DailyReport {
+      date: NaiveDate::from_ymd_opt(2025, 3, 1).unwrap(),
+      timezone: "Asia/Shanghai".to_string(),
+      start: DateTime::parse_from_rfc3339("2025-02-28T16:00:00Z")
+        .unwrap()
+        .to_utc(),
+      end: DateTime::parse_from_rfc3339("2025-03-01T16:00:00Z")
+        .unwrap()
+        .to_utc(),
+      total: totals(),
+      printers: vec![PrinterReport {
+        printer: "Gprinter GP-1134T".to_string(),
+        totals: totals(),
+      }],
+    })
+  }
+}
+
+impl Example for CoveragePayload {
+  fn example() -> Self {
+    Self {
+      file: Base64(b"%PDF-1.7\n...".to_vec()),
+      pages: None,
+      dpi: Some(DEFAULT_COVERAGE_DPI),
+    }
+  }
+}
+
+impl Example for CoverageEstimate {
+  fn example() -> Self {
+    Self {
+      pages: vec![
+        PageCoverage {
+          page: 1,
+          coverage: 6.12,
+          color: false,
+          channels: None,
+        },
+        PageCoverage {
+          page: 2,
+          coverage: 18.4,
+          color: true,
+          channels: Some(InkChannels {
+            cyan: 9.75,
+            magenta: 12.3,
+            yellow: 4.1,
+            black: 11.02,
+          }),
+        },
+      ],
+      coverage: 12.26,
+      channels: Some(InkChannels {
+        cyan: 4.88,
+        magenta: 6.15,
+        yellow: 2.05,
+        black: 8.57,
+      }),
+      dpi: DEFAULT_COVERAGE_DPI,
+    }
+  }
+}
+
+impl Example for Response<CoverageEstimate> {
+  fn example() -> Self {
+    Self::example_of(CoverageEstimate::example())
+  }
+}
+
+impl Example for InstanceInfo {
+  fn example() -> Self {
+    Self {
+      instance_id: "0f8c2b6e-4d1a-4c3f-9a57-2e6b8d0c1f49".to_string(),
+      hostname: "KIOSK-03".to_string(),
+      os_version: Some("Windows 10 Pro 22H2 (build 19045.4046)".to_string()),
+      version: env!("CARGO_PKG_VERSION").to_string(),
+      built_at: None,
+      commit: Some("e13b5c0a7f21".to_string()),
+      features: vec!["with-ui".to_string()],
+      data_dir: Some(r"C:\Users\kiosk\AppData\Local\ubesthelp\direct-printing\data".to_string()),
+      config_dir: Some(
+        r"C:\Users\kiosk\AppData\Local\ubesthelp\direct-printing\config".to_string(),
+      ),
+      spool_dir: Some(r"C:\Windows\system32\spool\PRINTERS".to_string()),
+      pause: None,
+    }
+  }
+}
+
+impl Example for ServicePause {
+  fn example() -> Self {
+    Self {
+      paused: true,
+      paused_at: DateTime::parse_from_rfc3339("2025-03-01T12:00:00Z")
+        .ok()
+        .map(|t| t.to_utc()),
+      message: Some("Printer maintenance".to_string()),
+      resume_at: DateTime::parse_from_rfc3339("2025-03-01T14:00:00Z")
+        .ok()
+        .map(|t| t.to_utc()),
+    }
+  }
+}
+
+impl Example for Response<ServicePause> {
+  fn example() -> Self {
+    Self::example_of(ServicePause::example())
+  }
+}
+
+impl Example for RetentionPreview {
+  fn example() -> Self {
+    Self {
+      classes: vec![RetentionClass {
+        class: "quarantine".to_string(),
+        max_age_days: Some(30),
+        max_count: Some(100),
+        max_bytes: None,
+        count: 102,
+        bytes: 8_388_608,
+        expired_count: 2,
+        expired_bytes: 65_536,
+        expired: vec![ExpiredArtifact {
+          id: "Q-3F9A12C0".to_string(),
+          modified_at: DateTime::parse_from_rfc3339("2025-01-20T09:30:00Z")
+            .map(|t| t.to_utc())
+            .unwrap_or_default(),
+          size: 32_768,
+        }],
+        error: None,
+      }],
+    }
+  }
+}
+
+impl Example for Response<RetentionPreview> {
+  fn example() -> Self {
+    Self::example_of(RetentionPreview::example())
+  }
+}
+
+impl Example for PausePayload {
+  fn example() -> Self {
+    Self {
+      message: Some("Printer maintenance".to_string()),
+      resume_at: DateTime::parse_from_rfc3339("2025-03-01T14:00:00+08:00").ok(),
+    }
+  }
+}
+
+impl Example for Response<InstanceInfo> {
+  fn example() -> Self {
+    Self::example_of(InstanceInfo::example())
+  }
+}
+
+impl Example for Response<PublicKey> {
+  fn example() -> Self {
+    Self::example_of(PublicKey {
+      algorithm: "Ed25519".to_string(),
+      key: "Gb9ECWmEzf6FQbrBZ9w7lshQhqowtrbLDFw4rXAxZuE=".to_string(),
+    })
+  }
+}
+
+impl Example for Response<WarmupResult> {
+  fn example() -> Self {
+    Self::example_of(WarmupResult {
+      pdfium: 35,
+      pdfium_error: None,
+      printers_elapsed: 12,
+      printers: vec![PrinterWarmup {
+        printer: "Gprinter GP-1134T".to_string(),
+        capabilities: Some(420),
+        ticket: Some(180),
+        error: None,
+      }],
+      total: 650,
+    })
+  }
+}
+
+impl Example for Response<PurgeResult> {
+  fn example() -> Self {
+    Self::example_of(PurgeResult {
+      internal_jobs: 3,
+      spooler_jobs: 12,
+    })
+  }
+}
+
+impl Example for Response<Vec<Job>> {
+  fn example() -> Self {
+    Self::example_of(vec![Response::<Job>::example().data.unwrap()])
+  }
+}
+
+impl Example for Response<Job> {
+  fn example() -> Self {
+    Self::example_of(Job {
+      id: "3f2a9c1e8b7d4a6f9e0c1b2a3d4e5f60".to_string(),
+      state: JobState::Scheduled,
+      printer: "Gprinter GP-1134T".to_string(),
+      print_at: DateTime::parse_from_rfc3339("2025-01-01T06:00:00+08:00").unwrap(),
+      ttl_seconds: Some(3600),
+      priority: Some(JobPriority::Normal),
+      job_name: Some("Order 10086 shipping label".to_string()),
+      document: Some(DocumentInfo {
+        title: Some("Order 10086 shipping label".to_string()),
+        author: None,
+        producer: Some("Skia/PDF m120".to_string()),
+        creation_date: Some(DateTime::parse_from_rfc3339("2025-01-01T05:58:00+08:00").unwrap()),
+      }),
+      queue_position: None,
+      estimated_start_seconds: None,
+      reason: None,
+      post_print_hook: None,
+      pages_printed: None,
+    })
+  }
+}
+
+impl Example for PrinterConnectionPayload {
+  fn example() -> Self {
+    Self {
+      path: r"\\server\FrontOfficePrinter".to_string(),
+    }
+  }
+}
+
+impl Example for TemplatePayload {
+  fn example() -> Self {
+    Self {
+      name: "Badge".to_string(),
+      file: Base64(b"%PDF-1.7\n...".to_vec()),
+    }
+  }
+}
+
+impl Example for Template {
+  fn example() -> Self {
+    Self {
+      id: "9b1c7e2d4f6a8b0c1d3e5f7a9b2c4d6e".to_string(),
+      name: "Badge".to_string(),
+      fields: vec![
+        TemplateField {
+          name: "name".to_string(),
+          required: true,
+          checkbox: false,
+        },
+        TemplateField {
+          name: "vip".to_string(),
+          required: false,
+          checkbox: true,
+        },
+      ],
+      created_at: DateTime::parse_from_rfc3339("2025-01-01T00:00:00Z")
+        .unwrap()
+        .to_utc(),
+    }
+  }
+}
+
+impl Example for TemplatePrintPayload {
+  fn example() -> Self {
+    let item = |name: &str, vip: bool| {
+      HashMap::from([
+        ("name".to_string(), name.to_string()),
+        ("vip".to_string(), vip.to_string()),
+      ])
+    };
+
+    Self {
+      variables: None,
+      items: Some(vec![item("Alice", true), item("Bob", false)]),
+      settings: Some(PrintSettings::example()),
+      queue_if_not_ready: None,
+      print_at: None,
+      ttl_seconds: None,
+    }
+  }
+}
+
+impl Example for TablePayload {
+  fn example() -> Self {
+    Self {
+      csv: Some("SKU,Name,Qty\nA-001,Widget,12\nB-002,Gadget,3\n".to_string()),
+      csv_header: Some(true),
+      rows: None,
+      columns: Some(vec![
+        TableColumn {
+          header: "SKU".to_string(),
+          width: Some(30000),
+          align: None,
+        },
+        TableColumn {
+          header: "Name".to_string(),
+          width: None,
+          align: None,
+        },
+        TableColumn {
+          header: "Qty".to_string(),
+          width: Some(20000),
+          align: Some(TextAlign::Right),
+        },
+      ]),
+      margin: None,
+      font_size: None,
+      zebra: Some(true),
+      dry_run: None,
+      settings: Some(PrintSettings::example()),
+      queue_if_not_ready: None,
+      print_at: None,
+      ttl_seconds: None,
+    }
+  }
+}
+
+impl Example for LabelPayload {
+  fn example() -> Self {
+    let element = |kind, data: &str, x: &str, y: &str| LabelElement {
+      kind,
+      data: data.to_string(),
+      x: x.to_string(),
+      y: y.to_string(),
+      width: None,
+      height: None,
+      font_size: None,
+    };
+
+    Self {
+      width: 50.0,
+      height: 30.0,
+      elements: vec![
+        LabelElement {
+          width: Some("24mm".to_string()),
+          ..element(
+            LabelElementType::Qr,
+            "https://example.com/p/A-001",
+            "3",
+            "3",
+          )
+        },
+        LabelElement {
+          font_size: Some(12.0),
+          ..element(LabelElementType::Text, "A-001", "56%", "5")
+        },
+        element(LabelElementType::Text, "Widget\nShelf 3", "56%", "40%"),
+      ],
+      settings: Some(PrintSettings::example()),
+      queue_if_not_ready: None,
+      print_at: None,
+      ttl_seconds: None,
+    }
+  }
+}
+
+impl Example for UploadPayload {
+  fn example() -> Self {
+    Self {
+      size: 209_715_200,
+      sha256: None,
+    }
+  }
+}
+
+impl Example for UploadStatus {
+  fn example() -> Self {
+    Self {
+      id: "3f2b8c1d9e4a4b7f8c6d5e4f3a2b1c0d".to_string(),
+      size: 209_715_200,
+      chunk_size: upload::CHUNK_SIZE,
+      chunk_count: 50,
+      received: vec![
+        ChunkRange { first: 0, last: 11 },
+        ChunkRange {
+          first: 13,
+          last: 20,
+        },
+      ],
+      missing: vec![12].into_iter().chain(21..50).collect(),
+      expires_at: DateTime::parse_from_rfc3339("2025-01-01T01:00:00Z")
+        .unwrap()
+        .to_utc(),
+    }
+  }
+}
+
+impl Example for UploadPrintPayload {
+  fn example() -> Self {
+    Self {
+      settings: Some(PrintSettings::example()),
+      queue_if_not_ready: None,
+      print_at: None,
+      ttl_seconds: None,
+    }
+  }
+}
+
+impl Example for Response<UploadStatus> {
+  fn example() -> Self {
+    Self::example_of(UploadStatus::example())
+  }
+}
+
+impl Example for Response<Box<PrintResult>> {
+  fn example() -> Self {
+    Self::example_of(Box::new(PrintResult::example()))
+  }
+}
+
+impl Example for Response<Template> {
+  fn example() -> Self {
+    Self::example_of(Template::example())
+  }
+}
+
+impl Example for Response<Vec<Template>> {
+  fn example() -> Self {
+    Self::example_of(vec![Template::example()])
+  }
+}
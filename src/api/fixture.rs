@@ -0,0 +1,194 @@
+use std::{
+  collections::BTreeMap,
+  fs::{create_dir_all, read, read_dir, read_to_string},
+  path::Path,
+};
+
+use anyhow::{bail, Context};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use winprint::ticket::{
+  document::{
+    reader::{ParsableXmlDocument, ParsePrintSchemaError},
+    PrintCapabilitiesDocument,
+  },
+  PrintCapabilities,
+};
+
+use crate::storage::{file_stem, write_atomically};
+
+/// 与驱动返回的 XML 一起保存的打印机信息
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FixtureInfo {
+  /// 打印机名称，文件名是编码后的名称，回放时以此为准
+  pub printer: String,
+  pub recorded_at: DateTime<Utc>,
+  /// 由打印机能力得到的 `GET /printers/:name` 结果，便于对照
+  #[serde(default)]
+  pub capability: Value,
+}
+
+/// 将驱动返回的打印机能力保存到 `dir`，分别为 `<打印机>.xml` 和 `<打印机>.json`
+pub fn record(dir: &Path, xml: &[u8], info: &FixtureInfo) -> anyhow::Result<()> {
+  create_dir_all(dir)?;
+  let stem = file_stem(&info.printer);
+
+  write_atomically(&dir.join(format!("{}.xml", stem)), xml)?;
+  write_atomically(
+    &dir.join(format!("{}.json", stem)),
+    serde_json::to_string_pretty(info)?,
+  )?;
+
+  Ok(())
+}
+
+/// 录制的打印机能力，回放时代替已安装的打印机，键为打印机名称，值为驱动返回的 XML
+#[derive(Debug)]
+pub struct Fixtures(BTreeMap<String, Vec<u8>>);
+
+impl Fixtures {
+  /// 读取 `dir` 中成对的 `<打印机>.json` 和 `<打印机>.xml`
+  pub fn load(dir: &Path) -> anyhow::Result<Self> {
+    let entries =
+      read_dir(dir).with_context(|| format!("Cannot read fixtures directory {}", dir.display()))?;
+    let mut fixtures = BTreeMap::new();
+
+    for entry in entries {
+      let path = entry?.path();
+      if path.extension().is_none_or(|ext| ext != "json") {
+        continue;
+      }
+
+      let json =
+        read_to_string(&path).with_context(|| format!("Cannot read fixture {}", path.display()))?;
+      let info: FixtureInfo = serde_json::from_str(&json)
+        .with_context(|| format!("Invalid fixture {}", path.display()))?;
+      let xml_path = path.with_extension("xml");
+      let xml =
+        read(&xml_path).with_context(|| format!("Cannot read fixture {}", xml_path.display()))?;
+      // 启动时检查，回放时不再出现解析错误
+      PrintCapabilitiesDocument::parse_from_bytes(xml.as_slice())
+        .with_context(|| format!("Invalid print capabilities {}", xml_path.display()))?;
+
+      fixtures.insert(info.printer, xml);
+    }
+
+    if fixtures.is_empty() {
+      bail!("No fixtures in {}", dir.display());
+    }
+
+    Ok(Self(fixtures))
+  }
+
+  /// 录制的打印机名称，按名称排序
+  pub fn printers(&self) -> impl Iterator<Item = &str> {
+    self.0.keys().map(String::as_str)
+  }
+
+  pub fn contains(&self, printer: &str) -> bool {
+    self.0.contains_key(printer)
+  }
+
+  /// 录制的打印机能力，没有该打印机时为空
+  pub fn capabilities(
+    &self,
+    printer: &str,
+  ) -> Option<Result<PrintCapabilities, ParsePrintSchemaError>> {
+    let xml = self.0.get(printer)?;
+    Some(
+      PrintCapabilitiesDocument::parse_from_bytes(xml.as_slice())
+        .map(|document| PrintCapabilities { document }),
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::PathBuf;
+
+  use super::{
+    super::{
+      capability::{get_orientations, get_page_sizes},
+      locale::Languages,
+      settings::resolve_settings,
+      v1::{PageSize, PageSizeSort, PrintSettings},
+    },
+    *,
+  };
+
+  /// 仓库中录制的打印机能力
+  fn shipped() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+  }
+
+  #[test]
+  fn loads_shipped_fixtures() {
+    let fixtures = Fixtures::load(&shipped()).unwrap();
+
+    assert_eq!(
+      fixtures.printers().collect::<Vec<_>>(),
+      ["Gprinter GP-1134T", "HP LaserJet Pro M404"]
+    );
+    assert!(fixtures.capabilities("Brother QL-820NWB").is_none());
+  }
+
+  #[test]
+  fn resolves_every_recorded_page_size() {
+    let fixtures = Fixtures::load(&shipped()).unwrap();
+
+    for printer in fixtures.printers() {
+      let cap = fixtures.capabilities(printer).unwrap().unwrap();
+      let orientations = get_orientations(&cap);
+      assert!(orientations.is_some_and(|o| !o.is_empty()), "{}", printer);
+
+      let sizes = get_page_sizes(&cap, None, PageSizeSort::Area, &Languages::default());
+      for size in sizes.unwrap() {
+        let settings = PrintSettings {
+          printer: printer.to_string(),
+          page_size: Some(PageSize {
+            name: size.name.clone(),
+            width: size.width,
+            height: size.height,
+            ..Default::default()
+          }),
+          ..Default::default()
+        };
+
+        let plan = resolve_settings(&cap, &settings).unwrap();
+        assert_eq!(
+          plan.media_size,
+          Some((size.width, size.height)),
+          "{} {:?}",
+          printer,
+          size.name
+        );
+      }
+    }
+  }
+
+  #[test]
+  fn replays_recorded_printers() {
+    let dir = tempfile::tempdir().unwrap();
+    let xml = read(shipped().join("HP LaserJet Pro M404.xml")).unwrap();
+    let printer = r"\\print-server\Front desk: HP";
+    let info = FixtureInfo {
+      printer: printer.to_string(),
+      recorded_at: Utc::now(),
+      capability: Value::Null,
+    };
+
+    record(dir.path(), &xml, &info).unwrap();
+    let fixtures = Fixtures::load(dir.path()).unwrap();
+
+    assert_eq!(fixtures.printers().collect::<Vec<_>>(), [printer]);
+    let cap = fixtures.capabilities(printer).unwrap().unwrap();
+    assert_eq!(cap.max_copies().map(|copies| copies.0), Some(999));
+  }
+
+  #[test]
+  fn rejects_a_directory_without_fixtures() {
+    let dir = tempfile::tempdir().unwrap();
+    assert!(Fixtures::load(dir.path()).is_err());
+  }
+}
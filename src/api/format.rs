@@ -0,0 +1,156 @@
+use std::fmt;
+
+/// 按内容识别的文件格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+  Pdf,
+  Png,
+  Jpeg,
+  Tiff,
+  /// XPS 文档，ZIP 包中有 OPC 的 `[Content_Types].xml` 及文档序列 `.fdseq`
+  Xps,
+  /// 其他 ZIP 文件
+  Zip,
+  /// 纯文本，可以带 UTF-8 或 UTF-16 的 BOM
+  Text,
+}
+
+impl fmt::Display for Format {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      Format::Pdf => "PDF",
+      Format::Png => "PNG",
+      Format::Jpeg => "JPEG",
+      Format::Tiff => "TIFF",
+      Format::Xps => "XPS",
+      Format::Zip => "ZIP",
+      Format::Text => "plain text",
+    })
+  }
+}
+
+/// PDF 文件头之前允许的字节数，与 Acrobat 及 Pdfium 的处理相同
+const PDF_HEADER_WINDOW: usize = 1024;
+/// 判断纯文本时检查的字节数
+const TEXT_SAMPLE: usize = 4096;
+
+/// 按内容识别文件格式，不依赖客户端声明的类型。魔数不完整或无法识别时为空，
+/// 截断的文件头为可打印字符时按纯文本处理
+pub fn detect_format(data: &[u8]) -> Option<Format> {
+  if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+    Some(Format::Png)
+  } else if data.starts_with(b"\xff\xd8\xff") {
+    Some(Format::Jpeg)
+  } else if data.starts_with(b"II*\0") || data.starts_with(b"MM\0*") {
+    Some(Format::Tiff)
+  } else if data.starts_with(b"PK\x03\x04") {
+    let opc = contains(data, b"[Content_Types].xml");
+    Some(if opc && contains(data, b".fdseq") {
+      Format::Xps
+    } else {
+      Format::Zip
+    })
+  } else if contains(&data[..data.len().min(PDF_HEADER_WINDOW)], b"%PDF-") {
+    Some(Format::Pdf)
+  } else if is_text(data) {
+    Some(Format::Text)
+  } else {
+    None
+  }
+}
+
+fn contains(data: &[u8], needle: &[u8]) -> bool {
+  data.windows(needle.len()).any(|window| window == needle)
+}
+
+/// 是否为纯文本：去掉 BOM 后开头部分为 UTF-8 且没有换行、制表等以外的控制字符
+fn is_text(data: &[u8]) -> bool {
+  if data.starts_with(b"\xfe\xff") || data.starts_with(b"\xff\xfe") {
+    return true;
+  }
+
+  let data = data.strip_prefix(b"\xef\xbb\xbf").unwrap_or(data);
+  if data.is_empty() {
+    return false;
+  }
+
+  let sample = &data[..data.len().min(TEXT_SAMPLE)];
+  // 截断的位置可能在多字节字符中间，只要求截断前的部分有效
+  let text = match std::str::from_utf8(sample) {
+    Ok(text) => text,
+    Err(e) if e.error_len().is_none() && sample.len() < data.len() => {
+      std::str::from_utf8(&sample[..e.valid_up_to()]).unwrap_or_default()
+    }
+    Err(_) => return false,
+  };
+
+  text
+    .chars()
+    .all(|c| !c.is_control() || matches!(c, '\t' | '\n' | '\r' | '\x0c'))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn detects_complete_magic_bytes() {
+    assert_eq!(detect_format(b"\x89PNG\r\n\x1a\n"), Some(Format::Png));
+    assert_eq!(detect_format(b"\xff\xd8\xff\xe0"), Some(Format::Jpeg));
+    assert_eq!(detect_format(b"II*\0"), Some(Format::Tiff));
+    assert_eq!(detect_format(b"MM\0*"), Some(Format::Tiff));
+    assert_eq!(detect_format(b"PK\x03\x04"), Some(Format::Zip));
+    assert_eq!(
+      detect_format(b"PK\x03\x04[Content_Types].xml FixedDocSeq.fdseq"),
+      Some(Format::Xps)
+    );
+    assert_eq!(detect_format(b"%PDF-1.7"), Some(Format::Pdf));
+  }
+
+  #[test]
+  fn rejects_truncated_magic_bytes() {
+    assert_eq!(detect_format(b""), None);
+    assert_eq!(detect_format(b"\x89PNG\r\n\x1a"), None);
+    assert_eq!(detect_format(b"\x89P"), None);
+    assert_eq!(detect_format(b"\xff\xd8"), None);
+    assert_eq!(detect_format(b"MM\0"), None);
+    assert_eq!(detect_format(b"PK\x03"), None);
+    // 截断的文件头是可打印字符，按纯文本处理，不会识别为原格式
+    assert_eq!(detect_format(b"%PDF"), Some(Format::Text));
+    assert_eq!(detect_format(b"II*"), Some(Format::Text));
+  }
+
+  #[test]
+  fn finds_pdf_headers_after_leading_bytes() {
+    let mut data = vec![b' '; PDF_HEADER_WINDOW - 8];
+    data.extend_from_slice(b"%PDF-1.4");
+    assert_eq!(detect_format(&data), Some(Format::Pdf));
+
+    let mut data = vec![0; PDF_HEADER_WINDOW];
+    data.extend_from_slice(b"%PDF-1.4");
+    assert_eq!(detect_format(&data), None);
+  }
+
+  #[test]
+  fn detects_text_with_a_bom() {
+    assert_eq!(detect_format(b"\xef\xbb\xbfhello"), Some(Format::Text));
+    assert_eq!(detect_format("\u{feff}标签".as_bytes()), Some(Format::Text));
+    assert_eq!(detect_format(b"\xff\xfeh\0i\0"), Some(Format::Text));
+    assert_eq!(detect_format(b"\xfe\xff\0h\0i"), Some(Format::Text));
+    // 只有 BOM 或 BOM 后为二进制内容
+    assert_eq!(detect_format(b"\xef\xbb\xbf"), None);
+    assert_eq!(detect_format(b"\xef\xbb\xbf\0\x01\x02"), None);
+    // BOM 后的 PDF 文件头仍在允许的范围内
+    assert_eq!(detect_format(b"\xef\xbb\xbf%PDF-1.7"), Some(Format::Pdf));
+  }
+
+  #[test]
+  fn allows_text_truncated_in_a_character() {
+    let mut data = "a".repeat(TEXT_SAMPLE - 1).into_bytes();
+    data.extend_from_slice("打印".as_bytes());
+    assert_eq!(detect_format(&data), Some(Format::Text));
+
+    // 在结尾不完整的字符不是截断造成的
+    assert_eq!(detect_format(b"abc\xe6\x89"), None);
+  }
+}
@@ -0,0 +1,80 @@
+use std::{
+  collections::HashMap,
+  fs::read_to_string,
+  path::Path,
+  sync::atomic::{AtomicUsize, Ordering},
+};
+
+use anyhow::{bail, Context};
+use serde::Deserialize;
+
+/// 选择组内打印机的策略
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoutingPolicy {
+  /// 按配置的顺序，前面的打印机失败时使用下一台
+  #[default]
+  Failover,
+  /// 轮流使用
+  RoundRobin,
+  /// 优先使用系统打印队列中任务最少的
+  LeastQueued,
+}
+
+/// 打印机组，打印设置中的打印机名称为组名时，按策略选择组内的打印机
+#[derive(Debug, Deserialize)]
+pub struct PrinterGroup {
+  #[serde(default)]
+  pub policy: RoutingPolicy,
+  /// 组内的打印机名称
+  pub printers: Vec<String>,
+  /// 轮流使用时下一次的起始位置
+  #[serde(skip)]
+  next: AtomicUsize,
+}
+
+impl PrinterGroup {
+  /// 按策略排列的候选打印机，依次尝试。`queued` 返回打印机在系统打印队列中的任务数
+  pub fn candidates(&self, queued: impl Fn(&str) -> Option<u32>) -> Vec<String> {
+    let mut printers = self.printers.clone();
+
+    match self.policy {
+      RoutingPolicy::Failover => {}
+      RoutingPolicy::RoundRobin => {
+        let start = self.next.fetch_add(1, Ordering::Relaxed) % printers.len();
+        printers.rotate_left(start);
+      }
+      RoutingPolicy::LeastQueued => {
+        // 无法读取任务数的排在最后，任务数相同时保持配置的顺序
+        printers.sort_by_cached_key(|printer| queued(printer).unwrap_or(u32::MAX));
+      }
+    }
+
+    printers
+  }
+}
+
+/// 打印机组，从 JSON 文件读取，如
+/// `{"packing": {"policy": "least_queued", "printers": ["Label 1", "Label 2", "Label 3"]}}`
+#[derive(Debug, Deserialize)]
+pub struct PrinterGroups(HashMap<String, PrinterGroup>);
+
+impl PrinterGroups {
+  pub fn load(path: &Path) -> anyhow::Result<Self> {
+    let json = read_to_string(path)
+      .with_context(|| format!("Cannot read printer groups file {}", path.display()))?;
+    let groups: Self = serde_json::from_str(&json)
+      .with_context(|| format!("Invalid printer groups file {}", path.display()))?;
+
+    if let Some((name, _)) = groups.0.iter().find(|(_, g)| g.printers.is_empty()) {
+      bail!("Printer group {} has no printers", name);
+    }
+
+    Ok(groups)
+  }
+
+  /// 获取打印机组，`name` 不是组名时为空
+  pub fn get(&self, name: &str) -> Option<&PrinterGroup> {
+    self.0.get(name)
+  }
+}
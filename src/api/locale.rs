@@ -0,0 +1,222 @@
+/// 按 `Accept-Language` 排序的客户端语言，只保留主语言标签，如 `zh`、`en`
+#[derive(Debug, Clone, Default)]
+pub struct Languages(Vec<String>);
+
+impl Languages {
+  /// 解析 `Accept-Language` 请求头，如 `en-US,en;q=0.9,zh-CN;q=0.8`，按权重从高到低排序
+  pub fn parse(header: Option<&str>) -> Self {
+    let Some(header) = header else {
+      return Self::default();
+    };
+
+    let mut tags: Vec<(String, f32)> = header
+      .split(',')
+      .filter_map(|item| {
+        let mut parts = item.split(';').map(str::trim);
+        let tag = parts.next().filter(|tag| !tag.is_empty() && *tag != "*")?;
+        let weight = parts
+          .find_map(|p| p.strip_prefix("q="))
+          .and_then(|q| q.parse().ok())
+          .unwrap_or(1.0);
+        let primary = tag.split('-').next().unwrap_or(tag).to_ascii_lowercase();
+        (weight > 0.0).then_some((primary, weight))
+      })
+      .collect();
+    // 稳定排序，权重相同时保持请求头中的顺序
+    tags.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let mut languages: Vec<String> = Vec::new();
+    for (tag, _) in tags {
+      if !languages.contains(&tag) {
+        languages.push(tag);
+      }
+    }
+    Self(languages)
+  }
+
+  /// 选择名称。驱动的显示名称为操作系统的语言，客户端优先的语言与之相同时使用显示名称；
+  /// 优先英文时使用 PrintSchema 关键字生成的英文名称，如 `ISOA4` 为 `ISO A4`。
+  /// 都不匹配时依次使用关键字名称和显示名称。未指定语言时总是使用显示名称
+  pub fn name(&self, keyword: Option<&str>, display_name: Option<&str>) -> Option<String> {
+    if self.0.is_empty() {
+      return display_name.map(str::to_string);
+    }
+
+    let english = keyword.map(keyword_name);
+    let display_language = display_name.and_then(guess_language);
+
+    for language in &self.0 {
+      if display_language == Some(language.as_str()) {
+        return display_name.map(str::to_string);
+      }
+      if language == "en" && english.is_some() {
+        return english;
+      }
+    }
+
+    english.or_else(|| display_name.map(str::to_string))
+  }
+}
+
+/// 关键字中单独成词的前缀
+const KEYWORD_PREFIXES: &[&str] = &["ISO", "JIS", "JPN", "PRC", "ANSI"];
+
+/// 由 PrintSchema 关键字生成英文名称，在大小写变化处分词，如 `NorthAmericaLetter` 为
+/// `North America Letter`，`NorthAmerica4x6` 为 `North America 4x6`，
+/// `ISOA4Rotated` 为 `ISO A4 Rotated`，`PRC16K` 为 `PRC 16K`
+pub fn keyword_name(keyword: &str) -> String {
+  let (prefix, rest) = KEYWORD_PREFIXES
+    .iter()
+    .find_map(|prefix| {
+      keyword
+        .strip_prefix(prefix)
+        .filter(|rest| !rest.is_empty())
+        .map(|rest| (Some(*prefix), rest))
+    })
+    .unwrap_or((None, keyword));
+
+  let mut name = String::with_capacity(keyword.len() + 4);
+  let mut prev: Option<char> = None;
+
+  for c in rest.chars() {
+    if let Some(p) = prev {
+      let word =
+        c.is_ascii_uppercase() && (p.is_ascii_lowercase() || p.is_ascii_digit() && c != 'K');
+      // 尺寸中的 x 不分开，如 `4x6`
+      let number = c.is_ascii_digit() && p.is_ascii_lowercase() && p != 'x';
+      if word || number {
+        name.push(' ');
+      }
+    }
+    name.push(c);
+    prev = Some(c);
+  }
+
+  match prefix {
+    Some(prefix) => format!("{} {}", prefix, name),
+    None => name,
+  }
+}
+
+/// 按文字推测名称的语言，无法判断时为空
+fn guess_language(name: &str) -> Option<&'static str> {
+  let has = |range: fn(char) -> bool| name.chars().any(range);
+
+  if has(|c| matches!(c, '\u{3040}'..='\u{30ff}')) {
+    Some("ja")
+  } else if has(|c| matches!(c, '\u{ac00}'..='\u{d7af}')) {
+    Some("ko")
+  } else if has(|c| matches!(c, '\u{4e00}'..='\u{9fff}')) {
+    Some("zh")
+  } else if name.is_ascii() {
+    Some("en")
+  } else {
+    None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::Path;
+
+  use winprint::ticket::FeatureOptionPack;
+
+  use super::{
+    super::{
+      capability::{is_custom_media, keyword},
+      fixture::Fixtures,
+    },
+    *,
+  };
+
+  /// 仓库中录制的打印机的纸张名称，见 `tests/fixtures`。HP 的驱动为英文，Gprinter 的驱动为中文
+  fn page_size_names(printer: &str, accept_language: Option<&str>) -> Vec<String> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let cap = Fixtures::load(&dir)
+      .unwrap()
+      .capabilities(printer)
+      .unwrap()
+      .unwrap();
+    let languages = Languages::parse(accept_language);
+
+    cap
+      .page_media_sizes()
+      .filter(|pms| !is_custom_media(pms))
+      .map(|pms| {
+        languages
+          .name(keyword(&pms), pms.display_name())
+          .unwrap_or_default()
+      })
+      .collect()
+  }
+
+  #[test]
+  fn parses_accept_language() {
+    let languages = Languages::parse(Some("en-US,en;q=0.9,zh-CN;q=0.8"));
+    assert_eq!(languages.0, ["en", "zh"]);
+
+    let languages = Languages::parse(Some("zh-CN;q=0.5, ja, *, fr;q=0"));
+    assert_eq!(languages.0, ["ja", "zh"]);
+
+    assert!(Languages::parse(None).0.is_empty());
+  }
+
+  #[test]
+  fn names_keywords_in_english() {
+    assert_eq!(keyword_name("NorthAmericaLetter"), "North America Letter");
+    assert_eq!(keyword_name("NorthAmerica4x6"), "North America 4x6");
+    assert_eq!(keyword_name("ISOA4Rotated"), "ISO A4 Rotated");
+    assert_eq!(keyword_name("JISB5"), "JIS B5");
+    assert_eq!(keyword_name("PRC16K"), "PRC 16K");
+    assert_eq!(
+      keyword_name("NorthAmericaNumber10Envelope"),
+      "North America Number 10 Envelope"
+    );
+  }
+
+  #[test]
+  fn keeps_display_names_without_accept_language() {
+    assert_eq!(
+      page_size_names("HP LaserJet Pro M404", None)[..4],
+      ["Letter", "Legal", "Executive", "A4"]
+    );
+    assert_eq!(page_size_names("Gprinter GP-1134T", None)[0], "4 x 6 英寸");
+  }
+
+  #[test]
+  fn uses_display_names_in_the_preferred_language() {
+    assert_eq!(
+      page_size_names("HP LaserJet Pro M404", Some("en-US,en;q=0.9,zh-CN;q=0.8"))[..4],
+      ["Letter", "Legal", "Executive", "A4"]
+    );
+    assert_eq!(
+      page_size_names("Gprinter GP-1134T", Some("zh-CN,zh;q=0.9,en;q=0.8"))[0],
+      "4 x 6 英寸"
+    );
+  }
+
+  #[test]
+  fn uses_keyword_names_for_other_languages() {
+    // 中文的驱动，客户端优先英文
+    let names = page_size_names("Gprinter GP-1134T", Some("en-US"));
+    assert_eq!(names[0], "North America 4x6");
+    // 驱动自定义的纸张没有关键字，仍使用显示名称
+    assert!(names[1].starts_with("100 x 150"));
+
+    // 英文的驱动，客户端只接受中文
+    let names = page_size_names("HP LaserJet Pro M404", Some("zh-CN"));
+    assert_eq!(
+      names,
+      [
+        "North America Letter",
+        "North America Legal",
+        "North America Executive",
+        "ISO A4",
+        "ISO A5",
+        "JIS B5",
+        "North America Number 10 Envelope",
+        "8x10 in",
+      ]
+    );
+  }
+}
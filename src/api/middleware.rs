@@ -0,0 +1,372 @@
+use std::io::{Read, Write};
+
+use flate2::{
+  read::{DeflateDecoder, MultiGzDecoder, ZlibDecoder},
+  write::{GzEncoder, ZlibEncoder},
+  Compression,
+};
+use log::debug;
+use poem::{
+  error::InternalServerError,
+  http::{header, Method, StatusCode},
+  Endpoint, IntoResponse, Request,
+};
+use poem_openapi::{
+  registry::{MetaSchema, MetaSchemaRef, Registry},
+  types::Type,
+};
+use serde_json::Value;
+
+use super::{
+  error::{CodedError, ErrorCode},
+  print::blocking,
+  v1::{PrintPayload, PrintSettings},
+};
+
+/// 请求体中的未知字段
+pub struct UnknownField {
+  /// 字段的路径，如 `settings.orientations`
+  pub path: String,
+  /// 名称最接近的有效字段
+  pub suggestion: Option<&'static str>,
+}
+
+impl std::fmt::Display for UnknownField {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "Unknown field `{}`", self.path)?;
+    if let Some(suggestion) = self.suggestion {
+      write!(f, ", did you mean `{}`?", suggestion)?;
+    }
+    Ok(())
+  }
+}
+
+/// 拒绝请求体中包含未知字段的打印请求。`strict` 为 false 时只检查设置了 `"strict": true` 的请求
+pub async fn reject_unknown_fields<E: Endpoint>(
+  ep: std::sync::Arc<E>,
+  mut req: Request,
+  strict: bool,
+) -> poem::Result<poem::Response> {
+  let mut registry = Registry::new();
+  let schema = match (req.method(), req.uri().path()) {
+    (&Method::POST, "/print") => {
+      PrintPayload::register(&mut registry);
+      PrintPayload::schema_ref()
+    }
+    (&Method::POST, "/settings") => {
+      PrintSettings::register(&mut registry);
+      PrintSettings::schema_ref()
+    }
+    _ => return Ok(ep.call(req).await?.into_response()),
+  };
+
+  let body = req.take_body().into_bytes().await?;
+
+  if let Ok(value) = serde_json::from_slice::<Value>(&body) {
+    let requested = value.get("strict").and_then(Value::as_bool) == Some(true);
+
+    if strict || requested {
+      if let Some(unknown) = find_unknown_field(&value, &schema, &registry, "") {
+        let err = CodedError::new(StatusCode::BAD_REQUEST, ErrorCode::InvalidRequest, unknown);
+        return Ok(err.into_error::<String>().into_response());
+      }
+    }
+  }
+
+  req.set_body(body);
+  Ok(ep.call(req).await?.into_response())
+}
+
+/// 超过该大小的 JSON 或 YAML 响应，在客户端支持时压缩
+pub const COMPRESS_MIN_SIZE: usize = 8 * 1024;
+
+/// 请求体及响应体的压缩方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentCoding {
+  Gzip,
+  Deflate,
+}
+
+impl ContentCoding {
+  pub fn as_str(self) -> &'static str {
+    match self {
+      Self::Gzip => "gzip",
+      Self::Deflate => "deflate",
+    }
+  }
+
+  /// 解压 `data`，解压后超过 `limit` 字节时为空
+  pub fn decode(self, data: &[u8], limit: usize) -> std::io::Result<Option<Vec<u8>>> {
+    let mut decoded = Vec::new();
+    let reader: Box<dyn Read> = match self {
+      Self::Gzip => Box::new(MultiGzDecoder::new(data)),
+      // 按规范 deflate 为 zlib 格式，但部分客户端发送不带 zlib 头的原始 deflate 数据
+      Self::Deflate if is_zlib(data) => Box::new(ZlibDecoder::new(data)),
+      Self::Deflate => Box::new(DeflateDecoder::new(data)),
+    };
+    // 多读一个字节以判断是否超过限制，避免解压炸弹占满内存
+    reader.take(limit as u64 + 1).read_to_end(&mut decoded)?;
+    Ok((decoded.len() <= limit).then_some(decoded))
+  }
+
+  pub fn encode(self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+    match self {
+      Self::Gzip => {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(data)?;
+        encoder.finish()
+      }
+      Self::Deflate => {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(data)?;
+        encoder.finish()
+      }
+    }
+  }
+}
+
+/// zlib 头的校验，见 RFC 1950
+pub fn is_zlib(data: &[u8]) -> bool {
+  match data {
+    [cmf, flg, ..] => cmf & 0x0f == 8 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0,
+    _ => false,
+  }
+}
+
+/// 按请求头 `Accept-Encoding` 选择响应的压缩方式，优先 gzip
+pub fn accepted_coding(req: &Request) -> Option<ContentCoding> {
+  let accept = req.headers().get(header::ACCEPT_ENCODING)?.to_str().ok()?;
+  let accepted = |name: &str| {
+    accept.split(',').any(|item| {
+      let mut parts = item.split(';').map(str::trim);
+      let coding = parts.next().unwrap_or_default();
+      let refused = parts.any(|param| {
+        param
+          .strip_prefix("q=")
+          .and_then(|q| q.parse::<f32>().ok())
+          .is_some_and(|q| q <= 0.0)
+      });
+      (coding.eq_ignore_ascii_case(name) || coding == "*") && !refused
+    })
+  };
+
+  [ContentCoding::Gzip, ContentCoding::Deflate]
+    .into_iter()
+    .find(|coding| accepted(coding.as_str()))
+}
+
+/// 解压按 `Content-Encoding` 压缩的请求体，解压前后都不能超过 `max_body_size` 字节，
+/// 不支持的压缩方式返回 415。客户端支持时压缩较大的 JSON 或 YAML 响应，如打印机能力和任务列表
+pub async fn content_encoding<E: Endpoint>(
+  ep: std::sync::Arc<E>,
+  mut req: Request,
+  max_body_size: usize,
+) -> poem::Result<poem::Response> {
+  let too_large = || {
+    let msg = format!(
+      "The request body exceeds the limit of {} bytes",
+      max_body_size
+    );
+    CodedError::new(
+      StatusCode::PAYLOAD_TOO_LARGE,
+      ErrorCode::PayloadTooLarge,
+      msg,
+    )
+    .into_error::<String>()
+    .into_response()
+  };
+
+  let encoding = req
+    .headers()
+    .get(header::CONTENT_ENCODING)
+    .map(|v| v.to_str().unwrap_or_default().trim().to_ascii_lowercase());
+  let coding = match encoding.as_deref() {
+    None | Some("" | "identity") => None,
+    Some("gzip" | "x-gzip") => Some(ContentCoding::Gzip),
+    Some("deflate") => Some(ContentCoding::Deflate),
+    Some(other) => {
+      let err = CodedError::new(
+        StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        ErrorCode::UnsupportedEncoding,
+        format!(
+          "Unsupported Content-Encoding `{}`, use gzip or deflate",
+          other
+        ),
+      );
+      return Ok(err.into_error::<String>().into_response());
+    }
+  };
+
+  let body = match req.take_body().into_bytes_limit(max_body_size).await {
+    Ok(body) => body,
+    Err(poem::error::ReadBodyError::PayloadTooLarge) => return Ok(too_large()),
+    Err(e) => return Err(e.into()),
+  };
+
+  if let Some(coding) = coding {
+    let decoded = match blocking(|| coding.decode(&body, max_body_size)) {
+      Ok(Some(decoded)) => decoded,
+      Ok(None) => return Ok(too_large()),
+      Err(e) => {
+        let err = CodedError::new(
+          StatusCode::BAD_REQUEST,
+          ErrorCode::InvalidRequest,
+          format!(
+            "Cannot decompress the {} request body: {}",
+            coding.as_str(),
+            e
+          ),
+        );
+        return Ok(err.into_error::<String>().into_response());
+      }
+    };
+    debug!(
+      "Decompressed the {} request body from {} to {} bytes",
+      coding.as_str(),
+      body.len(),
+      decoded.len()
+    );
+
+    let headers = req.headers_mut();
+    headers.remove(header::CONTENT_ENCODING);
+    headers.insert(header::CONTENT_LENGTH, decoded.len().into());
+    req.set_body(decoded);
+  } else {
+    req.set_body(body);
+  }
+
+  let accepted = accepted_coding(&req);
+  let mut resp = ep.call(req).await?.into_response();
+
+  let Some(coding) = accepted else {
+    return Ok(resp);
+  };
+  let compressible = resp
+    .content_type()
+    .is_some_and(|t| t.contains("json") || t.contains("yaml"));
+  if !compressible || resp.headers().contains_key(header::CONTENT_ENCODING) {
+    return Ok(resp);
+  }
+
+  let body = resp.take_body().into_bytes().await?;
+  if body.len() < COMPRESS_MIN_SIZE {
+    resp.set_body(body);
+    return Ok(resp);
+  }
+
+  let compressed = blocking(|| coding.encode(&body)).map_err(InternalServerError)?;
+  let headers = resp.headers_mut();
+  headers.insert(
+    header::CONTENT_ENCODING,
+    header::HeaderValue::from_static(coding.as_str()),
+  );
+  headers.append(
+    header::VARY,
+    header::HeaderValue::from_static("Accept-Encoding"),
+  );
+  headers.remove(header::CONTENT_LENGTH);
+  resp.set_body(compressed);
+  Ok(resp)
+}
+
+/// 展开引用及 `allOf`，得到描述 `schema` 的全部结构
+pub fn resolve_schema<'a>(
+  schema: &'a MetaSchemaRef,
+  registry: &'a Registry,
+) -> Vec<&'a MetaSchema> {
+  let schema = match schema {
+    MetaSchemaRef::Inline(schema) => schema.as_ref(),
+    MetaSchemaRef::Reference(name) => match registry.schemas.get(name) {
+      Some(schema) => schema,
+      None => return Vec::new(),
+    },
+  };
+
+  let mut schemas = vec![schema];
+  for part in &schema.all_of {
+    schemas.extend(resolve_schema(part, registry));
+  }
+  schemas
+}
+
+/// 按结构查找 `value` 中的第一个未知字段，`path` 为 `value` 的路径
+pub fn find_unknown_field(
+  value: &Value,
+  schema: &MetaSchemaRef,
+  registry: &Registry,
+  path: &str,
+) -> Option<UnknownField> {
+  let schemas = resolve_schema(schema, registry);
+
+  match value {
+    Value::Object(map) => {
+      // 键值对形式的对象只检查值
+      if let Some(values) = schemas
+        .iter()
+        .find_map(|s| s.additional_properties.as_ref())
+      {
+        return map.iter().find_map(|(key, value)| {
+          find_unknown_field(value, values, registry, &field_path(path, key))
+        });
+      }
+
+      let properties: Vec<_> = schemas.iter().flat_map(|s| &s.properties).collect();
+      if properties.is_empty() {
+        return None;
+      }
+
+      map.iter().find_map(
+        |(key, value)| match properties.iter().find(|(name, _)| name == key) {
+          Some((_, schema)) => find_unknown_field(value, schema, registry, &field_path(path, key)),
+          None => Some(UnknownField {
+            path: field_path(path, key),
+            suggestion: closest_name(key, properties.iter().map(|(name, _)| *name)),
+          }),
+        },
+      )
+    }
+    Value::Array(items) => {
+      let schema = schemas.iter().find_map(|s| s.items.as_deref())?;
+      items.iter().enumerate().find_map(|(i, item)| {
+        find_unknown_field(item, schema, registry, &format!("{}[{}]", path, i))
+      })
+    }
+    _ => None,
+  }
+}
+
+pub fn field_path(parent: &str, key: &str) -> String {
+  if parent.is_empty() {
+    key.to_string()
+  } else {
+    format!("{}.{}", parent, key)
+  }
+}
+
+/// 编辑距离最小且足够接近的名称，用于提示拼写错误
+pub fn closest_name(key: &str, names: impl Iterator<Item = &'static str>) -> Option<&'static str> {
+  names
+    .map(|name| (edit_distance(key, name), name))
+    .filter(|(distance, _)| *distance <= (key.chars().count() / 3).max(2))
+    .min_by_key(|(distance, _)| *distance)
+    .map(|(_, name)| name)
+}
+
+/// 两个字符串的编辑距离（Levenshtein）
+pub fn edit_distance(a: &str, b: &str) -> usize {
+  let b: Vec<char> = b.chars().collect();
+  let mut row: Vec<usize> = (0..=b.len()).collect();
+
+  for (i, ca) in a.chars().enumerate() {
+    let mut prev = row[0];
+    row[0] = i + 1;
+
+    for (j, cb) in b.iter().enumerate() {
+      let substitution = prev + usize::from(ca != *cb);
+      prev = row[j + 1];
+      row[j + 1] = substitution.min(prev + 1).min(row[j] + 1);
+    }
+  }
+
+  row[b.len()]
+}
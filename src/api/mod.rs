@@ -0,0 +1,73 @@
+use std::sync::Arc;
+
+use log::info;
+use poem::{middleware::SetHeader, Endpoint, IntoResponse, Request, Response};
+
+pub mod access;
+pub mod capability;
+pub mod diagnostics;
+pub mod dispatch;
+pub mod drift;
+pub mod error;
+pub mod events;
+mod examples;
+pub mod fixture;
+pub mod format;
+pub mod group;
+pub mod locale;
+pub mod middleware;
+pub mod options;
+pub mod paging;
+pub mod pricing;
+pub mod print;
+pub mod printers;
+pub mod quarantine;
+pub mod receipt;
+pub mod retention;
+pub mod schedule;
+pub mod settings;
+pub mod simulate;
+pub mod summary;
+pub mod template;
+pub mod token;
+pub mod upload;
+pub mod v1;
+
+/// 标记已弃用的 API 路径，提示客户端改用带版本号的路径
+pub fn deprecated(successor: &str) -> SetHeader {
+  SetHeader::new()
+    .overriding("Deprecation", "true")
+    .overriding(
+      "Link",
+      format!("<{}>; rel=\"successor-version\"", successor),
+    )
+}
+
+/// 记录响应体，超过 `limit` 字节的部分被截断
+pub async fn log_response<E: Endpoint>(
+  ep: Arc<E>,
+  req: Request,
+  limit: Option<usize>,
+) -> poem::Result<Response> {
+  let mut resp = ep.call(req).await?.into_response();
+
+  if let Some(limit) = limit {
+    let body = resp.take_body().into_bytes().await?;
+    let text = String::from_utf8_lossy(&body);
+    let mut end = text.len().min(limit);
+
+    while !text.is_char_boundary(end) {
+      end -= 1;
+    }
+
+    if end < text.len() {
+      info!("Response body: {}... ({} bytes)", &text[..end], text.len());
+    } else {
+      info!("Response body: {}", text);
+    }
+
+    resp.set_body(body);
+  }
+
+  Ok(resp)
+}
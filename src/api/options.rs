@@ -0,0 +1,126 @@
+use std::{fmt, path::PathBuf, sync::Arc, time::Duration};
+
+use tokio::sync::Semaphore;
+use winprint::ticket::{document::reader::ParsePrintSchemaError, PrintCapabilities};
+
+use super::{
+  access::AccessPolicy, diagnostics::JobDiagnostics, fixture::Fixtures, group::PrinterGroups,
+  pricing::PriceTable, quarantine::Quarantine, retention::Retention, schedule::QueueLimits,
+  simulate, summary::ReportTimezone, token::Tokens, upload::UploadQuota,
+};
+
+use crate::{hook::Hooks, pdf, spooler::RetryPolicy};
+
+/// API 选项
+#[derive(Debug, Clone, Default)]
+pub struct ApiOptions {
+  /// 记录请求体，文件内容等字段会被隐藏
+  pub log_bodies: bool,
+  /// 不使用不适用于当前打印机的默认打印设置
+  pub strict_settings: bool,
+  /// 打印机访问策略
+  pub access: AccessPolicy,
+  /// 签发打印令牌所需的 API 密钥
+  pub api_keys: Vec<String>,
+  /// 打印令牌，不为空时打印必须提供令牌
+  pub tokens: Option<Arc<Tokens>>,
+  /// 暂时性打印错误的重试策略
+  pub retry: RetryPolicy,
+  /// 定时任务默认的有效期（秒），超过计划时间仍未打印时过期
+  pub job_ttl: Option<u64>,
+  /// 排队的任务每等待此时间提升一级优先级，为空时不提升
+  pub priority_aging: Option<Duration>,
+  /// 估算排队任务的开始时间时，每台打印机平均最近多少个任务的打印耗时，0 表示不估算
+  pub eta_window: usize,
+  /// 按天统计使用的时区
+  pub report_timezone: ReportTimezone,
+  /// 价格表，不为空时估算打印费用
+  pub pricing: Option<Arc<PriceTable>>,
+  /// 排版表格和标签使用的 TrueType 字体
+  pub font: Option<Arc<Vec<u8>>>,
+  /// 打印机组，打印设置可以使用组名代替打印机名称，访问策略和打印令牌按组名检查
+  pub groups: Option<Arc<PrinterGroups>>,
+  /// 模拟模式下保存打印结果的目录，不为空时加入模拟打印机
+  pub simulate: Option<PathBuf>,
+  /// 模拟打印机的能力，为空时使用内置的模拟能力
+  pub capability_source: Option<CapabilitySource>,
+  /// 保存从驱动获取的打印机能力的目录，用于在其他计算机上回放
+  pub record_fixtures: Option<PathBuf>,
+  /// 回放录制的打印机能力，不为空时打印机列表只包含录制的打印机，打印时只解析打印设置
+  pub replay: Option<Arc<Fixtures>>,
+  /// 预热的打印机，为空时预热全部打印机
+  pub warmup_printers: Vec<String>,
+  /// 同时查询打印机能力的名额，整个服务共用，超时的查询完成前仍占用名额。为空时不限制
+  pub capability_permits: Option<Arc<Semaphore>>,
+  /// 获取单台打印机能力的超时时间
+  pub capability_timeout: Duration,
+  /// 默认超过此页数时拆分为多个打印作业，打印设置中的 `split_every_pages` 优先
+  pub split_every_pages: Option<u32>,
+  /// 打印设置指定 `skip_blank_pages` 时的空白页检测
+  pub blank_pages: pdf::BlankPageDetection,
+  /// 提交到打印队列前对文档执行的外部命令，为空时不执行
+  pub hooks: Option<Arc<Hooks>>,
+  /// 任务结束后在后台执行的外部命令，为空时不执行
+  pub post_print_hooks: Option<Arc<Hooks>>,
+  /// 保存被拒绝的请求及文档，为空时不保存
+  pub quarantine: Option<Arc<Quarantine>>,
+  /// 上传超过此时间没有收到新的分块时过期
+  pub upload_idle_timeout: Duration,
+  /// 进行中的上传的上限
+  pub upload_quota: UploadQuota,
+  /// 保存任务的诊断信息，为空时不保存
+  pub job_diagnostics: Option<Arc<JobDiagnostics>>,
+  /// 每台打印机在服务内排队的上限
+  pub queue_limits: QueueLimits,
+  /// 纸张高度随内容时的最大高度（微米），为空时不限制
+  pub max_auto_page_height: Option<u32>,
+  /// 各类保存的数据的保留策略
+  pub retention: Option<Arc<Retention>>,
+  /// 合并端口指向同一设备的打印机，如从多台打印服务器映射的同一打印队列
+  pub dedupe_printers: bool,
+  /// 打印设置未指定 `allow_letter_a4_interchange` 时是否以 A4 代替 Letter 等
+  pub letter_a4_interchange: bool,
+}
+
+impl ApiOptions {
+  /// 是否为模拟打印机，回放的打印机也不使用驱动
+  pub fn simulates(&self, printer: &str) -> bool {
+    (self.simulate.is_some() && printer == simulate::PRINTER)
+      || self.replay.as_ref().is_some_and(|f| f.contains(printer))
+  }
+
+  /// 模拟打印机及回放的打印机名称
+  pub fn simulated_printers(&self) -> Vec<String> {
+    let replayed = self.replay.iter().flat_map(|f| f.printers());
+    replayed
+      .chain(self.simulate.is_some().then_some(simulate::PRINTER))
+      .map(str::to_string)
+      .collect()
+  }
+
+  /// 模拟打印机的能力，回放的打印机使用录制的能力
+  pub fn simulated_capabilities(
+    &self,
+    printer: &str,
+  ) -> std::result::Result<PrintCapabilities, ParsePrintSchemaError> {
+    match self.replay.as_ref().and_then(|f| f.capabilities(printer)) {
+      Some(cap) => cap,
+      None => match &self.capability_source {
+        Some(source) => (source.0)(),
+        None => simulate::capabilities(),
+      },
+    }
+  }
+}
+
+/// 替换内置的模拟打印机能力，如测试中统计获取能力的次数
+#[derive(Clone)]
+pub struct CapabilitySource(
+  pub Arc<dyn Fn() -> std::result::Result<PrintCapabilities, ParsePrintSchemaError> + Send + Sync>,
+);
+
+impl fmt::Debug for CapabilitySource {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("CapabilitySource").finish_non_exhaustive()
+  }
+}
@@ -0,0 +1,24 @@
+/// 分页参数，都为空时不分页
+#[derive(Debug, Clone, Copy)]
+pub struct Paging {
+  pub offset: Option<u32>,
+  pub limit: Option<u32>,
+}
+
+impl Paging {
+  /// 取出一页，同时返回总数及偏移，不分页时返回全部且总数及偏移为空
+  pub fn apply<T>(self, items: Vec<T>) -> (Vec<T>, Option<(u32, u32)>) {
+    if self.offset.is_none() && self.limit.is_none() {
+      return (items, None);
+    }
+
+    let total = items.len() as u32;
+    let offset = self.offset.unwrap_or_default();
+    let page = items
+      .into_iter()
+      .skip(offset as usize)
+      .take(self.limit.map_or(usize::MAX, |n| n as usize))
+      .collect();
+    (page, Some((total, offset)))
+  }
+}
@@ -0,0 +1,52 @@
+use std::{collections::HashMap, fs::read_to_string, path::Path};
+
+use anyhow::Context;
+use serde::Deserialize;
+
+/// 价格表，从 JSON 文件读取，如
+/// `{"currency": "CNY", "printers": {"HP LaserJet": {"mono_page": 0.1, "sheet": 0.05}}}`
+#[derive(Debug, Clone, Deserialize)]
+pub struct PriceTable {
+  /// 货币代码，如 `CNY`
+  pub currency: String,
+  /// 无法确定是否彩色打印时是否按彩色计价
+  #[serde(default)]
+  pub default_color: bool,
+  /// 各打印机的价格，键为打印机名称
+  pub printers: HashMap<String, Prices>,
+}
+
+impl PriceTable {
+  pub fn load(path: &Path) -> anyhow::Result<Self> {
+    let json = read_to_string(path)
+      .with_context(|| format!("Cannot read pricing file {}", path.display()))?;
+    serde_json::from_str(&json).with_context(|| format!("Invalid pricing file {}", path.display()))
+  }
+}
+
+/// 打印机的价格
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Prices {
+  /// 每个黑白页面的价格
+  #[serde(default)]
+  pub mono_page: f64,
+  /// 每个彩色页面的价格
+  #[serde(default)]
+  pub color_page: f64,
+  /// 每张纸的价格
+  #[serde(default)]
+  pub sheet: f64,
+}
+
+impl Prices {
+  /// 按页数和纸张数估算费用，保留两位小数
+  pub fn estimate(&self, pages: u32, sheets: u32, color: bool) -> f64 {
+    let page = if color {
+      self.color_page
+    } else {
+      self.mono_page
+    };
+    let cost = pages as f64 * page + sheets as f64 * self.sheet;
+    (cost * 100.0).round() / 100.0
+  }
+}
@@ -0,0 +1,1261 @@
+use std::{
+  collections::{HashMap, HashSet},
+  io::Write,
+  panic::{catch_unwind, AssertUnwindSafe},
+  thread,
+  time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail};
+use chrono::Utc;
+use flate2::{write::GzEncoder, Compression};
+use log::{debug, info, warn};
+use poem::http::StatusCode;
+use poem_openapi::types::{Base64, ToJSON};
+use serde_json::Value;
+use tokio::runtime::RuntimeFlavor;
+use tracing::{field, info_span};
+use winprint::{
+  printer::{FilePrinter, PdfiumPrinter, PrinterDevice},
+  ticket::{
+    document::{reader::ParsableXmlDocument, PrintTicketDocument},
+    Copies, FeatureOptionPack, FeatureOptionPackWithPredefined, PageMediaSize, PageOrientation,
+    PredefinedPageOutputColor, PrintCapabilities, PrintTicket, PrintTicketBuilder,
+  },
+};
+
+use super::{
+  access::Client,
+  capability::{fetch_print_capabilities, printer_capability, property_text},
+  diagnostics,
+  error::{CodedError, ErrorCode},
+  locale::Languages,
+  options::ApiOptions,
+  pricing::PriceTable,
+  printers::{printer_device, select_named_printer, select_printer},
+  receipt::Receipt,
+  schedule,
+  settings::{
+    changed_options, content_height, default_settings, effective_settings, resolve_driver_options,
+    resolve_settings, select_pages, select_rotated_pages,
+  },
+  simulate::{self, SimulatedJob},
+  token::TokenClaims,
+  v1::{
+    ChunkResult, CoverageEstimate, EstimatedCost, JobState, OutputTarget, PageSizeSort,
+    PrintPayload, PrintResult, PrintSettings, PrintTimings, TicketDebug, DEFAULT_COVERAGE_DPI,
+    MAX_COVERAGE_PAGES,
+  },
+};
+
+use crate::{
+  hook::{self, HookError, HookInput, Hooks},
+  pdf, spooler, status,
+  storage::is_reserved_name,
+};
+
+/// 执行打印，崩溃时转换为 `internal_panic` 错误。
+/// 临时文件随栈展开删除，各处的锁在中毒后仍可使用，崩溃不影响之后的打印
+pub fn catch_panic<T>(f: impl FnOnce() -> anyhow::Result<T>) -> anyhow::Result<T> {
+  catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|err| Err(CodedError::panic(&*err).into()))
+}
+
+/// 请求的截止时间，见 `deadline_ms`。超过时尚未提交到打印队列的任务取消，已提交的任务仍然完成
+#[derive(Debug, Clone, Copy)]
+pub struct Deadline(Instant);
+
+impl Deadline {
+  /// 从现在起 `ms` 毫秒后，超出时间范围时为空，即不限时间
+  pub fn after(ms: u64) -> Option<Self> {
+    Instant::now()
+      .checked_add(Duration::from_millis(ms))
+      .map(Self)
+  }
+
+  /// 剩余时间，已到截止时间时为空
+  pub fn remaining(&self) -> Option<Duration> {
+    self.remaining_at(Instant::now())
+  }
+
+  /// 到 `now` 时的剩余时间，恰好在截止时间时也视为已超过
+  fn remaining_at(&self, now: Instant) -> Option<Duration> {
+    self
+      .0
+      .checked_duration_since(now)
+      .filter(|remaining| !remaining.is_zero())
+  }
+
+  pub fn passed(&self) -> bool {
+    self.remaining().is_none()
+  }
+}
+
+/// 进入 `stage` 阶段前检查截止时间，已超过时返回 `deadline_exceeded` 错误，任务不再继续
+pub fn check_deadline(deadline: Option<Deadline>, stage: &str) -> anyhow::Result<()> {
+  match deadline {
+    Some(deadline) if deadline.passed() => Err(
+      CodedError::deadline_exceeded(format!(
+        "The deadline passed before {}, the job is cancelled",
+        stage
+      ))
+      .into(),
+    ),
+    _ => Ok(()),
+  }
+}
+
+/// 已经提交到打印队列，超过截止时间也不撤回，只在结果中说明
+fn spooled_deadline_warning(deadline: Option<Deadline>) -> Option<String> {
+  deadline
+    .is_some_and(|d| d.passed())
+    .then(|| "The deadline passed after the job was handed to the spooler".to_string())
+}
+
+/// 任务使用的打印机名称，未指定打印设置时为默认打印设置中的打印机
+pub fn target_printer(payload: &PrintPayload) -> Option<String> {
+  match &payload.settings {
+    Some(settings) => Some(settings.printer.clone()),
+    None => default_settings().map(|settings| settings.printer),
+  }
+}
+
+/// 检查打印的页数（包括份数）是否超出令牌允许的范围
+pub fn check_page_limit(
+  claims: Option<&TokenClaims>,
+  settings: &PrintSettings,
+  pages: &[u32],
+) -> anyhow::Result<()> {
+  if let Some(max) = claims.and_then(|c| c.max_pages) {
+    let total = pages.len() as u32 * settings.copies.unwrap_or(1) as u32;
+
+    if total > max {
+      let msg = format!("The print token allows {} pages, {} requested", max, total);
+      return Err(CodedError::out_of_scope(msg).into());
+    }
+  }
+
+  Ok(())
+}
+
+/// 打印文件，`job_id` 用于登记分部分打印的任务，以便取消剩余的部分
+pub fn print_file(
+  mut payload: PrintPayload,
+  options: &ApiOptions,
+  client: &Client,
+  claims: Option<&TokenClaims>,
+  deadline: Option<Deadline>,
+  job_id: Option<&str>,
+) -> anyhow::Result<PrintResult> {
+  if payload.output == Some(OutputTarget::Pdf) {
+    let mut settings = payload
+      .settings
+      .take()
+      .or_else(default_settings)
+      .ok_or_else(|| anyhow!("No print settings"))?;
+    settings.normalize_units();
+    return render_pdf(payload, settings, claims, options.blank_pages);
+  }
+
+  let settings = effective_settings(payload.settings.take(), options, client, claims)?;
+  let group = options
+    .groups
+    .as_deref()
+    .and_then(|groups| groups.get(&settings.printer));
+  let fallbacks = settings.fallback_printers.clone().unwrap_or_default();
+
+  if group.is_none() && fallbacks.is_empty() {
+    return print_to(payload, settings, options, claims, deadline, job_id);
+  }
+
+  // 先按策略排列组内的打印机，再依次是备用打印机
+  let mut candidates = match group {
+    Some(group) => {
+      let printers = PrinterDevice::all()?;
+      let mut candidates = group.candidates(|name| {
+        let printer = select_named_printer(&printers, name).ok()??;
+        spooler::printer_status(printer.os_name())
+          .ok()
+          .map(|status| status.jobs)
+      });
+      // 组内从不同打印服务器映射的同一设备只尝试一次
+      if options.dedupe_printers {
+        let mut devices = HashSet::new();
+        candidates.retain(|name| {
+          select_named_printer(&printers, name)
+            .ok()
+            .flatten()
+            .and_then(printer_device)
+            .is_none_or(|device| devices.insert(device))
+        });
+      }
+      candidates
+    }
+    None => vec![settings.printer.clone()],
+  };
+  candidates.extend(fallbacks);
+
+  // 各打印机按同样的格式再次检查，警告只在最终的结果中出现一次
+  let file = payload.take_document(&mut Vec::new())?;
+  let mut failures = Vec::new();
+
+  for printer in candidates {
+    let attempt = PrintPayload {
+      file: Some(Base64(file.clone())),
+      queue_if_not_ready: payload.queue_if_not_ready,
+      ttl_seconds: payload.ttl_seconds,
+      debug_ticket: payload.debug_ticket,
+      format: payload.format,
+      ..Default::default()
+    };
+    // 打印机 ID 只适用于原来的打印机
+    let attempt_settings = PrintSettings {
+      printer_id: settings
+        .printer_id
+        .clone()
+        .filter(|_| printer == settings.printer),
+      printer: printer.clone(),
+      ..settings.clone()
+    };
+
+    match print_to(attempt, attempt_settings, options, claims, deadline, job_id) {
+      Ok(mut result) => {
+        if !failures.is_empty() {
+          info!(
+            "Printed to {} after {} failed printers",
+            printer,
+            failures.len()
+          );
+          result.failed_printers = Some(failures);
+        }
+
+        return Ok(result);
+      }
+      // 打印设置或文档的错误换打印机也无法解决
+      Err(e) if !is_device_error(&e) => return Err(e),
+      Err(e) => {
+        warn!(
+          "Failed to print to {}, trying the next printer: {:#}",
+          printer, e
+        );
+        failures.push(format!("{}: {}", printer, e));
+      }
+    }
+  }
+
+  bail!("All printers failed: {}", failures.join("; "))
+}
+
+/// 打印机或系统打印队列的错误，可以改用其他打印机重试
+#[derive(Debug)]
+pub struct DeviceError(anyhow::Error);
+
+impl std::fmt::Display for DeviceError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{:#}", self.0)
+  }
+}
+
+impl std::error::Error for DeviceError {}
+
+pub fn device_error(e: impl Into<anyhow::Error>) -> anyhow::Error {
+  DeviceError(e.into()).into()
+}
+
+/// 是否为打印机或系统打印队列的错误，包括打印机未就绪或不存在
+pub fn is_device_error(e: &anyhow::Error) -> bool {
+  e.is::<DeviceError>()
+    || e.downcast_ref::<CodedError>().is_some_and(|e| {
+      matches!(
+        e.code,
+        ErrorCode::PrinterNotReady | ErrorCode::PrinterNotFound
+      )
+    })
+}
+
+/// 按打印设置选择、旋转页面及调整边距后的文档
+pub struct PreparedDocument {
+  pub data: Vec<u8>,
+  /// 实际打印的页码及顺序
+  pub pages: Vec<u32>,
+  /// 被旋转的页码
+  pub rotated: Vec<u32>,
+  pub degrees: u16,
+  /// 原文档的元数据
+  pub metadata: pdf::DocumentMetadata,
+}
+
+/// 按打印设置处理文档，不涉及打印机
+pub fn prepare_document(
+  payload: &mut PrintPayload,
+  settings: &PrintSettings,
+  claims: Option<&TokenClaims>,
+  blank: pdf::BlankPageDetection,
+  timings: &mut PrintTimings,
+  warnings: &mut Vec<String>,
+) -> anyhow::Result<PreparedDocument> {
+  // 选择页面
+  let file = timed("pdf", &mut timings.pdf, || payload.take_document(warnings))?;
+  let summary = timed("pdf", &mut timings.pdf, || pdf::inspect(&file))?;
+  let count = summary.pages;
+  let mut pages = select_pages(settings, count)?;
+
+  if settings.skip_blank_pages.unwrap_or_default() {
+    skip_blank_pages(&file, count, &mut pages, blank, timings, warnings)?;
+  }
+
+  check_page_limit(claims, settings, &pages)?;
+  let rotated = select_rotated_pages(settings, &pages, count)?;
+  let degrees = settings.rotate_degrees.unwrap_or_default();
+  let margins = settings.margins.as_ref().map(pdf::PageMargins::from);
+
+  let data = if pages.iter().copied().eq(1..=count) && rotated.is_empty() && margins.is_none() {
+    file
+  } else {
+    let rearranged = timed("pdf", &mut timings.pdf, || {
+      pdf::rearrange(&file, &pages, |page| pdf::PageTransform {
+        rotate: if rotated.contains(&page) { degrees } else { 0 },
+        margins,
+      })
+    })?;
+
+    if !rearranged.off_page.is_empty() {
+      warnings.push(format!(
+        "Content of pages {:?} is moved entirely off the page by the margins",
+        rearranged.off_page
+      ));
+    }
+
+    rearranged.data
+  };
+
+  Ok(PreparedDocument {
+    data,
+    pages,
+    rotated,
+    degrees,
+    metadata: summary.metadata,
+  })
+}
+
+/// 从选择的页码中去掉空白页，文档页数超过限制时不检测
+pub fn skip_blank_pages(
+  file: &[u8],
+  count: u32,
+  pages: &mut Vec<u32>,
+  blank: pdf::BlankPageDetection,
+  timings: &mut PrintTimings,
+  warnings: &mut Vec<String>,
+) -> anyhow::Result<()> {
+  if count > blank.max_pages {
+    warnings.push(format!(
+      "Blank pages are not skipped, the document has {} pages and the limit is {}",
+      count, blank.max_pages
+    ));
+    return Ok(());
+  }
+
+  // 逐页渲染较慢，不占用异步运行时的工作线程
+  let skipped = timed("pdf", &mut timings.pdf, || {
+    blocking(|| pdf::blank_pages(file, pages, blank.max_coverage))
+  })?;
+
+  if skipped.is_empty() {
+    return Ok(());
+  }
+  if skipped.len() == pages.len() {
+    bail!("All selected pages are blank");
+  }
+
+  pages.retain(|page| !skipped.contains(page));
+  info!("Skipped blank pages {:?}", skipped);
+  warnings.push(format!("Skipped blank pages {:?}", skipped));
+  Ok(())
+}
+
+/// 在当前线程执行阻塞的处理，在异步运行时的工作线程中时先转为阻塞线程
+pub fn blocking<T>(f: impl FnOnce() -> T) -> T {
+  match tokio::runtime::Handle::try_current() {
+    Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+      tokio::task::block_in_place(f)
+    }
+    _ => f(),
+  }
+}
+
+/// 只处理文档，返回处理后的 PDF，不选择打印机也不打印
+pub fn render_pdf(
+  mut payload: PrintPayload,
+  settings: PrintSettings,
+  claims: Option<&TokenClaims>,
+  blank: pdf::BlankPageDetection,
+) -> anyhow::Result<PrintResult> {
+  let start = Instant::now();
+  let mut timings = PrintTimings::default();
+  let mut warnings = Vec::new();
+  let PreparedDocument {
+    data,
+    pages,
+    rotated,
+    degrees,
+    ..
+  } = prepare_document(
+    &mut payload,
+    &settings,
+    claims,
+    blank,
+    &mut timings,
+    &mut warnings,
+  )?;
+
+  // 没有打印机时按打印设置中的纸张大小拼版
+  let mut sheets = None;
+  let data = if settings.booklet.unwrap_or_default() {
+    let media = settings.page_size.as_ref().map(|p| (p.width, p.height));
+    let booklet = timed("pdf", &mut timings.pdf, || {
+      pdf::impose_booklet(&data, media)
+    })?;
+    sheets = Some(booklet.sheets);
+    booklet.data
+  } else {
+    data
+  };
+
+  // 估算处理后的文档，拼版后按实际的纸面计算
+  let coverage = if payload.estimate_coverage.unwrap_or_default() {
+    let estimated = timed("pdf", &mut timings.pdf, || {
+      blocking(|| pdf::coverage(&data, None, DEFAULT_COVERAGE_DPI, MAX_COVERAGE_PAGES))
+    });
+    match estimated {
+      Ok(pages) => Some(CoverageEstimate::new(pages, DEFAULT_COVERAGE_DPI)),
+      Err(e) => {
+        warnings.push(format!("Coverage is not estimated: {}", e));
+        None
+      }
+    }
+  } else {
+    None
+  };
+
+  timings.total = start.elapsed().as_millis() as u64;
+  info!(
+    "Rendered {} pages to PDF in {} ms",
+    pages.len(),
+    timings.total
+  );
+
+  Ok(PrintResult {
+    printer: None,
+    failed_printers: None,
+    pages,
+    rotate_degrees: (!rotated.is_empty()).then_some(degrees),
+    rotated_pages: (!rotated.is_empty()).then_some(rotated),
+    sheets,
+    total_pages: None,
+    total_sheets: None,
+    estimated_cost: None,
+    warnings: (!warnings.is_empty()).then_some(warnings),
+    timings,
+    attempts: 0,
+    retried_errors: None,
+    state: None,
+    job_id: None,
+    print_at: None,
+    document: Some(Base64(data)),
+    ticket: None,
+    deadline_exceeded: None,
+    chunks: None,
+    coverage,
+    page_height: None,
+  })
+}
+
+/// 打印到指定的打印机，文档超过拆分页数时按顺序分为多个打印作业提交
+/// 任务名称的最大长度（字符），临时文件的完整路径不能超过 `MAX_PATH`
+pub const MAX_JOB_NAME_CHARS: usize = 100;
+
+/// 打印队列中显示的文档名称。Pdfium 打印时以文件名作为文档名称，替换不能用于文件名的字符后截断，
+/// 为空时使用默认名称
+pub fn job_name(requested: Option<&str>) -> String {
+  let name: String = requested
+    .unwrap_or_default()
+    .chars()
+    .map(|c| match c {
+      '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+      c if c.is_control() => '_',
+      c => c,
+    })
+    .take(MAX_JOB_NAME_CHARS)
+    .collect();
+  // 文件名不能以空格或点结尾
+  let name = name.trim().trim_end_matches(['.', ' ']);
+
+  if name.is_empty() {
+    return format!(
+      "direct-printing {}",
+      chrono::Local::now().format("%Y-%m-%d %H-%M-%S")
+    );
+  }
+  if is_reserved_name(name) {
+    return format!("_{}", name);
+  }
+  name.to_string()
+}
+
+pub fn print_to(
+  mut payload: PrintPayload,
+  mut settings: PrintSettings,
+  options: &ApiOptions,
+  claims: Option<&TokenClaims>,
+  deadline: Option<Deadline>,
+  job_id: Option<&str>,
+) -> anyhow::Result<PrintResult> {
+  let _span = info_span!("print_job", printer = %settings.printer).entered();
+  let start = Instant::now();
+  let mut timings = PrintTimings::default();
+  // 记录打印期间的诊断信息，任务结束时随回执保存
+  let _recording = job_id
+    .filter(|_| options.job_diagnostics.is_some())
+    .map(diagnostics::begin);
+  diagnostics::record(|capture| capture.settings = settings.to_json());
+
+  let mut warnings = Vec::new();
+
+  if payload.ttl_seconds.is_some() {
+    // 已提交到系统打印队列的任务不受服务控制
+    warnings.push(
+      "ttl_seconds only applies to scheduled jobs, this job is handed to the spooler immediately and does not expire"
+        .to_string(),
+    );
+  }
+
+  let PreparedDocument {
+    data,
+    pages,
+    rotated,
+    degrees,
+    metadata,
+  } = prepare_document(
+    &mut payload,
+    &settings,
+    claims,
+    options.blank_pages,
+    &mut timings,
+    &mut warnings,
+  )?;
+  // 未指定名称时使用文档的标题
+  let job_name = job_name(settings.job_name.as_deref().or(metadata.title.as_deref()));
+
+  // 纸张高度随内容时按处理后的文档确定
+  if let Some(page_size) = settings.page_size.as_mut().filter(|p| p.is_auto_height()) {
+    page_size.height = timed("pdf", &mut timings.pdf, || {
+      content_height(&data, page_size.width, options.max_auto_page_height)
+    })?;
+  }
+
+  // 未配置打印前命令时不写入临时文件
+  if let Some(hooks) = &options.hooks {
+    timed("hooks", &mut timings.hooks, || run_hooks(hooks, &data))?;
+  }
+  check_deadline(deadline, "selecting the printer")?;
+
+  // 查找打印机，模拟打印机使用模拟的打印机能力
+  let simulated = options.simulates(&settings.printer);
+  let printer = if simulated {
+    None
+  } else {
+    let printers =
+      timed("printers", &mut timings.printers, PrinterDevice::all).map_err(device_error)?;
+    let printer = select_printer(&printers, &settings)?
+      .cloned()
+      .ok_or_else(|| {
+        CodedError::new(
+          StatusCode::NOT_FOUND,
+          ErrorCode::PrinterNotFound,
+          "No such printer",
+        )
+      })?;
+    Some(printer)
+  };
+  let cap = match &printer {
+    Some(printer) => timed("capabilities", &mut timings.capabilities, || {
+      fetch_print_capabilities(printer, options.record_fixtures.as_deref())
+    })
+    .map_err(device_error)?,
+    None => options.simulated_capabilities(&settings.printer)?,
+  };
+  if diagnostics::is_recording() {
+    let capability = printer_capability(&cap, None, PageSizeSort::default(), &Languages::default());
+    diagnostics::record(|capture| capture.capabilities = capability.to_json());
+  }
+
+  // 打印设置，打印时依次合并
+  let mut deltas: Vec<PrintTicket> = Vec::new();
+
+  // 份数
+  if let Some(copies) = settings.copies {
+    deltas.push(Copies(copies).into());
+  }
+
+  // 按打印机能力解析布局和纸张大小，未指定时按服务的设置代替 Letter、A4 等纸张
+  settings
+    .allow_letter_a4_interchange
+    .get_or_insert(options.letter_a4_interchange);
+  let plan = resolve_settings(&cap, &settings).map_err(|e| {
+    CodedError::new(
+      StatusCode::UNPROCESSABLE_ENTITY,
+      ErrorCode::UnsupportedSettings,
+      format!("{:#}", e),
+    )
+  })?;
+  warnings.extend(plan.warnings);
+  let page_height = settings
+    .page_size
+    .as_ref()
+    .filter(|p| p.is_auto_height())
+    .and(plan.media_size)
+    .map(|(_, height)| height);
+
+  let orientation = plan
+    .orientation
+    .as_ref()
+    .and_then(|ori| ori.display_name().map(str::to_string));
+  let page_size = plan
+    .media
+    .as_ref()
+    .and_then(|page| page.display_name().map(str::to_string));
+
+  // 驱动校验打印设置时可能改变布局或纸张，生成打印设置后检查
+  let mut expected = Vec::new();
+
+  if let Some(ori) = plan.orientation {
+    expected.extend(
+      ori
+        .option()
+        .name
+        .clone()
+        .map(|n| (PageOrientation::feature_name(), n)),
+    );
+    deltas.push(ori.into());
+  }
+
+  if let Some(page) = plan.media {
+    expected.extend(
+      page
+        .option()
+        .name
+        .clone()
+        .map(|n| (PageMediaSize::feature_name(), n)),
+    );
+    deltas.push(page.into());
+  }
+
+  // 驱动私有的功能及参数，检查驱动是否保留了选择的选项
+  let mut expected_driver = Vec::new();
+  if let Some(document) = resolve_driver_options(&cap, &settings, &mut warnings)? {
+    expected_driver.extend(document.features.iter().filter_map(|f| {
+      let option = f.options.first()?.name.clone()?;
+      Some((f.name.clone(), option))
+    }));
+    deltas.push(document.into());
+  }
+
+  // 小册子
+  let mut sheets = None;
+  let copies = settings.copies.unwrap_or(1) as u32;
+  // 计算纸张数用的每份页数、是否双面及每面页数
+  let mut layout = (pages.len() as u32, false, 1);
+  let data = if settings.booklet.unwrap_or_default() {
+    layout = (
+      (pages.len() as u32).div_ceil(4) * 4,
+      plan.duplex.is_some(),
+      2,
+    );
+
+    if let Some(duplex) = plan.duplex {
+      deltas.push(duplex.into());
+    }
+
+    let booklet = timed("pdf", &mut timings.pdf, || {
+      pdf::impose_booklet(&data, plan.media_size)
+    })?;
+    sheets = Some(booklet.sheets);
+    booklet.data
+  } else {
+    data
+  };
+
+  // 拆分为多个打印作业，页码已按范围及倒序选择。小册子的页面顺序依赖整份文档，不拆分
+  let split = settings
+    .split_every_pages
+    .or(options.split_every_pages)
+    .filter(|&every| every > 0 && pages.len() as u32 > every);
+  let chunks = match split {
+    Some(_) if sheets.is_some() => {
+      warnings.push("Booklets are not split into multiple jobs".to_string());
+      vec![(pages.clone(), data)]
+    }
+    Some(every) => {
+      if copies > 1 {
+        warnings.push(format!(
+          "Each chunk of up to {} pages is printed {} times before the next chunk",
+          every, copies
+        ));
+      }
+      let files = timed("pdf", &mut timings.pdf, || pdf::split(&data, every))?;
+      pages
+        .chunks(every as usize)
+        .map(<[u32]>::to_vec)
+        .zip(files)
+        .collect()
+    }
+    None => vec![(pages.clone(), data)],
+  };
+  // 分部分打印时登记任务，取消后不再提交剩余的部分
+  let running = job_id
+    .filter(|_| chunks.len() > 1)
+    .map(|id| schedule::start(id, &settings.printer, &job_name, metadata.clone()));
+  let cancelled = || running.as_ref().is_some_and(|job| job.is_cancelled());
+  let mut chunk_results = Vec::new();
+
+  let mut attempts = 0;
+  let mut retried_errors = Vec::new();
+  let mut ticket_debug = None;
+
+  if let Some(printer) = &printer {
+    // 检查打印机状态，避免任务停在队列中
+    match spooler::printer_status(printer.os_name()) {
+      Ok(status) => {
+        let problems = status.problems();
+
+        if !problems.is_empty() {
+          let msg = format!(
+            "Printer {} is not ready: {}",
+            settings.printer,
+            problems.join(", ")
+          );
+
+          if !payload.queue_if_not_ready.unwrap_or_default() {
+            return Err(
+              CodedError::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                ErrorCode::PrinterNotReady,
+                msg,
+              )
+              .into(),
+            );
+          }
+
+          warn!("{}, queueing anyway", msg);
+          warnings.push(msg);
+        }
+      }
+      Err(e) => warn!(
+        "Failed to read the status of printer {}: {}",
+        settings.printer, e
+      ),
+    }
+
+    // 打印
+    let mut builder = PrintTicketBuilder::new(printer).map_err(device_error)?;
+
+    for delta in deltas {
+      timed("merge", &mut timings.merge, || builder.merge(delta))?;
+    }
+
+    let ticket = timed("build", &mut timings.build, || builder.build())?;
+    diagnostics::record(|capture| capture.ticket = Some(ticket.get_xml().to_vec()));
+    // 之后提交到打印队列，超过截止时间时不再提交，临时文件随之删除
+    check_deadline(deadline, "spooling")?;
+
+    if payload.debug_ticket.unwrap_or_default() {
+      ticket_debug = Some(debug_ticket(&ticket)?);
+    }
+
+    for (feature, requested, actual) in changed_options(&ticket, &expected) {
+      let msg = format!(
+        "The driver changed {} from {} to {}",
+        feature.local_name, requested.local_name, actual.local_name
+      );
+      settings
+        .orientation_conflict
+        .unwrap_or_default()
+        .report(msg, &mut warnings)?;
+    }
+    for (feature, requested, actual) in changed_options(&ticket, &expected_driver) {
+      let msg = format!(
+        "The driver changed driver option {} from {} to {}",
+        feature.local_name, requested.local_name, actual.local_name
+      );
+      warn!("{}", msg);
+      warnings.push(msg);
+    }
+
+    let pdf = PdfiumPrinter::new(printer.clone());
+
+    for (index, (chunk_pages, chunk)) in chunks.iter().enumerate() {
+      if cancelled() {
+        break;
+      }
+
+      // 打印队列中的文档名称为文件名，在临时目录中按任务名称保存
+      let document_name = match chunks.len() {
+        1 => job_name.clone(),
+        n => format!("{} ({} of {})", job_name, index + 1, n),
+      };
+      let (_dir, file) = timed("write", &mut timings.write, || {
+        let dir = tempfile::tempdir()?;
+        let file = dir.path().join(&document_name);
+        std::fs::write(&file, chunk)?;
+        anyhow::Ok((dir, file))
+      })?;
+
+      let chunk_start = Instant::now();
+      let mut chunk_attempts = 0;
+      let result = timed("print", &mut timings.print, || loop {
+        chunk_attempts += 1;
+        let result = {
+          let _guard = pdf::lock();
+          pdf.print(&file, ticket.clone())
+        };
+
+        match result {
+          // 等待后重试会超过截止时间时不再重试
+          Err(e)
+            if chunk_attempts <= options.retry.retries
+              && spooler::is_transient(&e)
+              && deadline
+                .is_none_or(|d| d.remaining() > Some(options.retry.backoff(chunk_attempts))) =>
+          {
+            let e = anyhow::Error::new(e);
+            warn!("Print attempt {} failed, retrying: {:#}", chunk_attempts, e);
+            retried_errors.push(format!("{:#}", e));
+            thread::sleep(options.retry.backoff(chunk_attempts));
+          }
+          result => break result,
+        }
+      });
+      attempts += chunk_attempts;
+
+      if let Err(e) = result {
+        // 已提交部分后改用其他打印机会重复打印，不再作为打印机的错误
+        if index == 0 {
+          return Err(device_error(e));
+        }
+        bail!(
+          "Chunk {} of {} failed after the previous chunks were spooled: {:#}",
+          index + 1,
+          chunks.len(),
+          anyhow::Error::new(e)
+        );
+      }
+
+      // 按文档名称在系统打印队列中查询打印进度
+      if let Some(id) = job_id {
+        schedule::track(id, &settings.printer, &document_name);
+      }
+      chunk_results.push(ChunkResult {
+        pages: chunk_pages.clone(),
+        spooled: true,
+        attempts: chunk_attempts,
+        print: chunk_start.elapsed().as_millis() as u64,
+      });
+    }
+  } else if let Some(dir) = options.simulate.as_deref() {
+    // 模拟打印，保存最终的 PDF 及解析后的打印设置，不提交到系统打印队列
+    check_deadline(deadline, "spooling")?;
+
+    for (chunk_pages, chunk) in &chunks {
+      if cancelled() {
+        break;
+      }
+
+      let job = SimulatedJob {
+        printer: settings.printer.clone(),
+        settings: settings.to_json().unwrap_or_default(),
+        pages: chunk_pages.clone(),
+        copies,
+        orientation: orientation.clone(),
+        page_size: page_size.clone(),
+        media_size: plan.media_size,
+        duplex: layout.1,
+        warnings: warnings.clone(),
+        printed_at: Utc::now(),
+      };
+      let chunk_start = Instant::now();
+      let path = timed("write", &mut timings.write, || {
+        simulate::write(dir, chunk, &job)
+      })?;
+      info!("Simulated print written to {}", path.display());
+      attempts += 1;
+      chunk_results.push(ChunkResult {
+        pages: chunk_pages.clone(),
+        spooled: true,
+        attempts: 1,
+        print: chunk_start.elapsed().as_millis() as u64,
+      });
+    }
+  } else {
+    // 回放录制的打印机能力，只解析打印设置，不打印
+    info!(
+      "Replayed printer {}, the job stops after resolving the settings",
+      settings.printer
+    );
+    warnings.push(format!(
+      "{} is a replayed printer, nothing is printed",
+      settings.printer
+    ));
+  }
+
+  // 取消后剩余的部分未提交，费用及回执只计算已提交的页数
+  let stopped = chunk_results.len() < chunks.len() && cancelled();
+  if stopped {
+    warn!(
+      "Job to {} cancelled after {} of {} chunks were spooled",
+      settings.printer,
+      chunk_results.len(),
+      chunks.len()
+    );
+    warnings.push(format!(
+      "The job was cancelled after {} of {} chunks were spooled",
+      chunk_results.len(),
+      chunks.len()
+    ));
+    layout.0 = chunk_results
+      .iter()
+      .map(|chunk| chunk.pages.len() as u32)
+      .sum();
+  }
+  for (chunk_pages, _) in chunks.iter().skip(chunk_results.len()) {
+    chunk_results.push(ChunkResult {
+      pages: chunk_pages.clone(),
+      spooled: false,
+      attempts: 0,
+      print: 0,
+    });
+  }
+
+  let deadline_exceeded = if let Some(warning) = spooled_deadline_warning(deadline) {
+    warn!(
+      "The deadline passed while printing to {}, the job is printed anyway",
+      settings.printer
+    );
+    warnings.push(warning);
+    true
+  } else {
+    false
+  };
+
+  timings.total = start.elapsed().as_millis() as u64;
+  status::record_duration(&settings.printer, start.elapsed(), options.eta_window);
+  diagnostics::record(|capture| capture.timings = timings.to_json());
+  info!(
+    "Printed to {} in {} ms: pdf {} ms, printers {} ms, capabilities {} ms, hooks {} ms, merge {} ms, build {} ms, write {} ms, print {} ms",
+    settings.printer,
+    timings.total,
+    timings.pdf,
+    timings.printers,
+    timings.capabilities,
+    timings.hooks,
+    timings.merge,
+    timings.build,
+    timings.write,
+    timings.print
+  );
+
+  let total_pages = if stopped {
+    layout.0 * copies
+  } else {
+    pages.len() as u32 * copies
+  };
+  let total_sheets = pdf::sheet_count(layout.0, copies, layout.1, layout.2);
+  let estimated_cost = options
+    .pricing
+    .as_deref()
+    .and_then(|pricing| estimate_cost(pricing, &settings.printer, &cap, total_pages, total_sheets));
+
+  Ok(PrintResult {
+    printer: Some(settings.printer.clone()),
+    failed_printers: None,
+    pages,
+    rotate_degrees: (!rotated.is_empty()).then_some(degrees),
+    rotated_pages: (!rotated.is_empty()).then_some(rotated),
+    sheets,
+    total_pages: Some(total_pages),
+    total_sheets: Some(total_sheets),
+    estimated_cost,
+    warnings: (!warnings.is_empty()).then_some(warnings),
+    timings,
+    attempts,
+    retried_errors: (!retried_errors.is_empty()).then_some(retried_errors),
+    state: stopped.then_some(JobState::Cancelled),
+    job_id: None,
+    print_at: None,
+    document: None,
+    ticket: ticket_debug,
+    deadline_exceeded: deadline_exceeded.then_some(true),
+    chunks: (chunks.len() > 1).then_some(chunk_results),
+    coverage: None,
+    page_height,
+  })
+}
+
+/// 按价格表估算费用，价格表中没有该打印机时为空。
+/// 打印设置不指定颜色，打印机同时支持彩色和黑白时按默认值估算
+pub fn estimate_cost(
+  pricing: &PriceTable,
+  printer: &str,
+  cap: &PrintCapabilities,
+  pages: u32,
+  sheets: u32,
+) -> Option<EstimatedCost> {
+  let prices = pricing.printers.get(printer)?;
+  let colors = cap
+    .page_output_colors()
+    .filter_map(|c| c.as_predefined_name())
+    .collect::<Vec<_>>();
+  let has_color = colors.contains(&PredefinedPageOutputColor::Color);
+  let (color, approximate) = if colors.is_empty() {
+    (pricing.default_color, true)
+  } else if !has_color {
+    (false, false)
+  } else if colors
+    .iter()
+    .all(|c| *c == PredefinedPageOutputColor::Color)
+  {
+    (true, false)
+  } else {
+    (pricing.default_color, true)
+  };
+
+  Some(EstimatedCost {
+    amount: prices.estimate(pages, sheets, color),
+    currency: pricing.currency.clone(),
+    color,
+    approximate,
+  })
+}
+
+/// 在 tracing span 中执行 `f`，并将耗时（毫秒）累加到 `elapsed`
+pub fn timed<T>(step: &'static str, elapsed: &mut u64, f: impl FnOnce() -> T) -> T {
+  let span = info_span!("print_step", step, elapsed_ms = field::Empty);
+  let _enter = span.enter();
+  let start = Instant::now();
+  let result = f();
+  let ms = start.elapsed().as_millis() as u64;
+
+  span.record("elapsed_ms", ms);
+  *elapsed += ms;
+  result
+}
+
+/// 将文档写入临时文件并依次执行打印前命令。命令拒绝或超时时返回对应的错误类型
+pub fn run_hooks(hooks: &Hooks, data: &[u8]) -> anyhow::Result<()> {
+  let mut file = tempfile::Builder::new().suffix(".pdf").tempfile()?;
+  file.write_all(data)?;
+  file.flush()?;
+
+  let input = HookInput {
+    file: Some(file.path()),
+    ..Default::default()
+  };
+  hooks.run(&input).map(drop).map_err(|e| match e {
+    HookError::Rejected { .. } => CodedError::new(
+      StatusCode::UNPROCESSABLE_ENTITY,
+      ErrorCode::RejectedByHook,
+      e,
+    )
+    .into(),
+    HookError::TimedOut { .. } => {
+      CodedError::new(StatusCode::SERVICE_UNAVAILABLE, ErrorCode::HookTimedOut, e).into()
+    }
+    HookError::Failed { .. } => anyhow::Error::new(e),
+  })
+}
+
+/// 定时任务是否设置了 `skip_hooks`，用于未解析的任务内容
+pub fn skips_hooks(payload: &Value) -> bool {
+  payload.get("skip_hooks").and_then(Value::as_bool) == Some(true)
+}
+
+/// 任务结束后在后台执行打印后命令，通过标准输入传入回执的 JSON，并设置任务信息的环境变量。
+/// 命令的结果不影响任务，在回执及任务列表的 `post_print_hook` 中查询
+pub fn run_post_print_hooks(receipt: &Receipt, options: &ApiOptions, skip: bool) {
+  let Some(hooks) = options.post_print_hooks.clone() else {
+    return;
+  };
+  if skip {
+    debug!("Skipping the post-print hooks of job {}", receipt.job_id);
+    return;
+  }
+
+  let input = HookInput {
+    file: None,
+    stdin: serde_json::to_vec(receipt).ok(),
+    env: vec![
+      ("DIRECT_PRINTING_JOB_ID", receipt.job_id.clone()),
+      ("DIRECT_PRINTING_PRINTER", receipt.printer.clone()),
+      (
+        "DIRECT_PRINTING_PAGES",
+        receipt.pages.map(|p| p.to_string()).unwrap_or_default(),
+      ),
+      (
+        "DIRECT_PRINTING_OUTCOME",
+        receipt.outcome.as_str().to_string(),
+      ),
+    ],
+  };
+  hook::spawn_post_print(hooks, receipt.job_id.clone(), input);
+}
+
+/// 票据 XML 不超过此大小时不压缩
+pub const TICKET_XML_INLINE_LIMIT: usize = 16 * 1024;
+/// 压缩后的票据 XML 超过此大小时不返回
+pub const TICKET_XML_MAX: usize = 1024 * 1024;
+
+/// 生成用于排查驱动问题的票据信息
+pub fn debug_ticket(ticket: &PrintTicket) -> anyhow::Result<TicketDebug> {
+  let xml = ticket.get_xml();
+  let mut features = HashMap::new();
+
+  if let Ok(document) = PrintTicketDocument::parse_from_bytes(xml) {
+    let mut pending: Vec<_> = document
+      .features
+      .iter()
+      .map(|f| (String::new(), f))
+      .collect();
+
+    while let Some((parent, feature)) = pending.pop() {
+      let name = format!("{}{}", parent, feature.name.local_name);
+
+      if let Some(option) = feature.options.first().and_then(|o| o.name.as_ref()) {
+        features.insert(name.clone(), option.local_name.clone());
+      }
+      pending.extend(feature.features.iter().map(|f| (format!("{}.", name), f)));
+    }
+
+    for param in &document.parameter_inits {
+      features.insert(param.name.local_name.clone(), property_text(&param.value));
+    }
+  }
+
+  let (text, gzip, truncated) = if xml.len() <= TICKET_XML_INLINE_LIMIT {
+    (Some(String::from_utf8_lossy(xml).into_owned()), None, false)
+  } else {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(xml)?;
+    let compressed = encoder.finish()?;
+
+    if compressed.len() <= TICKET_XML_MAX {
+      (None, Some(Base64(compressed)), false)
+    } else {
+      (None, None, true)
+    }
+  };
+
+  Ok(TicketDebug {
+    xml: text,
+    xml_gzip: gzip,
+    size: xml.len() as u32,
+    truncated,
+    features,
+  })
+}
+
+/// 一次打印的耗时，单位为毫秒，用于基准测试
+#[derive(Debug, Clone, Copy)]
+pub struct JobTiming {
+  /// 合并打印设置并生成打印票据
+  pub ticket: u64,
+  /// 写入临时文件并提交到打印队列，模拟打印时为保存文件
+  pub spool: u64,
+}
+
+/// 不经过 HTTP 直接打印 PDF 文件，打印设置只指定打印机，用于基准测试
+pub fn print_pdf(data: Vec<u8>, printer: &str, options: &ApiOptions) -> anyhow::Result<JobTiming> {
+  let settings = PrintSettings {
+    printer: printer.to_string(),
+    ..Default::default()
+  };
+  let payload = PrintPayload {
+    file: Some(Base64(data)),
+    settings: Some(settings),
+    ..Default::default()
+  };
+  let timings =
+    catch_panic(|| print_file(payload, options, &Client::default(), None, None, None))?.timings;
+
+  Ok(JobTiming {
+    ticket: timings.merge + timings.build,
+    spool: timings.write + timings.print,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn is_deadline_exceeded(result: anyhow::Result<()>) -> bool {
+    result.err().is_some_and(|e| {
+      e.downcast_ref::<CodedError>()
+        .is_some_and(|e| matches!(e.code, ErrorCode::DeadlineExceeded))
+    })
+  }
+
+  #[test]
+  fn cancels_when_the_deadline_passed_before_spooling() {
+    let deadline = Deadline(Instant::now() - Duration::from_millis(1));
+    assert!(deadline.passed());
+
+    let result = check_deadline(Some(deadline), "spooling");
+    let msg = result.as_ref().err().map(|e| e.to_string());
+    assert!(is_deadline_exceeded(result));
+    assert_eq!(
+      msg.as_deref(),
+      Some("The deadline passed before spooling, the job is cancelled")
+    );
+  }
+
+  #[test]
+  fn cancels_exactly_at_the_deadline() {
+    let now = Instant::now();
+    let deadline = Deadline(now);
+    assert_eq!(deadline.remaining_at(now), None);
+    assert_eq!(
+      deadline.remaining_at(now - Duration::from_millis(1)),
+      Some(Duration::from_millis(1))
+    );
+
+    // `Instant::now()` 不早于 `now`，检查时已到截止时间
+    assert!(is_deadline_exceeded(check_deadline(
+      Some(deadline),
+      "spooling"
+    )));
+  }
+
+  #[test]
+  fn prints_when_the_deadline_passes_after_spooling() {
+    let deadline = Deadline::after(50).unwrap();
+    assert!(check_deadline(Some(deadline), "spooling").is_ok());
+
+    assert_eq!(spooled_deadline_warning(Some(deadline)), None);
+
+    // 提交到打印队列后超过截止时间，任务不取消，只在结果中说明
+    thread::sleep(Duration::from_millis(60));
+    assert!(deadline.passed());
+    assert_eq!(
+      spooled_deadline_warning(Some(deadline)).as_deref(),
+      Some("The deadline passed after the job was handed to the spooler")
+    );
+    assert_eq!(spooled_deadline_warning(None), None);
+  }
+
+  #[test]
+  fn does_not_limit_without_a_deadline() {
+    assert!(check_deadline(None, "spooling").is_ok());
+    assert!(Deadline::after(u64::MAX).is_none());
+
+    let deadline = Deadline::after(60_000).unwrap();
+    assert!(!deadline.passed());
+    assert!(deadline.remaining() > Some(Duration::from_secs(59)));
+  }
+}
@@ -0,0 +1,335 @@
+use std::{collections::HashMap, net::IpAddr};
+
+use log::warn;
+use poem::{http::StatusCode, FromRequest, Request, RequestBody};
+use sha2::{Digest, Sha256};
+use winprint::printer::PrinterDevice;
+
+use super::{
+  access::Client,
+  dispatch::oldest_queued_seconds,
+  error::{CodedError, ErrorCode},
+  schedule::{self, QueueLimits},
+  v1::{PrintSettings, PrinterEntry, PrinterFormat, PrinterState},
+};
+
+use crate::{spooler, status};
+
+/// 打印机状态，系统状态使用缓存
+pub fn printer_state(name: &str, printer: &PrinterDevice, limits: &QueueLimits) -> PrinterState {
+  let status = spooler::printer_status(printer.os_name())
+    .inspect_err(|e| warn!("Failed to read the status of printer {}: {}", name, e))
+    .ok();
+
+  PrinterState {
+    spooler_paused: status.map(|s| s.is_paused()),
+    problems: status.map(|s| s.problems().into_iter().map(str::to_string).collect()),
+    ..queue_state(name, limits)
+  }
+}
+
+/// 模拟或回放的打印机的状态，没有系统打印队列，不会暂停或出错
+pub fn simulated_printer_state(name: &str, limits: &QueueLimits) -> PrinterState {
+  PrinterState {
+    spooler_paused: Some(false),
+    problems: Some(Vec::new()),
+    ..queue_state(name, limits)
+  }
+}
+
+/// 服务内的暂停及排队状态，不含系统打印队列的状态
+fn queue_state(name: &str, limits: &QueueLimits) -> PrinterState {
+  let depth = schedule::depth(name);
+
+  PrinterState {
+    paused: status::is_printer_paused(name),
+    spooler_paused: None,
+    problems: None,
+    queued_jobs: depth.jobs,
+    queued_bytes: depth.bytes,
+    oldest_queued_seconds: oldest_queued_seconds(&depth),
+    max_queued_jobs: limits.max_jobs,
+    max_queued_bytes: limits.max_bytes,
+  }
+}
+
+/// 发起请求的客户端，包括 IP 地址和 `X-API-Key` 请求头
+pub fn request_client(req: &Request) -> Client {
+  let ip = req.remote_addr().as_socket_addr().map(|addr| addr.ip());
+  Client::new(ip, req.header("X-API-Key"))
+}
+
+impl<'a> FromRequest<'a> for Client {
+  async fn from_request(req: &'a Request, _body: &mut RequestBody) -> poem::Result<Self> {
+    Ok(request_client(req))
+  }
+}
+
+/// 是否为 `\\server\printer` 形式的共享打印机路径
+pub fn is_unc_printer_path(path: &str) -> bool {
+  let Some(rest) = path.strip_prefix(r"\\") else {
+    return false;
+  };
+  let mut parts = rest.split('\\');
+
+  matches!(
+    (parts.next(), parts.next(), parts.next()),
+    (Some(server), Some(printer), None) if !server.is_empty() && !printer.is_empty()
+  )
+}
+
+/// 打印机列表的排序键，默认打印机在前，其余按规范化的名称排序，
+/// 规范化后相同的名称再按原名称排序，同名的打印机相邻且顺序固定
+pub fn printer_order(name: &str, is_default: bool) -> (bool, String, String) {
+  (!is_default, normalize_printer_name(name), name.to_string())
+}
+
+/// 还原 XML 实体，合并连续的空白并转为小写
+pub fn normalize_printer_name(name: &str) -> String {
+  let decoded = [
+    ("&lt;", "<"),
+    ("&gt;", ">"),
+    ("&quot;", "\""),
+    ("&apos;", "'"),
+    // 最后还原 `&`，避免重复还原
+    ("&amp;", "&"),
+  ]
+  .iter()
+  .fold(name.to_string(), |name, (entity, c)| {
+    name.replace(entity, c)
+  });
+  decoded
+    .split_whitespace()
+    .collect::<Vec<_>>()
+    .join(" ")
+    .to_lowercase()
+}
+
+/// 打印机的详细信息，无法读取时端口、驱动等为空
+pub fn printer_entry(printer: &PrinterDevice) -> PrinterEntry {
+  let info = spooler::printer_info(printer.os_name()).unwrap_or_default();
+  let non_empty = |s: String| (!s.is_empty()).then_some(s);
+
+  PrinterEntry {
+    id: printer_id(printer.name(), &info),
+    name: printer.name().to_string(),
+    location: non_empty(info.location),
+    comment: non_empty(info.comment),
+    shared: info.shared,
+    share_name: non_empty(info.share_name),
+    remote: printer.is_remote(),
+    supported_formats: PrinterFormat::from_datatypes(&info.datatypes),
+    aliases: None,
+    port: info.port,
+    driver: info.driver,
+  }
+}
+
+/// 网络端口指向的设备地址，如 `IP_10.0.0.5`、`10.0.0.5_1` 为 `10.0.0.5`。
+/// USB、LPT 等本地端口在不同的计算机上是不同的设备，为空
+pub fn port_device(port: &str) -> Option<String> {
+  let port = port.trim().to_lowercase();
+  let target = port.strip_prefix("ip_").unwrap_or(&port);
+  // 系统为同一地址创建多个端口时加上 `_1` 等后缀
+  let target = match target.rsplit_once('_') {
+    Some((host, n))
+      if !n.is_empty()
+        && n.bytes().all(|b| b.is_ascii_digit())
+        && host.parse::<IpAddr>().is_ok() =>
+    {
+      host
+    }
+    _ => target,
+  };
+
+  (target.parse::<IpAddr>().is_ok() || (target.contains('.') && !target.ends_with(':')))
+    .then(|| target.to_string())
+}
+
+/// 打印机所在的设备，只比较网络打印机，无法读取端口时为空
+pub fn printer_device(printer: &PrinterDevice) -> Option<String> {
+  if !printer.is_remote() {
+    return None;
+  }
+  let info = spooler::printer_info(printer.os_name()).ok()?;
+  port_device(&info.port)
+}
+
+/// 合并端口指向同一设备的打印机，保留排在最前的一台，其余作为它的别名
+pub fn dedupe_printers(printers: Vec<PrinterDevice>) -> Vec<(PrinterDevice, Vec<String>)> {
+  dedupe_by_device(printers, printer_device, |printer| {
+    printer.name().to_string()
+  })
+}
+
+/// 按 `device` 合并指向同一设备的项，设备为空的项不合并。其余项的 `name` 作为保留的项的别名
+fn dedupe_by_device<T>(
+  items: Vec<T>,
+  device: impl Fn(&T) -> Option<String>,
+  name: impl Fn(&T) -> String,
+) -> Vec<(T, Vec<String>)> {
+  let mut deduped: Vec<(T, Vec<String>)> = Vec::new();
+  let mut devices: HashMap<String, usize> = HashMap::new();
+
+  for item in items {
+    let device = device(&item);
+    if let Some(&index) = device.as_ref().and_then(|d| devices.get(d)) {
+      deduped[index].1.push(name(&item));
+      continue;
+    }
+    if let Some(device) = device {
+      devices.insert(device, deduped.len());
+    }
+    deduped.push((item, Vec::new()));
+  }
+
+  deduped
+}
+
+/// 由名称、端口及驱动计算的打印机 ID，重新安装到同一端口时不变
+pub fn printer_id(name: &str, info: &spooler::PrinterInfo) -> String {
+  let hash = Sha256::new()
+    .chain_update(name)
+    .chain_update([0])
+    .chain_update(&info.port)
+    .chain_update([0])
+    .chain_update(&info.driver)
+    .finalize();
+  hash[..8].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn device_printer_id(printer: &PrinterDevice) -> String {
+  let info = spooler::printer_info(printer.os_name()).unwrap_or_default();
+  printer_id(printer.name(), &info)
+}
+
+/// 按打印设置查找打印机，指定 `printer_id` 时按 ID 查找，否则同 [`select_named_printer`]
+pub fn select_printer<'a>(
+  printers: &'a [PrinterDevice],
+  settings: &PrintSettings,
+) -> anyhow::Result<Option<&'a PrinterDevice>> {
+  if let Some(id) = &settings.printer_id {
+    return Ok(printers.iter().find(|p| device_printer_id(p) == *id));
+  }
+
+  Ok(select_named_printer(printers, &settings.printer)?)
+}
+
+/// 按打印设置中的名称查找打印机。
+/// 名称对应多台打印机时返回错误并列出各打印机的 ID，不随意选择其中一台
+pub fn select_named_printer<'a>(
+  printers: &'a [PrinterDevice],
+  name: &str,
+) -> Result<Option<&'a PrinterDevice>, CodedError> {
+  let matched = printers
+    .iter()
+    .filter(|p| display_name(p) == name)
+    .collect::<Vec<_>>();
+
+  if matched.len() > 1 {
+    let ids = matched
+      .iter()
+      .map(|p| device_printer_id(p))
+      .collect::<Vec<_>>();
+    let msg = format!(
+      "Printer {} matches {} printers, set printer_id to one of {}",
+      name,
+      matched.len(),
+      ids.join(", ")
+    );
+    return Err(CodedError::new(
+      StatusCode::CONFLICT,
+      ErrorCode::AmbiguousPrinter,
+      msg,
+    ));
+  }
+
+  Ok(matched.first().copied())
+}
+
+/// 打印设置中使用的打印机名称
+pub fn display_name(printer: &PrinterDevice) -> String {
+  printer.name().replace("&#xEB;米", "毫米")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn finds_network_port_devices() {
+    assert_eq!(port_device("IP_10.0.0.5").as_deref(), Some("10.0.0.5"));
+    assert_eq!(port_device("10.0.0.5").as_deref(), Some("10.0.0.5"));
+    assert_eq!(port_device(" ip_10.0.0.5 ").as_deref(), Some("10.0.0.5"));
+    assert_eq!(
+      port_device("HP404.Office.Example.com").as_deref(),
+      Some("hp404.office.example.com")
+    );
+    assert_eq!(port_device("fe80::1").as_deref(), Some("fe80::1"));
+  }
+
+  #[test]
+  fn strips_duplicate_port_suffixes() {
+    assert_eq!(port_device("10.0.0.5_1").as_deref(), Some("10.0.0.5"));
+    assert_eq!(port_device("IP_10.0.0.5_12").as_deref(), Some("10.0.0.5"));
+    // 只去掉 IP 地址后的数字后缀
+    assert_eq!(
+      port_device("printer_1.example.com").as_deref(),
+      Some("printer_1.example.com")
+    );
+  }
+
+  #[test]
+  fn ignores_local_ports() {
+    for port in [
+      "USB001",
+      "LPT1:",
+      "COM3:",
+      "FILE:",
+      "PORTPROMPT:",
+      "nul:",
+      "WSD-3f2a",
+      "",
+    ] {
+      assert_eq!(port_device(port), None, "{}", port);
+    }
+  }
+
+  #[test]
+  fn merges_printers_on_the_same_device() {
+    let printers = vec![
+      ("HP LaserJet", "IP_10.0.0.5"),
+      ("Label", "USB001"),
+      ("HP LaserJet (Copy 1)", "10.0.0.5_1"),
+      ("Label (Copy 1)", "USB001"),
+      ("Office", "office.example.com"),
+      ("HP LaserJet (Copy 2)", "IP_10.0.0.5"),
+    ];
+    let deduped = dedupe_by_device(
+      printers,
+      |(_, port)| port_device(port),
+      |(name, _)| name.to_string(),
+    );
+
+    let deduped = deduped
+      .iter()
+      .map(|((name, _), aliases)| (*name, aliases.clone()))
+      .collect::<Vec<_>>();
+    assert_eq!(
+      deduped,
+      [
+        (
+          "HP LaserJet",
+          vec![
+            "HP LaserJet (Copy 1)".to_string(),
+            "HP LaserJet (Copy 2)".to_string()
+          ]
+        ),
+        // 本地端口的打印机不合并
+        ("Label", vec![]),
+        ("Label (Copy 1)", vec![]),
+        ("Office", vec![]),
+      ]
+    );
+  }
+}
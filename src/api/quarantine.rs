@@ -0,0 +1,206 @@
+use std::{
+  collections::HashMap,
+  fs::{create_dir_all, read, read_dir, read_to_string},
+  path::PathBuf,
+};
+
+use chrono::{DateTime, Utc};
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{
+  receipt,
+  retention::{Artifact, ArtifactStore},
+};
+use crate::storage::{file_stem, write_atomically};
+
+/// 隔离 ID 的前缀，之后为 8 位大写十六进制数
+const ID_PREFIX: &str = "Q-";
+
+/// 保存被拒绝的打印请求，便于支持人员复现问题。只在配置了目录时启用，按保留策略删除
+#[derive(Debug)]
+pub struct Quarantine {
+  dir: PathBuf,
+  /// 保存的文档总大小的上限（字节），超过时只保存请求及拒绝原因
+  max_size: usize,
+}
+
+/// 隔离的文件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuarantinedFile {
+  pub size: usize,
+  pub sha256: String,
+}
+
+/// 隔离记录，保存为 `<ID>.json`，文件内容保存为 `<ID>.<序号>.bin`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuarantineInfo {
+  pub id: String,
+  pub created_at: DateTime<Utc>,
+  /// 错误类型，没有时为空
+  pub error: Option<String>,
+  /// 拒绝的原因，即返回的错误信息
+  pub reason: String,
+  /// 隐藏文件内容后的请求体
+  pub request: Value,
+  pub files: Vec<QuarantinedFile>,
+  /// 文件超过大小上限，没有保存内容
+  pub omitted: bool,
+}
+
+/// 隔离记录及保存的文件内容
+#[derive(Debug)]
+pub struct QuarantineEntry {
+  pub info: QuarantineInfo,
+  pub contents: Vec<Vec<u8>>,
+}
+
+/// 处理请求前记录的请求内容，请求被拒绝时才保存
+#[derive(Debug)]
+pub struct Evidence {
+  request: Value,
+  files: Vec<QuarantinedFile>,
+  /// 超过大小上限时为空
+  contents: Option<Vec<Vec<u8>>>,
+}
+
+impl Quarantine {
+  pub fn new(dir: PathBuf, max_size: usize) -> Self {
+    Self { dir, max_size }
+  }
+
+  /// 记录请求内容，文件总大小不超过上限时复制文件内容
+  pub fn evidence(&self, files: &[&[u8]], request: Value) -> Evidence {
+    let total: usize = files.iter().map(|f| f.len()).sum();
+    Evidence {
+      request,
+      files: files
+        .iter()
+        .map(|f| QuarantinedFile {
+          size: f.len(),
+          sha256: receipt::document_hash([*f]),
+        })
+        .collect(),
+      contents: (total <= self.max_size).then(|| files.iter().map(|f| f.to_vec()).collect()),
+    }
+  }
+
+  /// 保存被拒绝的请求，返回隔离 ID
+  pub fn store(
+    &self,
+    evidence: Evidence,
+    error: Option<String>,
+    reason: &str,
+  ) -> anyhow::Result<String> {
+    create_dir_all(&self.dir)?;
+    let id = format!("{}{:08X}", ID_PREFIX, rand::random::<u32>());
+
+    let info = QuarantineInfo {
+      id: id.clone(),
+      created_at: Utc::now(),
+      error,
+      reason: reason.to_string(),
+      request: evidence.request,
+      files: evidence.files,
+      omitted: evidence.contents.is_none(),
+    };
+    // 先写入文件内容，记录存在时文件一定完整
+    for (i, content) in evidence.contents.iter().flatten().enumerate() {
+      write_atomically(&self.content_path(&id, i), content)?;
+    }
+    let json = serde_json::to_vec_pretty(&info)?;
+    write_atomically(&self.info_path(&id), &json)?;
+    info!("Quarantined the rejected request as {}", id);
+    Ok(id)
+  }
+
+  /// 读取隔离记录，ID 格式错误或不存在时为空
+  pub fn get(&self, id: &str) -> anyhow::Result<Option<QuarantineEntry>> {
+    if !is_valid_id(id) {
+      return Ok(None);
+    }
+    let path = self.info_path(id);
+    if !path.exists() {
+      return Ok(None);
+    }
+
+    let info: QuarantineInfo = serde_json::from_str(&read_to_string(path)?)?;
+    let contents = if info.omitted {
+      Vec::new()
+    } else {
+      (0..info.files.len())
+        .map(|i| read(self.content_path(id, i)))
+        .collect::<std::io::Result<_>>()?
+    };
+    Ok(Some(QuarantineEntry { info, contents }))
+  }
+
+  fn info_path(&self, id: &str) -> PathBuf {
+    self.dir.join(format!("{}.json", file_stem(id)))
+  }
+
+  fn content_path(&self, id: &str, index: usize) -> PathBuf {
+    self.dir.join(format!("{}.{}.bin", file_stem(id), index))
+  }
+}
+
+/// 每条记录包括记录及其文件，按记录的修改时间判断新旧
+impl ArtifactStore for Quarantine {
+  fn artifacts(&self) -> anyhow::Result<Vec<Artifact>> {
+    let entries = match read_dir(&self.dir) {
+      Ok(entries) => entries,
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+      Err(e) => return Err(e.into()),
+    };
+
+    let mut records: HashMap<String, Artifact> = HashMap::new();
+    let mut contents = Vec::new();
+    for entry in entries.flatten() {
+      let Ok(name) = entry.file_name().into_string() else {
+        continue;
+      };
+      let Ok(meta) = entry.metadata() else {
+        continue;
+      };
+      if let Some(id) = name.strip_suffix(".json").filter(|id| is_valid_id(id)) {
+        let Ok(modified) = meta.modified() else {
+          continue;
+        };
+        let record = records.entry(id.to_string()).or_insert_with(|| Artifact {
+          id: id.to_string(),
+          modified,
+          size: 0,
+          paths: Vec::new(),
+        });
+        record.modified = modified;
+        record.size += meta.len();
+        // 先删除记录，删除文件失败时不再被读取
+        record.paths.insert(0, entry.path());
+      } else if let Some((id, _)) = name.split_once('.').filter(|(id, _)| is_valid_id(id)) {
+        if name.ends_with(".bin") {
+          contents.push((id.to_string(), meta.len(), entry.path()));
+        }
+      }
+    }
+
+    // 没有记录的文件是写入记录前中断的，不删除，以免删除正在写入的文件
+    for (id, size, path) in contents {
+      if let Some(record) = records.get_mut(&id) {
+        record.size += size;
+        record.paths.push(path);
+      }
+    }
+    Ok(records.into_values().collect())
+  }
+}
+
+/// ID 是否为 `Q-` 加 8 位大写十六进制数，避免读取目录外的文件
+fn is_valid_id(id: &str) -> bool {
+  id.strip_prefix(ID_PREFIX).is_some_and(|hex| {
+    hex.len() == 8
+      && hex
+        .chars()
+        .all(|c| c.is_ascii_digit() || ('A'..='F').contains(&c))
+  })
+}
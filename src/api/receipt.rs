@@ -0,0 +1,379 @@
+use std::{
+  fs::{create_dir_all, read, read_dir, read_to_string},
+  path::{Path, PathBuf},
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    LazyLock,
+  },
+};
+
+use anyhow::{anyhow, Context};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, Utc};
+use log::{error, info};
+use ring::{
+  rand::SystemRandom,
+  signature::{self, Ed25519KeyPair, KeyPair, UnparsedPublicKey},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{
+  events,
+  retention::{Artifact, ArtifactStore},
+};
+use crate::{
+  instance,
+  storage::{file_stem, write_atomically},
+};
+
+/// 任务的结果
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+  /// 已提交到系统打印队列
+  Printed,
+  /// 打印失败
+  Failed,
+  /// 定时任务超过有效期
+  Expired,
+  /// 定时任务被取消
+  Cancelled,
+}
+
+impl Outcome {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Self::Printed => "printed",
+      Self::Failed => "failed",
+      Self::Expired => "expired",
+      Self::Cancelled => "cancelled",
+    }
+  }
+}
+
+/// 任务结束时生成的回执，签名后保存
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Receipt {
+  pub job_id: String,
+  /// 打印文档的 SHA-256，十六进制。多个文件时为按顺序连接后的 SHA-256
+  pub document_sha256: Option<String>,
+  pub printer: String,
+  /// 打印的总页数，包括份数
+  pub pages: Option<u32>,
+  pub submitted_at: Option<DateTime<Utc>>,
+  pub finished_at: DateTime<Utc>,
+  pub outcome: Outcome,
+  /// 失败或取消的原因
+  pub detail: Option<String>,
+  /// 签发回执的服务实例，见 `GET /info`
+  #[serde(default)]
+  pub instance_id: Option<String>,
+  /// 使用的纸张总数，考虑份数、双面及拼版
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub sheets: Option<u32>,
+  /// 失败的错误类型，与响应中的 `error` 相同
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub error: Option<String>,
+}
+
+/// 签名的回执。`receipt` 为签名的 JSON 原文，验证时不要重新序列化
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedReceipt {
+  pub receipt: String,
+  /// Ed25519 签名，Base64
+  pub signature: String,
+}
+
+impl SignedReceipt {
+  /// 解析签名的回执内容
+  pub fn parse(&self) -> anyhow::Result<Receipt> {
+    Ok(serde_json::from_str(&self.receipt)?)
+  }
+
+  /// 用公钥验证签名，通过后解析回执内容
+  pub fn verify(&self, public_key: &[u8]) -> anyhow::Result<Receipt> {
+    let signature = STANDARD
+      .decode(&self.signature)
+      .context("Invalid receipt signature")?;
+    UnparsedPublicKey::new(&signature::ED25519, public_key)
+      .verify(self.receipt.as_bytes(), &signature)
+      .map_err(|_| anyhow!("The receipt signature does not match"))?;
+    self.parse()
+  }
+}
+
+/// 签名密钥，首次使用时生成并以 PKCS#8 格式保存在数据目录中。密钥不输出到日志
+struct Signer {
+  key: Ed25519KeyPair,
+  dir: PathBuf,
+}
+
+static SIGNER: LazyLock<Option<Signer>> = LazyLock::new(|| {
+  let dir = instance::data_dir()?;
+
+  match load_or_generate_key(&dir.join("receipt.key")) {
+    Ok(key) => Some(Signer {
+      key,
+      dir: dir.join("receipts"),
+    }),
+    Err(e) => {
+      error!("Job receipts are disabled: {:#}", e);
+      None
+    }
+  }
+});
+
+fn load_or_generate_key(path: &Path) -> anyhow::Result<Ed25519KeyPair> {
+  if path.exists() {
+    let pkcs8 = read(path)?;
+    return Ed25519KeyPair::from_pkcs8(&pkcs8)
+      .map_err(|_| anyhow!("Invalid receipt signing key {}", path.display()));
+  }
+
+  let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+    .map_err(|_| anyhow!("Failed to generate the receipt signing key"))?;
+  if let Some(dir) = path.parent() {
+    create_dir_all(dir)?;
+  }
+  write_atomically(path, pkcs8.as_ref())
+    .with_context(|| format!("Cannot save the receipt signing key to {}", path.display()))?;
+  info!("Generated the receipt signing key in {}", path.display());
+
+  Ed25519KeyPair::from_pkcs8(pkcs8.as_ref())
+    .map_err(|_| anyhow!("Failed to generate the receipt signing key"))
+}
+
+/// 是否在回执中保留文档的 SHA-256，见保留策略的 `keep_document_hashes`
+static KEEP_DOCUMENT_HASHES: AtomicBool = AtomicBool::new(true);
+
+/// 设置是否在回执中保留文档的 SHA-256，之后签发的回执生效
+pub fn set_keep_document_hashes(keep: bool) {
+  KEEP_DOCUMENT_HASHES.store(keep, Ordering::Relaxed);
+}
+
+fn signer() -> anyhow::Result<&'static Signer> {
+  SIGNER
+    .as_ref()
+    .ok_or_else(|| anyhow!("Job receipts are not available"))
+}
+
+/// 验证签名的公钥，Base64 编码的 32 字节
+pub fn public_key() -> anyhow::Result<String> {
+  Ok(STANDARD.encode(signer()?.key.public_key()))
+}
+
+/// 文档的 SHA-256，十六进制
+pub fn document_hash<'a>(files: impl IntoIterator<Item = &'a [u8]>) -> String {
+  let mut hasher = Sha256::new();
+  for file in files {
+    hasher.update(file);
+  }
+  hasher
+    .finalize()
+    .iter()
+    .map(|b| format!("{:02x}", b))
+    .collect()
+}
+
+/// 签名并保存回执，失败时只记录错误，不影响任务。同时通知任务结束
+pub fn issue(receipt: &Receipt) {
+  if let Err(e) = try_issue(receipt) {
+    error!(
+      "Failed to issue the receipt of job {}: {:#}",
+      receipt.job_id, e
+    );
+  }
+  // 保存后再通知，收到通知时可以读取回执
+  events::publish(receipt.into());
+}
+
+fn try_issue(receipt: &Receipt) -> anyhow::Result<()> {
+  let signer = signer()?;
+  let signed = sign(
+    &signer.key,
+    receipt,
+    KEEP_DOCUMENT_HASHES.load(Ordering::Relaxed),
+  )?;
+
+  create_dir_all(&signer.dir)?;
+  write_atomically(
+    &receipt_filepath(&signer.dir, &receipt.job_id),
+    serde_json::to_string(&signed)?,
+  )?;
+  Ok(())
+}
+
+fn sign(
+  key: &Ed25519KeyPair,
+  receipt: &Receipt,
+  keep_document_hash: bool,
+) -> anyhow::Result<SignedReceipt> {
+  let text = if keep_document_hash {
+    serde_json::to_string(receipt)?
+  } else {
+    serde_json::to_string(&Receipt {
+      document_sha256: None,
+      ..receipt.clone()
+    })?
+  };
+  Ok(SignedReceipt {
+    signature: STANDARD.encode(key.sign(text.as_bytes())),
+    receipt: text,
+  })
+}
+
+/// 获取任务的回执，任务不存在或尚未结束时为空
+pub fn get(job_id: &str) -> Option<SignedReceipt> {
+  // 任务 ID 由服务生成，只包含十六进制字符，避免读取其他文件
+  if job_id.is_empty() || !job_id.chars().all(|c| c.is_ascii_hexdigit()) {
+    return None;
+  }
+
+  let signer = signer().ok()?;
+  let json = read_to_string(receipt_filepath(&signer.dir, job_id)).ok()?;
+  serde_json::from_str(&json).ok()
+}
+
+/// 结束时间在 `start` 及 `end` 之间（不包括 `end`）的任务的回执，无法读取的回执被忽略
+pub fn finished_between(start: DateTime<Utc>, end: DateTime<Utc>) -> Vec<Receipt> {
+  let Ok(signer) = signer() else {
+    return Vec::new();
+  };
+  let Ok(entries) = read_dir(&signer.dir) else {
+    return Vec::new();
+  };
+
+  entries
+    .flatten()
+    // 回执在任务结束后写入，修改时间早于开始时间的回执不在范围内，不必读取
+    .filter(|entry| {
+      entry
+        .metadata()
+        .and_then(|meta| meta.modified())
+        .map_or(true, |modified| DateTime::<Utc>::from(modified) >= start)
+    })
+    .filter_map(|entry| read_to_string(entry.path()).ok())
+    .filter_map(|json| serde_json::from_str::<SignedReceipt>(&json).ok())
+    .filter_map(|signed| signed.parse().ok())
+    .filter(|receipt| receipt.finished_at >= start && receipt.finished_at < end)
+    .collect()
+}
+
+fn receipt_filepath(dir: &Path, job_id: &str) -> PathBuf {
+  dir.join(format!("{}.json", file_stem(job_id)))
+}
+
+/// 保存的回执，按保留策略删除
+pub struct ReceiptStore;
+
+impl ArtifactStore for ReceiptStore {
+  fn artifacts(&self) -> anyhow::Result<Vec<Artifact>> {
+    let Ok(signer) = signer() else {
+      return Ok(Vec::new());
+    };
+    let entries = match read_dir(&signer.dir) {
+      Ok(entries) => entries,
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+      Err(e) => return Err(e.into()),
+    };
+
+    Ok(
+      entries
+        .flatten()
+        .filter_map(|entry| {
+          let name = entry.file_name().into_string().ok()?;
+          let job_id = name.strip_suffix(".json")?.to_string();
+          let meta = entry.metadata().ok()?;
+          Some(Artifact {
+            id: job_id,
+            modified: meta.modified().ok()?,
+            size: meta.len(),
+            paths: vec![entry.path()],
+          })
+        })
+        .collect(),
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn receipt() -> Receipt {
+    Receipt {
+      job_id: "job-1".to_string(),
+      document_sha256: Some(document_hash([b"%PDF-1.7".as_slice()])),
+      printer: "HP LaserJet Pro M404".to_string(),
+      pages: Some(2),
+      submitted_at: None,
+      finished_at: Utc::now(),
+      outcome: Outcome::Printed,
+      detail: None,
+      instance_id: None,
+      sheets: Some(1),
+      error: None,
+    }
+  }
+
+  fn signing_key(dir: &Path) -> Ed25519KeyPair {
+    load_or_generate_key(&dir.join("receipt.key")).unwrap()
+  }
+
+  #[test]
+  fn verifies_signed_receipts() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = signing_key(dir.path());
+    let signed = sign(&key, &receipt(), true).unwrap();
+
+    let verified = signed.verify(key.public_key().as_ref()).unwrap();
+    assert_eq!(verified.job_id, "job-1");
+    assert_eq!(verified.document_sha256, receipt().document_sha256);
+    assert!(matches!(verified.outcome, Outcome::Printed));
+
+    // 重新加载保存的密钥，签名仍可验证
+    let reloaded = load_or_generate_key(&dir.path().join("receipt.key")).unwrap();
+    assert_eq!(reloaded.public_key().as_ref(), key.public_key().as_ref());
+    assert!(signed.verify(reloaded.public_key().as_ref()).is_ok());
+  }
+
+  #[test]
+  fn omits_document_hashes_before_signing() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = signing_key(dir.path());
+    let signed = sign(&key, &receipt(), false).unwrap();
+
+    let verified = signed.verify(key.public_key().as_ref()).unwrap();
+    assert_eq!(verified.document_sha256, None);
+  }
+
+  #[test]
+  fn rejects_tampered_receipts() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = signing_key(dir.path());
+    let public_key = key.public_key().as_ref().to_vec();
+    let signed = sign(&key, &receipt(), true).unwrap();
+
+    let tampered = SignedReceipt {
+      receipt: signed.receipt.replace("\"pages\":2", "\"pages\":20"),
+      ..signed.clone()
+    };
+    assert_ne!(tampered.receipt, signed.receipt);
+    assert!(tampered.verify(&public_key).is_err());
+
+    let mut signature = STANDARD.decode(&signed.signature).unwrap();
+    signature[0] ^= 1;
+    let tampered = SignedReceipt {
+      signature: STANDARD.encode(signature),
+      ..signed.clone()
+    };
+    assert!(tampered.verify(&public_key).is_err());
+
+    // 其他密钥的签名
+    let other = tempfile::tempdir().unwrap();
+    assert!(signed
+      .verify(signing_key(other.path()).public_key().as_ref())
+      .is_err());
+  }
+}
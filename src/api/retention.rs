@@ -0,0 +1,289 @@
+use std::{
+  collections::HashMap,
+  fs::{read_to_string, remove_dir_all, remove_file},
+  io,
+  path::{Path, PathBuf},
+  sync::Arc,
+  time::{Duration, SystemTime},
+};
+
+use anyhow::{bail, Context};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+/// 任务的回执
+pub const RECEIPTS: &str = "receipts";
+/// 被拒绝的请求
+pub const QUARANTINE: &str = "quarantine";
+/// 任务的诊断信息
+pub const DIAGNOSTICS: &str = "diagnostics";
+
+/// 可以配置保留策略的数据类别
+const CLASSES: &[&str] = &[RECEIPTS, QUARANTINE, DIAGNOSTICS];
+
+/// 定期清理的间隔
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// 一类数据的保留策略，各项为空时不限制。超过任何一项时从最早的开始删除
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+  /// 保留的天数
+  pub max_age_days: Option<u64>,
+  /// 保留的数量
+  pub max_count: Option<usize>,
+  /// 保留的总字节数
+  pub max_bytes: Option<u64>,
+}
+
+impl Policy {
+  pub fn max_age(&self) -> Option<Duration> {
+    self
+      .max_age_days
+      .map(|days| Duration::from_secs(days.saturating_mul(24 * 3600)))
+  }
+}
+
+/// 保留策略的配置，从 JSON 文件读取，如
+/// `{"receipts": {"max_age_days": 365}, "quarantine": {"max_count": 50}, "keep_document_hashes": false}`。
+/// 配置了的类别完全使用配置的策略，未配置的类别使用命令行参数决定的默认策略
+#[derive(Debug, Deserialize)]
+pub struct RetentionConfig {
+  /// 是否在回执中保留文档的 SHA-256
+  #[serde(default = "keep_document_hashes")]
+  pub keep_document_hashes: bool,
+  #[serde(flatten)]
+  classes: HashMap<String, Policy>,
+}
+
+fn keep_document_hashes() -> bool {
+  true
+}
+
+impl Default for RetentionConfig {
+  fn default() -> Self {
+    Self {
+      keep_document_hashes: true,
+      classes: HashMap::new(),
+    }
+  }
+}
+
+impl RetentionConfig {
+  pub fn load(path: &Path) -> anyhow::Result<Self> {
+    let json = read_to_string(path)
+      .with_context(|| format!("Cannot read retention policy file {}", path.display()))?;
+    let config: Self = serde_json::from_str(&json)
+      .with_context(|| format!("Invalid retention policy file {}", path.display()))?;
+
+    if let Some(class) = config
+      .classes
+      .keys()
+      .find(|c| !CLASSES.contains(&c.as_str()))
+    {
+      bail!(
+        "Unknown artifact class {} in the retention policy, expected one of {}",
+        class,
+        CLASSES.join(", ")
+      );
+    }
+
+    Ok(config)
+  }
+
+  /// 类别的保留策略，未配置时为 `default`
+  pub fn policy(&self, class: &str, default: Policy) -> Policy {
+    self.classes.get(class).copied().unwrap_or(default)
+  }
+}
+
+/// 保存的一项数据，可以包括多个文件或目录
+#[derive(Debug, Clone)]
+pub struct Artifact {
+  pub id: String,
+  /// 最后修改的时间，按此判断新旧
+  pub modified: SystemTime,
+  /// 总字节数
+  pub size: u64,
+  /// 删除时一起删除的文件或目录
+  pub paths: Vec<PathBuf>,
+}
+
+/// 保存数据的功能，列出保存的数据，由 [`Retention`] 统一删除
+pub trait ArtifactStore: Send + Sync {
+  fn artifacts(&self) -> anyhow::Result<Vec<Artifact>>;
+}
+
+struct Class {
+  name: &'static str,
+  policy: Policy,
+  store: Arc<dyn ArtifactStore>,
+}
+
+/// 一类数据按保留策略应删除的数据
+#[derive(Debug)]
+pub struct Preview {
+  pub class: &'static str,
+  pub policy: Policy,
+  /// 保存的数量
+  pub count: usize,
+  /// 保存的总字节数
+  pub bytes: u64,
+  /// 应删除的数据，从最早的开始
+  pub expired: Vec<Artifact>,
+  /// 列出数据失败时的错误
+  pub error: Option<String>,
+}
+
+/// 按保留策略定期删除各类数据
+pub struct Retention {
+  config: RetentionConfig,
+  classes: Vec<Class>,
+}
+
+impl std::fmt::Debug for Retention {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_map()
+      .entries(self.classes.iter().map(|c| (c.name, c.policy)))
+      .finish()
+  }
+}
+
+impl Retention {
+  pub fn new(config: RetentionConfig) -> Self {
+    Self {
+      config,
+      classes: Vec::new(),
+    }
+  }
+
+  pub fn config(&self) -> &RetentionConfig {
+    &self.config
+  }
+
+  /// 按配置的策略清理数据，返回实际使用的策略
+  pub fn register(
+    &mut self,
+    name: &'static str,
+    store: Arc<dyn ArtifactStore>,
+    default: Policy,
+  ) -> Policy {
+    let policy = self.config.policy(name, default);
+    self.classes.push(Class {
+      name,
+      policy,
+      store,
+    });
+    policy
+  }
+
+  /// 不删除，只列出各类应删除的数据
+  pub fn preview(&self) -> Vec<Preview> {
+    let now = SystemTime::now();
+    self
+      .classes
+      .iter()
+      .map(|class| match class.store.artifacts() {
+        Ok(artifacts) => Preview {
+          class: class.name,
+          policy: class.policy,
+          count: artifacts.len(),
+          bytes: artifacts.iter().map(|a| a.size).sum(),
+          expired: expired(artifacts, &class.policy, now),
+          error: None,
+        },
+        Err(e) => Preview {
+          class: class.name,
+          policy: class.policy,
+          count: 0,
+          bytes: 0,
+          expired: Vec::new(),
+          error: Some(format!("{:#}", e)),
+        },
+      })
+      .collect()
+  }
+
+  /// 删除各类应删除的数据，记录各类删除的数量
+  fn cleanup(&self) {
+    for preview in self.preview() {
+      if let Some(e) = &preview.error {
+        warn!("Failed to list {} for retention: {}", preview.class, e);
+        continue;
+      }
+      if preview.expired.is_empty() {
+        continue;
+      }
+
+      let (mut removed, mut bytes, mut failed) = (0, 0, 0);
+      for artifact in &preview.expired {
+        match remove(artifact) {
+          Ok(()) => {
+            removed += 1;
+            bytes += artifact.size;
+          }
+          Err(e) => {
+            failed += 1;
+            warn!("Failed to remove {} {}: {}", preview.class, artifact.id, e);
+          }
+        }
+      }
+      info!(
+        "Retention removed {} {} ({} bytes), {} failed",
+        removed, preview.class, bytes, failed
+      );
+    }
+  }
+
+  /// 启动后及之后定期在后台清理
+  pub fn spawn_cleanup(self: Arc<Self>) {
+    tokio::spawn(async move {
+      loop {
+        let retention = self.clone();
+        let _ = tokio::task::spawn_blocking(move || retention.cleanup()).await;
+        tokio::time::sleep(CLEANUP_INTERVAL).await;
+      }
+    });
+  }
+}
+
+/// 按策略应删除的数据。从最新的开始保留，超过保留时间，或保留的数量、总字节数将超过上限时删除
+fn expired(mut artifacts: Vec<Artifact>, policy: &Policy, now: SystemTime) -> Vec<Artifact> {
+  artifacts.sort_by_key(|a| std::cmp::Reverse(a.modified));
+  let cutoff = policy.max_age().and_then(|age| now.checked_sub(age));
+  let (mut count, mut bytes) = (0, 0);
+
+  let mut expired = artifacts
+    .into_iter()
+    .filter(|artifact| {
+      let keep = cutoff.is_none_or(|cutoff| artifact.modified >= cutoff)
+        && policy.max_count.is_none_or(|max| count < max)
+        && policy
+          .max_bytes
+          .is_none_or(|max| bytes + artifact.size <= max);
+      if keep {
+        count += 1;
+        bytes += artifact.size;
+      }
+      !keep
+    })
+    .collect::<Vec<_>>();
+  expired.reverse();
+  expired
+}
+
+/// 删除数据的全部文件，已不存在的忽略
+fn remove(artifact: &Artifact) -> io::Result<()> {
+  for path in &artifact.paths {
+    let removed = if path.is_dir() {
+      remove_dir_all(path)
+    } else {
+      remove_file(path)
+    };
+    match removed {
+      Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+      _ => {}
+    }
+  }
+  Ok(())
+}
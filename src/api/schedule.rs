@@ -0,0 +1,1051 @@
+use std::{
+  cmp::Reverse,
+  collections::{HashMap, HashSet, VecDeque},
+  fs::{create_dir_all, read, read_dir, read_to_string, remove_dir_all, remove_file},
+  io,
+  net::IpAddr,
+  path::{Path, PathBuf},
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, LazyLock, Mutex, MutexGuard,
+  },
+  time::Duration,
+};
+
+use anyhow::anyhow;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, FixedOffset, TimeDelta, Utc};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::{watch, Notify};
+
+use super::{
+  events::{self, JobEvent, JobStatus},
+  receipt::{self, Outcome, Receipt},
+  token::TokenClaims,
+};
+use crate::{
+  instance,
+  pdf::DocumentMetadata,
+  sqlite::Connection,
+  storage::{file_stem, write_atomically},
+};
+
+/// 任务在服务内排队时的优先级，同一打印机的任务先打印优先级高的，正在打印的任务不被打断
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+  Low,
+  #[default]
+  Normal,
+  High,
+}
+
+impl Priority {
+  /// 等待 `waited` 后的优先级，每等待 `aging` 提升一级，避免优先级低的任务一直等待
+  fn promoted(self, waited: TimeDelta, aging: Option<Duration>) -> Self {
+    let steps = aging
+      .and_then(|aging| TimeDelta::from_std(aging).ok())
+      .filter(|aging| *aging > TimeDelta::zero())
+      .map_or(0, |aging| {
+        (waited.num_seconds() / aging.num_seconds().max(1)).max(0)
+      });
+    match (self, steps) {
+      (priority, 0) => priority,
+      (Self::Low, 1) => Self::Normal,
+      _ => Self::High,
+    }
+  }
+}
+
+/// 定时打印任务，每个任务保存为数据目录下的一个 JSON 文件，文件内容另外保存在 spool 目录中，重启后继续执行
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledJob {
+  pub id: String,
+  /// 计划打印的时间
+  pub print_at: DateTime<FixedOffset>,
+  /// 打印机名称
+  pub printer: String,
+  /// 提交任务的客户端，执行时按此检查访问策略
+  pub client: Option<IpAddr>,
+  /// 提交时 API 密钥的 SHA-256，执行时按此检查按密钥的访问规则
+  #[serde(default)]
+  pub api_key_sha256: Option<String>,
+  /// 提交时使用的打印令牌
+  pub claims: Option<TokenClaims>,
+  /// 超过计划时间多少秒仍未打印时过期
+  #[serde(default)]
+  pub ttl_seconds: Option<u64>,
+  /// 打印负载，包括文件内容，过期后为空
+  pub payload: Value,
+  /// 提交的时间，用于回执
+  #[serde(default)]
+  pub submitted_at: Option<DateTime<Utc>>,
+  /// 文档的 SHA-256，用于回执
+  #[serde(default)]
+  pub document_sha256: Option<String>,
+  /// 打印的总页数，包括份数
+  #[serde(default)]
+  pub total_pages: Option<u32>,
+  /// 暂缓打印，释放后才打印，未释放时在提交后超过有效期过期
+  #[serde(default)]
+  pub held: bool,
+  /// 释放暂缓任务需要的 PIN 的 SHA-256
+  #[serde(default)]
+  pub release_pin_sha256: Option<String>,
+  /// 排队时的优先级
+  #[serde(default)]
+  pub priority: Priority,
+  /// 文档的元数据
+  #[serde(default)]
+  pub document: Option<DocumentMetadata>,
+  /// 文档的字节数，用于限制排队的总大小
+  #[serde(default)]
+  pub document_size: Option<u64>,
+  /// 服务在提交到系统打印队列期间停止，可能已经打印。重启后作为暂缓任务保留，不会过期，需手动释放或取消
+  #[serde(default)]
+  pub interrupted: bool,
+}
+
+impl ScheduledJob {
+  /// 调度执行任务的时间。暂缓的任务只在过期时执行，没有有效期时不执行
+  pub fn due_at(&self) -> Option<DateTime<Utc>> {
+    let print_at = self.print_at.to_utc();
+    if self.interrupted {
+      return None;
+    }
+    if !self.held {
+      return Some(print_at);
+    }
+    self
+      .ttl_seconds
+      .map(|ttl| print_at + TimeDelta::seconds(ttl as i64))
+  }
+
+  /// 排队占用的字节数，旧版本保存的任务按文件的 Base64 长度估算
+  fn queued_bytes(&self) -> u64 {
+    self.document_size.unwrap_or_else(|| {
+      self.payload["file"]
+        .as_str()
+        .map_or(0, |file| file.len() as u64 / 4 * 3)
+    })
+  }
+
+  /// 任务结束时的回执
+  pub fn receipt(&self, outcome: Outcome, detail: Option<String>) -> Receipt {
+    Receipt {
+      job_id: self.id.clone(),
+      document_sha256: self.document_sha256.clone(),
+      printer: self.printer.clone(),
+      pages: self.total_pages,
+      submitted_at: self.submitted_at,
+      finished_at: Utc::now(),
+      outcome,
+      detail,
+      instance_id: Some(instance::id().to_string()),
+      sheets: None,
+      error: None,
+    }
+  }
+}
+
+/// 正在打印的任务，拆分为多个打印作业时可以取消尚未提交的部分
+#[derive(Debug, Clone)]
+pub struct RunningJob {
+  pub id: String,
+  /// 打印机名称
+  pub printer: String,
+  /// 开始打印的时间
+  pub started_at: DateTime<Utc>,
+  /// 在系统打印队列中显示的文档名称
+  pub job_name: String,
+  /// 文档的元数据
+  pub document: DocumentMetadata,
+  cancelled: Arc<AtomicBool>,
+}
+
+impl RunningJob {
+  /// 是否已取消，已提交的部分不受影响
+  pub fn is_cancelled(&self) -> bool {
+    self.cancelled.load(Ordering::Relaxed)
+  }
+}
+
+/// 正在打印的任务，释放时移除
+pub struct RunningGuard(RunningJob);
+
+impl RunningGuard {
+  pub fn is_cancelled(&self) -> bool {
+    self.0.is_cancelled()
+  }
+}
+
+impl Drop for RunningGuard {
+  fn drop(&mut self) {
+    running_lock().remove(&self.0.id);
+  }
+}
+
+/// 保留的已结束任务数
+const MAX_FINISHED: usize = 100;
+
+/// 保留打印进度的时间，文档名称在系统打印队列中找不到时也在此之后停止跟踪
+const PROGRESS_RETENTION: TimeDelta = TimeDelta::hours(1);
+
+/// 已提交到系统打印队列的文档的打印进度
+#[derive(Debug, Clone)]
+pub struct DocumentProgress {
+  /// 系统打印队列中的文档名称
+  pub name: String,
+  /// 已打印的页数，驱动未报告页数时为空
+  pub pages_printed: Option<u32>,
+  /// 总页数，驱动未报告时为空
+  pub total_pages: Option<u32>,
+  /// 是否已打印完成或离开系统打印队列
+  pub done: bool,
+}
+
+/// 已提交到系统打印队列的任务的打印进度，分部分打印时每部分为一个文档
+#[derive(Debug, Clone)]
+pub struct Progress {
+  /// 打印机名称
+  pub printer: String,
+  pub documents: Vec<DocumentProgress>,
+  /// 提交第一个文档的时间
+  pub started_at: DateTime<Utc>,
+}
+
+impl Progress {
+  /// 已打印的总页数，驱动均未报告页数时为空
+  pub fn pages_printed(&self) -> Option<u32> {
+    self
+      .documents
+      .iter()
+      .filter_map(|document| document.pages_printed)
+      .reduce(|a, b| a + b)
+  }
+}
+
+/// 任务未打印而结束的原因
+#[derive(Debug, Clone)]
+pub enum Ending {
+  /// 超过有效期
+  Expired,
+  /// 被取消
+  Cancelled(String),
+}
+
+/// 是否将任务保存到数据目录，重启后继续执行
+static PERSISTENT: AtomicBool = AtomicBool::new(true);
+
+/// 设置是否保存任务，需要在访问任何任务前调用
+pub fn set_persistent(persistent: bool) {
+  PERSISTENT.store(persistent, Ordering::Relaxed);
+}
+
+struct Schedule {
+  /// 保存任务的数据库，不保存任务或数据库无法打开时为空
+  store: Option<JobStore>,
+  /// 是否保存任务，不保存时任务只在内存中
+  persistent: bool,
+  jobs: Mutex<HashMap<String, ScheduledJob>>,
+  /// 最近未打印而结束的任务，只保留在内存中
+  finished: Mutex<VecDeque<(ScheduledJob, Ending)>>,
+  /// 正在打印的任务，只保留在内存中
+  running: Mutex<HashMap<String, RunningJob>>,
+  /// 正在依次打印排队任务的打印机
+  draining: Mutex<HashSet<String>>,
+  /// 任务变化时唤醒调度
+  changed: Notify,
+  /// 各任务状态变化的通知，等待某个任务时创建，没有等待者后移除
+  watches: Mutex<HashMap<String, watch::Sender<()>>>,
+  /// 已提交到系统打印队列的任务的打印进度
+  progress: Mutex<HashMap<String, Progress>>,
+}
+
+static SCHEDULE: LazyLock<Schedule> = LazyLock::new(|| {
+  let persistent = PERSISTENT.load(Ordering::Relaxed);
+  let store = instance::data_dir().filter(|_| persistent).and_then(|dir| {
+    JobStore::open(&dir.join("scheduled"))
+      .inspect_err(|e| error!("Failed to open the job store: {:#}", e))
+      .ok()
+  });
+  let jobs = store
+    .as_ref()
+    .map(|store| {
+      store.restore_interrupted();
+      store.load()
+    })
+    .unwrap_or_default();
+
+  if !jobs.is_empty() {
+    info!("Loaded {} scheduled print jobs", jobs.len());
+  }
+
+  Schedule {
+    store,
+    persistent,
+    jobs: Mutex::new(jobs),
+    finished: Mutex::new(VecDeque::new()),
+    running: Mutex::new(HashMap::new()),
+    draining: Mutex::new(HashSet::new()),
+    changed: Notify::new(),
+    watches: Mutex::new(HashMap::new()),
+    progress: Mutex::new(HashMap::new()),
+  }
+});
+
+/// 等待打印的任务，取出后到有结果前为正在提交
+const QUEUED: &str = "queued";
+/// 正在提交的任务，服务在提交期间停止时重启后可以找回
+const SUBMITTING: &str = "submitting";
+
+/// 保存任务的 SQLite 数据库 `jobs.db`，只保存不含文件内容的任务，文件内容保存在 spool 目录
+struct JobStore {
+  db: Mutex<Connection>,
+  spool: PathBuf,
+}
+
+impl JobStore {
+  /// 打开 `dir` 中的数据库，并导入旧版本保存为 JSON 文件的任务
+  fn open(dir: &Path) -> anyhow::Result<Self> {
+    create_dir_all(dir)?;
+    let db = Connection::open(&dir.join("jobs.db"))?;
+    db.execute(
+      "CREATE TABLE IF NOT EXISTS jobs (id TEXT PRIMARY KEY, state TEXT NOT NULL, job TEXT NOT NULL)",
+      &[],
+    )?;
+    let store = Self {
+      db: Mutex::new(db),
+      spool: spool_dir(dir),
+    };
+    store.import_job_files(dir, QUEUED);
+    store.import_job_files(&dir.join("submitting"), SUBMITTING);
+    Ok(store)
+  }
+
+  fn db(&self) -> MutexGuard<'_, Connection> {
+    self.db.lock().unwrap_or_else(|e| e.into_inner())
+  }
+
+  /// 导入 `dir` 中旧版本保存的任务，文件内容可能在任务中，导入后删除
+  fn import_job_files(&self, dir: &Path, state: &str) {
+    let Ok(entries) = read_dir(dir) else {
+      return;
+    };
+
+    for path in entries.flatten().map(|entry| entry.path()) {
+      if path.extension().is_none_or(|ext| ext != "json") {
+        continue;
+      }
+      let imported = read_to_string(&path)
+        .map_err(anyhow::Error::from)
+        .and_then(|json| {
+          let mut job = serde_json::from_str::<ScheduledJob>(&json)?;
+          unspool_files(&job_spool_dir(&self.spool, &job.id), &mut job.payload)?;
+          self.write(&job, state)
+        });
+
+      match imported {
+        Ok(()) => {
+          info!("Imported scheduled job {}", path.display());
+          let _ = remove_file(&path);
+        }
+        Err(e) => error!("Failed to import scheduled job {}: {:#}", path.display(), e),
+      }
+    }
+  }
+
+  /// 先保存文件内容，再写入不含文件内容的任务，避免重启时读到缺少文件的任务
+  fn write(&self, job: &ScheduledJob, state: &str) -> anyhow::Result<()> {
+    let mut stored = job.clone();
+    spool_files(&job_spool_dir(&self.spool, &job.id), &mut stored.payload)?;
+    self.db().execute(
+      "INSERT OR REPLACE INTO jobs (id, state, job) VALUES (?, ?, ?)",
+      &[&job.id, state, &serde_json::to_string(&stored)?],
+    )
+  }
+
+  /// 加载状态为 `state` 的任务，并从 spool 目录读取文件内容放回负载
+  fn load_state(&self, state: &str) -> HashMap<String, ScheduledJob> {
+    let rows = match self
+      .db()
+      .query("SELECT id, job FROM jobs WHERE state = ?", &[state])
+    {
+      Ok(rows) => rows,
+      Err(e) => {
+        error!("Failed to load scheduled jobs: {:#}", e);
+        return HashMap::new();
+      }
+    };
+
+    rows
+      .into_iter()
+      .filter_map(|row| {
+        let [id, json] = <[String; 2]>::try_from(row).ok()?;
+        let job = serde_json::from_str::<ScheduledJob>(&json)
+          .map_err(anyhow::Error::from)
+          .and_then(|mut job| {
+            unspool_files(&job_spool_dir(&self.spool, &job.id), &mut job.payload)?;
+            Ok(job)
+          });
+
+        job
+          .inspect_err(|e| error!("Failed to load scheduled job {}: {:#}", id, e))
+          .ok()
+      })
+      .map(|job| (job.id.clone(), job))
+      .collect()
+  }
+
+  /// 加载等待打印的任务
+  fn load(&self) -> HashMap<String, ScheduledJob> {
+    self.load_state(QUEUED)
+  }
+
+  /// 取出任务打印时标记为正在提交，服务在提交期间停止时重启后可以找回
+  fn mark_submitting(&self, id: &str) {
+    let marked = self
+      .db()
+      .execute("UPDATE jobs SET state = ? WHERE id = ?", &[SUBMITTING, id]);
+    if let Err(e) = marked {
+      warn!("Failed to keep job {} while submitting it: {:#}", id, e);
+      self.remove(id);
+    }
+  }
+
+  /// 删除任务，不删除文件内容
+  fn remove(&self, id: &str) {
+    if let Err(e) = self.db().execute("DELETE FROM jobs WHERE id = ?", &[id]) {
+      error!("Failed to remove scheduled job {}: {:#}", id, e);
+    }
+  }
+
+  /// 将上次停止时正在提交的任务恢复为需要手动释放的暂缓任务
+  fn restore_interrupted(&self) {
+    for (id, mut job) in self.load_state(SUBMITTING) {
+      warn!(
+        "Job {} was being submitted to {} when the service stopped, it may have been printed and is held until released or cancelled",
+        id, job.printer
+      );
+      job.held = true;
+      job.interrupted = true;
+
+      if let Err(e) = self.write(&job, QUEUED) {
+        error!("Failed to restore interrupted job {}: {:#}", id, e);
+      }
+    }
+  }
+}
+
+/// 任务的文件内容保存在此目录，数据库中只保存不含文件内容的任务
+fn spool_dir(dir: &Path) -> PathBuf {
+  dir.join("spool")
+}
+
+/// 每个任务的文件内容保存在 spool 目录下的一个子目录中，任务结束后删除
+fn job_spool_dir(spool: &Path, id: &str) -> PathBuf {
+  spool.join(file_stem(id))
+}
+
+/// 将负载中 Base64 编码的文件内容解码后保存到 `dir`，并从负载中移除。
+/// `file` 保存为 `file`，`files` 依次保存为 `files.0`、`files.1` 等
+fn spool_files(dir: &Path, payload: &mut Value) -> anyhow::Result<()> {
+  let Some(payload) = payload.as_object_mut() else {
+    return Ok(());
+  };
+
+  let mut contents = Vec::new();
+  if let Some(file) = payload.remove("file").filter(|file| !file.is_null()) {
+    contents.push(("file".to_string(), file));
+  }
+  if let Some(Value::Array(files)) = payload.remove("files") {
+    contents.extend(
+      files
+        .into_iter()
+        .enumerate()
+        .map(|(i, file)| (format!("files.{}", i), file)),
+    );
+  }
+  if contents.is_empty() {
+    return Ok(());
+  }
+
+  create_dir_all(dir)?;
+  for (name, content) in contents {
+    let content = content
+      .as_str()
+      .ok_or_else(|| anyhow!("Invalid {} in the job payload", name))?;
+    write_atomically(&dir.join(name), STANDARD.decode(content)?)?;
+  }
+  Ok(())
+}
+
+/// 将 `dir` 中保存的文件内容放回负载，没有保存文件内容时负载不变，如旧版本保存的任务
+fn unspool_files(dir: &Path, payload: &mut Value) -> anyhow::Result<()> {
+  let Some(payload) = payload.as_object_mut() else {
+    return Ok(());
+  };
+
+  let file = dir.join("file");
+  if file.exists() {
+    payload.insert("file".to_string(), STANDARD.encode(read(file)?).into());
+  }
+
+  let mut files = Vec::new();
+  loop {
+    let path = dir.join(format!("files.{}", files.len()));
+    if !path.exists() {
+      break;
+    }
+    files.push(Value::from(STANDARD.encode(read(path)?)));
+  }
+  if !files.is_empty() {
+    payload.insert("files".to_string(), files.into());
+  }
+  Ok(())
+}
+
+/// 删除任务保存的文件内容
+fn remove_spooled(spool: &Path, id: &str) {
+  match remove_dir_all(job_spool_dir(spool, id)) {
+    Err(e) if e.kind() != io::ErrorKind::NotFound => {
+      warn!("Failed to remove the files of job {}: {}", id, e)
+    }
+    _ => (),
+  }
+}
+
+/// 新的任务 ID
+pub fn new_id() -> String {
+  format!("{:032x}", rand::random::<u128>())
+}
+
+/// 保存定时任务
+pub fn add(job: ScheduledJob) -> anyhow::Result<()> {
+  if SCHEDULE.persistent {
+    let store = SCHEDULE
+      .store
+      .as_ref()
+      .ok_or_else(|| anyhow::anyhow!("No job store for scheduled jobs"))?;
+    store.write(&job, QUEUED)?;
+  }
+
+  let status = if job.held {
+    JobStatus::Held
+  } else {
+    JobStatus::Scheduled
+  };
+  events::publish(JobEvent::new(&job.id, status, &job.printer));
+  lock().insert(job.id.clone(), job);
+  SCHEDULE.changed.notify_one();
+  Ok(())
+}
+
+/// 取消定时任务或正在打印的任务，任务不存在时返回 `false`
+pub fn cancel(id: &str, reason: &str) -> bool {
+  let Some(job) = lock().remove(id) else {
+    // 正在打印的任务停止提交剩余的部分，由打印流程签发回执
+    let Some(running) = running_lock().get(id).cloned() else {
+      return false;
+    };
+    info!("Stopping running job {}: {}", id, reason);
+    running.cancelled.store(true, Ordering::Relaxed);
+    return true;
+  };
+
+  if let Some(store) = &SCHEDULE.store {
+    store.remove(id);
+  }
+
+  finish(job, Ending::Cancelled(reason.to_string()));
+  SCHEDULE.changed.notify_one();
+  true
+}
+
+/// 取消打印机的全部任务，包括正在打印的任务，返回取消的任务数
+pub fn cancel_printer(printer: &str, reason: &str) -> usize {
+  let mut ids = lock()
+    .values()
+    .filter(|job| job.printer == printer)
+    .map(|job| job.id.clone())
+    .collect::<Vec<_>>();
+  ids.extend(
+    running_lock()
+      .values()
+      .filter(|job| job.printer == printer && !job.is_cancelled())
+      .map(|job| job.id.clone()),
+  );
+
+  ids.iter().filter(|id| cancel(id, reason)).count()
+}
+
+/// 获取定时任务
+pub fn get(id: &str) -> Option<ScheduledJob> {
+  lock().get(id).cloned()
+}
+
+/// 取出暂缓的任务以便立即打印，任务不存在或未暂缓时为空
+pub fn release(id: &str) -> Option<ScheduledJob> {
+  let job = {
+    let mut jobs = lock();
+    jobs.get(id).filter(|job| job.held)?;
+    jobs.remove(id)?
+  };
+
+  if let Some(store) = &SCHEDULE.store {
+    store.mark_submitting(id);
+  }
+  SCHEDULE.changed.notify_one();
+  Some(job)
+}
+
+/// 登记正在打印的任务，返回的守卫释放时移除
+pub fn start(id: &str, printer: &str, job_name: &str, document: DocumentMetadata) -> RunningGuard {
+  let job = RunningJob {
+    id: id.to_string(),
+    printer: printer.to_string(),
+    started_at: Utc::now(),
+    job_name: job_name.to_string(),
+    document,
+    cancelled: Arc::new(AtomicBool::new(false)),
+  };
+  running_lock().insert(job.id.clone(), job.clone());
+  RunningGuard(job)
+}
+
+/// 跟踪已提交到系统打印队列的文档，`document` 为队列中显示的文档名称
+pub fn track(id: &str, printer: &str, document: &str) {
+  progress_lock()
+    .entry(id.to_string())
+    .or_insert_with(|| Progress {
+      printer: printer.to_string(),
+      documents: Vec::new(),
+      started_at: Utc::now(),
+    })
+    .documents
+    .push(DocumentProgress {
+      name: document.to_string(),
+      pages_printed: None,
+      total_pages: None,
+      done: false,
+    });
+}
+
+/// 任务的打印进度，未提交到系统打印队列或已超过保留时间时为空
+pub fn progress(id: &str) -> Option<Progress> {
+  progress_lock().get(id).cloned()
+}
+
+/// 仍在打印的任务的进度，包括还有部分未提交的任务。同时移除超过保留时间的进度
+pub fn tracked() -> Vec<(String, Progress)> {
+  let mut progress = progress_lock();
+  let now = Utc::now();
+  progress.retain(|_, p| p.started_at + PROGRESS_RETENTION > now);
+
+  let running = running_lock();
+  progress
+    .iter()
+    .filter(|(id, p)| running.contains_key(*id) || p.documents.iter().any(|d| !d.done))
+    .map(|(id, p)| (id.clone(), p.clone()))
+    .collect()
+}
+
+/// 更新任务各文档的打印进度
+pub fn update_progress(id: &str, documents: Vec<DocumentProgress>) {
+  if let Some(progress) = progress_lock().get_mut(id) {
+    // 读取队列期间提交的文档保留
+    let added = progress
+      .documents
+      .split_off(documents.len().min(progress.documents.len()));
+    progress.documents = documents;
+    progress.documents.extend(added);
+  }
+}
+
+/// 获取正在打印的任务
+pub fn get_running(id: &str) -> Option<RunningJob> {
+  running_lock().get(id).cloned()
+}
+
+/// 正在打印的任务，按开始时间排序
+pub fn running() -> Vec<RunningJob> {
+  let mut jobs = running_lock().values().cloned().collect::<Vec<_>>();
+  jobs.sort_by_key(|job| job.started_at);
+  jobs
+}
+
+/// 打印机在服务内排队的任务，包括定时及暂缓的任务
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QueueDepth {
+  pub jobs: u32,
+  /// 排队的文档总字节数
+  pub bytes: u64,
+  /// 最早提交的任务的提交时间，没有任务时为空
+  pub oldest_submitted_at: Option<DateTime<Utc>>,
+}
+
+/// 每台打印机在服务内排队的上限，为空时不限制
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QueueLimits {
+  pub max_jobs: Option<u32>,
+  pub max_bytes: Option<u64>,
+}
+
+impl QueueLimits {
+  /// 再加入 `bytes` 字节的任务是否超过上限
+  pub fn is_exceeded_by(&self, depth: &QueueDepth, bytes: u64) -> bool {
+    self.max_jobs.is_some_and(|max| depth.jobs >= max)
+      || self.max_bytes.is_some_and(|max| depth.bytes + bytes > max)
+  }
+}
+
+/// 打印机在服务内排队的任务数及总字节数
+pub fn depth(printer: &str) -> QueueDepth {
+  lock()
+    .values()
+    .filter(|job| job.printer == printer)
+    .fold(QueueDepth::default(), |depth, job| QueueDepth {
+      jobs: depth.jobs + 1,
+      bytes: depth.bytes + job.queued_bytes(),
+      oldest_submitted_at: match (depth.oldest_submitted_at, job.submitted_at) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+      },
+    })
+}
+
+/// 全部定时任务，按计划时间排序
+pub fn list() -> Vec<ScheduledJob> {
+  let mut jobs = lock().values().cloned().collect::<Vec<_>>();
+  jobs.sort_by_key(|job| job.print_at);
+  jobs
+}
+
+/// 有执行时间不晚于 `now` 且 `ready` 的任务的打印机，标记为正在依次打印，已在依次打印的打印机除外
+pub fn start_draining(now: DateTime<Utc>, ready: impl Fn(&ScheduledJob) -> bool) -> Vec<String> {
+  let printers = lock()
+    .values()
+    .filter(|job| job.due_at().is_some_and(|due| due <= now) && ready(job))
+    .map(|job| job.printer.clone())
+    .collect::<HashSet<_>>();
+
+  let mut draining = draining_lock();
+  printers
+    .into_iter()
+    .filter(|printer| draining.insert(printer.clone()))
+    .collect()
+}
+
+/// 打印机不再依次打印，唤醒调度检查期间加入的任务
+pub fn stop_draining(printer: &str) {
+  draining_lock().remove(printer);
+  SCHEDULE.changed.notify_one();
+}
+
+/// 取出打印机下一个执行时间不晚于 `now` 且 `ready` 的任务。按等待时间提升后的优先级排序，
+/// 同一优先级按计划时间排序。同时返回被它超过的任务 ID，即计划时间更早却排在它之后的任务
+pub fn take_next(
+  printer: &str,
+  now: DateTime<Utc>,
+  aging: Option<Duration>,
+  ready: impl Fn(&ScheduledJob) -> bool,
+) -> Option<(ScheduledJob, Vec<String>)> {
+  let (job, overtaken) = {
+    let mut jobs = lock();
+    let (id, overtaken) = {
+      let mut due = jobs
+        .values()
+        .filter(|job| job.printer == printer)
+        .filter(|job| job.due_at().is_some_and(|due| due <= now) && ready(job))
+        .collect::<Vec<_>>();
+      due.sort_by_key(|job| queue_order(job, now, aging));
+
+      let (next, rest) = due.split_first()?;
+      let overtaken = rest
+        .iter()
+        .filter(|job| job.print_at < next.print_at)
+        .map(|job| job.id.clone())
+        .collect::<Vec<_>>();
+      (next.id.clone(), overtaken)
+    };
+    (jobs.remove(&id)?, overtaken)
+  };
+
+  if let Some(store) = &SCHEDULE.store {
+    store.mark_submitting(&job.id);
+  }
+
+  Some((job, overtaken))
+}
+
+/// 队列中的顺序，按等待时间提升后的优先级排序，同一优先级按计划时间排序
+fn queue_order(
+  job: &ScheduledJob,
+  now: DateTime<Utc>,
+  aging: Option<Duration>,
+) -> (Reverse<Priority>, DateTime<FixedOffset>) {
+  let lane = job.priority.promoted(now - job.print_at.to_utc(), aging);
+  (Reverse(lane), job.print_at)
+}
+
+/// 打印机队列中已到计划时间、未暂缓的任务 ID，按 [`take_next`] 取出的顺序排列。
+/// 打印机暂停时任务也在队列中，恢复后按此顺序打印
+pub fn queue(printer: &str, now: DateTime<Utc>, aging: Option<Duration>) -> Vec<String> {
+  let jobs = lock();
+  let mut queued = jobs
+    .values()
+    .filter(|job| job.printer == printer && !job.held && job.print_at.to_utc() <= now)
+    .collect::<Vec<_>>();
+  queued.sort_by_key(|job| queue_order(job, now, aging));
+  queued.into_iter().map(|job| job.id.clone()).collect()
+}
+
+/// 打印机是否正在依次打印排队的任务，此时正在打印的任务已不在队列中
+pub fn is_draining(printer: &str) -> bool {
+  draining_lock().contains(printer)
+}
+
+/// 取出的任务已有结果，不再需要在重启后找回。同时删除保存的文件内容
+pub fn settle(id: &str) {
+  if let Some(store) = &SCHEDULE.store {
+    store.remove(id);
+    remove_spooled(&store.spool, id);
+  }
+}
+
+/// 记录未打印而结束的任务并签发回执，不再保留文件内容。返回签发的回执
+pub fn finish(mut job: ScheduledJob, ending: Ending) -> Receipt {
+  settle(&job.id);
+  job.payload = Value::Null;
+  let receipt = match &ending {
+    Ending::Expired => job.receipt(Outcome::Expired, None),
+    Ending::Cancelled(reason) => job.receipt(Outcome::Cancelled, Some(reason.clone())),
+  };
+  receipt::issue(&receipt);
+
+  let mut finished = SCHEDULE.finished.lock().unwrap_or_else(|e| e.into_inner());
+  if finished.len() >= MAX_FINISHED {
+    finished.pop_front();
+  }
+  finished.push_back((job, ending));
+  receipt
+}
+
+/// 最近未打印而结束的任务，按结束顺序排序
+pub fn finished() -> Vec<(ScheduledJob, Ending)> {
+  let finished = SCHEDULE.finished.lock().unwrap_or_else(|e| e.into_inner());
+  finished.iter().cloned().collect()
+}
+
+/// `ready` 的任务中最早的执行时间
+pub fn next_due(ready: impl Fn(&ScheduledJob) -> bool) -> Option<DateTime<Utc>> {
+  lock()
+    .values()
+    .filter(|job| ready(job))
+    .filter_map(ScheduledJob::due_at)
+    .min()
+}
+
+/// 唤醒调度，如打印机恢复后
+pub fn wake() {
+  SCHEDULE.changed.notify_one();
+}
+
+/// 等待任务变化
+pub async fn changed() {
+  SCHEDULE.changed.notified().await
+}
+
+/// 订阅任务的状态变化，任务之后的每次变化都会标记接收者
+pub fn watch(id: &str) -> watch::Receiver<()> {
+  let mut watches = watches_lock();
+  watches.retain(|_, sender| sender.receiver_count() > 0);
+  watches
+    .entry(id.to_string())
+    .or_insert_with(|| watch::channel(()).0)
+    .subscribe()
+}
+
+/// 通知等待任务的请求，任务的状态已变化
+pub fn notify(id: &str) {
+  if let Some(sender) = watches_lock().get(id) {
+    sender.send_replace(());
+  }
+}
+
+fn lock() -> MutexGuard<'static, HashMap<String, ScheduledJob>> {
+  SCHEDULE.jobs.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn running_lock() -> MutexGuard<'static, HashMap<String, RunningJob>> {
+  SCHEDULE.running.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn draining_lock() -> MutexGuard<'static, HashSet<String>> {
+  SCHEDULE.draining.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn watches_lock() -> MutexGuard<'static, HashMap<String, watch::Sender<()>>> {
+  SCHEDULE.watches.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn progress_lock() -> MutexGuard<'static, HashMap<String, Progress>> {
+  SCHEDULE.progress.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::api::v1::{waiting_state, JobState};
+
+  fn job(id: &str, held: bool, payload: Value) -> ScheduledJob {
+    ScheduledJob {
+      id: id.to_string(),
+      print_at: Utc::now().fixed_offset(),
+      printer: "HP LaserJet Pro M404".to_string(),
+      client: None,
+      api_key_sha256: None,
+      claims: None,
+      ttl_seconds: None,
+      payload,
+      submitted_at: Some(Utc::now()),
+      document_sha256: None,
+      total_pages: Some(1),
+      held,
+      release_pin_sha256: None,
+      priority: Priority::High,
+      document: None,
+      document_size: Some(8),
+      interrupted: false,
+    }
+  }
+
+  fn payload() -> Value {
+    serde_json::json!({
+      "file": STANDARD.encode(b"%PDF-1.7"),
+      "settings": { "printer": "HP LaserJet Pro M404" },
+    })
+  }
+
+  fn open(dir: &Path) -> JobStore {
+    JobStore::open(dir).unwrap()
+  }
+
+  #[test]
+  fn keeps_files_out_of_the_job_store() {
+    let dir = tempfile::tempdir().unwrap();
+    let dir = dir.path();
+    let store = open(dir);
+    let files = serde_json::json!({
+      "files": [STANDARD.encode(b"%PDF-1.4"), STANDARD.encode(b"%PDF-1.5")],
+    });
+    store.write(&job("a", false, payload()), QUEUED).unwrap();
+    store
+      .write(&job("b", false, files.clone()), QUEUED)
+      .unwrap();
+
+    let rows = store
+      .db()
+      .query("SELECT job FROM jobs WHERE id = ?", &["a"])
+      .unwrap();
+    assert!(!rows[0][0].contains(&STANDARD.encode(b"%PDF-1.7")));
+    let spool = job_spool_dir(&spool_dir(dir), "a");
+    assert_eq!(read(spool.join("file")).unwrap(), b"%PDF-1.7");
+
+    let jobs = store.load();
+    assert_eq!(jobs["a"].payload, payload());
+    assert_eq!(jobs["b"].payload, files);
+  }
+
+  #[test]
+  fn imports_jobs_saved_as_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let dir = dir.path();
+    let submitting = dir.join("submitting");
+    create_dir_all(&submitting).unwrap();
+    for (dir, id) in [(dir, "queued"), (submitting.as_path(), "interrupted")] {
+      let json = serde_json::to_string(&job(id, false, payload())).unwrap();
+      write_atomically(&dir.join(format!("{}.json", id)), json).unwrap();
+    }
+
+    let store = open(dir);
+    assert!(!dir.join("queued.json").exists());
+    assert!(!submitting.join("interrupted.json").exists());
+    store.restore_interrupted();
+
+    let jobs = store.load();
+    assert_eq!(jobs.len(), 2);
+    assert_eq!(jobs["queued"].payload, payload());
+    assert!(!jobs["queued"].held);
+    assert!(jobs["interrupted"].interrupted);
+    assert_eq!(jobs["interrupted"].payload, payload());
+  }
+
+  #[test]
+  fn rebuilds_the_queue_after_a_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let dir = dir.path();
+    let store = open(dir);
+    store
+      .write(&job("queued", false, payload()), QUEUED)
+      .unwrap();
+    store.write(&job("held", true, payload()), QUEUED).unwrap();
+    drop(store);
+
+    // 重启后重新打开数据库，状态及执行时间不变
+    let store = open(dir);
+    store.restore_interrupted();
+    let jobs = store.load();
+    assert_eq!(jobs.len(), 2);
+
+    let queued = &jobs["queued"];
+    assert_eq!(queued.priority, Priority::High);
+    assert!(queued.due_at().is_some_and(|due| due <= Utc::now()));
+    assert_eq!(queued.payload, payload());
+
+    let held = &jobs["held"];
+    assert!(held.held && !held.interrupted);
+    assert_eq!(held.due_at(), None);
+    assert!(matches!(waiting_state(held), JobState::Held));
+  }
+
+  #[test]
+  fn holds_jobs_interrupted_while_submitting() {
+    let dir = tempfile::tempdir().unwrap();
+    let dir = dir.path();
+    let store = open(dir);
+    store
+      .write(&job("interrupted", false, payload()), QUEUED)
+      .unwrap();
+    store
+      .write(&job("settled", false, payload()), QUEUED)
+      .unwrap();
+
+    // 取出任务打印，其中一个在服务停止前已有结果
+    store.mark_submitting("interrupted");
+    store.mark_submitting("settled");
+    store.remove("settled");
+    remove_spooled(&spool_dir(dir), "settled");
+    assert!(store.load().is_empty());
+    drop(store);
+
+    let store = open(dir);
+    store.restore_interrupted();
+    let jobs = store.load();
+    assert_eq!(jobs.len(), 1);
+    assert!(store.load_state(SUBMITTING).is_empty());
+
+    let job = &jobs["interrupted"];
+    assert!(job.held && job.interrupted);
+    assert_eq!(job.due_at(), None);
+    assert_eq!(job.payload, payload());
+    assert!(matches!(waiting_state(job), JobState::Unknown));
+    drop(store);
+
+    // 再次重启仍为需要手动释放的任务
+    let jobs = open(dir).load();
+    assert!(jobs["interrupted"].interrupted);
+  }
+}
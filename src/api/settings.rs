@@ -0,0 +1,1326 @@
+use std::{
+  fs::{create_dir_all, read_to_string, remove_file},
+  path::PathBuf,
+  sync::{LazyLock, RwLock},
+  time::Duration,
+};
+
+use anyhow::{anyhow, bail};
+use log::{debug, error, info, warn};
+use notify_debouncer_mini::{
+  new_debouncer,
+  notify::{RecommendedWatcher, RecursiveMode},
+  DebounceEventResult, Debouncer,
+};
+use poem_openapi::types::{Base64, ParseFromJSON, ToJSON};
+use serde_json::{Map, Value};
+use winprint::{
+  printer::PrinterDevice,
+  ticket::{
+    document::{
+      reader::ParsableXmlDocument, OwnedName, ParameterInit, PrintFeature, PrintFeatureOption,
+      PrintTicketDocument, PropertyValue,
+    },
+    FeatureOptionPack, FeatureOptionPackWithPredefined, JobDuplex, PageMediaSize, PageOrientation,
+    PredefinedDuplexType, PrintCapabilities, PrintTicket,
+  },
+};
+
+use super::{
+  access::Client,
+  capability::{
+    build_custom_media, display_name_of, fetch_print_capabilities, get_custom_page_size,
+    interchangeable_page_size, is_custom_media, is_driver_private, keyword, parameter_integer,
+    parameter_type, standard_page_size, PAGE_SIZE_TOLERANCE,
+  },
+  error::CodedError,
+  locale,
+  options::ApiOptions,
+  printers::{display_name, select_named_printer, select_printer},
+  template,
+  token::TokenClaims,
+  v1::{
+    BundledTemplate, ConfigBundle, DriverOptionValue, DriverValueType, Orientation, PageSize,
+    PrintSettings, SettingsValidation,
+  },
+};
+
+use crate::{instance, pdf, storage::write_atomically};
+
+/// 按打印机能力将 `driver_options` 转换为打印设置，功能选择指定的选项，参数按类型和范围检查后初始化。
+/// 打印机没有的名称在设置了 `ignore_unknown_driver_options` 时跳过并记录警告，否则报错
+pub fn resolve_driver_options(
+  cap: &PrintCapabilities,
+  settings: &PrintSettings,
+  warnings: &mut Vec<String>,
+) -> anyhow::Result<Option<PrintTicketDocument>> {
+  let Some(options) = settings.driver_options.as_ref().filter(|o| !o.is_empty()) else {
+    return Ok(None);
+  };
+  let mut document = PrintTicketDocument {
+    properties: Vec::new(),
+    parameter_inits: Vec::new(),
+    features: Vec::new(),
+  };
+
+  for (key, value) in options {
+    let feature = cap
+      .document
+      .features
+      .iter()
+      .find(|f| is_driver_private(&f.name) && f.name.local_name == *key);
+    if let Some(feature) = feature {
+      let requested = value.to_string();
+      let option = feature
+        .options
+        .iter()
+        .find(|o| {
+          o.name.as_ref().is_some_and(|n| n.local_name == requested)
+            || display_name_of(*o).as_deref() == Some(requested.as_str())
+        })
+        .ok_or_else(|| {
+          anyhow!(
+            "Driver option {} must be one of {}",
+            key,
+            feature
+              .options
+              .iter()
+              .filter_map(|o| o.name.as_ref().map(|n| n.local_name.as_str()))
+              .collect::<Vec<_>>()
+              .join(", ")
+          )
+        })?;
+      document.features.push(PrintFeature {
+        name: feature.name.clone(),
+        properties: Vec::new(),
+        options: vec![PrintFeatureOption {
+          properties: Vec::new(),
+          ..option.clone()
+        }],
+        features: Vec::new(),
+      });
+      continue;
+    }
+
+    let def = cap
+      .document
+      .parameter_defs
+      .iter()
+      .find(|p| is_driver_private(&p.name) && p.name.local_name == *key);
+    if let Some(def) = def {
+      let value = match parameter_type(def) {
+        DriverValueType::Integer => {
+          let parsed = match value {
+            DriverOptionValue::Integer(i) => i32::try_from(*i).ok(),
+            DriverOptionValue::Text(s) => s.trim().parse().ok(),
+          };
+          let Some(parsed) = parsed else {
+            bail!("Driver option {} must be an integer", key);
+          };
+          let min = parameter_integer(def, "MinValue");
+          let max = parameter_integer(def, "MaxValue");
+          if min.is_some_and(|min| parsed < min) || max.is_some_and(|max| parsed > max) {
+            bail!(
+              "Driver option {} must be between {} and {}",
+              key,
+              min
+                .map(|x| x.to_string())
+                .unwrap_or_else(|| "-".to_string()),
+              max
+                .map(|x| x.to_string())
+                .unwrap_or_else(|| "-".to_string())
+            );
+          }
+          PropertyValue::Integer(parsed)
+        }
+        DriverValueType::String => PropertyValue::String(value.to_string()),
+      };
+      document.parameter_inits.push(ParameterInit {
+        name: def.name.clone(),
+        value,
+      });
+      continue;
+    }
+
+    let msg = format!("Printer {} has no driver option {}", settings.printer, key);
+    if !settings.ignore_unknown_driver_options.unwrap_or_default() {
+      bail!(msg);
+    }
+    warn!("{}", msg);
+    warnings.push(msg);
+  }
+
+  Ok(Some(document).filter(|d| !d.features.is_empty() || !d.parameter_inits.is_empty()))
+}
+
+/// 按页码范围、奇偶及顺序选择要打印的页面
+pub fn select_pages(settings: &PrintSettings, count: u32) -> anyhow::Result<Vec<u32>> {
+  let mut pages = match &settings.page_range {
+    Some(range) => pdf::parse_page_range(range, count)?,
+    None => (1..=count).collect(),
+  };
+
+  if let Some(parity) = settings.page_parity {
+    pages.retain(|page| parity.matches(*page));
+  }
+
+  if settings.reverse_order == Some(true) {
+    pages.reverse();
+  }
+
+  if pages.is_empty() {
+    bail!("No pages selected to print");
+  }
+
+  Ok(pages)
+}
+
+/// 获取需要旋转的页面，只包含实际打印的页面
+pub fn select_rotated_pages(
+  settings: &PrintSettings,
+  pages: &[u32],
+  count: u32,
+) -> anyhow::Result<Vec<u32>> {
+  if settings.rotate_degrees.unwrap_or_default() == 0 {
+    return Ok(Vec::new());
+  }
+
+  let mut rotated = match &settings.rotate_range {
+    Some(range) => pdf::parse_page_range(range, count)?,
+    None => (1..=count).collect(),
+  };
+  rotated.retain(|page| pages.contains(page));
+  rotated.sort_unstable();
+  rotated.dedup();
+
+  Ok(rotated)
+}
+
+/// 获取打印设置，未指定时使用默认打印设置，并检查访问策略和令牌允许的打印机
+pub fn effective_settings(
+  settings: Option<PrintSettings>,
+  options: &ApiOptions,
+  client: &Client,
+  claims: Option<&TokenClaims>,
+) -> anyhow::Result<PrintSettings> {
+  let mut settings = if let Some(settings) = settings {
+    settings
+  } else if let Some(settings) = default_settings() {
+    if options.strict_settings {
+      let issues = check_settings(&settings, options)?;
+
+      if !issues.is_empty() {
+        bail!("Invalid default settings: {}", issues.join("; "));
+      }
+    }
+
+    settings
+  } else {
+    bail!("No print settings");
+  };
+  settings.normalize_units();
+
+  // 指定打印机 ID 时按 ID 确定名称，访问策略及令牌按名称检查
+  if let Some(id) = &settings.printer_id {
+    let printers = PrinterDevice::all()?;
+    let printer =
+      select_printer(&printers, &settings)?.ok_or_else(|| anyhow!("No printer with ID {}", id))?;
+    settings.printer = display_name(printer);
+  } else if settings.printer.is_empty() {
+    bail!("No printer specified");
+  }
+
+  check_printer(&settings.printer, options, client, claims)?;
+
+  for printer in settings.fallback_printers.iter().flatten() {
+    check_printer(printer, options, client, claims)?;
+  }
+
+  Ok(settings)
+}
+
+/// 检查访问策略和令牌是否允许使用打印机
+pub fn check_printer(
+  printer: &str,
+  options: &ApiOptions,
+  client: &Client,
+  claims: Option<&TokenClaims>,
+) -> anyhow::Result<()> {
+  if !options.access.allows(client, printer) {
+    return Err(CodedError::forbidden(printer).into());
+  }
+
+  if let Some(claims) = claims {
+    if claims.printer != printer {
+      let msg = format!("The print token only allows printer {}", claims.printer);
+      return Err(CodedError::out_of_scope(msg).into());
+    }
+  }
+
+  Ok(())
+}
+
+/// 按打印机能力解析的打印设置
+pub struct ResolvedTicketPlan {
+  /// 布局
+  pub orientation: Option<PageOrientation>,
+  /// 纸张
+  pub media: Option<PageMediaSize>,
+  /// 纸张大小（微米），用于小册子拼版
+  pub media_size: Option<(u32, u32)>,
+  /// 小册子使用的短边双面打印，不是小册子或打印机不支持时为空
+  pub duplex: Option<JobDuplex>,
+  /// 没有完全匹配的设置
+  pub warnings: Vec<String>,
+}
+
+/// 打印设置中功能 `feature` 选择的选项名称
+pub fn ticket_option(ticket: &PrintTicket, feature: &OwnedName) -> Option<OwnedName> {
+  let document = PrintTicketDocument::parse_from_bytes(ticket.get_xml()).ok()?;
+  document
+    .features
+    .into_iter()
+    .find(|f| same_name(&f.name, feature))
+    .and_then(|f| f.options.into_iter().next())
+    .and_then(|option| option.name)
+}
+
+/// 驱动校验打印设置时改变的选项。`expected` 为功能及请求的选项，返回功能、请求的选项及驱动选择的选项
+pub fn changed_options<'a>(
+  ticket: &PrintTicket,
+  expected: &'a [(OwnedName, OwnedName)],
+) -> Vec<(&'a OwnedName, &'a OwnedName, OwnedName)> {
+  expected
+    .iter()
+    .filter_map(|(feature, requested)| {
+      let actual = ticket_option(ticket, feature)?;
+      (!same_name(&actual, requested)).then_some((feature, requested, actual))
+    })
+    .collect()
+}
+
+/// 名称是否相同，只比较命名空间和本地名称，忽略前缀
+pub fn same_name(a: &OwnedName, b: &OwnedName) -> bool {
+  a.local_name == b.local_name && a.namespace == b.namespace
+}
+
+/// 按打印机能力解析打印设置，布局按预定义名称匹配，纸张大小见 [`resolve_page_size`]。
+/// 同一份设置可以用于不同的打印机，只有打印机没有相近的选项时才报错
+pub fn resolve_settings(
+  cap: &PrintCapabilities,
+  settings: &PrintSettings,
+) -> anyhow::Result<ResolvedTicketPlan> {
+  let booklet = settings.booklet.unwrap_or_default();
+  let mut warnings = Vec::new();
+
+  // 小册子默认横向
+  let requested = if booklet && settings.orientation.is_none() {
+    Some(Orientation::Landscape)
+  } else {
+    settings.orientation
+  };
+  let orientation = match requested {
+    Some(ori) => {
+      let predefined = Some(ori.into());
+      let ori = cap
+        .page_orientations()
+        .find(|x| x.as_predefined_name() == predefined)
+        .ok_or_else(|| anyhow!("No such orientation"))?;
+      Some(ori)
+    }
+    None => None,
+  };
+
+  let (media, media_size) = match &settings.page_size {
+    Some(page_size) => {
+      let interchange = settings.allow_letter_a4_interchange.unwrap_or_default();
+      let (media, size) = resolve_page_size(cap, page_size, interchange, &mut warnings)?;
+      (Some(media), Some(size))
+    }
+    None => (None, None),
+  };
+
+  // 纸张本身已经是横向的，再使用横向布局时驱动会把内容转回纵向
+  if let (Some(ori), Some((width, height))) = (requested, media_size) {
+    if matches!(ori, Orientation::Landscape | Orientation::ReverseLandscape) && width > height {
+      let msg = format!(
+        "Page size {} is already landscape ({}x{} mm), printing it in {:?} orientation turns the content back to portrait",
+        media
+          .as_ref()
+          .and_then(|m| m.display_name())
+          .unwrap_or_default(),
+        width as f64 / 1000.0,
+        height as f64 / 1000.0,
+        ori
+      );
+      settings
+        .orientation_conflict
+        .unwrap_or_default()
+        .report(msg, &mut warnings)?;
+    }
+  }
+
+  let duplex = booklet
+    .then(|| {
+      cap
+        .duplexes()
+        .find(|x| x.as_predefined_name() == Some(PredefinedDuplexType::TwoSidedShortEdge))
+    })
+    .flatten();
+
+  Ok(ResolvedTicketPlan {
+    orientation,
+    media,
+    media_size,
+    duplex,
+    warnings,
+  })
+}
+
+/// 匹配纸张。先按尺寸在容差内匹配，有多个时优先名称相同的，其次尺寸最接近的；
+/// 尺寸都不匹配时按名称匹配，`interchange` 为 true 时再尝试可以代替的标准纸张，最后尝试自定义纸张。
+/// 返回纸张及其大小（微米）
+pub fn resolve_page_size(
+  cap: &PrintCapabilities,
+  page_size: &PageSize,
+  interchange: bool,
+  warnings: &mut Vec<String>,
+) -> anyhow::Result<(PageMediaSize, (u32, u32))> {
+  if page_size.is_auto_height() {
+    return resolve_auto_height(cap, page_size.width, page_size.height, warnings);
+  }
+
+  let name = page_size.name.as_deref();
+  // 名称可以是显示名称，也可以是按 `Accept-Language` 返回的英文名称
+  let has_name = |pms: &PageMediaSize| {
+    name.is_some_and(|name| {
+      pms.display_name() == Some(name)
+        || keyword(pms).map(locale::keyword_name).as_deref() == Some(name)
+    })
+  };
+  let size_of = |pms: &PageMediaSize| {
+    let size = pms.size();
+    (size.width_in_micron(), size.height_in_micron())
+  };
+  let distance = |pms: &PageMediaSize| {
+    let (width, height) = size_of(pms);
+    u32::max(
+      width.abs_diff(page_size.width),
+      height.abs_diff(page_size.height),
+    )
+  };
+
+  let by_size = cap
+    .page_media_sizes()
+    .filter(|x| !is_custom_media(x) && distance(x) <= PAGE_SIZE_TOLERANCE)
+    .min_by_key(|x| (!has_name(x), distance(x)));
+
+  if let Some(pms) = by_size {
+    if let Some(name) = name.filter(|_| !has_name(&pms)) {
+      warnings.push(format!(
+        "Page size {} is matched by size as {}",
+        name,
+        pms.display_name().unwrap_or_default()
+      ));
+    }
+
+    let size = size_of(&pms);
+    return Ok((pms, size));
+  }
+
+  if let Some(name) = name {
+    if let Some(pms) = cap.page_media_sizes().find(|x| has_name(x)) {
+      let size = size_of(&pms);
+      warnings.push(format!(
+        "Page size {} of this printer is {}x{} microns, not {}x{}",
+        name, size.0, size.1, page_size.width, page_size.height
+      ));
+      return Ok((pms, size));
+    }
+  }
+
+  // 如 Letter 的文档打印到只有 A4 的打印机，打印时内容缩放到纸张大小
+  let requested = standard_page_size(page_size.width, page_size.height);
+  if let Some((requested, counterpart)) = requested
+    .filter(|_| interchange)
+    .and_then(|name| Some((name, interchangeable_page_size(name)?)))
+  {
+    let substitute = cap
+      .page_media_sizes()
+      .filter(|x| !is_custom_media(x))
+      .find(|x| {
+        let (width, height) = size_of(x);
+        standard_page_size(width, height) == Some(counterpart)
+      });
+
+    if let Some(pms) = substitute {
+      let size = size_of(&pms);
+      warnings.push(format!(
+        "Page size {} is not available, substituted {} ({}), content is scaled to fit",
+        requested,
+        counterpart,
+        pms.display_name().unwrap_or_default()
+      ));
+      return Ok((pms, size));
+    }
+  }
+
+  // 没有匹配的纸张时，尝试使用自定义纸张
+  if let Some((custom, range)) = get_custom_page_size(cap) {
+    let custom = build_custom_media(custom, &range, page_size.width, page_size.height)?;
+    return Ok((custom, (page_size.width, page_size.height)));
+  }
+
+  bail!("No such page size")
+}
+
+/// 文档按纸张宽度（微米）等比缩放后的高度，超过上限时报错
+pub fn content_height(data: &[u8], width: u32, max: Option<u32>) -> anyhow::Result<u32> {
+  if width == 0 {
+    bail!("The page width is required when the height is auto");
+  }
+  let height = pdf::fitted_height(data, width)?;
+  if let Some(max) = max.filter(|max| height > *max) {
+    bail!(
+      "The content is {} mm long at a width of {} mm, exceeding the maximum of {} mm",
+      height as f64 / 1000.0,
+      width as f64 / 1000.0,
+      max as f64 / 1000.0
+    );
+  }
+  Ok(height)
+}
+
+/// 匹配高度随内容的纸张。驱动支持自定义纸张时使用内容的高度，不足最小高度时使用最小高度；
+/// 否则使用宽度在容差内、不短于内容的最短纸张
+pub fn resolve_auto_height(
+  cap: &PrintCapabilities,
+  width: u32,
+  height: u32,
+  warnings: &mut Vec<String>,
+) -> anyhow::Result<(PageMediaSize, (u32, u32))> {
+  if let Some((custom, range)) = get_custom_page_size(cap) {
+    let height = height.max(range.min_height);
+    if range.contains(width, height) {
+      let custom = build_custom_media(custom, &range, width, height)?;
+      return Ok((custom, (width, height)));
+    }
+  }
+
+  let size_of = |pms: &PageMediaSize| {
+    let size = pms.size();
+    (size.width_in_micron(), size.height_in_micron())
+  };
+  let shortest = cap
+    .page_media_sizes()
+    .filter(|x| !is_custom_media(x))
+    .map(|x| {
+      let size = size_of(&x);
+      (x, size)
+    })
+    .filter(|(_, (w, h))| w.abs_diff(width) <= PAGE_SIZE_TOLERANCE && *h >= height)
+    .min_by_key(|(_, (_, h))| *h);
+
+  match shortest {
+    Some((pms, size)) => {
+      warnings.push(format!(
+        "The printer does not support a custom page size of {}x{} microns, using {} ({}x{} microns)",
+        width,
+        height,
+        pms.display_name().unwrap_or_default(),
+        size.0,
+        size.1
+      ));
+      Ok((pms, size))
+    }
+    None => bail!(
+      "No page size of width {} microns is at least {} microns long",
+      width,
+      height
+    ),
+  }
+}
+
+/// 检查打印设置是否适用于当前的打印机，返回发现的问题
+pub fn check_settings(
+  settings: &PrintSettings,
+  options: &ApiOptions,
+) -> anyhow::Result<Vec<String>> {
+  let cap = if options.simulates(&settings.printer) {
+    options.simulated_capabilities(&settings.printer)?
+  } else {
+    let printers = PrinterDevice::all()?;
+    let Some(printer) = select_named_printer(&printers, &settings.printer)? else {
+      return Ok(vec![format!(
+        "Printer {} is not installed",
+        settings.printer
+      )]);
+    };
+    fetch_print_capabilities(printer, options.record_fixtures.as_deref())?
+  };
+  Ok(check_settings_with(
+    settings,
+    &cap,
+    options.letter_a4_interchange,
+  ))
+}
+
+/// 按打印机能力检查打印设置，返回发现的问题。设置未指定 `allow_letter_a4_interchange` 时按
+/// `letter_a4_interchange`，与打印时相同
+pub fn check_settings_with(
+  settings: &PrintSettings,
+  cap: &PrintCapabilities,
+  letter_a4_interchange: bool,
+) -> Vec<String> {
+  let mut issues = Vec::new();
+
+  if let (Some(copies), Some(max)) = (settings.copies, cap.max_copies()) {
+    if copies > max.0 {
+      issues.push(format!("Copies {} exceed the maximum of {}", copies, max.0));
+    }
+  }
+
+  if let Some(ori) = settings.orientation {
+    let predefined = Some(ori.into());
+
+    if !cap
+      .page_orientations()
+      .any(|x| x.as_predefined_name() == predefined)
+    {
+      issues.push(format!("Orientation {:?} is not supported", ori));
+    }
+  }
+
+  if let Some(page_size) = &settings.page_size {
+    // 与打印时相同的匹配方式，按尺寸匹配到其他名称的纸张也会记录
+    let interchange = settings
+      .allow_letter_a4_interchange
+      .unwrap_or(letter_a4_interchange);
+    if resolve_page_size(cap, page_size, interchange, &mut issues).is_err() {
+      issues.push(match &page_size.name {
+        Some(name) => format!("Page size {} is not supported", name),
+        None => format!(
+          "Page size {}x{} microns is not supported",
+          page_size.width, page_size.height
+        ),
+      });
+    }
+  }
+
+  if let Err(e) = resolve_driver_options(cap, settings, &mut issues) {
+    issues.push(e.to_string());
+  }
+
+  issues
+}
+
+/// 检查保存的默认打印设置，记录发现的问题
+pub fn check_default_settings(options: &ApiOptions) {
+  let Some(settings) = default_settings() else {
+    return;
+  };
+
+  for issue in SettingsValidation::from(check_settings(&settings, options)).issues {
+    warn!("Default settings: {}", issue);
+  }
+}
+
+/// 内存中的默认打印设置，首次使用时从设置文件读取
+pub static DEFAULT_SETTINGS: LazyLock<RwLock<Option<PrintSettings>>> = LazyLock::new(|| {
+  let settings = get_settings_filepath().and_then(|f| read_settings(f).ok());
+  RwLock::new(settings)
+});
+
+/// 获取默认打印设置
+pub fn default_settings() -> Option<PrintSettings> {
+  DEFAULT_SETTINGS
+    .read()
+    .unwrap_or_else(|e| e.into_inner())
+    .clone()
+}
+
+pub fn set_default_settings(settings: Option<PrintSettings>) {
+  *DEFAULT_SETTINGS.write().unwrap_or_else(|e| e.into_inner()) = settings;
+}
+
+/// 重新读取设置文件，文件有误时保留之前的设置
+pub fn reload_default_settings(filepath: PathBuf) {
+  if !filepath.exists() {
+    info!("Default settings file removed");
+    set_default_settings(None);
+    return;
+  }
+
+  match read_settings(filepath) {
+    Ok(settings) => {
+      info!("Default settings reloaded");
+      set_default_settings(Some(settings));
+    }
+    Err(e) => error!("Keeping the previous default settings: {}", e),
+  }
+}
+
+/// 监视设置文件，变化时重新加载默认打印设置。返回的监视器需要一直保留
+pub fn watch_settings() -> anyhow::Result<Debouncer<RecommendedWatcher>> {
+  let filepath = get_settings_filepath().ok_or_else(|| anyhow!("No settings directory"))?;
+  let dir = filepath
+    .parent()
+    .ok_or_else(|| anyhow!("No settings directory"))?
+    .to_path_buf();
+  let watched = filepath.clone();
+
+  // 编辑器保存文件时可能触发多次事件，合并 500 毫秒内的事件
+  let mut debouncer = new_debouncer(
+    Duration::from_millis(500),
+    move |events: DebounceEventResult| match events {
+      Ok(events) if events.iter().any(|e| e.path == watched) => {
+        reload_default_settings(watched.clone())
+      }
+      Ok(_) => {}
+      Err(e) => error!("Settings watcher error: {}", e),
+    },
+  )?;
+  debouncer
+    .watcher()
+    .watch(&dir, RecursiveMode::NonRecursive)?;
+
+  LazyLock::force(&DEFAULT_SETTINGS);
+  Ok(debouncer)
+}
+
+pub fn get_settings_filepath() -> Option<PathBuf> {
+  if let Some(dir) = instance::config_dir() {
+    let mut filepath: PathBuf = dir;
+    let _ = create_dir_all(&filepath);
+    filepath.push("default.json");
+    Some(filepath)
+  } else {
+    None
+  }
+}
+
+/// 设置文件格式的版本（主版本，次版本），主版本不同表示不兼容的变化
+pub const SETTINGS_SCHEMA: (u32, u32) = (1, 0);
+
+pub fn read_settings(filepath: PathBuf) -> anyhow::Result<PrintSettings> {
+  let json = read_to_string(filepath)?;
+
+  match parse_settings(&json) {
+    Ok(settings) => Ok(settings),
+    Err(e) => {
+      error!("Failed to parse settings file: {:#?}", e);
+      bail!("Failed to parse settings file: {:#?}", e);
+    }
+  }
+}
+
+/// 解析设置文件，旧版本的格式会被升级
+pub fn parse_settings(json: &str) -> anyhow::Result<PrintSettings> {
+  let mut value: Value = serde_json::from_str(json)?;
+  let Some(obj) = value.as_object_mut() else {
+    bail!("Settings file is not a JSON object");
+  };
+
+  // 没有版本号的是 0.x 版本
+  let version = match obj.remove("schema_version") {
+    Some(Value::String(v)) => parse_schema_version(&v)?,
+    Some(v) => bail!("Invalid schema_version: {}", v),
+    None => (0, 0),
+  };
+
+  migrate_settings(obj, version);
+
+  let settings = match PrintSettings::parse_from_json(Some(value.clone())) {
+    Ok(settings) => settings,
+    Err(e) => bail!("{}", e.into_message()),
+  };
+
+  // 重新序列化后缺少的字段是当前版本不认识的
+  let known = settings.to_json().unwrap_or_default();
+  let unknown = value
+    .as_object()
+    .into_iter()
+    .flatten()
+    .filter(|(k, v)| !v.is_null() && known.get(k.as_str()).is_none())
+    .map(|(k, _)| k.as_str())
+    .collect::<Vec<_>>();
+
+  if !unknown.is_empty() {
+    if version.0 > SETTINGS_SCHEMA.0 {
+      bail!(
+        "Settings file schema {}.{} is newer than the supported {}.{}, unknown field `{}`",
+        version.0,
+        version.1,
+        SETTINGS_SCHEMA.0,
+        SETTINGS_SCHEMA.1,
+        unknown.join("`, `")
+      );
+    }
+
+    warn!("Ignoring unknown settings fields: {}", unknown.join(", "));
+  }
+
+  Ok(settings)
+}
+
+/// 解析 `主版本.次版本` 格式的版本号
+pub fn parse_schema_version(version: &str) -> anyhow::Result<(u32, u32)> {
+  let parse = || {
+    let (major, minor) = version.split_once('.')?;
+    Some((major.parse().ok()?, minor.parse().ok()?))
+  };
+
+  parse().ok_or_else(|| anyhow!("Invalid schema_version: {}", version))
+}
+
+/// 将旧版本的设置升级到当前版本
+pub fn migrate_settings(obj: &mut Map<String, Value>, version: (u32, u32)) {
+  // 0.x 只有 printer、copies、orientation 和 page_size，未设置的字段写为 null
+  if version.0 == 0 {
+    debug!("Migrating settings file from schema 0.x");
+    obj.retain(|_, v| !v.is_null());
+
+    if let Some(Value::Object(page_size)) = obj.get_mut("page_size") {
+      page_size.retain(|_, v| !v.is_null());
+    }
+  }
+}
+
+pub fn write_settings(filepath: PathBuf, settings: &PrintSettings) -> anyhow::Result<()> {
+  let mut json = settings.to_json().unwrap_or_default();
+
+  if let Some(obj) = json.as_object_mut() {
+    let (major, minor) = SETTINGS_SCHEMA;
+    obj.insert(
+      "schema_version".to_string(),
+      format!("{}.{}", major, minor).into(),
+    );
+  }
+
+  // 先写入临时文件再替换，避免写入一半的设置文件被读取
+  write_atomically(&filepath, json.to_string())?;
+  Ok(())
+}
+
+pub fn export_bundle() -> anyhow::Result<ConfigBundle> {
+  let (major, minor) = SETTINGS_SCHEMA;
+  let templates = template::list()
+    .into_iter()
+    .map(|t| {
+      Ok(BundledTemplate {
+        file: Base64(template::file(&t.id)?),
+        id: t.id,
+        name: t.name,
+        created_at: t.created_at,
+      })
+    })
+    .collect::<anyhow::Result<Vec<_>>>()?;
+
+  Ok(ConfigBundle {
+    schema_version: format!("{}.{}", major, minor),
+    default_settings: default_settings(),
+    templates: Some(templates),
+  })
+}
+
+pub fn import_bundle(bundle: ConfigBundle, merge: bool) -> anyhow::Result<()> {
+  let version = parse_schema_version(&bundle.schema_version)?;
+
+  if version.0 != SETTINGS_SCHEMA.0 {
+    bail!(
+      "Unsupported configuration schema {}, expected {}.x",
+      bundle.schema_version,
+      SETTINGS_SCHEMA.0
+    );
+  }
+
+  let filepath = get_settings_filepath().ok_or_else(|| anyhow!("No settings directory"))?;
+
+  if merge && default_settings().is_some() {
+    info!("Keeping the existing default settings");
+  } else if let Some(settings) = bundle.default_settings {
+    write_settings(filepath, &settings)?;
+    set_default_settings(Some(settings));
+  } else if !merge {
+    if filepath.exists() {
+      remove_file(filepath)?;
+    }
+    set_default_settings(None);
+  }
+
+  if let Some(templates) = bundle.templates {
+    let existing = template::list();
+
+    // 替换时删除配置包中没有的模板
+    if !merge {
+      for t in existing
+        .iter()
+        .filter(|t| !templates.iter().any(|b| b.id == t.id))
+      {
+        template::remove(&t.id)?;
+      }
+    }
+
+    for t in templates {
+      if merge && existing.iter().any(|e| e.id == t.id) {
+        info!("Keeping the existing template {}", t.id);
+        continue;
+      }
+      template::restore(t.id, t.name, t.created_at, &t.file.0)?;
+    }
+  }
+
+  Ok(())
+}
+
+/// 导出全部保存的配置为 JSON
+pub fn export_config() -> anyhow::Result<String> {
+  Ok(export_bundle()?.to_json_string())
+}
+
+/// 从 JSON 导入配置，`merge` 为 true 时只添加缺少的配置
+pub fn import_config(json: &str, merge: bool) -> anyhow::Result<()> {
+  match ConfigBundle::parse_from_json_string(json) {
+    Ok(bundle) => import_bundle(bundle, merge),
+    Err(e) => bail!("Invalid configuration bundle: {}", e.into_message()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::Path;
+
+  use winprint::ticket::{
+    document::{PrintCapabilitiesDocument, NS_PSK},
+    PredefinedPageOrientation,
+  };
+
+  use super::{
+    super::{fixture::Fixtures, simulate, v1::ConflictPolicy},
+    *,
+  };
+
+  #[test]
+  fn parses_unversioned_settings_with_nulls() {
+    // 0.x 版本的设置文件，未设置的字段写为 null
+    let json = r#"{
+      "printer": "HP LaserJet",
+      "copies": 2,
+      "orientation": null,
+      "page_size": { "name": "A4", "width": null, "height": null }
+    }"#;
+
+    let settings = parse_settings(json).unwrap();
+    assert_eq!(settings.printer, "HP LaserJet");
+    assert_eq!(settings.copies, Some(2));
+    assert!(settings.orientation.is_none());
+
+    let page_size = settings.page_size.unwrap();
+    assert_eq!(page_size.name.as_deref(), Some("A4"));
+    assert_eq!((page_size.width, page_size.height), (0, 0));
+  }
+
+  #[test]
+  fn parses_unversioned_settings_without_page_size() {
+    let settings = parse_settings(r#"{ "printer": "Gprinter", "page_size": null }"#).unwrap();
+    assert_eq!(settings.printer, "Gprinter");
+    assert!(settings.page_size.is_none());
+  }
+
+  #[test]
+  fn parses_current_settings() {
+    let json = r#"{
+      "schema_version": "1.0",
+      "printer": "HP LaserJet",
+      "orientation": "landscape",
+      "page_size": { "width": 210000, "height": 297000 },
+      "page_range": "1-3"
+    }"#;
+
+    let settings = parse_settings(json).unwrap();
+    assert!(matches!(settings.orientation, Some(Orientation::Landscape)));
+    assert_eq!(settings.page_range.as_deref(), Some("1-3"));
+
+    let page_size = settings.page_size.unwrap();
+    assert_eq!((page_size.width, page_size.height), (210000, 297000));
+  }
+
+  #[test]
+  fn ignores_unknown_fields_of_same_major_version() {
+    let json = r#"{ "schema_version": "1.7", "printer": "HP", "watermark": "draft" }"#;
+    assert_eq!(parse_settings(json).unwrap().printer, "HP");
+  }
+
+  #[test]
+  fn rejects_unknown_fields_of_newer_major_version() {
+    let json = r#"{ "schema_version": "2.0", "printer": "HP", "watermark": "draft" }"#;
+    let err = parse_settings(json).unwrap_err().to_string();
+    assert!(err.contains("`watermark`"), "{}", err);
+  }
+
+  #[test]
+  fn accepts_newer_major_version_without_unknown_fields() {
+    let json = r#"{ "schema_version": "2.1", "printer": "HP" }"#;
+    assert_eq!(parse_settings(json).unwrap().printer, "HP");
+  }
+
+  #[test]
+  fn rejects_invalid_settings_files() {
+    assert!(parse_settings("[]").is_err());
+    assert!(parse_settings(r#"{ "schema_version": 1 }"#).is_err());
+    assert!(parse_settings(r#"{ "schema_version": "one" }"#).is_err());
+  }
+
+  #[test]
+  fn parses_schema_versions() {
+    assert_eq!(parse_schema_version("1.0").unwrap(), (1, 0));
+    assert_eq!(parse_schema_version("12.34").unwrap(), (12, 34));
+    assert!(parse_schema_version("1").is_err());
+    assert!(parse_schema_version("1.x").is_err());
+    assert!(parse_schema_version("").is_err());
+  }
+
+  #[test]
+  fn written_settings_parse_back() {
+    let settings = PrintSettings {
+      printer: "HP".to_string(),
+      copies: Some(3),
+      ..Default::default()
+    };
+
+    // 与 `write_settings` 写入的内容相同
+    let mut json = settings.to_json().unwrap();
+    json
+      .as_object_mut()
+      .unwrap()
+      .insert("schema_version".to_string(), "1.0".into());
+
+    let parsed = parse_settings(&json.to_string()).unwrap();
+    assert_eq!(parsed.printer, "HP");
+    assert_eq!(parsed.copies, Some(3));
+  }
+
+  /// 合成的打印机能力，只有给定的纸张及布局。纸张为选项名称、显示名称、宽度和高度（微米）
+  fn capabilities(media: &[(&str, &str, u32, u32)], orientations: &[&str]) -> PrintCapabilities {
+    let media = media
+      .iter()
+      .map(|(name, display_name, width, height)| {
+        format!(
+          r#"<psf:Option name="{name}">
+            <psf:ScoredProperty name="psk:MediaSizeWidth"><psf:Value xsi:type="xsd:integer">{width}</psf:Value></psf:ScoredProperty>
+            <psf:ScoredProperty name="psk:MediaSizeHeight"><psf:Value xsi:type="xsd:integer">{height}</psf:Value></psf:ScoredProperty>
+            <psf:Property name="psk:DisplayName"><psf:Value xsi:type="xsd:string">{display_name}</psf:Value></psf:Property>
+          </psf:Option>"#
+        )
+      })
+      .collect::<String>();
+    let orientations = orientations
+      .iter()
+      .map(|name| format!(r#"<psf:Option name="psk:{name}"></psf:Option>"#))
+      .collect::<String>();
+    let xml = format!(
+      r#"<?xml version="1.0" encoding="UTF-8"?>
+      <psf:PrintCapabilities
+          xmlns:psf="http://schemas.microsoft.com/windows/2003/08/printing/printschemaframework"
+          xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance"
+          xmlns:xsd="http://www.w3.org/2001/XMLSchema" version="1"
+          xmlns:ns0000="urn:direct-printing:test"
+          xmlns:psk="http://schemas.microsoft.com/windows/2003/08/printing/printschemakeywords">
+        <psf:Feature name="psk:PageMediaSize">{media}</psf:Feature>
+        <psf:Feature name="psk:PageOrientation">{orientations}</psf:Feature>
+      </psf:PrintCapabilities>"#
+    );
+    let document = PrintCapabilitiesDocument::parse_from_bytes(xml.as_bytes()).unwrap();
+    PrintCapabilities { document }
+  }
+
+  fn page_size(name: &str, width: u32, height: u32) -> PageSize {
+    PageSize {
+      name: Some(name.to_string()),
+      width,
+      height,
+      ..Default::default()
+    }
+  }
+
+  fn settings_with(page_size: PageSize) -> PrintSettings {
+    PrintSettings {
+      page_size: Some(page_size),
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn resolves_page_size_by_dimensions_on_another_printer() {
+    // 分组中的另一台打印机对 100×150 mm 标签使用不同的名称
+    let cap = capabilities(
+      &[
+        ("ns0000:User1", "Label 4x6 (100x150)", 100000, 150000),
+        ("psk:ISOA6", "A6", 105000, 148000),
+      ],
+      &["Portrait", "Landscape"],
+    );
+
+    let plan = resolve_settings(
+      &cap,
+      &settings_with(page_size("100 x 150 mm", 100000, 150000)),
+    )
+    .unwrap();
+    let media = plan.media.unwrap();
+    assert_eq!(media.display_name(), Some("Label 4x6 (100x150)"));
+    assert_eq!(plan.media_size, Some((100000, 150000)));
+    assert_eq!(plan.warnings.len(), 1);
+    assert!(plan.warnings[0].contains("matched by size"));
+  }
+
+  #[test]
+  fn resolves_page_size_within_tolerance() {
+    let cap = capabilities(&[("ns0000:Label", "Label", 100500, 149500)], &["Portrait"]);
+
+    let plan = resolve_settings(&cap, &settings_with(page_size("Label", 100000, 150000))).unwrap();
+    assert_eq!(plan.media_size, Some((100500, 149500)));
+    assert!(plan.warnings.is_empty());
+  }
+
+  #[test]
+  fn prefers_the_same_name_among_matching_sizes() {
+    let cap = capabilities(
+      &[
+        ("ns0000:Label1", "Shipping", 100000, 150000),
+        ("ns0000:Label2", "Return", 100000, 150000),
+      ],
+      &["Portrait"],
+    );
+
+    let plan = resolve_settings(&cap, &settings_with(page_size("Return", 100000, 150000))).unwrap();
+    assert_eq!(plan.media.unwrap().display_name(), Some("Return"));
+    assert!(plan.warnings.is_empty());
+  }
+
+  #[test]
+  fn resolves_orientation_by_predefined_name() {
+    let cap = capabilities(&[], &["Portrait", "Landscape"]);
+    let settings = PrintSettings {
+      orientation: Some(Orientation::Landscape),
+      ..Default::default()
+    };
+
+    let plan = resolve_settings(&cap, &settings).unwrap();
+    assert!(matches!(
+      plan.orientation.unwrap().as_predefined_name(),
+      Some(PredefinedPageOrientation::Landscape)
+    ));
+    assert!(plan.media.is_none());
+  }
+
+  #[test]
+  fn fails_without_the_orientation() {
+    let cap = capabilities(&[], &["Portrait"]);
+    let settings = PrintSettings {
+      orientation: Some(Orientation::ReverseLandscape),
+      ..Default::default()
+    };
+
+    assert!(resolve_settings(&cap, &settings).is_err());
+  }
+
+  #[test]
+  fn fails_without_a_reasonable_page_size() {
+    let cap = capabilities(&[("psk:ISOA4", "A4", 210000, 297000)], &["Portrait"]);
+
+    let result = resolve_settings(&cap, &settings_with(page_size("Label", 100000, 150000)));
+    assert_eq!(
+      result.err().map(|e| e.to_string()).as_deref(),
+      Some("No such page size")
+    );
+  }
+
+  #[test]
+  fn substitutes_a4_for_letter_only_when_allowed() {
+    let cap = capabilities(&[("psk:ISOA4", "A4", 210000, 297000)], &["Portrait"]);
+    let mut settings = settings_with(page_size("Letter", 215900, 279400));
+
+    assert!(resolve_settings(&cap, &settings).is_err());
+
+    settings.allow_letter_a4_interchange = Some(true);
+    let plan = resolve_settings(&cap, &settings).unwrap();
+    assert_eq!(plan.media_size, Some((210000, 297000)));
+    assert!(plan.warnings[0].contains("substituted A4"));
+  }
+
+  /// 允许互相代替时按 `requested` 打印到只有 `available` 纸张的打印机
+  fn substitute(
+    requested: PageSize,
+    available: (&str, &str, u32, u32),
+  ) -> anyhow::Result<ResolvedTicketPlan> {
+    let cap = capabilities(&[available], &["Portrait"]);
+    let settings = PrintSettings {
+      allow_letter_a4_interchange: Some(true),
+      ..settings_with(requested)
+    };
+    resolve_settings(&cap, &settings)
+  }
+
+  #[test]
+  fn substitutes_letter_for_a4() {
+    let plan = substitute(
+      page_size("A4", 210000, 297000),
+      ("psk:NorthAmericaLetter", "Letter", 215900, 279400),
+    )
+    .unwrap();
+    assert_eq!(plan.media_size, Some((215900, 279400)));
+    assert!(plan.warnings[0].contains("Page size A4 is not available, substituted Letter"));
+  }
+
+  #[test]
+  fn substitutes_between_legal_and_folio() {
+    let plan = substitute(
+      page_size("Legal", 215900, 355600),
+      ("ns0000:Folio", "Folio", 210000, 330000),
+    )
+    .unwrap();
+    assert_eq!(plan.media_size, Some((210000, 330000)));
+    assert!(plan.warnings[0].contains("substituted Folio"));
+
+    let plan = substitute(
+      page_size("Folio", 210000, 330000),
+      ("psk:NorthAmericaLegal", "Legal", 215900, 355600),
+    )
+    .unwrap();
+    assert_eq!(plan.media_size, Some((215900, 355600)));
+    assert!(plan.warnings[0].contains("substituted Legal"));
+  }
+
+  #[test]
+  fn does_not_substitute_without_a_counterpart() {
+    // A5 没有可以代替的纸张
+    let result = substitute(
+      page_size("A5", 148000, 210000),
+      ("psk:ISOA4", "A4", 210000, 297000),
+    );
+    assert!(result.is_err());
+
+    // Letter 可以由 A4 代替，但打印机只有 Legal
+    let result = substitute(
+      page_size("Letter", 215900, 279400),
+      ("psk:NorthAmericaLegal", "Legal", 215900, 355600),
+    );
+    assert!(result.is_err());
+  }
+
+  #[test]
+  fn checks_settings_with_the_service_default() {
+    let cap = capabilities(&[("psk:ISOA4", "A4", 210000, 297000)], &["Portrait"]);
+    let mut settings = PrintSettings {
+      printer: "HP".to_string(),
+      ..settings_with(page_size("Letter", 215900, 279400))
+    };
+
+    // 未指定时按服务的设置，与打印时相同
+    let issues = check_settings_with(&settings, &cap, true);
+    assert_eq!(issues.len(), 1);
+    assert!(issues[0].contains("substituted A4"));
+    assert_eq!(
+      check_settings_with(&settings, &cap, false),
+      ["Page size Letter is not supported"]
+    );
+
+    // 打印设置中的值优先
+    settings.allow_letter_a4_interchange = Some(false);
+    assert_eq!(
+      check_settings_with(&settings, &cap, true),
+      ["Page size Letter is not supported"]
+    );
+  }
+
+  #[test]
+  fn prints_booklets_in_landscape_on_short_edge() {
+    let cap = simulate::capabilities().unwrap();
+    let settings = PrintSettings {
+      booklet: Some(true),
+      ..Default::default()
+    };
+
+    let plan = resolve_settings(&cap, &settings).unwrap();
+    assert!(matches!(
+      plan.orientation.unwrap().as_predefined_name(),
+      Some(PredefinedPageOrientation::Landscape)
+    ));
+    assert!(matches!(
+      plan.duplex.unwrap().as_predefined_name(),
+      Some(PredefinedDuplexType::TwoSidedShortEdge)
+    ));
+  }
+
+  /// 仓库中录制的打印机能力，见 `tests/fixtures`
+  fn recorded(printer: &str) -> PrintCapabilities {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    Fixtures::load(&dir)
+      .unwrap()
+      .capabilities(printer)
+      .unwrap()
+      .unwrap()
+  }
+
+  #[test]
+  fn warns_about_landscape_on_landscape_media() {
+    let cap = recorded("Gprinter GP-1134T");
+    // 按尺寸匹配，不带名称
+    let size = |width, height| PageSize {
+      width,
+      height,
+      ..Default::default()
+    };
+    let mut settings = PrintSettings {
+      orientation: Some(Orientation::Landscape),
+      ..settings_with(size(60000, 40000))
+    };
+
+    let plan = resolve_settings(&cap, &settings).unwrap();
+    assert_eq!(plan.media_size, Some((60000, 40000)));
+    assert_eq!(plan.warnings.len(), 1);
+    assert!(plan.warnings[0].contains("already landscape"));
+
+    settings.orientation_conflict = Some(ConflictPolicy::Error);
+    assert!(resolve_settings(&cap, &settings).is_err());
+
+    // 纵向的纸张没有冲突
+    settings.page_size = Some(size(100000, 150000));
+    assert!(resolve_settings(&cap, &settings).is_ok_and(|plan| plan.warnings.is_empty()));
+  }
+
+  #[test]
+  fn detects_options_changed_by_the_driver() {
+    // 驱动校验后的打印设置，请求的横向被改回纵向，浓度从 12 改为 10
+    let xml = include_bytes!("../../tests/fixtures/tickets/Gprinter GP-1134T landscape label.xml");
+    let ticket = PrintTicket::from_xml(xml.as_slice());
+    let gprinter = "http://schemas.gprinter.com/2012/GP1134T";
+    let name = |local: &str, namespace: &str| OwnedName::qualified(local, namespace, None::<&str>);
+    let expected = [
+      (PageOrientation::feature_name(), name("Landscape", NS_PSK)),
+      (
+        PageMediaSize::feature_name(),
+        name("User0000000259", gprinter),
+      ),
+      (name("Darkness", gprinter), name("Darkness12", gprinter)),
+    ];
+
+    let changed = changed_options(&ticket, &expected)
+      .into_iter()
+      .map(|(feature, requested, actual)| {
+        (
+          feature.local_name.as_str(),
+          requested.local_name.as_str(),
+          actual.local_name,
+        )
+      })
+      .collect::<Vec<_>>();
+    assert_eq!(
+      changed,
+      [
+        ("PageOrientation", "Landscape", "Portrait".to_string()),
+        ("Darkness", "Darkness12", "Darkness10".to_string()),
+      ]
+    );
+  }
+}
@@ -0,0 +1,71 @@
+use std::{
+  fs::create_dir_all,
+  path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use winprint::ticket::{
+  document::{
+    reader::{ParsableXmlDocument, ParsePrintSchemaError},
+    PrintCapabilitiesDocument,
+  },
+  PrintCapabilities,
+};
+
+use crate::storage::write_atomically;
+
+/// 模拟打印机的名称，模拟模式下加入打印机列表
+pub const PRINTER: &str = "Simulated Printer";
+
+/// 模拟打印机的能力，包括常用纸张、布局、双面和彩色
+const CAPABILITIES: &str = include_str!("simulated_printer.xml");
+
+/// 模拟打印机的能力
+pub fn capabilities() -> Result<PrintCapabilities, ParsePrintSchemaError> {
+  let document = PrintCapabilitiesDocument::parse_from_bytes(CAPABILITIES)?;
+  Ok(PrintCapabilities { document })
+}
+
+/// 模拟打印的任务，与 PDF 文件一起保存，记录解析后的打印设置
+#[derive(Debug, Serialize)]
+pub struct SimulatedJob {
+  /// 任务提交的打印机
+  pub printer: String,
+  /// 提交的打印设置
+  pub settings: Value,
+  /// 打印的页码
+  pub pages: Vec<u32>,
+  pub copies: u32,
+  /// 布局
+  pub orientation: Option<String>,
+  /// 纸张名称
+  pub page_size: Option<String>,
+  /// 纸张大小（微米）
+  pub media_size: Option<(u32, u32)>,
+  /// 是否双面打印
+  pub duplex: bool,
+  pub warnings: Vec<String>,
+  pub printed_at: DateTime<Utc>,
+}
+
+/// 将最终的 PDF 及任务信息保存到 `dir`，分别为 `<时间>-<id>.pdf` 和 `<时间>-<id>.json`，返回 PDF 路径
+pub fn write(dir: &Path, data: &[u8], job: &SimulatedJob) -> anyhow::Result<PathBuf> {
+  create_dir_all(dir)?;
+
+  let name = format!(
+    "{}-{}",
+    job.printed_at.format("%Y%m%d%H%M%S"),
+    super::schedule::new_id()
+  );
+  let path = dir.join(format!("{}.pdf", name));
+
+  write_atomically(&path, data)?;
+  write_atomically(
+    &dir.join(format!("{}.json", name)),
+    serde_json::to_string_pretty(job)?,
+  )?;
+
+  Ok(path)
+}
@@ -0,0 +1,348 @@
+use std::{
+  collections::HashMap,
+  fmt,
+  fs::{create_dir_all, write},
+  path::{Path, PathBuf},
+  str::FromStr,
+  time::Duration,
+};
+
+use anyhow::Context;
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeDelta, TimeZone, Utc};
+use chrono_tz::Tz;
+use log::{error, info, warn};
+use serde::Serialize;
+use ureq::{
+  tls::{TlsConfig, TlsProvider},
+  Agent,
+};
+
+use super::receipt::{self, Outcome, Receipt};
+use crate::instance;
+
+/// 每个打印机及合计中列出的错误类型数
+const TOP_ERRORS: usize = 5;
+
+/// 没有错误类型的失败，如驱动或打印服务返回的错误
+const OTHER_ERROR: &str = "other";
+
+/// 过了午夜多久生成前一天的日报，等待午夜前开始的任务结束
+const REPORT_DELAY: Duration = Duration::from_secs(60);
+
+/// 等待生成日报的最长时间，系统时间被调整后也能按时生成
+const MAX_WAIT: Duration = Duration::from_secs(3600);
+
+/// 发送日报的超时时间
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 按天统计使用的时区，`local` 为系统时区，其他为 IANA 名称，如 `Asia/Shanghai`
+#[derive(Debug, Clone, Copy, Default)]
+pub enum ReportTimezone {
+  #[default]
+  Local,
+  Named(Tz),
+}
+
+impl FromStr for ReportTimezone {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    if s.eq_ignore_ascii_case("local") {
+      return Ok(Self::Local);
+    }
+    s.parse::<Tz>().map(Self::Named).map_err(|_| {
+      format!(
+        "Unknown time zone {}, expected local or an IANA name such as Asia/Shanghai",
+        s
+      )
+    })
+  }
+}
+
+impl fmt::Display for ReportTimezone {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Local => write!(f, "local"),
+      Self::Named(tz) => write!(f, "{}", tz.name()),
+    }
+  }
+}
+
+impl ReportTimezone {
+  /// 该时区的当天
+  pub fn today(&self) -> NaiveDate {
+    self.date_of(Utc::now())
+  }
+
+  fn date_of(&self, time: DateTime<Utc>) -> NaiveDate {
+    match self {
+      Self::Local => time.with_timezone(&Local).date_naive(),
+      Self::Named(tz) => time.with_timezone(tz).date_naive(),
+    }
+  }
+
+  /// 一天在该时区的开始及结束时间，结束时间为下一天的开始，不包括在内
+  pub fn day_bounds(&self, date: NaiveDate) -> (DateTime<Utc>, DateTime<Utc>) {
+    let next = date.succ_opt().unwrap_or(date);
+    match self {
+      Self::Local => (midnight(&Local, date), midnight(&Local, next)),
+      Self::Named(tz) => (midnight(tz, date), midnight(tz, next)),
+    }
+  }
+}
+
+/// 某天的零点，夏令时导致零点不存在时为之后最早的时间
+fn midnight<T: TimeZone>(tz: &T, date: NaiveDate) -> DateTime<Utc> {
+  let naive = date.and_time(NaiveTime::MIN);
+  (0..24)
+    .find_map(|hour| {
+      tz.from_local_datetime(&(naive + TimeDelta::hours(hour)))
+        .earliest()
+    })
+    .map_or_else(|| naive.and_utc(), |time| time.to_utc())
+}
+
+/// 一组任务的合计
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Totals {
+  /// 结束的任务数
+  pub jobs: u32,
+  pub printed: u32,
+  pub failed: u32,
+  pub expired: u32,
+  pub cancelled: u32,
+  /// 打印的总页数，包括份数
+  pub pages: u64,
+  /// 使用的纸张数，回执中没有纸张数的任务不计入
+  pub sheets: u64,
+  /// 失败最多的错误类型，按次数排序
+  pub top_errors: Vec<ErrorCount>,
+}
+
+/// 错误类型及次数
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorCount {
+  pub error: String,
+  pub count: u32,
+}
+
+/// 打印机的合计
+#[derive(Debug, Clone, Serialize)]
+pub struct PrinterTotals {
+  pub printer: String,
+  pub totals: Totals,
+}
+
+/// 一天的打印统计
+#[derive(Debug, Clone, Serialize)]
+pub struct DailySummary {
+  pub date: NaiveDate,
+  /// 划分日期使用的时区
+  pub timezone: String,
+  pub start: DateTime<Utc>,
+  pub end: DateTime<Utc>,
+  pub total: Totals,
+  /// 各打印机的合计，按打印机名称排序，没有任务时为空
+  pub printers: Vec<PrinterTotals>,
+}
+
+/// 累计合计，完成时再整理错误类型
+#[derive(Default)]
+struct Tally {
+  totals: Totals,
+  errors: HashMap<String, u32>,
+}
+
+impl Tally {
+  fn add(&mut self, receipt: &Receipt) {
+    let totals = &mut self.totals;
+    totals.jobs += 1;
+    match receipt.outcome {
+      Outcome::Printed => totals.printed += 1,
+      Outcome::Failed => {
+        totals.failed += 1;
+        let error = receipt.error.as_deref().unwrap_or(OTHER_ERROR);
+        *self.errors.entry(error.to_string()).or_default() += 1;
+      }
+      Outcome::Expired => totals.expired += 1,
+      Outcome::Cancelled => totals.cancelled += 1,
+    }
+    // 只统计实际打印的页数及纸张，分部分打印时取消的任务已提交的部分也计入
+    if matches!(receipt.outcome, Outcome::Printed | Outcome::Cancelled) {
+      totals.pages += receipt.pages.unwrap_or_default() as u64;
+      totals.sheets += receipt.sheets.unwrap_or_default() as u64;
+    }
+  }
+
+  fn finish(self) -> Totals {
+    let mut errors = self
+      .errors
+      .into_iter()
+      .map(|(error, count)| ErrorCount { error, count })
+      .collect::<Vec<_>>();
+    errors.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.error.cmp(&b.error)));
+    errors.truncate(TOP_ERRORS);
+    Totals {
+      top_errors: errors,
+      ..self.totals
+    }
+  }
+}
+
+/// 统计某天结束的任务，`allows` 过滤打印机。没有任务时合计为零
+pub fn daily(
+  date: NaiveDate,
+  timezone: ReportTimezone,
+  allows: impl Fn(&str) -> bool,
+) -> DailySummary {
+  let (start, end) = timezone.day_bounds(date);
+  let mut total = Tally::default();
+  let mut printers: HashMap<String, Tally> = HashMap::new();
+
+  for receipt in receipt::finished_between(start, end) {
+    if !allows(&receipt.printer) {
+      continue;
+    }
+    total.add(&receipt);
+    printers
+      .entry(receipt.printer.clone())
+      .or_default()
+      .add(&receipt);
+  }
+
+  let mut printers = printers
+    .into_iter()
+    .map(|(printer, tally)| PrinterTotals {
+      printer,
+      totals: tally.finish(),
+    })
+    .collect::<Vec<_>>();
+  printers.sort_by(|a, b| a.printer.cmp(&b.printer));
+
+  DailySummary {
+    date,
+    timezone: timezone.to_string(),
+    start,
+    end,
+    total: total.finish(),
+    printers,
+  }
+}
+
+fn reports_dir() -> Option<PathBuf> {
+  instance::data_dir().map(|dir| dir.join("reports"))
+}
+
+fn report_filepath(dir: &Path, date: NaiveDate, ext: &str) -> PathBuf {
+  dir.join(format!("{}.{}", date.format("%Y-%m-%d"), ext))
+}
+
+/// 每行为一台打印机，最后一行为合计
+fn write_csv(path: &Path, summary: &DailySummary) -> anyhow::Result<()> {
+  let mut writer = csv::Writer::from_path(path)?;
+  writer.write_record([
+    "printer",
+    "jobs",
+    "printed",
+    "failed",
+    "expired",
+    "cancelled",
+    "pages",
+    "sheets",
+    "top_errors",
+  ])?;
+
+  let rows = summary
+    .printers
+    .iter()
+    .map(|p| (p.printer.as_str(), &p.totals))
+    .chain([("(total)", &summary.total)]);
+  for (printer, totals) in rows {
+    let errors = totals
+      .top_errors
+      .iter()
+      .map(|e| format!("{}:{}", e.error, e.count))
+      .collect::<Vec<_>>()
+      .join(";");
+    writer.write_record([
+      printer.to_string(),
+      totals.jobs.to_string(),
+      totals.printed.to_string(),
+      totals.failed.to_string(),
+      totals.expired.to_string(),
+      totals.cancelled.to_string(),
+      totals.pages.to_string(),
+      totals.sheets.to_string(),
+      errors,
+    ])?;
+  }
+  writer.flush()?;
+  Ok(())
+}
+
+/// 生成日报并保存为 JSON 及 CSV，配置了地址时再发送 JSON
+fn write_daily(
+  dir: &Path,
+  date: NaiveDate,
+  timezone: ReportTimezone,
+  webhook: Option<&str>,
+) -> anyhow::Result<()> {
+  let summary = daily(date, timezone, |_| true);
+  create_dir_all(dir)?;
+  write_csv(&report_filepath(dir, date, "csv"), &summary)?;
+  // 最后写入 JSON，以 JSON 文件是否存在判断是否已生成
+  let json = serde_json::to_vec_pretty(&summary)?;
+  write(report_filepath(dir, date, "json"), &json)?;
+  info!("Wrote the daily report of {} to {}", date, dir.display());
+
+  if let Some(url) = webhook {
+    let agent: Agent = Agent::config_builder()
+      .timeout_global(Some(WEBHOOK_TIMEOUT))
+      .tls_config(
+        TlsConfig::builder()
+          .provider(TlsProvider::NativeTls)
+          .build(),
+      )
+      .build()
+      .into();
+    agent
+      .post(url)
+      .header("Content-Type", "application/json")
+      .send(&json[..])
+      .with_context(|| format!("Failed to send the daily report to {}", url))?;
+    info!("Sent the daily report of {} to {}", date, url);
+  }
+  Ok(())
+}
+
+/// 每天过了午夜后生成前一天的日报，启动时补生成前一天缺少的日报
+pub fn spawn_daily(timezone: ReportTimezone, webhook: Option<String>) {
+  let Some(dir) = reports_dir() else {
+    error!("No data directory for daily reports");
+    return;
+  };
+
+  tokio::spawn(async move {
+    loop {
+      let yesterday = timezone.today().pred_opt();
+      let pending = yesterday.filter(|date| !report_filepath(&dir, *date, "json").exists());
+      if let Some(date) = pending {
+        let dir = dir.clone();
+        let webhook = webhook.clone();
+        let written = tokio::task::spawn_blocking(move || {
+          write_daily(&dir, date, timezone, webhook.as_deref())
+        })
+        .await;
+        match written {
+          Ok(Ok(())) => {}
+          Ok(Err(e)) => warn!("Daily report of {} is incomplete: {:#}", date, e),
+          Err(e) => warn!("Daily report of {} stopped abnormally: {}", date, e),
+        }
+      }
+
+      let (_, midnight) = timezone.day_bounds(timezone.today());
+      let wait = (midnight - Utc::now()).to_std().unwrap_or_default() + REPORT_DELAY;
+      tokio::time::sleep(wait.min(MAX_WAIT)).await;
+    }
+  });
+}
@@ -0,0 +1,191 @@
+use std::{
+  fs::{create_dir_all, read, read_dir, read_to_string, remove_file},
+  path::{Path, PathBuf},
+  sync::{LazyLock, Mutex, MutexGuard},
+};
+
+use chrono::{DateTime, Utc};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  instance,
+  pdf::{self, FormField},
+  storage::{file_stem, write_atomically},
+};
+
+/// 模板字段
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateField {
+  pub name: String,
+  /// 是否必填
+  pub required: bool,
+  /// 是否为复选框
+  pub checkbox: bool,
+}
+
+impl From<FormField> for TemplateField {
+  fn from(field: FormField) -> Self {
+    Self {
+      name: field.name,
+      required: field.required,
+      checkbox: field.checkbox,
+    }
+  }
+}
+
+/// 带表单的 PDF 模板，信息和文件分别保存为数据目录下的 `<id>.json` 和 `<id>.pdf`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Template {
+  pub id: String,
+  /// 模板名称
+  pub name: String,
+  /// 可填写的字段
+  pub fields: Vec<TemplateField>,
+  /// 上传时间
+  pub created_at: DateTime<Utc>,
+}
+
+struct Templates {
+  dir: Option<PathBuf>,
+  templates: Mutex<Vec<Template>>,
+}
+
+static TEMPLATES: LazyLock<Templates> = LazyLock::new(|| {
+  let dir = instance::data_dir().map(|dir| dir.join("templates"));
+  let mut templates = dir.as_deref().map(load_templates).unwrap_or_default();
+  templates.sort_by_key(|t| t.created_at);
+
+  if !templates.is_empty() {
+    info!("Loaded {} templates", templates.len());
+  }
+
+  Templates {
+    dir,
+    templates: Mutex::new(templates),
+  }
+});
+
+fn load_templates(dir: &Path) -> Vec<Template> {
+  let Ok(entries) = read_dir(dir) else {
+    return Vec::new();
+  };
+
+  entries
+    .flatten()
+    .map(|entry| entry.path())
+    .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+    .filter_map(|path| {
+      let template = read_to_string(&path)
+        .map_err(anyhow::Error::from)
+        .and_then(|json| Ok(serde_json::from_str::<Template>(&json)?));
+
+      template
+        .inspect_err(|e| error!("Failed to load template {}: {}", path.display(), e))
+        .ok()
+    })
+    .collect()
+}
+
+/// 保存模板，返回模板信息
+pub fn add(name: String, file: &[u8]) -> anyhow::Result<Template> {
+  save(super::schedule::new_id(), name, Utc::now(), file)
+}
+
+/// 按导出时的 ID 和上传时间保存模板，用于导入配置，已有相同 ID 的模板时替换
+pub fn restore(
+  id: String,
+  name: String,
+  created_at: DateTime<Utc>,
+  file: &[u8],
+) -> anyhow::Result<Template> {
+  // ID 用作文件名，只接受 `new_id` 生成的格式
+  if id.is_empty() || !id.chars().all(|c| c.is_ascii_hexdigit()) {
+    anyhow::bail!("Invalid template id {}", id);
+  }
+
+  save(id, name, created_at, file)
+}
+
+fn save(
+  id: String,
+  name: String,
+  created_at: DateTime<Utc>,
+  file: &[u8],
+) -> anyhow::Result<Template> {
+  let dir = TEMPLATES
+    .dir
+    .as_ref()
+    .ok_or_else(|| anyhow::anyhow!("No data directory for templates"))?;
+  let fields = pdf::form_fields(file)?;
+
+  if fields.is_empty() {
+    anyhow::bail!("The file has no fillable form fields");
+  }
+
+  let template = Template {
+    id,
+    name,
+    fields: fields.into_iter().map(TemplateField::from).collect(),
+    created_at,
+  };
+  create_dir_all(dir)?;
+
+  // 先写入文件再写入信息，加载时只认信息文件
+  let stem = file_stem(&template.id);
+  write_atomically(&dir.join(format!("{}.pdf", stem)), file)?;
+  write_atomically(
+    &dir.join(format!("{}.json", stem)),
+    serde_json::to_string(&template)?,
+  )?;
+
+  let mut templates = lock();
+  templates.retain(|t| t.id != template.id);
+  templates.push(template.clone());
+  templates.sort_by_key(|t| t.created_at);
+  Ok(template)
+}
+
+/// 删除模板，先删除信息文件，删除一半时不会加载到没有文件的模板
+pub fn remove(id: &str) -> anyhow::Result<()> {
+  let dir = TEMPLATES
+    .dir
+    .as_ref()
+    .ok_or_else(|| anyhow::anyhow!("No data directory for templates"))?;
+
+  for ext in ["json", "pdf"] {
+    match remove_file(dir.join(format!("{}.{}", file_stem(id), ext))) {
+      Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+      _ => {}
+    }
+  }
+
+  lock().retain(|t| t.id != id);
+  Ok(())
+}
+
+/// 全部模板，按上传时间排序
+pub fn list() -> Vec<Template> {
+  lock().clone()
+}
+
+/// 获取模板信息
+pub fn get(id: &str) -> Option<Template> {
+  lock().iter().find(|t| t.id == id).cloned()
+}
+
+/// 读取模板文件
+pub fn file(id: &str) -> anyhow::Result<Vec<u8>> {
+  let dir = TEMPLATES
+    .dir
+    .as_ref()
+    .ok_or_else(|| anyhow::anyhow!("No data directory for templates"))?;
+  Ok(read(dir.join(format!("{}.pdf", file_stem(id))))?)
+}
+
+fn lock() -> MutexGuard<'static, Vec<Template>> {
+  TEMPLATES
+    .templates
+    .lock()
+    .unwrap_or_else(|e| e.into_inner())
+}
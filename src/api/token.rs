@@ -0,0 +1,140 @@
+use std::{
+  collections::HashMap,
+  fmt,
+  sync::Mutex,
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// 一次性打印令牌的内容
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenClaims {
+  /// 允许使用的打印机
+  pub printer: String,
+  /// 最多打印的页数，包括份数
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub max_pages: Option<u32>,
+  /// 过期时间，Unix 时间戳（秒）
+  pub exp: u64,
+  /// 随机数，用于防止重复使用
+  nonce: String,
+}
+
+/// 令牌校验错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenError {
+  /// 格式或签名错误
+  Invalid,
+  /// 已过期
+  Expired,
+  /// 已使用过
+  Reused,
+}
+
+/// 签发和校验一次性打印令牌，令牌格式为 `base64url(内容).base64url(HMAC-SHA256)`
+pub struct Tokens {
+  secret: Vec<u8>,
+  /// 已使用的随机数及其过期时间
+  used: Mutex<HashMap<String, u64>>,
+}
+
+impl Tokens {
+  pub fn new(secret: &[u8]) -> Self {
+    Self {
+      secret: secret.to_vec(),
+      used: Mutex::new(HashMap::new()),
+    }
+  }
+
+  /// 签发令牌，`ttl` 为有效期（秒）
+  pub fn issue(&self, printer: String, max_pages: Option<u32>, ttl: u64) -> (String, u64) {
+    let exp = now() + ttl;
+    let claims = TokenClaims {
+      printer,
+      max_pages,
+      exp,
+      nonce: URL_SAFE_NO_PAD.encode(rand::random::<[u8; 16]>()),
+    };
+    let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims).unwrap_or_default());
+    let signature = URL_SAFE_NO_PAD.encode(self.mac(&payload).finalize().into_bytes());
+
+    (format!("{}.{}", payload, signature), exp)
+  }
+
+  /// 校验令牌并将其标记为已使用
+  pub fn verify(&self, token: &str) -> Result<TokenClaims, TokenError> {
+    let claims = self.check(token)?;
+    let now = now();
+
+    let mut used = self.used.lock().unwrap_or_else(|e| e.into_inner());
+    used.retain(|_, exp| *exp > now);
+
+    if used.insert(claims.nonce.clone(), claims.exp).is_some() {
+      return Err(TokenError::Reused);
+    }
+
+    Ok(claims)
+  }
+
+  /// 校验令牌但不标记为已使用，如分块上传时令牌留到打印时使用
+  pub fn check(&self, token: &str) -> Result<TokenClaims, TokenError> {
+    let (payload, signature) = token.split_once('.').ok_or(TokenError::Invalid)?;
+    let signature = URL_SAFE_NO_PAD
+      .decode(signature)
+      .map_err(|_| TokenError::Invalid)?;
+
+    // 常量时间比较
+    self
+      .mac(payload)
+      .verify_slice(&signature)
+      .map_err(|_| TokenError::Invalid)?;
+
+    let claims: TokenClaims = URL_SAFE_NO_PAD
+      .decode(payload)
+      .ok()
+      .and_then(|json| serde_json::from_slice(&json).ok())
+      .ok_or(TokenError::Invalid)?;
+    let now = now();
+
+    if claims.exp <= now {
+      return Err(TokenError::Expired);
+    }
+
+    let used = self.used.lock().unwrap_or_else(|e| e.into_inner());
+    if used.contains_key(&claims.nonce) {
+      return Err(TokenError::Reused);
+    }
+
+    Ok(claims)
+  }
+
+  fn mac(&self, payload: &str) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts any key length");
+    mac.update(payload.as_bytes());
+    mac
+  }
+}
+
+impl fmt::Debug for Tokens {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Tokens").finish_non_exhaustive()
+  }
+}
+
+/// 常量时间比较两个字符串，用于比较 API 密钥
+pub fn constant_time_eq(a: &str, b: &str) -> bool {
+  let (a, b) = (a.as_bytes(), b.as_bytes());
+  a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn now() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map_or(0, |d| d.as_secs())
+}
@@ -0,0 +1,533 @@
+use std::{
+  collections::BTreeSet,
+  fmt,
+  fs::{create_dir_all, read, read_dir, read_to_string, remove_dir_all},
+  path::{Path, PathBuf},
+  sync::{LazyLock, Mutex},
+  time::Duration,
+};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use log::{info, warn};
+use poem::http::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use super::{
+  error::{CodedError, ErrorCode},
+  receipt, schedule,
+};
+use crate::{instance, storage::write_atomically};
+
+/// 分块的大小（字节），最后一块可以较小
+pub const CHUNK_SIZE: u64 = 4 * 1024 * 1024;
+
+/// 上传文件的大小上限（字节），打印时需要读入内存
+pub const MAX_UPLOAD_SIZE: u64 = 512 * 1024 * 1024;
+
+/// 清理过期上传的间隔
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 保存上传的目录，每次上传一个子目录，包括 `upload.json` 及各分块 `<序号>.chunk`
+static DIR: LazyLock<Option<PathBuf>> =
+  LazyLock::new(|| instance::data_dir().map(|dir| dir.join("uploads")));
+
+/// 开始上传时持有，避免同时开始的上传都未计入上限
+static CREATING: Mutex<()> = Mutex::new(());
+
+/// 进行中的上传的上限，达到后拒绝开始新的上传
+#[derive(Debug, Clone, Copy)]
+pub struct UploadQuota {
+  pub max_uploads: u32,
+  /// 各上传声明的文件大小之和（字节）
+  pub max_bytes: u64,
+}
+
+impl Default for UploadQuota {
+  fn default() -> Self {
+    Self {
+      max_uploads: 64,
+      max_bytes: 4 * 1024 * 1024 * 1024,
+    }
+  }
+}
+
+/// 上传的信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadInfo {
+  pub id: String,
+  /// 文件的总大小（字节）
+  pub size: u64,
+  /// 完整文件的 SHA-256，不为空时打印前校验
+  pub sha256: Option<String>,
+  pub chunk_size: u64,
+  pub created_at: DateTime<Utc>,
+  /// 最近一次收到分块的时间，超过空闲时间后过期
+  pub updated_at: DateTime<Utc>,
+}
+
+/// 上传的进度
+#[derive(Debug, Clone)]
+pub struct Upload {
+  pub info: UploadInfo,
+  /// 已收到的分块序号，从 0 开始
+  pub received: BTreeSet<u32>,
+}
+
+impl Upload {
+  /// 分块数，空文件也有一个分块
+  pub fn chunk_count(&self) -> u32 {
+    self.info.size.div_ceil(self.info.chunk_size).max(1) as u32
+  }
+
+  /// 分块的大小，最后一块为剩余的部分
+  fn chunk_len(&self, index: u32) -> u64 {
+    let start = index as u64 * self.info.chunk_size;
+    self.info.chunk_size.min(self.info.size - start)
+  }
+
+  /// 尚未收到的分块序号
+  pub fn missing(&self) -> Vec<u32> {
+    (0..self.chunk_count())
+      .filter(|i| !self.received.contains(i))
+      .collect()
+  }
+
+  /// 已收到的连续分块，每项为首尾的序号
+  pub fn ranges(&self) -> Vec<(u32, u32)> {
+    let mut ranges: Vec<(u32, u32)> = Vec::new();
+    for &i in &self.received {
+      match ranges.last_mut() {
+        Some((_, last)) if *last + 1 == i => *last = i,
+        _ => ranges.push((i, i)),
+      }
+    }
+    ranges
+  }
+
+  /// 过期的时间
+  pub fn expires_at(&self, idle: Duration) -> DateTime<Utc> {
+    self.info.updated_at + TimeDelta::from_std(idle).unwrap_or(TimeDelta::MAX)
+  }
+}
+
+/// 上传的错误
+#[derive(Debug)]
+pub enum UploadError {
+  /// 上传不存在或已过期
+  NotFound,
+  /// 分块序号或大小错误
+  InvalidChunk(String),
+  /// 分块或完整文件的 SHA-256 不符
+  ChecksumMismatch(String),
+  /// 进行中的上传数或总大小已达到上限
+  QuotaExceeded(CodedError),
+  /// 读写文件失败
+  Failed(anyhow::Error),
+}
+
+impl fmt::Display for UploadError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::NotFound => write!(f, "No such upload"),
+      Self::InvalidChunk(msg) | Self::ChecksumMismatch(msg) => f.write_str(msg),
+      Self::QuotaExceeded(e) => f.write_str(&e.msg),
+      Self::Failed(e) => write!(f, "{:#}", e),
+    }
+  }
+}
+
+impl std::error::Error for UploadError {}
+
+impl From<anyhow::Error> for UploadError {
+  fn from(e: anyhow::Error) -> Self {
+    Self::Failed(e)
+  }
+}
+
+impl From<std::io::Error> for UploadError {
+  fn from(e: std::io::Error) -> Self {
+    Self::Failed(e.into())
+  }
+}
+
+fn dir() -> anyhow::Result<&'static Path> {
+  DIR
+    .as_deref()
+    .ok_or_else(|| anyhow::anyhow!("No data directory for uploads"))
+}
+
+/// ID 由 [`schedule::new_id`] 生成，检查格式避免访问目录外的文件
+fn upload_dir(root: &Path, id: &str) -> Option<PathBuf> {
+  let valid = id.len() == 32 && id.chars().all(|c| c.is_ascii_hexdigit());
+  valid.then(|| root.join(id))
+}
+
+fn write_info(dir: &Path, info: &UploadInfo) -> anyhow::Result<()> {
+  write_atomically(&dir.join("upload.json"), serde_json::to_string(info)?)?;
+  Ok(())
+}
+
+/// 开始上传，进行中的上传达到上限时拒绝
+pub fn create(
+  size: u64,
+  sha256: Option<String>,
+  quota: UploadQuota,
+  idle: Duration,
+) -> Result<Upload, UploadError> {
+  create_in(dir()?, size, sha256, quota, idle)
+}
+
+fn create_in(
+  root: &Path,
+  size: u64,
+  sha256: Option<String>,
+  quota: UploadQuota,
+  idle: Duration,
+) -> Result<Upload, UploadError> {
+  if size > MAX_UPLOAD_SIZE {
+    return Err(UploadError::InvalidChunk(format!(
+      "The file is too large, the limit is {} bytes",
+      MAX_UPLOAD_SIZE
+    )));
+  }
+
+  let _creating = CREATING.lock().unwrap_or_else(|e| e.into_inner());
+  let uploads = list(root, idle);
+  if uploads.len() >= quota.max_uploads as usize {
+    return Err(UploadError::QuotaExceeded(CodedError::new(
+      StatusCode::TOO_MANY_REQUESTS,
+      ErrorCode::UploadQuotaExceeded,
+      format!(
+        "{} uploads are in progress, the limit is {}",
+        uploads.len(),
+        quota.max_uploads
+      ),
+    )));
+  }
+  let bytes: u64 = uploads.iter().map(|upload| upload.info.size).sum();
+  if bytes + size > quota.max_bytes {
+    return Err(UploadError::QuotaExceeded(CodedError::new(
+      StatusCode::INSUFFICIENT_STORAGE,
+      ErrorCode::UploadQuotaExceeded,
+      format!(
+        "Uploads in progress take {} bytes, {} more would exceed the limit of {} bytes",
+        bytes, size, quota.max_bytes
+      ),
+    )));
+  }
+
+  let now = Utc::now();
+  let info = UploadInfo {
+    id: schedule::new_id(),
+    size,
+    sha256: sha256.map(|s| s.to_ascii_lowercase()),
+    chunk_size: CHUNK_SIZE,
+    created_at: now,
+    updated_at: now,
+  };
+  let dir = root.join(&info.id);
+  create_dir_all(&dir)?;
+  write_info(&dir, &info)?;
+  info!("Started upload {} of {} bytes", info.id, size);
+
+  Ok(Upload {
+    info,
+    received: BTreeSet::new(),
+  })
+}
+
+/// 读取上传的进度，不存在或超过空闲时间时为空，过期的上传随之删除
+pub fn get(id: &str, idle: Duration) -> Option<Upload> {
+  get_in(dir().ok()?, id, idle)
+}
+
+fn get_in(root: &Path, id: &str, idle: Duration) -> Option<Upload> {
+  let dir = upload_dir(root, id)?;
+  let info: UploadInfo =
+    serde_json::from_str(&read_to_string(dir.join("upload.json")).ok()?).ok()?;
+
+  let received = read_dir(&dir)
+    .ok()?
+    .flatten()
+    .filter_map(|entry| {
+      let name = entry.file_name().into_string().ok()?;
+      name.strip_suffix(".chunk")?.parse().ok()
+    })
+    .collect();
+  let upload = Upload { info, received };
+
+  if upload.expires_at(idle) <= Utc::now() {
+    remove_in(root, id);
+    return None;
+  }
+  Some(upload)
+}
+
+/// 未过期的上传，读取时删除过期的上传
+fn list(root: &Path, idle: Duration) -> Vec<Upload> {
+  let Ok(entries) = read_dir(root) else {
+    return Vec::new();
+  };
+
+  entries
+    .flatten()
+    .filter_map(|entry| get_in(root, entry.file_name().to_str()?, idle))
+    .collect()
+}
+
+/// 保存分块，校验大小及 SHA-256。重复上传同一分块时覆盖，原子地写入，不会留下不完整的分块
+pub fn put_chunk(
+  id: &str,
+  index: u32,
+  data: &[u8],
+  sha256: &str,
+  idle: Duration,
+) -> Result<Upload, UploadError> {
+  put_chunk_in(dir()?, id, index, data, sha256, idle)
+}
+
+fn put_chunk_in(
+  root: &Path,
+  id: &str,
+  index: u32,
+  data: &[u8],
+  sha256: &str,
+  idle: Duration,
+) -> Result<Upload, UploadError> {
+  let mut upload = get_in(root, id, idle).ok_or(UploadError::NotFound)?;
+  let count = upload.chunk_count();
+  if index >= count {
+    return Err(UploadError::InvalidChunk(format!(
+      "Chunk {} is out of range, the upload has {} chunks",
+      index, count
+    )));
+  }
+
+  let expected = upload.chunk_len(index);
+  if data.len() as u64 != expected {
+    return Err(UploadError::InvalidChunk(format!(
+      "Chunk {} should be {} bytes, got {}",
+      index,
+      expected,
+      data.len()
+    )));
+  }
+
+  let actual = receipt::document_hash([data]);
+  if !actual.eq_ignore_ascii_case(sha256.trim()) {
+    return Err(UploadError::ChecksumMismatch(format!(
+      "The SHA-256 of chunk {} is {}, not {}",
+      index, actual, sha256
+    )));
+  }
+
+  let dir = upload_dir(root, id).ok_or(UploadError::NotFound)?;
+  write_atomically(&dir.join(format!("{}.chunk", index)), data)?;
+  upload.info.updated_at = Utc::now();
+  write_info(&dir, &upload.info)?;
+  upload.received.insert(index);
+  Ok(upload)
+}
+
+/// 按顺序合并全部分块，有完整文件的 SHA-256 时校验
+pub fn assemble(upload: &Upload) -> Result<Vec<u8>, UploadError> {
+  assemble_in(dir()?, upload)
+}
+
+fn assemble_in(root: &Path, upload: &Upload) -> Result<Vec<u8>, UploadError> {
+  let dir = upload_dir(root, &upload.info.id).ok_or(UploadError::NotFound)?;
+  let mut file = Vec::with_capacity(upload.info.size as usize);
+  for i in 0..upload.chunk_count() {
+    file.extend(read(dir.join(format!("{}.chunk", i)))?);
+  }
+
+  if let Some(expected) = &upload.info.sha256 {
+    let actual = receipt::document_hash([file.as_slice()]);
+    if actual != *expected {
+      return Err(UploadError::ChecksumMismatch(format!(
+        "The SHA-256 of the assembled file is {}, not {}",
+        actual, expected
+      )));
+    }
+  }
+  Ok(file)
+}
+
+/// 删除上传及其分块
+pub fn remove(id: &str) {
+  if let Ok(root) = dir() {
+    remove_in(root, id);
+  }
+}
+
+fn remove_in(root: &Path, id: &str) {
+  let Some(dir) = upload_dir(root, id) else {
+    return;
+  };
+  match remove_dir_all(&dir) {
+    Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+      warn!("Failed to remove upload {}: {}", id, e)
+    }
+    _ => {}
+  }
+}
+
+/// 删除超过空闲时间的上传，包括服务停止期间过期的
+fn remove_expired(root: &Path, idle: Duration) {
+  let Ok(entries) = read_dir(root) else {
+    return;
+  };
+
+  for entry in entries.flatten() {
+    let Ok(id) = entry.file_name().into_string() else {
+      continue;
+    };
+    // 读取时删除过期的上传，信息文件损坏的也删除
+    if get_in(root, &id, idle).is_none() && upload_dir(root, &id).is_some() {
+      info!("Removing expired upload {}", id);
+      remove_in(root, &id);
+    }
+  }
+}
+
+/// 定期在后台删除过期的上传
+pub fn spawn_cleanup(idle: Duration) {
+  tokio::spawn(async move {
+    loop {
+      if let Ok(root) = dir() {
+        let _ = tokio::task::spawn_blocking(move || remove_expired(root, idle)).await;
+      }
+      tokio::time::sleep(CLEANUP_INTERVAL).await;
+    }
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const IDLE: Duration = Duration::from_secs(3600);
+
+  fn put(root: &Path, upload: &Upload, index: u32, data: &[u8]) -> Result<Upload, UploadError> {
+    let sha256 = receipt::document_hash([data]);
+    put_chunk_in(root, &upload.info.id, index, data, &sha256, IDLE)
+  }
+
+  #[test]
+  fn accepts_chunks_out_of_order_and_again() {
+    let root = tempfile::tempdir().unwrap();
+    let file: Vec<u8> = (0..CHUNK_SIZE + 3).map(|i| i as u8).collect();
+    let sha256 = receipt::document_hash([file.as_slice()]);
+    let (first, last) = file.split_at(CHUNK_SIZE as usize);
+    let upload = create_in(
+      root.path(),
+      file.len() as u64,
+      Some(sha256),
+      UploadQuota::default(),
+      IDLE,
+    )
+    .unwrap();
+
+    let upload = put(root.path(), &upload, 1, last).unwrap();
+    assert_eq!(upload.missing(), vec![0]);
+    put(root.path(), &upload, 0, first).unwrap();
+    let upload = put(root.path(), &upload, 0, first).unwrap();
+
+    assert!(upload.missing().is_empty());
+    assert_eq!(upload.ranges(), vec![(0, 1)]);
+    assert_eq!(assemble_in(root.path(), &upload).unwrap(), file);
+  }
+
+  #[test]
+  fn rejects_chunks_of_the_wrong_size() {
+    let root = tempfile::tempdir().unwrap();
+    let upload = create_in(root.path(), 10, None, UploadQuota::default(), IDLE).unwrap();
+
+    let longer = put(root.path(), &upload, 0, &[0; 11]);
+    assert!(matches!(longer, Err(UploadError::InvalidChunk(_))));
+    let beyond = put(root.path(), &upload, 1, &[0; 10]);
+    assert!(matches!(beyond, Err(UploadError::InvalidChunk(_))));
+    let too_large = create_in(
+      root.path(),
+      MAX_UPLOAD_SIZE + 1,
+      None,
+      UploadQuota::default(),
+      IDLE,
+    );
+    assert!(matches!(too_large, Err(UploadError::InvalidChunk(_))));
+
+    let upload = get_in(root.path(), &upload.info.id, IDLE).unwrap();
+    assert!(upload.received.is_empty());
+  }
+
+  #[test]
+  fn rejects_chunks_with_a_wrong_checksum() {
+    let root = tempfile::tempdir().unwrap();
+    let upload = create_in(root.path(), 4, None, UploadQuota::default(), IDLE).unwrap();
+
+    let sha256 = receipt::document_hash([b"other".as_slice()]);
+    let result = put_chunk_in(root.path(), &upload.info.id, 0, b"data", &sha256, IDLE);
+    assert!(matches!(result, Err(UploadError::ChecksumMismatch(_))));
+  }
+
+  #[test]
+  fn removes_idle_uploads() {
+    let root = tempfile::tempdir().unwrap();
+    let upload = create_in(root.path(), 4, None, UploadQuota::default(), IDLE).unwrap();
+    let id = upload.info.id.clone();
+    put(root.path(), &upload, 0, b"data").unwrap();
+
+    assert!(get_in(root.path(), &id, Duration::ZERO).is_none());
+    assert!(!root.path().join(&id).exists());
+    let result = put(root.path(), &upload, 0, b"data");
+    assert!(matches!(result, Err(UploadError::NotFound)));
+  }
+
+  #[test]
+  fn rejects_invalid_ids() {
+    let root = tempfile::tempdir().unwrap();
+    std::fs::create_dir(root.path().join("uploads")).unwrap();
+    let not_hex = "g".repeat(32);
+
+    for id in ["", "..", "../uploads", "uploads", not_hex.as_str()] {
+      assert!(upload_dir(root.path(), id).is_none());
+      assert!(get_in(root.path(), id, IDLE).is_none());
+      let result = put_chunk_in(root.path(), id, 0, b"", "", IDLE);
+      assert!(matches!(result, Err(UploadError::NotFound)));
+    }
+    remove_in(root.path(), "..");
+    assert!(root.path().join("uploads").exists());
+  }
+
+  #[test]
+  fn rejects_uploads_over_the_quota() {
+    let root = tempfile::tempdir().unwrap();
+    let quota = UploadQuota {
+      max_uploads: 2,
+      max_bytes: 100,
+    };
+    create_in(root.path(), 60, None, quota, IDLE).unwrap();
+
+    match create_in(root.path(), 41, None, quota, IDLE) {
+      Err(UploadError::QuotaExceeded(e)) => assert_eq!(e.status, StatusCode::INSUFFICIENT_STORAGE),
+      result => panic!("unexpected {:?}", result),
+    }
+    create_in(root.path(), 40, None, quota, IDLE).unwrap();
+    match create_in(root.path(), 0, None, quota, IDLE) {
+      Err(UploadError::QuotaExceeded(e)) => assert_eq!(e.status, StatusCode::TOO_MANY_REQUESTS),
+      result => panic!("unexpected {:?}", result),
+    }
+  }
+
+  #[test]
+  fn frees_the_quota_of_expired_uploads() {
+    let root = tempfile::tempdir().unwrap();
+    let quota = UploadQuota {
+      max_uploads: 1,
+      max_bytes: 100,
+    };
+    create_in(root.path(), 100, None, quota, IDLE).unwrap();
+
+    assert!(create_in(root.path(), 1, None, quota, Duration::ZERO).is_ok());
+  }
+}
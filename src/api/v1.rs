@@ -0,0 +1,5078 @@
+use std::{
+  collections::{BTreeMap, HashMap, HashSet},
+  ffi::OsStr,
+  path::PathBuf,
+  time::Duration,
+};
+
+use anyhow::{anyhow, bail};
+use chrono::{DateTime, FixedOffset, NaiveDate, TimeDelta, Utc};
+use image::ImageFormat;
+use log::{debug, error, info, warn};
+use poem::{
+  error::InternalServerError,
+  http::{header, StatusCode},
+  Body, Request,
+};
+use poem_openapi::{
+  param::{Header, Path, Query},
+  payload::{Binary, Json},
+  types::{Base64, ParseFromJSON, ToJSON},
+  ApiResponse, Enum, Object, OpenApi, OpenApiService, ResponseContent, Tags, Union,
+};
+use serde_json::Value;
+use winprint::{printer::PrinterDevice, ticket::PredefinedPageOrientation};
+
+use super::{
+  access::Client,
+  capability::{
+    cached_capabilities, fetch_capability, invalidate_capabilities, printer_capability, warm_up,
+    with_units,
+  },
+  dispatch::{
+    check_release, error_name, issue_receipt, print_saved, queue_estimate, queue_positions,
+    release_pin_hash, result_outcome, schedule_file, SCHEDULE_TOLERANCE,
+  },
+  drift::{self, CapabilityChanges},
+  error::{CodedError, ErrorCode, Response},
+  events::{self, JobEvent, JobStatus},
+  format::{self, Format},
+  locale::Languages,
+  options::ApiOptions,
+  paging::Paging,
+  print::{catch_panic, print_file, run_post_print_hooks, skips_hooks, target_printer, Deadline},
+  printers::{
+    dedupe_printers, is_unc_printer_path, printer_entry, printer_order, printer_state,
+    request_client, select_named_printer, simulated_printer_state,
+  },
+  quarantine::{Evidence, QuarantineEntry},
+  receipt::{self, Outcome, Receipt, SignedReceipt},
+  retention,
+  schedule::{self, Ending, Priority, RunningJob, ScheduledJob},
+  settings::{
+    check_settings, default_settings, export_bundle, get_settings_filepath, import_bundle,
+    set_default_settings, write_settings,
+  },
+  summary::{self, DailySummary, Totals},
+  template,
+  token::constant_time_eq,
+  upload::{self, Upload, UploadError},
+};
+
+use crate::{
+  barcode,
+  hook::{self, HookRun, HookState},
+  instance, pdf, spooler, status,
+};
+
+/// 布局
+#[derive(Debug, Clone, Copy, Enum)]
+#[oai(rename_all = "snake_case")]
+pub enum Orientation {
+  /// 纵向
+  Portrait,
+  /// 横向
+  Landscape,
+  ReversePortrait,
+  ReverseLandscape,
+}
+
+impl From<PredefinedPageOrientation> for Orientation {
+  fn from(value: PredefinedPageOrientation) -> Self {
+    match value {
+      PredefinedPageOrientation::Portrait => Orientation::Portrait,
+      PredefinedPageOrientation::Landscape => Orientation::Landscape,
+      PredefinedPageOrientation::ReversePortrait => Orientation::ReversePortrait,
+      PredefinedPageOrientation::ReverseLandscape => Orientation::ReverseLandscape,
+    }
+  }
+}
+
+impl From<Orientation> for PredefinedPageOrientation {
+  fn from(value: Orientation) -> Self {
+    match value {
+      Orientation::Portrait => PredefinedPageOrientation::Portrait,
+      Orientation::Landscape => PredefinedPageOrientation::Landscape,
+      Orientation::ReversePortrait => PredefinedPageOrientation::ReversePortrait,
+      Orientation::ReverseLandscape => PredefinedPageOrientation::ReverseLandscape,
+    }
+  }
+}
+
+/// 页码奇偶
+#[derive(Debug, Clone, Copy, Enum)]
+#[oai(rename_all = "snake_case")]
+pub enum PageParity {
+  /// 全部页
+  All,
+  /// 奇数页
+  Odd,
+  /// 偶数页
+  Even,
+}
+
+impl PageParity {
+  pub fn matches(self, page: u32) -> bool {
+    match self {
+      PageParity::All => true,
+      PageParity::Odd => !page.is_multiple_of(2),
+      PageParity::Even => page.is_multiple_of(2),
+    }
+  }
+}
+
+/// 长度单位
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Enum)]
+#[oai(rename_all = "lowercase")]
+pub enum LengthUnit {
+  /// 微米
+  #[default]
+  Micron,
+  /// 毫米
+  Mm,
+  /// 英寸
+  Inch,
+  /// 磅，1/72 英寸
+  Pt,
+}
+
+impl LengthUnit {
+  /// 每单位的微米数，表示为分数以避免换算误差
+  fn microns(self) -> (f64, f64) {
+    match self {
+      LengthUnit::Micron => (1.0, 1.0),
+      LengthUnit::Mm => (1000.0, 1.0),
+      LengthUnit::Inch => (25400.0, 1.0),
+      LengthUnit::Pt => (25400.0, 72.0),
+    }
+  }
+
+  /// 换算为微米，四舍五入
+  fn to_micron(self, value: f64) -> u32 {
+    let (num, den) = self.microns();
+    (value * num / den).round() as u32
+  }
+
+  /// 从微米换算，保留 6 位小数，换算回微米时不会产生偏差
+  pub fn micron_to_units(self, value: u32) -> f64 {
+    let (num, den) = self.microns();
+    (value as f64 * den / num * 1e6).round() / 1e6
+  }
+}
+
+/// 以指定单位表示的纸张尺寸
+#[derive(Debug, Clone, Object)]
+pub struct Dimensions {
+  pub width: f64,
+  pub height: f64,
+  pub units: LengthUnit,
+}
+
+/// 纸张大小
+#[derive(Debug, Clone, Default, Object)]
+#[oai(skip_serializing_if_is_none)]
+pub struct PageSize {
+  /// 名称
+  pub name: Option<String>,
+  /// 宽度，微米
+  #[oai(default)]
+  pub width: u32,
+  /// 高度，微米
+  #[oai(default)]
+  pub height: u32,
+  /// 以其他单位表示的尺寸。打印设置中指定时代替 `width`、`height`，换算为微米后再匹配纸张；
+  /// 获取打印机能力时指定 `units` 才返回
+  pub dimensions: Option<Dimensions>,
+  /// 按尺寸识别的标准纸张名称，如 `A4`、`Letter`、`4x6in`，仅在获取打印机能力时返回
+  pub standard: Option<String>,
+  /// 可打印区域，仅在获取打印机能力且驱动提供时返回
+  pub imageable_area: Option<ImageableArea>,
+  /// 高度随内容，用于小票等卷纸，打印设置中指定时忽略 `height`。打印时按 `width` 等比缩放文档，
+  /// 取最高一页的高度；驱动支持自定义纸张时使用该高度，否则使用宽度相同、足够长的最短纸张
+  pub auto_height: Option<bool>,
+}
+
+impl PageSize {
+  pub fn is_auto_height(&self) -> bool {
+    self.auto_height.unwrap_or_default()
+  }
+}
+
+impl PrintSettings {
+  /// 将纸张大小的 `dimensions` 换算为微米，写入 `width`、`height`
+  pub fn normalize_units(&mut self) {
+    if let Some(page_size) = &mut self.page_size {
+      if let Some(d) = page_size.dimensions.take() {
+        page_size.width = d.units.to_micron(d.width);
+        page_size.height = d.units.to_micron(d.height);
+      }
+    }
+  }
+}
+
+/// 纸张大小的排序方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Enum)]
+#[oai(rename_all = "lowercase")]
+pub enum PageSizeSort {
+  /// 按面积，其次按名称
+  #[default]
+  Area,
+  /// 驱动返回的原始顺序，不去重，用于调试
+  None,
+}
+
+/// 自定义纸张大小范围
+#[derive(Debug, Clone, Object)]
+pub struct CustomPageSize {
+  /// 最小宽度，微米
+  pub min_width: u32,
+  /// 最大宽度，微米
+  pub max_width: u32,
+  /// 最小高度，微米
+  pub min_height: u32,
+  /// 最大高度，微米
+  pub max_height: u32,
+}
+
+impl CustomPageSize {
+  pub fn contains(&self, width: u32, height: u32) -> bool {
+    (self.min_width..=self.max_width).contains(&width)
+      && (self.min_height..=self.max_height).contains(&height)
+  }
+}
+
+/// 可打印区域
+#[derive(Debug, Clone, Object)]
+pub struct ImageableArea {
+  /// 左边距，微米
+  pub origin_x: u32,
+  /// 上边距，微米
+  pub origin_y: u32,
+  /// 宽度，微米
+  pub width: u32,
+  /// 高度，微米
+  pub height: u32,
+}
+
+/// 页边距，以纸张上的上下左右为准，正值向内收缩，负值向外扩展
+#[derive(Debug, Clone, Object)]
+pub struct Margins {
+  /// 上边距，微米
+  #[oai(default)]
+  top: i32,
+  /// 右边距，微米
+  #[oai(default)]
+  right: i32,
+  /// 下边距，微米
+  #[oai(default)]
+  bottom: i32,
+  /// 左边距，微米
+  #[oai(default)]
+  left: i32,
+  /// 是否将内容等比缩放到页边距以内，否则只按 `left - right` 和 `top - bottom` 平移内容
+  #[oai(default)]
+  fit: bool,
+}
+
+impl From<&Margins> for pdf::PageMargins {
+  fn from(value: &Margins) -> Self {
+    Self {
+      top: value.top,
+      right: value.right,
+      bottom: value.bottom,
+      left: value.left,
+      fit: value.fit,
+    }
+  }
+}
+
+/// 打印机能力
+#[derive(Debug, Clone, Default, Object)]
+#[oai(skip_serializing_if_is_none, example)]
+pub struct PrinterCapability {
+  /// 最大打印份数
+  pub max_copies: Option<u16>,
+  /// 布局
+  pub orientations: Option<Vec<Orientation>>,
+  /// 纸张大小
+  pub page_sizes: Option<Vec<PageSize>>,
+  /// 自定义纸张大小范围，驱动不支持自定义纸张时为空
+  pub custom_page_size: Option<CustomPageSize>,
+  /// 驱动私有的功能及参数，如热敏打印机的浓度、速度，可在打印设置的 `driver_options` 中指定。
+  /// 驱动没有私有的功能时为空
+  pub driver_features: Option<Vec<DriverFeature>>,
+  /// 打印机的端口、驱动、位置等信息，指定 `detailed` 时才有
+  pub details: Option<PrinterEntry>,
+  /// 打印机的状态，指定 `exists_only` 时才有，此时不返回能力
+  pub state: Option<PrinterState>,
+}
+
+impl PrinterCapability {
+  /// 可用 `fields` 筛选的字段
+  const FIELDS: &'static [&'static str] = &[
+    "max_copies",
+    "orientations",
+    "page_sizes",
+    "custom_page_size",
+    "driver_features",
+  ];
+
+  /// 只保留 `fields` 中的字段
+  fn retain(&mut self, fields: &[&str]) {
+    let keep = |field| fields.contains(&field);
+
+    if !keep("max_copies") {
+      self.max_copies = None;
+    }
+    if !keep("orientations") {
+      self.orientations = None;
+    }
+    if !keep("page_sizes") {
+      self.page_sizes = None;
+    }
+    if !keep("custom_page_size") {
+      self.custom_page_size = None;
+    }
+    if !keep("driver_features") {
+      self.driver_features = None;
+    }
+  }
+}
+
+/// 驱动私有的功能或参数，不属于打印架构的标准关键字
+#[derive(Debug, Clone, Object)]
+#[oai(skip_serializing_if_is_none)]
+pub struct DriverFeature {
+  /// 名称，用作 `driver_options` 的键
+  pub name: String,
+  /// 驱动定义的命名空间
+  pub namespace: String,
+  /// 显示名称
+  pub display_name: Option<String>,
+  /// 功能可选的选项，为参数时为空
+  pub options: Option<Vec<DriverFeatureOption>>,
+  /// 参数值的类型，为功能时为空
+  pub value_type: Option<DriverValueType>,
+  /// 参数的最小值
+  pub min_value: Option<i32>,
+  /// 参数的最大值
+  pub max_value: Option<i32>,
+  /// 参数的默认值
+  pub default_value: Option<String>,
+}
+
+/// 驱动私有功能的选项
+#[derive(Debug, Clone, Object)]
+#[oai(skip_serializing_if_is_none)]
+pub struct DriverFeatureOption {
+  /// 选项名称，用作 `driver_options` 的值
+  pub name: String,
+  /// 显示名称
+  pub display_name: Option<String>,
+}
+
+/// 驱动私有参数值的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+#[oai(rename_all = "lowercase")]
+pub enum DriverValueType {
+  Integer,
+  String,
+}
+
+/// 驱动私有选项的值，功能为选项名称，参数为整数或文本
+#[derive(Debug, Clone, Union)]
+#[oai(one_of)]
+pub enum DriverOptionValue {
+  Integer(i64),
+  Text(String),
+}
+
+impl std::fmt::Display for DriverOptionValue {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      DriverOptionValue::Integer(i) => write!(f, "{}", i),
+      DriverOptionValue::Text(s) => f.write_str(s),
+    }
+  }
+}
+
+/// 一台打印机的能力，获取失败时为错误信息
+#[derive(Debug, Object)]
+#[oai(skip_serializing_if_is_none)]
+pub struct PrinterCapabilityResult {
+  pub capability: Option<PrinterCapability>,
+  /// 获取失败的原因，不影响其他打印机
+  pub error: Option<String>,
+}
+
+/// 打印设置
+#[derive(Debug, Clone, Default, Object)]
+#[oai(skip_serializing_if_is_none, example)]
+pub struct PrintSettings {
+  /// 要使用的打印机名称，指定 `printer_id` 时可以为空
+  #[oai(default)]
+  pub printer: String,
+  /// 打印机 ID，见 `GET /printers?detailed=true`，同一名称对应多台打印机时用于区分
+  pub printer_id: Option<String>,
+  /// 打印份数
+  pub copies: Option<u16>,
+  /// 布局
+  pub orientation: Option<Orientation>,
+  /// 纸张大小
+  pub page_size: Option<PageSize>,
+  /// 页码范围，从 1 开始，如 `1-3,5,8-`
+  pub page_range: Option<String>,
+  /// 页码奇偶
+  pub page_parity: Option<PageParity>,
+  /// 是否倒序打印
+  pub reverse_order: Option<bool>,
+  /// 顺时针旋转角度，只能是 0、90、180 或 270，先旋转再缩放到纸张大小
+  #[oai(validator(multiple_of = "90", maximum(value = "270")))]
+  pub rotate_degrees: Option<u16>,
+  /// 需要旋转的页码范围，格式同 `page_range`，默认全部页
+  pub rotate_range: Option<String>,
+  /// 页边距，用于调整内容在纸张上的位置
+  pub margins: Option<Margins>,
+  /// 是否按骑马钉顺序拼版为小册子，每面两页，未指定布局时使用横向，支持时使用短边双面打印
+  pub booklet: Option<bool>,
+  /// 备用打印机，打印机或打印队列出错时依次改用，按各打印机的能力重新匹配纸张大小和布局
+  pub fallback_printers: Option<Vec<String>>,
+  /// 布局与纸张冲突，或驱动校验打印设置时改变了布局、纸张时的处理方式，默认只记录警告
+  pub orientation_conflict: Option<ConflictPolicy>,
+  /// 超过此页数时按顺序拆分为多个打印作业，每个作业不超过此页数，在选择页码及倒序后拆分。
+  /// 默认使用服务的设置，0 表示不拆分。小册子不拆分
+  pub split_every_pages: Option<u32>,
+  /// 是否跳过空白页，在选择页码后以低分辨率渲染检测，跳过的页码见 `warnings`。
+  /// 页数超过服务限制的文档不检测
+  pub skip_blank_pages: Option<bool>,
+  /// 在系统打印队列中显示的文档名称，默认为 PDF 文档信息中的标题，没有标题时为 `direct-printing <提交时间>`。
+  /// 不能用于文件名的字符替换为 `_`，超过 100 个字符时截断，拆分为多个打印作业时加上序号
+  pub job_name: Option<String>,
+  /// 驱动私有的功能及参数，如 `{"Darkness": 12, "PrintSpeed": "Speed4"}`，可用的名称及取值见打印机能力的
+  /// `driver_features`。功能的值为选项名称，参数的值为整数或文本。应用后的值可用 `debug_ticket` 查看
+  pub driver_options: Option<BTreeMap<String, DriverOptionValue>>,
+  /// 跳过打印机没有的 `driver_options` 并记录警告，默认返回错误。用于备用打印机的驱动不同时
+  pub ignore_unknown_driver_options: Option<bool>,
+  /// 打印机没有请求的标准纸张时改用对应的纸张，内容缩放到纸张大小，并在 `warnings` 中说明。
+  /// 目前只有 Letter 与 A4、Legal 与 Folio 互相代替。默认使用服务的设置
+  pub allow_letter_a4_interchange: Option<bool>,
+}
+
+/// 打印设置冲突的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Enum)]
+#[oai(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+  /// 仍然打印，在 `warnings` 中说明驱动实际的处理
+  #[default]
+  Warn,
+  /// 不打印，返回错误
+  Error,
+}
+
+impl ConflictPolicy {
+  /// 按处理方式记录警告或返回错误
+  pub fn report(self, msg: String, warnings: &mut Vec<String>) -> anyhow::Result<()> {
+    match self {
+      ConflictPolicy::Warn => {
+        warn!("{}", msg);
+        warnings.push(msg);
+        Ok(())
+      }
+      ConflictPolicy::Error => Err(anyhow!(msg)),
+    }
+  }
+}
+
+/// 输出目标
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Enum)]
+#[oai(rename_all = "lowercase")]
+pub enum OutputTarget {
+  /// 打印到打印机
+  #[default]
+  Printer,
+  /// 不打印，返回处理后的 PDF
+  Pdf,
+}
+
+/// 打印负载
+#[derive(Default, Object)]
+#[oai(skip_serializing_if_is_none, example)]
+pub struct PrintPayload {
+  /// 要打印的 PDF 文件内容
+  pub file: Option<Base64<Vec<u8>>>,
+  /// 要合并为一个任务打印的多个 PDF 文件内容，与 `file` 二选一
+  pub files: Option<Vec<Base64<Vec<u8>>>>,
+  /// 打印设置
+  pub settings: Option<PrintSettings>,
+  /// 打印机未就绪时仍然提交到打印队列，默认直接返回错误
+  pub queue_if_not_ready: Option<bool>,
+  /// 计划打印的时间（RFC 3339），为空或不晚于当前时间时立即打印
+  pub print_at: Option<DateTime<FixedOffset>>,
+  /// 定时任务超过计划时间多少秒仍未打印时过期，不再打印。立即打印的任务直接提交到系统的打印队列，不会过期
+  pub ttl_seconds: Option<u64>,
+  /// 输出目标，为 `pdf` 时不选择打印机，按打印设置处理文档后在 `document` 中返回，
+  /// 不支持定时打印，打印设置中的打印机名称及纸张以外的打印机设置不生效
+  pub output: Option<OutputTarget>,
+  /// 拒绝包含未知字段的请求，默认忽略未知字段。服务启用 `--strict-requests` 时总是拒绝
+  pub strict: Option<bool>,
+  /// 在结果的 `ticket` 中返回驱动校验后的打印票据，用于排查驱动问题
+  pub debug_ticket: Option<bool>,
+  /// 截止时间，收到请求后多少毫秒内未提交到系统打印队列时取消任务，不再打印，同请求头
+  /// `X-Request-Deadline-Ms`。已提交的任务仍然完成，结果中 `deadline_exceeded` 为 true。不适用于定时任务
+  pub deadline_ms: Option<u64>,
+  /// 输出目标为 `pdf` 时在结果的 `coverage` 中返回处理后文档的墨迹覆盖率估算，见 `POST /pdf/coverage`
+  pub estimate_coverage: Option<bool>,
+  /// 文件的格式，默认按内容识别。指定时优先于识别的格式，与识别的格式不同时记录警告，
+  /// `strict` 为 true 时拒绝打印。目前只能打印 PDF 文件
+  pub format: Option<DocumentFormat>,
+  /// 任务结束后不执行服务配置的打印后命令，不影响打印前命令
+  pub skip_hooks: Option<bool>,
+  /// 暂缓打印，检查并处理文档后保存在服务内，调用 `POST /jobs/{id}/release` 时才打印。
+  /// 超过 `ttl_seconds` 或服务配置的有效期仍未释放时过期。不能与 `print_at` 或输出为 `pdf` 同时使用
+  pub hold: Option<bool>,
+  /// 释放暂缓任务时需要在请求头 `X-Release-Pin` 中提供的 PIN，只适用于 `hold` 为 true 的任务
+  pub release_pin: Option<String>,
+  /// 在服务内排队时的优先级，默认为 `normal`。同一打印机的任务先打印优先级高的，正在打印的任务不被打断。
+  /// 只适用于定时任务及打印机暂停时排队的任务，立即打印的任务直接提交到系统的打印队列
+  pub priority: Option<JobPriority>,
+}
+
+/// 任务在服务内排队时的优先级
+#[derive(Debug, Clone, Copy, Enum)]
+#[oai(rename_all = "lowercase")]
+pub enum JobPriority {
+  Low,
+  Normal,
+  High,
+}
+
+impl From<JobPriority> for Priority {
+  fn from(value: JobPriority) -> Self {
+    match value {
+      JobPriority::Low => Self::Low,
+      JobPriority::Normal => Self::Normal,
+      JobPriority::High => Self::High,
+    }
+  }
+}
+
+impl From<Priority> for JobPriority {
+  fn from(value: Priority) -> Self {
+    match value {
+      Priority::Low => Self::Low,
+      Priority::Normal => Self::Normal,
+      Priority::High => Self::High,
+    }
+  }
+}
+
+/// 文件格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+#[oai(rename_all = "lowercase")]
+pub enum DocumentFormat {
+  Pdf,
+  Png,
+  Jpeg,
+  Tiff,
+  Xps,
+  Zip,
+  Text,
+}
+
+impl From<DocumentFormat> for Format {
+  fn from(value: DocumentFormat) -> Self {
+    match value {
+      DocumentFormat::Pdf => Format::Pdf,
+      DocumentFormat::Png => Format::Png,
+      DocumentFormat::Jpeg => Format::Jpeg,
+      DocumentFormat::Tiff => Format::Tiff,
+      DocumentFormat::Xps => Format::Xps,
+      DocumentFormat::Zip => Format::Zip,
+      DocumentFormat::Text => Format::Text,
+    }
+  }
+}
+
+/// 打印设置的检查结果
+#[derive(Debug, Object)]
+pub struct SettingsValidation {
+  /// 是否适用于当前的打印机
+  pub valid: bool,
+  /// 发现的问题
+  pub issues: Vec<String>,
+}
+
+impl From<anyhow::Result<Vec<String>>> for SettingsValidation {
+  fn from(result: anyhow::Result<Vec<String>>) -> Self {
+    let issues = result.unwrap_or_else(|e| vec![format!("Failed to check settings: {}", e)]);
+
+    Self {
+      valid: issues.is_empty(),
+      issues,
+    }
+  }
+}
+
+/// 默认打印设置及其检查结果
+#[derive(Debug, Object)]
+pub struct DefaultSettings {
+  #[oai(flatten)]
+  pub settings: PrintSettings,
+  #[oai(flatten)]
+  pub validation: SettingsValidation,
+}
+
+/// 配置包，包括服务保存的配置。访问规则和保留策略由命令行参数及其指定的文件提供，随部署方式管理，
+/// 不在配置包中；暂停状态是运行状态，导入到其他实例时不应使其暂停，也不在配置包中
+#[derive(Debug, Object)]
+#[oai(skip_serializing_if_is_none)]
+pub struct ConfigBundle {
+  /// 设置文件格式的版本
+  pub schema_version: String,
+  /// 默认打印设置
+  pub default_settings: Option<PrintSettings>,
+  /// 模板，包括模板文件。为空时不导入模板
+  pub templates: Option<Vec<BundledTemplate>>,
+}
+
+/// 配置包中的模板
+#[derive(Debug, Object)]
+pub struct BundledTemplate {
+  pub id: String,
+  /// 模板名称
+  pub name: String,
+  /// 上传时间
+  pub created_at: DateTime<Utc>,
+  /// 模板文件
+  pub file: Base64<Vec<u8>>,
+}
+
+/// 打印令牌请求
+#[derive(Debug, Object)]
+#[oai(example)]
+pub struct TokenRequest {
+  /// 允许使用的打印机
+  pub printer: String,
+  /// 最多打印的页数，包括份数
+  pub max_pages: Option<u32>,
+  /// 有效期，秒，默认 300，最长 86400
+  #[oai(validator(minimum(value = "1"), maximum(value = "86400")))]
+  pub expires_in: Option<u64>,
+}
+
+/// 打印令牌
+#[derive(Debug, Object)]
+pub struct PrintToken {
+  /// 令牌，打印时放在 `X-Print-Token` 请求头中
+  pub token: String,
+  /// 过期时间，Unix 时间戳（秒）
+  pub expires_at: u64,
+}
+
+/// 记录日志时需要隐藏内容的请求体
+pub trait Redact: ToJSON {
+  /// 需要隐藏内容的字段，只记录其大小
+  const REDACTED_FIELDS: &'static [&'static str] = &[];
+
+  /// 隐藏字段后的 JSON
+  fn redacted(&self) -> String {
+    let mut json = self.to_json().unwrap_or_default();
+
+    if let Some(obj) = json.as_object_mut() {
+      for field in Self::REDACTED_FIELDS {
+        if let Some(value) = obj.get_mut(*field) {
+          let size = match value {
+            Value::String(s) => base64_decoded_len(s),
+            Value::Array(items) => items
+              .iter()
+              .filter_map(Value::as_str)
+              .map(base64_decoded_len)
+              .sum(),
+            _ => 0,
+          };
+          *value = format!("<redacted, {} bytes>", size).into();
+        }
+      }
+    }
+
+    json.to_string()
+  }
+}
+
+impl Redact for PrintSettings {}
+
+impl Redact for ConfigBundle {
+  fn redacted(&self) -> String {
+    let mut json = self.to_json().unwrap_or_default();
+    let templates = json.get_mut("templates").and_then(Value::as_array_mut);
+
+    for template in templates.into_iter().flatten() {
+      if let Some(file) = template.get_mut("file") {
+        let size = file.as_str().map(base64_decoded_len).unwrap_or_default();
+        *file = format!("<redacted, {} bytes>", size).into();
+      }
+    }
+
+    json.to_string()
+  }
+}
+
+impl Redact for TokenRequest {}
+
+impl Redact for PrintPayload {
+  const REDACTED_FIELDS: &'static [&'static str] = &["file", "files", "release_pin"];
+}
+
+impl Redact for MergePayload {
+  const REDACTED_FIELDS: &'static [&'static str] = &["files"];
+}
+
+impl Redact for ExtractPayload {
+  const REDACTED_FIELDS: &'static [&'static str] = &["file"];
+}
+
+impl Redact for RenderPayload {
+  const REDACTED_FIELDS: &'static [&'static str] = &["file"];
+}
+
+impl Redact for CoveragePayload {
+  const REDACTED_FIELDS: &'static [&'static str] = &["file"];
+}
+
+impl Redact for TemplatePayload {
+  const REDACTED_FIELDS: &'static [&'static str] = &["file"];
+}
+
+impl Redact for TemplatePrintPayload {}
+
+impl Redact for UploadPayload {}
+
+impl Redact for PausePayload {}
+
+impl Redact for UploadPrintPayload {}
+
+impl Redact for TablePayload {}
+
+impl Redact for LabelPayload {}
+
+impl PrintPayload {
+  /// 提交的各文件的内容
+  fn documents(&self) -> Vec<&[u8]> {
+    self
+      .file
+      .iter()
+      .chain(self.files.iter().flatten())
+      .map(|f| f.0.as_slice())
+      .collect()
+  }
+
+  /// 提交的文档的 SHA-256，用于回执，多个文件时按顺序连接后计算
+  pub fn document_sha256(&self) -> Option<String> {
+    let files = self.documents();
+    (!files.is_empty()).then(|| receipt::document_hash(files))
+  }
+
+  /// 取出要打印的文档，先按内容检查各文件的格式，多个文件时按顺序合并
+  pub fn take_document(&mut self, warnings: &mut Vec<String>) -> anyhow::Result<Vec<u8>> {
+    let strict = self.strict.unwrap_or_default();
+
+    match (self.file.take(), self.files.take()) {
+      (Some(file), None) => {
+        check_format(&file.0, self.format, strict, None, warnings)?;
+        Ok(file.0)
+      }
+      (None, Some(files)) if !files.is_empty() => {
+        for (i, file) in files.iter().enumerate() {
+          check_format(&file.0, self.format, strict, Some(i), warnings)?;
+        }
+        pdf::merge(&files.iter().map(|f| f.0.as_slice()).collect::<Vec<_>>())
+      }
+      (Some(_), Some(_)) => bail!("Only one of file and files can be set"),
+      (None, _) => bail!("No file to print"),
+    }
+  }
+}
+
+/// 检查文件的格式，`declared` 优先于按内容识别的格式，不是 PDF 时报错。
+/// `index` 为多个文件时的序号，用于错误信息
+fn check_format(
+  data: &[u8],
+  declared: Option<DocumentFormat>,
+  strict: bool,
+  index: Option<usize>,
+  warnings: &mut Vec<String>,
+) -> anyhow::Result<()> {
+  let name = match index {
+    Some(i) => format!("File {}", i),
+    None => "The file".to_string(),
+  };
+  let detected = format::detect_format(data);
+  let declared = declared.map(Format::from);
+
+  if let (Some(declared), Some(detected)) = (declared, detected) {
+    if declared != detected {
+      let msg = format!(
+        "{} is declared as {} but its content is {}",
+        name, declared, detected
+      );
+      if strict {
+        bail!(msg);
+      }
+      warn!("{}", msg);
+      warnings.push(msg);
+    }
+  }
+
+  match declared.or(detected) {
+    Some(Format::Pdf) | None => Ok(()),
+    Some(format) => bail!(
+      "Only PDF files can be printed, {} is {}",
+      name.to_lowercase(),
+      format
+    ),
+  }
+}
+
+/// 合并 PDF 的负载
+#[derive(Object)]
+#[oai(example)]
+pub struct MergePayload {
+  /// 按顺序合并的 PDF 文件内容
+  pub files: Vec<Base64<Vec<u8>>>,
+}
+
+/// 提取页面的负载
+#[derive(Object)]
+#[oai(example)]
+pub struct ExtractPayload {
+  /// PDF 文件内容
+  pub file: Base64<Vec<u8>>,
+  /// 要提取的页码范围，格式同打印设置的 `page_range`，按指定顺序输出
+  pub page_range: String,
+}
+
+/// PDF 工具处理的文件大小上限
+const MAX_PDF_SIZE: usize = 64 * 1024 * 1024;
+
+/// 一次最多渲染的页数
+const MAX_RENDER_PAGES: usize = 50;
+/// 一次渲染的像素总数上限
+const MAX_RENDER_PIXELS: u64 = 50_000_000;
+/// 默认的渲染宽度（像素），适合缩略图
+const DEFAULT_RENDER_WIDTH: u32 = 200;
+/// 一次最多估算墨迹覆盖率的页数
+pub const MAX_COVERAGE_PAGES: usize = 200;
+/// 估算墨迹覆盖率默认的分辨率（DPI）
+pub const DEFAULT_COVERAGE_DPI: u32 = 36;
+
+/// 表格最多的行数
+const MAX_TABLE_ROWS: usize = 10_000;
+/// 表格默认的纸张大小（微米），A4
+const DEFAULT_TABLE_PAGE: (u32, u32) = (210_000, 297_000);
+/// 表格默认的页边距（微米）
+const DEFAULT_TABLE_MARGIN: u32 = 10_000;
+/// 表格默认的字号（磅）
+const DEFAULT_TABLE_FONT_SIZE: f32 = 10.0;
+/// 标签文本默认的字号（磅）
+const DEFAULT_LABEL_FONT_SIZE: f32 = 10.0;
+/// 保留策略预览中每类最多列出的应删除数据
+const RETENTION_PREVIEW_LIMIT: usize = 1000;
+
+/// 重启后台处理程序服务的超时时间
+const SPOOLER_RESTART_TIMEOUT: Duration = Duration::from_secs(60);
+/// `GET /jobs/{id}` 最长的等待时间
+const MAX_JOB_WAIT: Duration = Duration::from_secs(60);
+
+/// 图片格式
+#[derive(Debug, Clone, Copy, Enum)]
+#[oai(rename_all = "lowercase")]
+pub enum ImageType {
+  Png,
+  Jpeg,
+  Webp,
+}
+
+impl From<ImageType> for ImageFormat {
+  fn from(value: ImageType) -> Self {
+    match value {
+      ImageType::Png => ImageFormat::Png,
+      ImageType::Jpeg => ImageFormat::Jpeg,
+      ImageType::Webp => ImageFormat::WebP,
+    }
+  }
+}
+
+/// 渲染页面的负载
+#[derive(Object)]
+#[oai(skip_serializing_if_is_none, example)]
+pub struct RenderPayload {
+  /// PDF 文件内容
+  pub file: Base64<Vec<u8>>,
+  /// 要渲染的页码，从 1 开始，默认全部页面
+  pub pages: Option<Vec<u32>>,
+  /// 图片宽度（像素），默认 200，高度按页面比例计算
+  #[oai(validator(minimum(value = "1"), maximum(value = "4096")))]
+  pub width: Option<u32>,
+  /// 图片格式，默认 png
+  pub format: Option<ImageType>,
+}
+
+/// 渲染的页面图片
+#[derive(Object)]
+pub struct PageImage {
+  /// 页码，从 1 开始
+  pub page: u32,
+  /// 图片内容
+  pub image: Base64<Vec<u8>>,
+  /// 图片宽度（像素）
+  pub width: u32,
+  /// 图片高度（像素）
+  pub height: u32,
+  /// 页面宽度（微米）
+  pub page_width: u32,
+  /// 页面高度（微米）
+  pub page_height: u32,
+}
+
+impl std::fmt::Debug for PageImage {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    // 只记录图片大小
+    f.debug_struct("PageImage")
+      .field("page", &self.page)
+      .field("image", &format_args!("<{} bytes>", self.image.0.len()))
+      .field("width", &self.width)
+      .field("height", &self.height)
+      .finish_non_exhaustive()
+  }
+}
+
+/// 估算墨迹覆盖率的负载
+#[derive(Object)]
+#[oai(skip_serializing_if_is_none, example)]
+pub struct CoveragePayload {
+  /// PDF 文件内容
+  pub file: Base64<Vec<u8>>,
+  /// 要估算的页码，从 1 开始，默认全部页面，最多 200 页
+  pub pages: Option<Vec<u32>>,
+  /// 渲染的分辨率（DPI），默认 36，最高 72
+  #[oai(validator(minimum(value = "1"), maximum(value = "72")))]
+  pub dpi: Option<u32>,
+}
+
+/// 墨迹覆盖率的估算，均为百分比。以低分辨率渲染后按屏幕颜色计算，只用于粗略估计耗材，
+/// 不考虑驱动的色彩管理、省墨模式及实际使用的墨粉，与打印机的计数不同
+#[derive(Debug, Object)]
+#[oai(skip_serializing_if_is_none, example)]
+pub struct CoverageEstimate {
+  /// 各页的覆盖率
+  pub pages: Vec<PageCoverage>,
+  /// 各页覆盖率的平均值
+  pub coverage: f32,
+  /// 各通道覆盖率的平均值，有彩色页面时才有
+  pub channels: Option<InkChannels>,
+  /// 渲染的分辨率（DPI）
+  pub dpi: u32,
+}
+
+/// 一页的墨迹覆盖率，百分比
+#[derive(Debug, Object)]
+#[oai(skip_serializing_if_is_none)]
+pub struct PageCoverage {
+  /// 页码，从 1 开始
+  pub page: u32,
+  /// 按灰度计算的覆盖率，全黑为 100
+  pub coverage: f32,
+  /// 是否有彩色内容
+  pub color: bool,
+  /// 各通道的覆盖率，彩色页面才有
+  pub channels: Option<InkChannels>,
+}
+
+/// 各颜色通道的覆盖率，百分比，由 RGB 简单换算为 CMYK
+#[derive(Debug, Clone, Object)]
+pub struct InkChannels {
+  pub cyan: f32,
+  pub magenta: f32,
+  pub yellow: f32,
+  pub black: f32,
+}
+
+impl From<[f32; 4]> for InkChannels {
+  fn from([cyan, magenta, yellow, black]: [f32; 4]) -> Self {
+    Self {
+      cyan: percent(cyan),
+      magenta: percent(magenta),
+      yellow: percent(yellow),
+      black: percent(black),
+    }
+  }
+}
+
+/// 0 到 1 的比例转换为百分比，保留两位小数
+fn percent(ratio: f32) -> f32 {
+  (ratio * 10000.0).round() / 100.0
+}
+
+impl CoverageEstimate {
+  pub fn new(pages: Vec<pdf::PageCoverage>, dpi: u32) -> Self {
+    let count = pages.len().max(1) as f32;
+    let coverage = pages.iter().map(|p| p.coverage).sum::<f32>() / count;
+    // 黑白页面只计黑色通道
+    let channels = pages.iter().any(|p| p.color).then(|| {
+      let mut sum = [0.0; 4];
+      for page in &pages {
+        if page.color {
+          for (total, value) in sum.iter_mut().zip(page.cmyk) {
+            *total += value;
+          }
+        } else {
+          sum[3] += page.coverage;
+        }
+      }
+      sum.map(|v| v / count).into()
+    });
+
+    Self {
+      pages: pages
+        .into_iter()
+        .map(|page| PageCoverage {
+          page: page.page,
+          coverage: percent(page.coverage),
+          color: page.color,
+          channels: page.color.then(|| page.cmyk.into()),
+        })
+        .collect(),
+      coverage: percent(coverage),
+      channels,
+      dpi,
+    }
+  }
+}
+
+impl From<pdf::RenderedPage> for PageImage {
+  fn from(page: pdf::RenderedPage) -> Self {
+    Self {
+      page: page.page,
+      image: Base64(page.data),
+      width: page.width,
+      height: page.height,
+      page_width: page.page_width,
+      page_height: page.page_height,
+    }
+  }
+}
+
+/// 返回 PDF 文件的响应内容，请求头 `Accept` 为 `application/pdf` 时直接返回文件
+#[derive(ResponseContent)]
+pub enum PdfContent {
+  Json(Json<Response<PdfFile>>),
+  #[oai(content_type = "application/pdf")]
+  Pdf(Binary<Vec<u8>>),
+}
+
+#[derive(ApiResponse)]
+pub enum PdfResponse {
+  #[oai(status = 200)]
+  Ok(PdfContent),
+}
+
+/// 任务的诊断包
+#[derive(ApiResponse)]
+pub enum DiagnosticsResponse {
+  /// ZIP 文件，包括任务信息及回执、各阶段耗时、打印票据 XML、打印机能力及打印期间的日志
+  #[oai(status = 200, content_type = "application/zip")]
+  Ok(
+    Binary<Body>,
+    /// 下载的文件名
+    #[oai(header = "Content-Disposition")]
+    String,
+  ),
+}
+
+/// PDF 文件
+#[derive(Object)]
+pub struct PdfFile {
+  /// 文件内容
+  pub file: Base64<Vec<u8>>,
+  /// 页数
+  pub pages: u32,
+}
+
+impl std::fmt::Debug for PdfFile {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    // 只记录文件大小
+    f.debug_struct("PdfFile")
+      .field("file", &format_args!("<{} bytes>", self.file.0.len()))
+      .field("pages", &self.pages)
+      .finish()
+  }
+}
+
+/// Base64 字符串解码后的字节数
+fn base64_decoded_len(s: &str) -> usize {
+  let padding = s.bytes().rev().take_while(|&b| b == b'=').count();
+  (s.len() / 4 * 3).saturating_sub(padding)
+}
+
+/// 打印结果
+#[derive(Debug, Object)]
+#[oai(skip_serializing_if_is_none, example)]
+pub struct PrintResult {
+  /// 实际使用的打印机，打印到打印机组或备用打印机时为最终打印的打印机
+  pub printer: Option<String>,
+  /// 改用其他打印机前各打印机的错误
+  pub failed_printers: Option<Vec<String>>,
+  /// 实际打印的页码及顺序，从 1 开始
+  pub pages: Vec<u32>,
+  /// 顺时针旋转角度
+  pub rotate_degrees: Option<u16>,
+  /// 被旋转的页码
+  pub rotated_pages: Option<Vec<u32>>,
+  /// 小册子每份使用的纸张数
+  pub sheets: Option<u32>,
+  /// 打印的总页数，包括份数
+  pub total_pages: Option<u32>,
+  /// 实际使用的纸张总数，考虑份数、双面及拼版
+  pub total_sheets: Option<u32>,
+  /// 估算的费用，配置了该打印机的价格时才有
+  pub estimated_cost: Option<EstimatedCost>,
+  /// 警告信息
+  pub warnings: Option<Vec<String>>,
+  /// 各阶段耗时
+  pub timings: PrintTimings,
+  /// 提交打印的尝试次数
+  pub attempts: u32,
+  /// 重试前各次尝试的错误
+  pub retried_errors: Option<Vec<String>>,
+  /// 未立即打印的任务状态
+  pub state: Option<JobState>,
+  /// 任务 ID，打印后可用于获取回执，未立即打印时还可用于取消任务
+  pub job_id: Option<String>,
+  /// 定时任务计划打印的时间
+  pub print_at: Option<DateTime<FixedOffset>>,
+  /// 输出目标为 `pdf` 时处理后的文档
+  pub document: Option<Base64<Vec<u8>>>,
+  /// 驱动校验后的打印票据，请求指定 `debug_ticket` 且提交到打印机时才有
+  pub ticket: Option<TicketDebug>,
+  /// 提交到打印队列时已超过请求的截止时间，任务仍会打印
+  pub deadline_exceeded: Option<bool>,
+  /// 拆分为多个打印作业时各部分的结果，按打印顺序排列
+  pub chunks: Option<Vec<ChunkResult>>,
+  /// 墨迹覆盖率的估算，输出目标为 `pdf` 且请求指定 `estimate_coverage` 时才有
+  pub coverage: Option<CoverageEstimate>,
+  /// 纸张高度随内容时实际使用的纸张高度，微米
+  pub page_height: Option<u32>,
+}
+
+/// 拆分打印时一部分的结果
+#[derive(Debug, Object)]
+pub struct ChunkResult {
+  /// 该部分打印的页码，从 1 开始
+  pub pages: Vec<u32>,
+  /// 是否已提交到打印队列，任务取消后剩余的部分不再提交
+  pub spooled: bool,
+  /// 提交打印的尝试次数
+  pub attempts: u32,
+  /// 提交打印的耗时（毫秒）
+  pub print: u64,
+}
+
+/// 驱动校验后的打印票据
+#[derive(Debug, Object)]
+#[oai(skip_serializing_if_is_none)]
+pub struct TicketDebug {
+  /// 票据 XML，不超过 16 KiB 时直接返回
+  pub xml: Option<String>,
+  /// gzip 压缩的票据 XML，超过 16 KiB 时返回
+  pub xml_gzip: Option<Base64<Vec<u8>>>,
+  /// 票据 XML 的字节数
+  pub size: u32,
+  /// 压缩后仍然过大，未返回 XML
+  pub truncated: bool,
+  /// 票据中各功能选择的选项及参数值，如 `PageOrientation: Landscape`、`JobCopiesAllDocuments: 2`
+  pub features: HashMap<String, String>,
+}
+
+/// 打印各阶段耗时，单位为毫秒
+#[derive(Debug, Default, Object)]
+pub struct PrintTimings {
+  /// 使用 Pdfium 处理文档
+  pub pdf: u64,
+  /// 枚举打印机
+  pub printers: u64,
+  /// 获取打印机能力
+  pub capabilities: u64,
+  /// 执行打印前命令，未配置时为 0
+  pub hooks: u64,
+  /// 合并打印设置，包括全部设置项
+  pub merge: u64,
+  /// 生成打印票据
+  pub build: u64,
+  /// 写入临时文件
+  pub write: u64,
+  /// 提交打印
+  pub print: u64,
+  /// 总耗时
+  pub total: u64,
+}
+
+/// 任务状态
+#[derive(Debug, Enum)]
+#[oai(rename_all = "snake_case")]
+pub enum JobState {
+  /// 等待计划时间到达
+  Scheduled,
+  /// 暂缓打印，等待 `POST /jobs/{id}/release`
+  Held,
+  /// 服务在提交到系统打印队列期间停止，可能已经打印，确认后 `POST /jobs/{id}/release` 重新打印或取消
+  Unknown,
+  /// 打印机已暂停，恢复后按提交顺序打印
+  QueuedPaused,
+  /// 正在按顺序提交拆分的各部分
+  Printing,
+  /// 超过有效期仍未打印，不再打印
+  Expired,
+  /// 已取消
+  Cancelled,
+  /// 已提交到系统打印队列
+  Printed,
+  /// 打印失败
+  Failed,
+}
+
+impl JobState {
+  /// 任务是否已结束，状态不会再变化
+  pub fn is_finished(&self) -> bool {
+    matches!(
+      self,
+      Self::Expired | Self::Cancelled | Self::Printed | Self::Failed
+    )
+  }
+}
+
+/// 等待中任务的状态
+pub fn waiting_state(job: &ScheduledJob) -> JobState {
+  if job.interrupted {
+    JobState::Unknown
+  } else if job.held {
+    JobState::Held
+  } else if status::is_printer_paused(&job.printer)
+    && job.print_at <= Utc::now() + SCHEDULE_TOLERANCE
+  {
+    JobState::QueuedPaused
+  } else {
+    JobState::Scheduled
+  }
+}
+
+/// 打印机状态
+#[derive(Debug, Clone, Object)]
+#[oai(skip_serializing_if_is_none, example)]
+pub struct PrinterState {
+  /// 服务是否暂停向该打印机提交任务
+  pub paused: bool,
+  /// 系统打印队列是否暂停，无法读取时为空
+  pub spooler_paused: Option<bool>,
+  /// 导致无法打印的问题，无法读取时为空
+  pub problems: Option<Vec<String>>,
+  /// 在服务内等待的任务数，包括定时任务
+  pub queued_jobs: u32,
+  /// 在服务内等待的任务的文档总字节数
+  pub queued_bytes: u64,
+  /// 最早的等待中任务已等待的秒数，没有任务时为空
+  pub oldest_queued_seconds: Option<u64>,
+  /// 服务内排队的任务数上限，达到时拒绝新的任务，未限制时为空
+  pub max_queued_jobs: Option<u32>,
+  /// 服务内排队的文档总字节数上限，未限制时为空
+  pub max_queued_bytes: Option<u64>,
+}
+
+/// 签名的任务回执，用 `GET /public-key` 返回的公钥验证
+#[derive(Debug, Object)]
+pub struct JobReceipt {
+  /// 回执内容，JSON 字符串，包括任务 ID、文档的 SHA-256、打印机、页数、时间及结果。验证签名时使用原文
+  pub receipt: String,
+  /// `receipt` 的 Ed25519 签名，Base64
+  pub signature: String,
+  /// 打印后命令的执行结果，未配置、任务设置了 `skip_hooks` 或结果已不在记录中时为空。不在签名范围内
+  pub post_print_hook: Option<PostPrintHook>,
+}
+
+impl From<SignedReceipt> for JobReceipt {
+  fn from(signed: SignedReceipt) -> Self {
+    Self {
+      receipt: signed.receipt,
+      signature: signed.signature,
+      post_print_hook: None,
+    }
+  }
+}
+
+/// 打印后命令的状态
+#[derive(Debug, Enum)]
+#[oai(rename_all = "snake_case")]
+pub enum PostPrintHookState {
+  Running,
+  Succeeded,
+  /// 命令以非零状态退出或无法启动
+  Failed,
+  /// 超时未结束，进程已被结束
+  TimedOut,
+}
+
+impl From<HookState> for PostPrintHookState {
+  fn from(value: HookState) -> Self {
+    match value {
+      HookState::Running => Self::Running,
+      HookState::Succeeded => Self::Succeeded,
+      HookState::Failed => Self::Failed,
+      HookState::TimedOut => Self::TimedOut,
+    }
+  }
+}
+
+/// 任务结束后在后台执行的打印后命令的结果，不影响任务的结果
+#[derive(Debug, Object)]
+#[oai(skip_serializing_if_is_none)]
+pub struct PostPrintHook {
+  pub state: PostPrintHookState,
+  pub started_at: DateTime<Utc>,
+  pub finished_at: Option<DateTime<Utc>>,
+  /// 各命令的输出，截断。失败时为命令的错误输出
+  pub output: Vec<String>,
+  pub error: Option<String>,
+}
+
+impl From<HookRun> for PostPrintHook {
+  fn from(run: HookRun) -> Self {
+    Self {
+      state: run.state.into(),
+      started_at: run.started_at,
+      finished_at: run.finished_at,
+      output: run.output,
+      error: run.error,
+    }
+  }
+}
+
+/// 一组任务的合计
+#[derive(Debug, Object)]
+pub struct ReportTotals {
+  /// 结束的任务数，包括失败、过期及取消的任务
+  pub jobs: u32,
+  pub printed: u32,
+  pub failed: u32,
+  pub expired: u32,
+  pub cancelled: u32,
+  /// 打印的总页数，包括份数
+  pub pages: u64,
+  /// 使用的纸张数
+  pub sheets: u64,
+  /// 失败最多的错误类型，按次数排序。没有错误类型的失败为 `other`
+  pub top_errors: Vec<ReportErrorCount>,
+}
+
+impl From<Totals> for ReportTotals {
+  fn from(totals: Totals) -> Self {
+    Self {
+      jobs: totals.jobs,
+      printed: totals.printed,
+      failed: totals.failed,
+      expired: totals.expired,
+      cancelled: totals.cancelled,
+      pages: totals.pages,
+      sheets: totals.sheets,
+      top_errors: totals
+        .top_errors
+        .into_iter()
+        .map(|e| ReportErrorCount {
+          error: e.error,
+          count: e.count,
+        })
+        .collect(),
+    }
+  }
+}
+
+/// 错误类型及次数
+#[derive(Debug, Object)]
+pub struct ReportErrorCount {
+  /// 错误类型，与响应中的 `error` 相同
+  pub error: String,
+  pub count: u32,
+}
+
+/// 打印机的合计
+#[derive(Debug, Object)]
+pub struct PrinterReport {
+  pub printer: String,
+  pub totals: ReportTotals,
+}
+
+/// 一天的打印统计，按回执中的结束时间划分日期
+#[derive(Debug, Object)]
+pub struct DailyReport {
+  pub date: NaiveDate,
+  /// 划分日期使用的时区，`local` 为服务所在计算机的时区
+  pub timezone: String,
+  /// 这一天的开始时间
+  pub start: DateTime<Utc>,
+  /// 下一天的开始时间，不包括在内
+  pub end: DateTime<Utc>,
+  /// 全部打印机的合计
+  pub total: ReportTotals,
+  /// 各打印机的合计，按打印机名称排序，只包括有任务的打印机
+  pub printers: Vec<PrinterReport>,
+}
+
+impl From<DailySummary> for DailyReport {
+  fn from(summary: DailySummary) -> Self {
+    Self {
+      date: summary.date,
+      timezone: summary.timezone,
+      start: summary.start,
+      end: summary.end,
+      total: summary.total.into(),
+      printers: summary
+        .printers
+        .into_iter()
+        .map(|p| PrinterReport {
+          printer: p.printer,
+          totals: p.totals.into(),
+        })
+        .collect(),
+    }
+  }
+}
+
+/// 打印机能力与上次快照相比的变化，驱动更新后纸张名称等可能改变
+#[derive(Debug, Object)]
+#[oai(skip_serializing_if_is_none)]
+pub struct CapabilityChangeSet {
+  pub printer: String,
+  /// 上次快照的时间
+  pub previous_at: DateTime<Utc>,
+  /// 发现变化的时间
+  pub detected_at: DateTime<Utc>,
+  /// 上次快照的打印机能力的 SHA-256
+  pub previous_sha256: String,
+  /// 当前打印机能力的 SHA-256
+  pub sha256: String,
+  /// 新增的纸张，没有名称的纸张为 `宽x高`（微米）
+  pub added_page_sizes: Vec<String>,
+  /// 移除的纸张
+  pub removed_page_sizes: Vec<String>,
+  pub added_orientations: Vec<String>,
+  pub removed_orientations: Vec<String>,
+  /// 最大份数有变化时为之前的值，之前没有限制时为空
+  pub previous_max_copies: Option<u64>,
+  /// 最大份数有变化时为当前的值
+  pub max_copies: Option<u64>,
+  /// 引用了已不存在的选项的默认打印设置等
+  pub affected_settings: Vec<String>,
+}
+
+impl From<CapabilityChanges> for CapabilityChangeSet {
+  fn from(changes: CapabilityChanges) -> Self {
+    let (previous_max_copies, max_copies) = changes.max_copies.unwrap_or_default();
+    Self {
+      printer: changes.printer,
+      previous_at: changes.previous_at,
+      detected_at: changes.detected_at,
+      previous_sha256: changes.previous_sha256,
+      sha256: changes.sha256,
+      added_page_sizes: changes.added_page_sizes,
+      removed_page_sizes: changes.removed_page_sizes,
+      added_orientations: changes.added_orientations,
+      removed_orientations: changes.removed_orientations,
+      previous_max_copies,
+      max_copies,
+      affected_settings: changes.affected_settings,
+    }
+  }
+}
+
+/// 被拒绝而隔离的打印请求
+#[derive(Debug, Object)]
+#[oai(skip_serializing_if_is_none)]
+pub struct QuarantineRecord {
+  /// 隔离 ID
+  pub id: String,
+  pub created_at: DateTime<Utc>,
+  /// 错误类型，同响应的 `error`
+  pub error: Option<String>,
+  /// 拒绝的原因，即返回的错误信息
+  pub reason: String,
+  /// 隐藏文件内容后的请求体
+  pub request: Value,
+  /// 请求中的各文件
+  pub files: Vec<QuarantinedDocument>,
+  /// 文件超过服务配置的大小上限，没有保存内容
+  pub omitted: bool,
+}
+
+/// 隔离的文件
+#[derive(Debug, Object)]
+#[oai(skip_serializing_if_is_none)]
+pub struct QuarantinedDocument {
+  /// 大小（字节）
+  pub size: u64,
+  pub sha256: String,
+  /// 文件内容，超过大小上限时为空
+  pub content: Option<Base64<Vec<u8>>>,
+}
+
+impl From<QuarantineEntry> for QuarantineRecord {
+  fn from(entry: QuarantineEntry) -> Self {
+    let info = entry.info;
+    let mut contents = entry.contents.into_iter();
+    Self {
+      id: info.id,
+      created_at: info.created_at,
+      error: info.error,
+      reason: info.reason,
+      request: info.request,
+      files: info
+        .files
+        .into_iter()
+        .map(|file| QuarantinedDocument {
+          size: file.size as u64,
+          sha256: file.sha256,
+          content: contents.next().map(Base64),
+        })
+        .collect(),
+      omitted: info.omitted,
+    }
+  }
+}
+
+/// 验证回执签名的公钥
+#[derive(Debug, Object)]
+pub struct PublicKey {
+  /// 签名算法，为 `Ed25519`
+  pub algorithm: String,
+  /// 公钥，Base64 编码的 32 字节
+  pub key: String,
+}
+
+/// 服务实例的信息，用于在多台计算机中识别打印的来源
+#[derive(Debug, Object)]
+#[oai(skip_serializing_if_is_none, example)]
+pub struct InstanceInfo {
+  /// 实例 ID，首次运行时生成并保存在数据目录中，与回执及 OTLP 资源属性中的相同
+  pub instance_id: String,
+  /// 计算机名称
+  pub hostname: String,
+  /// 操作系统版本，无法读取时为空
+  pub os_version: Option<String>,
+  /// 程序版本
+  pub version: String,
+  /// 编译的时间
+  pub built_at: Option<DateTime<Utc>>,
+  /// 编译时的 Git 提交
+  pub commit: Option<String>,
+  /// 启用的编译功能
+  pub features: Vec<String>,
+  /// 数据目录，保存定时任务、模板、回执等
+  pub data_dir: Option<String>,
+  /// 配置目录，保存默认打印设置
+  pub config_dir: Option<String>,
+  /// 系统打印队列的假脱机目录
+  pub spool_dir: Option<String>,
+  /// 暂停接受打印任务的状态，未暂停时为空
+  pub pause: Option<ServicePause>,
+}
+
+/// 暂停接受打印任务的状态
+#[derive(Debug, Object)]
+#[oai(skip_serializing_if_is_none, example)]
+pub struct ServicePause {
+  /// 是否暂停，暂停时打印请求返回 `paused` 错误，定时及排队的任务推迟打印，只读接口仍然可用
+  pub paused: bool,
+  pub paused_at: Option<DateTime<Utc>>,
+  /// 管理员的说明，包括在打印请求的错误信息中
+  pub message: Option<String>,
+  /// 自动恢复的时间，为空时需手动恢复
+  pub resume_at: Option<DateTime<Utc>>,
+}
+
+impl From<Option<status::Pause>> for ServicePause {
+  fn from(pause: Option<status::Pause>) -> Self {
+    Self {
+      paused: pause.is_some(),
+      paused_at: pause.as_ref().map(|p| p.paused_at),
+      resume_at: pause.as_ref().and_then(|p| p.resume_at),
+      message: pause.and_then(|p| p.message),
+    }
+  }
+}
+
+/// 按保留策略下次清理时将删除的数据
+#[derive(Debug, Object)]
+#[oai(example)]
+pub struct RetentionPreview {
+  pub classes: Vec<RetentionClass>,
+}
+
+/// 一类数据的保留策略及将删除的数据
+#[derive(Debug, Object)]
+#[oai(skip_serializing_if_is_none)]
+pub struct RetentionClass {
+  /// 类别，`receipts`、`quarantine` 或 `diagnostics`
+  pub class: String,
+  /// 保留的天数，为空时不限制
+  pub max_age_days: Option<u64>,
+  /// 保留的数量，为空时不限制
+  pub max_count: Option<u64>,
+  /// 保留的总字节数，为空时不限制
+  pub max_bytes: Option<u64>,
+  /// 保存的数量
+  pub count: u64,
+  /// 保存的总字节数
+  pub bytes: u64,
+  /// 将删除的数量
+  pub expired_count: u64,
+  /// 将删除的总字节数
+  pub expired_bytes: u64,
+  /// 将删除的数据，从最早的开始，最多列出 1000 项
+  pub expired: Vec<ExpiredArtifact>,
+  /// 列出数据失败时的错误
+  pub error: Option<String>,
+}
+
+/// 将删除的一项数据
+#[derive(Debug, Object)]
+pub struct ExpiredArtifact {
+  /// 任务 ID 或隔离 ID
+  pub id: String,
+  pub modified_at: DateTime<Utc>,
+  /// 字节数
+  pub size: u64,
+}
+
+impl From<retention::Preview> for RetentionClass {
+  fn from(preview: retention::Preview) -> Self {
+    Self {
+      class: preview.class.to_string(),
+      max_age_days: preview.policy.max_age_days,
+      max_count: preview.policy.max_count.map(|n| n as u64),
+      max_bytes: preview.policy.max_bytes,
+      count: preview.count as u64,
+      bytes: preview.bytes,
+      expired_count: preview.expired.len() as u64,
+      expired_bytes: preview.expired.iter().map(|a| a.size).sum(),
+      expired: preview
+        .expired
+        .into_iter()
+        .take(RETENTION_PREVIEW_LIMIT)
+        .map(|a| ExpiredArtifact {
+          id: a.id,
+          modified_at: a.modified.into(),
+          size: a.size,
+        })
+        .collect(),
+      error: preview.error,
+    }
+  }
+}
+
+/// 暂停接受打印任务的负载
+#[derive(Object)]
+#[oai(example)]
+pub struct PausePayload {
+  /// 返回给客户端的说明，如维护的原因
+  pub message: Option<String>,
+  /// 自动恢复的时间，为空时需调用 `POST /admin/resume` 恢复
+  pub resume_at: Option<DateTime<FixedOffset>>,
+}
+
+/// 打印任务
+#[derive(Debug, Object)]
+#[oai(skip_serializing_if_is_none)]
+pub struct Job {
+  pub id: String,
+  pub state: JobState,
+  /// 打印机名称
+  pub printer: String,
+  /// 计划打印的时间，正在打印的任务为开始打印的时间
+  pub print_at: DateTime<FixedOffset>,
+  /// 超过计划时间多少秒仍未打印时过期
+  pub ttl_seconds: Option<u64>,
+  /// 排队时的优先级，正在打印的任务为空
+  pub priority: Option<JobPriority>,
+  /// 在系统打印队列中显示的文档名称
+  pub job_name: Option<String>,
+  /// PDF 文档信息中的元数据，多个文件合并打印时没有
+  pub document: Option<DocumentInfo>,
+  /// 在打印机队列中的位置，从 1 开始，1 表示下一个打印。只有已到计划时间、等待打印的任务才有
+  pub queue_position: Option<u32>,
+  /// 预计多少秒后开始打印，按该打印机最近任务的平均打印耗时估算。
+  /// 打印记录不足或服务、打印机暂停时为空
+  pub estimated_start_seconds: Option<u64>,
+  /// 取消或失败的原因
+  pub reason: Option<String>,
+  /// 已结束任务的打印后命令的执行结果
+  pub post_print_hook: Option<PostPrintHook>,
+  /// 打印机已打印的页数，从系统打印队列读取，包括份数。未提交到系统打印队列或驱动未报告页数时为空
+  pub pages_printed: Option<u32>,
+}
+
+/// PDF 文档信息中的元数据，文档中没有或无法解码的项为空
+#[derive(Debug, Object)]
+#[oai(skip_serializing_if_is_none)]
+pub struct DocumentInfo {
+  pub title: Option<String>,
+  pub author: Option<String>,
+  /// 生成文档的程序
+  pub producer: Option<String>,
+  pub creation_date: Option<DateTime<FixedOffset>>,
+}
+
+impl From<pdf::DocumentMetadata> for DocumentInfo {
+  fn from(metadata: pdf::DocumentMetadata) -> Self {
+    Self {
+      title: metadata.title,
+      author: metadata.author,
+      producer: metadata.producer,
+      creation_date: metadata.creation_date,
+    }
+  }
+}
+
+impl Job {
+  pub fn new(job: ScheduledJob, state: JobState) -> Self {
+    let job_name = job.payload["settings"]["job_name"]
+      .as_str()
+      .map(str::to_string);
+    Self {
+      job_name,
+      document: job.document.map(Into::into),
+      queue_position: None,
+      estimated_start_seconds: None,
+      id: job.id,
+      state,
+      printer: job.printer,
+      print_at: job.print_at,
+      ttl_seconds: job.ttl_seconds,
+      priority: Some(job.priority.into()),
+      reason: None,
+      post_print_hook: None,
+      pages_printed: None,
+    }
+  }
+
+  /// 正在打印的任务
+  fn running(job: RunningJob) -> Self {
+    Self {
+      pages_printed: pages_printed(&job.id),
+      id: job.id,
+      state: JobState::Printing,
+      printer: job.printer,
+      print_at: job.started_at.fixed_offset(),
+      ttl_seconds: None,
+      priority: None,
+      job_name: Some(job.job_name),
+      document: Some(job.document.into()),
+      queue_position: None,
+      estimated_start_seconds: None,
+      reason: None,
+      post_print_hook: None,
+    }
+  }
+
+  /// 未打印而结束的任务
+  fn finished(job: ScheduledJob, ending: Ending) -> Self {
+    let post_print_hook = hook::run_of(&job.id).map(Into::into);
+    let job = match ending {
+      Ending::Expired => Self::new(job, JobState::Expired),
+      Ending::Cancelled(reason) => Self {
+        reason: Some(reason),
+        ..Self::new(job, JobState::Cancelled)
+      },
+    };
+    Self {
+      post_print_hook,
+      ..job
+    }
+  }
+
+  /// 按回执生成的已结束任务，包括立即打印的任务
+  fn from_receipt(receipt: Receipt) -> Self {
+    let state = match receipt.outcome {
+      Outcome::Printed => JobState::Printed,
+      Outcome::Failed => JobState::Failed,
+      Outcome::Expired => JobState::Expired,
+      Outcome::Cancelled => JobState::Cancelled,
+    };
+    Self {
+      post_print_hook: hook::run_of(&receipt.job_id).map(Into::into),
+      state,
+      printer: receipt.printer,
+      print_at: receipt
+        .submitted_at
+        .unwrap_or(receipt.finished_at)
+        .fixed_offset(),
+      ttl_seconds: None,
+      priority: None,
+      job_name: None,
+      document: None,
+      queue_position: None,
+      estimated_start_seconds: None,
+      reason: receipt.detail,
+      pages_printed: pages_printed(&receipt.job_id),
+      id: receipt.job_id,
+    }
+  }
+}
+
+/// 任务已打印的页数，见 `Job::pages_printed`
+fn pages_printed(id: &str) -> Option<u32> {
+  schedule::progress(id).and_then(|progress| progress.pages_printed())
+}
+
+/// 估算的打印费用
+#[derive(Debug, Object)]
+pub struct EstimatedCost {
+  /// 金额
+  pub amount: f64,
+  /// 货币代码
+  pub currency: String,
+  /// 是否按彩色计价
+  pub color: bool,
+  /// 无法确定是否彩色打印，按默认值估算
+  pub approximate: bool,
+}
+
+/// 打印机的价格
+#[derive(Debug, Object)]
+#[oai(example)]
+pub struct Pricing {
+  /// 货币代码
+  pub currency: String,
+  /// 每个黑白页面的价格
+  pub mono_page: f64,
+  /// 每个彩色页面的价格
+  pub color_page: f64,
+  /// 每张纸的价格
+  pub sheet: f64,
+  /// 无法确定是否彩色打印时是否按彩色计价
+  pub default_color: bool,
+}
+
+/// 打印机的详细信息
+#[derive(Debug, Clone, Object)]
+#[oai(skip_serializing_if_is_none)]
+pub struct PrinterEntry {
+  /// 稳定的打印机 ID，由名称、端口及驱动计算
+  pub id: String,
+  pub name: String,
+  /// 端口名称
+  pub port: String,
+  /// 驱动名称
+  pub driver: String,
+  /// 位置
+  pub location: Option<String>,
+  /// 备注
+  pub comment: Option<String>,
+  /// 是否共享给其他计算机
+  pub shared: bool,
+  /// 共享名称
+  pub share_name: Option<String>,
+  /// 是否为网络共享打印机
+  pub remote: bool,
+  /// 可以发送到该打印机的格式，由打印处理器接受的数据类型得到。`pdf` 由服务渲染后打印，总是支持
+  pub supported_formats: Vec<PrinterFormat>,
+  /// 端口指向同一设备的其他打印机名称，如从其他打印服务器映射的同一打印队列，打印时也可以使用
+  pub aliases: Option<Vec<String>>,
+}
+
+/// 打印机可以接受的格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+#[oai(rename_all = "lowercase")]
+pub enum PrinterFormat {
+  /// 由 Pdfium 渲染后通过 GDI 打印
+  Pdf,
+  /// XPS 文档，打印处理器接受 `XPS2GDI` 或 `XPS_PASS`
+  Xps,
+  /// 直接发送到打印机的数据，如 ZPL、ESC/POS，打印处理器接受 `RAW`
+  Raw,
+  /// 纯文本，打印处理器接受 `TEXT`
+  Text,
+}
+
+impl PrinterFormat {
+  /// 按打印处理器接受的数据类型得到可以接受的格式
+  pub fn from_datatypes(datatypes: &[String]) -> Vec<Self> {
+    let accepts = |names: &[&str]| {
+      datatypes
+        .iter()
+        .any(|d| names.iter().any(|n| d.eq_ignore_ascii_case(n)))
+    };
+
+    let mut formats = vec![Self::Pdf];
+    if accepts(&["XPS2GDI", "XPS_PASS"]) {
+      formats.push(Self::Xps);
+    }
+    if accepts(&["RAW"]) {
+      formats.push(Self::Raw);
+    }
+    if accepts(&["TEXT"]) {
+      formats.push(Self::Text);
+    }
+    formats
+  }
+}
+
+/// 打印机列表
+#[derive(Debug, Union)]
+#[oai(one_of)]
+pub enum PrinterList {
+  /// 打印机名称
+  Names(Vec<String>),
+  /// 打印机的详细信息
+  Detailed(Vec<PrinterEntry>),
+  /// 一页打印机名称，指定 `offset` 或 `limit` 时返回
+  NamesPage(PrinterPage<String>),
+  /// 一页打印机的详细信息，指定 `offset` 或 `limit` 时返回
+  DetailedPage(PrinterPage<PrinterEntry>),
+}
+
+/// 分页的打印机列表
+#[derive(Debug, Object)]
+pub struct PrinterPage<T>
+where
+  T: ParseFromJSON + ToJSON,
+{
+  /// 可用的打印机总数
+  total: u32,
+  /// 本页第一台打印机在全部打印机中的位置，从 0 开始
+  offset: u32,
+  printers: Vec<T>,
+}
+
+/// 后台处理程序服务的状态
+#[derive(Debug, Object)]
+#[oai(skip_serializing_if_is_none)]
+pub struct SpoolerHealth {
+  /// 服务是否正在运行
+  pub running: bool,
+  /// 服务状态，如 `running`、`stopped`、`start_pending`
+  pub state: String,
+  /// 服务进程的启动时间
+  pub started_at: Option<DateTime<Utc>>,
+  /// 服务已运行的秒数
+  pub uptime_seconds: Option<u64>,
+}
+
+impl From<spooler::ServiceStatus> for SpoolerHealth {
+  fn from(status: spooler::ServiceStatus) -> Self {
+    Self {
+      running: status.is_running(),
+      state: status.state_name().to_string(),
+      started_at: status.started_at,
+      uptime_seconds: status
+        .started_at
+        .map(|t| (Utc::now() - t).num_seconds().max(0) as u64),
+    }
+  }
+}
+
+/// 诊断信息
+#[derive(Debug, Object)]
+#[oai(skip_serializing_if_is_none)]
+pub struct Diagnostics {
+  /// 后台处理程序服务的状态，无法读取时为空
+  pub spooler: Option<SpoolerHealth>,
+  /// 读取后台处理程序服务状态的错误
+  pub spooler_error: Option<String>,
+  /// 系统中的打印机数
+  pub printers: u32,
+  /// 是否暂停接受打印任务
+  pub paused: bool,
+  /// 服务启动后打印成功的任务数
+  pub printed: u64,
+  /// 服务启动后打印失败的任务数
+  pub failed: u64,
+}
+
+/// 重启后台处理程序的结果
+#[derive(Debug, Object)]
+pub struct SpoolerRestart {
+  /// 重启后的服务状态
+  pub spooler: SpoolerHealth,
+  /// 重启后重新枚举的打印机
+  pub printers: Vec<String>,
+}
+
+/// 预热的结果，耗时单位为毫秒
+#[derive(Debug, Object)]
+#[oai(skip_serializing_if_is_none)]
+pub struct WarmupResult {
+  /// 加载 Pdfium 的耗时
+  pub pdfium: u64,
+  /// 加载 Pdfium 失败的原因
+  pub pdfium_error: Option<String>,
+  /// 枚举打印机的耗时
+  pub printers_elapsed: u64,
+  /// 各打印机的预热结果
+  pub printers: Vec<PrinterWarmup>,
+  /// 总耗时
+  pub total: u64,
+}
+
+/// 单台打印机的预热结果，耗时单位为毫秒
+#[derive(Debug, Object)]
+#[oai(skip_serializing_if_is_none)]
+pub struct PrinterWarmup {
+  pub printer: String,
+  /// 获取并缓存打印机能力的耗时
+  pub capabilities: Option<u64>,
+  /// 生成默认打印票据并由驱动校验的耗时，指定 `validate_ticket` 时才有
+  pub ticket: Option<u64>,
+  /// 失败的原因
+  pub error: Option<String>,
+}
+
+/// 清空打印机的结果
+#[derive(Debug, Object)]
+pub struct PurgeResult {
+  /// 取消的服务内排队任务数
+  pub internal_jobs: u32,
+  /// 清空的系统打印队列任务数
+  pub spooler_jobs: u32,
+}
+
+/// 连接网络共享打印机的负载
+#[derive(Debug, Object)]
+#[oai(example)]
+pub struct PrinterConnectionPayload {
+  /// 共享打印机的 UNC 路径，如 `\\server\FrontOfficePrinter`
+  pub path: String,
+}
+
+/// 上传模板的负载
+#[derive(Object)]
+#[oai(example)]
+pub struct TemplatePayload {
+  /// 模板名称
+  pub name: String,
+  /// 带表单的 PDF 文件内容，按表单字段名称填写变量
+  pub file: Base64<Vec<u8>>,
+}
+
+/// 模板
+#[derive(Debug, Object)]
+pub struct Template {
+  pub id: String,
+  /// 模板名称
+  pub name: String,
+  /// 可填写的字段
+  pub fields: Vec<TemplateField>,
+  /// 上传时间
+  pub created_at: DateTime<Utc>,
+}
+
+/// 模板字段
+#[derive(Debug, Object)]
+pub struct TemplateField {
+  /// 字段名称，即变量名称
+  pub name: String,
+  /// 是否必填
+  pub required: bool,
+  /// 是否为复选框，值为 `true`、`yes`、`on` 或 `1` 时勾选
+  pub checkbox: bool,
+}
+
+impl From<template::Template> for Template {
+  fn from(template: template::Template) -> Self {
+    Self {
+      id: template.id,
+      name: template.name,
+      fields: template
+        .fields
+        .into_iter()
+        .map(|field| TemplateField {
+          name: field.name,
+          required: field.required,
+          checkbox: field.checkbox,
+        })
+        .collect(),
+      created_at: template.created_at,
+    }
+  }
+}
+
+/// 按模板打印的负载
+#[derive(Object)]
+#[oai(skip_serializing_if_is_none, example)]
+pub struct TemplatePrintPayload {
+  /// 填写一份的变量，与 `items` 二选一
+  pub variables: Option<HashMap<String, String>>,
+  /// 批量填写的变量，每组变量生成一份文档，按顺序合并为一个任务打印
+  pub items: Option<Vec<HashMap<String, String>>>,
+  /// 打印设置
+  pub settings: Option<PrintSettings>,
+  /// 打印机未就绪时仍然提交到打印队列，默认直接返回错误
+  pub queue_if_not_ready: Option<bool>,
+  /// 计划打印的时间（RFC 3339），为空或不晚于当前时间时立即打印
+  pub print_at: Option<DateTime<FixedOffset>>,
+  /// 定时任务超过计划时间多少秒仍未打印时过期
+  pub ttl_seconds: Option<u64>,
+}
+
+/// 开始上传的负载
+#[derive(Object)]
+#[oai(example)]
+pub struct UploadPayload {
+  /// 文件的总大小（字节）
+  pub size: u64,
+  /// 完整文件的 SHA-256（十六进制），不为空时打印前校验合并后的文件
+  pub sha256: Option<String>,
+}
+
+/// 上传的进度，用于续传
+#[derive(Debug, Object)]
+pub struct UploadStatus {
+  pub id: String,
+  /// 文件的总大小（字节）
+  pub size: u64,
+  /// 分块的大小（字节），按此大小切分文件，最后一块为剩余的部分
+  pub chunk_size: u64,
+  /// 分块数，序号从 0 开始
+  pub chunk_count: u32,
+  /// 已收到的连续分块
+  pub received: Vec<ChunkRange>,
+  /// 尚未收到的分块序号
+  pub missing: Vec<u32>,
+  /// 过期的时间，之前没有收到新的分块时删除上传
+  pub expires_at: DateTime<Utc>,
+}
+
+/// 连续的分块，包括首尾
+#[derive(Debug, Object)]
+pub struct ChunkRange {
+  pub first: u32,
+  pub last: u32,
+}
+
+impl UploadStatus {
+  pub fn new(upload: Upload, idle: Duration) -> Self {
+    Self {
+      chunk_count: upload.chunk_count(),
+      received: upload
+        .ranges()
+        .into_iter()
+        .map(|(first, last)| ChunkRange { first, last })
+        .collect(),
+      missing: upload.missing(),
+      expires_at: upload.expires_at(idle),
+      id: upload.info.id,
+      size: upload.info.size,
+      chunk_size: upload.info.chunk_size,
+    }
+  }
+}
+
+/// 打印上传的文件的负载
+#[derive(Object)]
+#[oai(skip_serializing_if_is_none, example)]
+pub struct UploadPrintPayload {
+  /// 打印设置
+  pub settings: Option<PrintSettings>,
+  /// 打印机未就绪时仍然提交到打印队列，默认直接返回错误
+  pub queue_if_not_ready: Option<bool>,
+  /// 计划打印的时间（RFC 3339），为空或不晚于当前时间时立即打印
+  pub print_at: Option<DateTime<FixedOffset>>,
+  /// 定时任务超过计划时间多少秒仍未打印时过期
+  pub ttl_seconds: Option<u64>,
+}
+
+/// 文本对齐方式
+#[derive(Debug, Clone, Copy, Enum)]
+#[oai(rename_all = "snake_case")]
+pub enum TextAlign {
+  Left,
+  Center,
+  Right,
+}
+
+impl From<TextAlign> for pdf::TextAlign {
+  fn from(value: TextAlign) -> Self {
+    match value {
+      TextAlign::Left => pdf::TextAlign::Left,
+      TextAlign::Center => pdf::TextAlign::Center,
+      TextAlign::Right => pdf::TextAlign::Right,
+    }
+  }
+}
+
+/// 表格列
+#[derive(Debug, Object)]
+#[oai(skip_serializing_if_is_none)]
+pub struct TableColumn {
+  /// 表头
+  pub header: String,
+  /// 宽度，微米，为空时平分剩余宽度
+  pub width: Option<u32>,
+  /// 对齐方式，默认左对齐
+  pub align: Option<TextAlign>,
+}
+
+/// 打印表格的负载
+#[derive(Object)]
+#[oai(skip_serializing_if_is_none, example)]
+pub struct TablePayload {
+  /// CSV 内容，与 `rows` 二选一
+  pub csv: Option<String>,
+  /// CSV 的第一行是否为表头，是时不作为数据，未指定 `columns` 时用作表头
+  pub csv_header: Option<bool>,
+  /// 各行的单元格，与 `csv` 二选一
+  pub rows: Option<Vec<Vec<String>>>,
+  /// 列定义，为空时使用 CSV 的表头
+  pub columns: Option<Vec<TableColumn>>,
+  /// 页边距，微米，默认 10 毫米
+  pub margin: Option<u32>,
+  /// 字号，磅，默认 10
+  #[oai(validator(minimum(value = "4"), maximum(value = "72")))]
+  pub font_size: Option<f32>,
+  /// 是否隔行填充底色
+  pub zebra: Option<bool>,
+  /// 只生成并返回 PDF 文件，不打印，用于检查排版
+  pub dry_run: Option<bool>,
+  /// 打印设置，按其中的纸张大小及布局排版，未指定纸张大小时使用 A4
+  pub settings: Option<PrintSettings>,
+  /// 打印机未就绪时仍然提交到打印队列，默认直接返回错误
+  pub queue_if_not_ready: Option<bool>,
+  /// 计划打印的时间（RFC 3339），为空或不晚于当前时间时立即打印
+  pub print_at: Option<DateTime<FixedOffset>>,
+  /// 定时任务超过计划时间多少秒仍未打印时过期
+  pub ttl_seconds: Option<u64>,
+}
+
+#[derive(ResponseContent)]
+pub enum TableContent {
+  Json(Json<Response<Box<PrintResult>>>),
+  /// 试运行时生成的 PDF 文件
+  #[oai(content_type = "application/pdf")]
+  Pdf(Binary<Vec<u8>>),
+}
+
+#[derive(ApiResponse)]
+pub enum TableResponse {
+  #[oai(status = 200)]
+  Ok(TableContent),
+}
+
+/// 标签元素类型
+#[derive(Debug, Clone, Copy, Enum)]
+#[oai(rename_all = "lowercase")]
+pub enum LabelElementType {
+  /// 文本
+  Text,
+  /// 二维码
+  Qr,
+  /// Code 128 条码
+  Code128,
+  /// EAN-13 条码
+  Ean13,
+}
+
+/// 标签元素，位置和尺寸为毫米数或标签宽高的百分比，如 `5`、`5mm`、`10%`，从左上角起算
+#[derive(Debug, Object)]
+#[oai(skip_serializing_if_is_none)]
+pub struct LabelElement {
+  #[oai(rename = "type")]
+  pub kind: LabelElementType,
+  /// 文本或条码内容
+  pub data: String,
+  /// 左侧位置
+  pub x: String,
+  /// 顶部位置
+  pub y: String,
+  /// 条码宽度或二维码边长，条码和二维码必须指定
+  pub width: Option<String>,
+  /// 一维条码的高度，必须指定
+  pub height: Option<String>,
+  /// 文本字号，磅，默认 10
+  #[oai(validator(minimum(value = "1"), maximum(value = "200")))]
+  pub font_size: Option<f32>,
+}
+
+/// 打印标签的负载
+#[derive(Object)]
+#[oai(skip_serializing_if_is_none, example)]
+pub struct LabelPayload {
+  /// 标签宽度，毫米
+  #[oai(validator(minimum(value = "1"), maximum(value = "2000")))]
+  pub width: f32,
+  /// 标签高度，毫米
+  #[oai(validator(minimum(value = "1"), maximum(value = "2000")))]
+  pub height: f32,
+  /// 按顺序绘制的元素
+  pub elements: Vec<LabelElement>,
+  /// 打印设置，未指定纸张大小时使用标签大小
+  pub settings: Option<PrintSettings>,
+  /// 打印机未就绪时仍然提交到打印队列，默认直接返回错误
+  pub queue_if_not_ready: Option<bool>,
+  /// 计划打印的时间（RFC 3339），为空或不晚于当前时间时立即打印
+  pub print_at: Option<DateTime<FixedOffset>>,
+  /// 定时任务超过计划时间多少秒仍未打印时过期
+  pub ttl_seconds: Option<u64>,
+}
+
+#[derive(Tags)]
+pub enum ApiTag {
+  /// 打印 API
+  Printing,
+  /// 管理 API
+  Admin,
+  /// PDF 工具
+  Pdf,
+}
+
+pub type Result<T> = poem::Result<Json<Response<T>>>;
+
+pub struct Api {
+  options: ApiOptions,
+}
+
+impl Api {
+  /// 按选项记录请求体
+  fn log_request(&self, body: &impl Redact) {
+    if self.options.log_bodies {
+      info!("Request body: {}", body.redacted());
+    }
+  }
+
+  /// 按 ID 查找任务，依次查找正在打印、等待中、最近未打印而结束的任务及回执
+  fn find_job(&self, id: &str) -> Option<Job> {
+    if let Some(job) = schedule::get_running(id) {
+      return Some(Job::running(job));
+    }
+    if let Some(job) = schedule::get(id) {
+      let state = waiting_state(&job);
+      let position = queue_positions(HashSet::from([job.printer.as_str()]), &self.options)
+        .get(&job.id)
+        .copied();
+      return Some(Job {
+        queue_position: position,
+        estimated_start_seconds: position
+          .and_then(|position| queue_estimate(&job.printer, position, &self.options)),
+        ..Job::new(job, state)
+      });
+    }
+    if let Some((job, ending)) = schedule::finished()
+      .into_iter()
+      .find(|(job, _)| job.id == id)
+    {
+      return Some(Job::finished(job, ending));
+    }
+    let receipt = receipt::get(id)?.parse().ok()?;
+    Some(Job::from_receipt(receipt))
+  }
+
+  /// 是否提供了有效的 API 密钥
+  fn authorized(&self, api_key: Option<String>) -> bool {
+    api_key.is_some_and(|key| {
+      self
+        .options
+        .api_keys
+        .iter()
+        .any(|k| constant_time_eq(k, &key))
+    })
+  }
+
+  /// 启用打印令牌时，分块上传需要有效的打印令牌或 API 密钥。
+  /// 令牌只校验不标记为已使用，打印上传时与 `POST /print` 一样校验并使用
+  fn authorize_upload(
+    &self,
+    token: Option<String>,
+    api_key: Option<String>,
+  ) -> std::result::Result<(), CodedError> {
+    let Some(tokens) = &self.options.tokens else {
+      return Ok(());
+    };
+    if self.authorized(api_key) {
+      return Ok(());
+    }
+
+    match token {
+      Some(token) => tokens.check(&token).map(|_| ()).map_err(CodedError::from),
+      None => Err(CodedError::new(
+        StatusCode::UNAUTHORIZED,
+        ErrorCode::TokenRequired,
+        "A print token or an API key is required",
+      )),
+    }
+  }
+
+  /// 并行获取多台打印机的能力，同时查询的数量及每台的超时时间见选项。
+  /// 按 `printers` 的顺序返回打印机名称及结果，某台失败或超时不影响其他打印机
+  async fn fetch_capabilities(
+    &self,
+    printers: Vec<PrinterDevice>,
+    languages: &Languages,
+  ) -> poem::Result<Vec<(String, anyhow::Result<PrinterCapability>)>> {
+    let timeout = self.options.capability_timeout;
+
+    let fetching: Vec<_> = printers
+      .into_iter()
+      .map(|printer| {
+        // 限制同时查询的驱动数量，部分驱动查询较慢且不能并发太多
+        let permits = self.options.capability_permits.clone();
+        let languages = languages.clone();
+        let record = self.options.record_fixtures.clone();
+        let interchange = self.options.letter_a4_interchange;
+        let name = printer.name().to_string();
+        let handle = tokio::spawn(async move {
+          let permit = match permits {
+            Some(permits) => Some(permits.acquire_owned().await?),
+            None => None,
+          };
+          // 驱动查询完成后才释放名额，超时的查询仍在后台继续，完成后写入缓存
+          let fetched = tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            fetch_capability(&printer, &languages, record.as_deref(), interchange)
+          });
+          match tokio::time::timeout(timeout, fetched).await {
+            Ok(fetched) => fetched?,
+            Err(_) => Err(anyhow!("Timed out after {} seconds", timeout.as_secs_f32())),
+          }
+        });
+        (name, handle)
+      })
+      .collect();
+
+    let mut results = Vec::with_capacity(fetching.len());
+    for (name, handle) in fetching {
+      results.push((name, handle.await.map_err(InternalServerError)?));
+    }
+
+    Ok(results)
+  }
+
+  /// 暂停或恢复打印机，`spooler` 为 true 时先修改系统的打印队列，失败时不做任何修改
+  async fn set_printer_paused(
+    &self,
+    name: &str,
+    paused: bool,
+    spooler: bool,
+    client: &Client,
+  ) -> Result<PrinterState> {
+    if !self.options.access.allows(client, name) {
+      return Err(CodedError::forbidden(name).into_error::<PrinterState>());
+    }
+
+    let printers = PrinterDevice::all().unwrap_or_default();
+    let Some(printer) =
+      select_named_printer(&printers, name).map_err(CodedError::into_error::<PrinterState>)?
+    else {
+      return Ok(Response::err("No such printer"));
+    };
+
+    if spooler {
+      if let Err(e) = spooler::set_paused(printer.os_name(), paused) {
+        error!("Failed to change the spooler queue of {}: {}", name, e);
+        return Ok(Response::err(format!(
+          "Failed to change the spooler queue: {}",
+          e
+        )));
+      }
+    }
+
+    status::set_printer_paused(name, paused);
+    info!(
+      "Printer {} is {}",
+      name,
+      if paused { "paused" } else { "resumed" }
+    );
+
+    if !paused {
+      schedule::wake();
+    }
+
+    Ok(Response::ok(printer_state(
+      name,
+      printer,
+      &self.options.queue_limits,
+    )))
+  }
+
+  /// 检查令牌后立即打印，或按计划时间及打印机状态保存为定时任务。
+  /// 截止时间只适用于立即打印的任务，打印机暂停时直接取消
+  async fn submit(
+    &self,
+    mut payload: PrintPayload,
+    client: Client,
+    token: Option<String>,
+    deadline: Option<Deadline>,
+  ) -> Result<PrintResult> {
+    let claims = match (&self.options.tokens, &token) {
+      (None, _) => None,
+      (Some(tokens), Some(token)) => match tokens.verify(token) {
+        Ok(claims) => Some(claims),
+        Err(e) => return Err(CodedError::from(e).into_error::<PrintResult>()),
+      },
+      (Some(_), None) => {
+        let err = CodedError::new(
+          StatusCode::UNAUTHORIZED,
+          ErrorCode::TokenRequired,
+          "A print token is required",
+        );
+        return Err(err.into_error::<PrintResult>());
+      }
+    };
+
+    // 输出为 PDF 时不打印，不受暂停及计划时间影响
+    let render = payload.output == Some(OutputTarget::Pdf);
+
+    if status::is_paused() && !render {
+      return Err(CodedError::paused().into_error::<PrintResult>());
+    }
+
+    #[cfg(feature = "error-reporting")]
+    let printer = payload.settings.as_ref().map(|s| s.printer.clone());
+    let scheduled = payload
+      .print_at
+      .filter(|print_at| *print_at > Utc::now() + SCHEDULE_TOLERANCE);
+    // 打印机暂停时在服务内排队
+    let paused = target_printer(&payload).is_some_and(|p| status::is_printer_paused(&p));
+    // 暂缓的任务在释放时立即打印
+    let hold = payload.hold.unwrap_or_default();
+    if hold && (render || scheduled.is_some()) {
+      return Ok(Response::err(
+        "hold cannot be combined with print_at or the pdf output",
+      ));
+    }
+    if !hold && payload.release_pin.is_some() {
+      return Ok(Response::err("release_pin only applies to held jobs"));
+    }
+    // 定时任务保存时不保留截止时间
+    payload.deadline_ms = None;
+    // 打印时会取出文件，先记录请求内容，被拒绝时保存
+    let evidence = self.options.quarantine.as_ref().map(|quarantine| {
+      let request = serde_json::from_str(&payload.redacted()).unwrap_or_default();
+      quarantine.evidence(&payload.documents(), request)
+    });
+    let result = if render {
+      catch_panic(|| print_file(payload, &self.options, &client, claims.as_ref(), None, None))
+    } else if scheduled.is_none() && !hold && paused && deadline.is_some() {
+      // 排队的任务要等打印机恢复后才打印，必然超过截止时间
+      let printer = target_printer(&payload).unwrap_or_default();
+      Err(
+        CodedError::deadline_exceeded(format!(
+          "Printer {} is paused, the job is cancelled as it cannot meet the deadline",
+          printer
+        ))
+        .into(),
+      )
+    } else if scheduled.is_some() || paused || hold {
+      let print_at = scheduled.unwrap_or_else(|| Utc::now().fixed_offset());
+      schedule_file(payload, print_at, &self.options, &client, claims).map(|mut result| {
+        if deadline.is_some() {
+          result
+            .warnings
+            .get_or_insert_default()
+            .push("The deadline only applies to jobs printed immediately".to_string());
+        }
+        result
+      })
+    } else {
+      let submitted_at = Utc::now();
+      let document_sha256 = payload.document_sha256();
+      let skip_hooks = payload.skip_hooks.unwrap_or_default();
+      // 打印前生成 ID，分部分打印时可以按 ID 取消
+      let id = schedule::new_id();
+      let target = target_printer(&payload).unwrap_or_default();
+      events::publish(JobEvent::new(&id, JobStatus::Printing, &target));
+      let result = catch_panic(|| {
+        print_file(
+          payload,
+          &self.options,
+          &client,
+          claims.as_ref(),
+          deadline,
+          Some(&id),
+        )
+      });
+      status::record(result.is_ok());
+
+      // 打印后签发回执。失败的任务不返回 ID，回执只用于统计，不执行打印后命令
+      let receipt = Receipt {
+        job_id: id.clone(),
+        document_sha256,
+        printer: target,
+        pages: None,
+        submitted_at: Some(submitted_at),
+        finished_at: Utc::now(),
+        outcome: Outcome::Failed,
+        detail: None,
+        instance_id: Some(instance::id().to_string()),
+        sheets: None,
+        error: None,
+      };
+      match &result {
+        Ok(result) => {
+          let (outcome, detail) = result_outcome(result);
+          let receipt = Receipt {
+            printer: result.printer.clone().unwrap_or_default(),
+            pages: result.total_pages,
+            sheets: result.total_sheets,
+            outcome,
+            detail,
+            ..receipt
+          };
+          issue_receipt(&receipt, &self.options);
+          run_post_print_hooks(&receipt, &self.options, skip_hooks);
+        }
+        Err(e) => issue_receipt(
+          &Receipt {
+            detail: Some(format!("{:#}", e)),
+            error: error_name(e),
+            ..receipt
+          },
+          &self.options,
+        ),
+      }
+
+      result.map(|mut result| {
+        result.job_id = Some(id);
+        result
+      })
+    };
+
+    match result {
+      Ok(result) => Ok(Response::ok(result)),
+      Err(e) => match e.downcast::<CodedError>() {
+        Ok(e) if matches!(e.code, ErrorCode::InternalPanic) => {
+          error!("Print panicked: {}", e);
+          Err(e.into_error::<PrintResult>())
+        }
+        Ok(e) => {
+          warn!("Print rejected: {}", e);
+          // 只隔离因文档或请求内容被拒绝的请求，不包括令牌、暂停等与文档无关的错误
+          let quarantine_id = match e.code {
+            ErrorCode::InvalidRequest | ErrorCode::RejectedByHook => {
+              self.quarantine(evidence, Some(&e.code), &e.msg)
+            }
+            _ => None,
+          };
+          Err(e.quarantined(quarantine_id).into_error::<PrintResult>())
+        }
+        Err(e) => {
+          error!("Print error: {:#?}", e);
+          #[cfg(feature = "error-reporting")]
+          crate::report::print_failure(printer.as_deref(), &e);
+          let msg = format!("Failed to print: {}", e);
+          let mut resp = Response::err(&msg);
+          resp.0.quarantine_id = self.quarantine(evidence, None, &msg);
+          Ok(resp)
+        }
+      },
+    }
+  }
+
+  /// 保存被拒绝的请求，返回隔离 ID。未启用隔离或保存失败时为空
+  fn quarantine(
+    &self,
+    evidence: Option<Evidence>,
+    code: Option<&ErrorCode>,
+    reason: &str,
+  ) -> Option<String> {
+    let quarantine = self.options.quarantine.as_ref()?;
+    let error = code
+      .and_then(|code| code.to_json())
+      .and_then(|code| code.as_str().map(str::to_string));
+    match quarantine.store(evidence?, error, reason) {
+      Ok(id) => Some(id),
+      Err(e) => {
+        warn!("Failed to quarantine the rejected request: {:#}", e);
+        None
+      }
+    }
+  }
+}
+
+/// 创建 v1 版本的 API 服务
+pub fn service(server: &str, options: ApiOptions) -> OpenApiService<Api, ()> {
+  OpenApiService::new(Api { options }, "Direct Printing", "v1")
+    .description("可从 web 直接调用的打印 API。")
+    .server(server)
+}
+
+#[OpenApi(tag = "ApiTag::Printing")]
+impl Api {
+  /// Get printers
+  ///
+  /// 获取全部可用打印机名称列表，默认打印机在前，其余按名称排序，不区分大小写。`detailed` 为 true 时返回打印机的 ID、端口及驱动，用于区分同名的打印机。
+  /// 指定 `offset` 或 `limit` 时分页返回，并带打印机总数。端口指向同一设备的网络打印机只列出排在最前的一台，
+  /// 其余在详细信息的 `aliases` 中，仍可用于打印
+  #[oai(path = "/printers", method = "get", operation_id = "getPrinters")]
+  async fn get_printers(
+    &self,
+    /// 是否返回详细信息，需要逐个查询打印机，较慢
+    detailed: Query<Option<bool>>,
+    /// 跳过的打印机数
+    offset: Query<Option<u32>>,
+    /// 最多返回的打印机数
+    limit: Query<Option<u32>>,
+    /// 是否合并端口指向同一设备的打印机，默认按服务的设置。为 false 时返回系统中的全部打印机
+    dedupe: Query<Option<bool>>,
+    client: Client,
+  ) -> Json<Response<PrinterList>> {
+    debug!("Getting printers");
+    // 回放时不使用已安装的打印机
+    let printers = match self.options.replay {
+      Some(_) => Vec::new(),
+      None => PrinterDevice::all().unwrap_or_default(),
+    };
+    let default = spooler::default_printer();
+    let mut printers = printers
+      .into_iter()
+      .filter(|p| self.options.access.allows(&client, p.name()))
+      .collect::<Vec<_>>();
+    // 系统枚举打印机的顺序不固定，排序后分页才稳定
+    printers
+      .sort_by_cached_key(|p| printer_order(p.name(), default.as_deref() == Some(p.os_name())));
+    // 同一设备只列出一次，排在最前的为主要名称
+    let printers = if dedupe.0.unwrap_or(self.options.dedupe_printers) {
+      dedupe_printers(printers)
+    } else {
+      printers.into_iter().map(|p| (p, Vec::new())).collect()
+    };
+
+    let paging = Paging {
+      offset: offset.0,
+      limit: limit.0,
+    };
+
+    if detailed.0.unwrap_or_default() {
+      // 只查询本页打印机的详细信息
+      let (printers, page) = paging.apply(printers);
+      let printers = printers
+        .into_iter()
+        .map(|(printer, aliases)| PrinterEntry {
+          aliases: Some(aliases).filter(|a| !a.is_empty()),
+          ..printer_entry(&printer)
+        })
+        .collect();
+      return Response::ok(match page {
+        Some((total, offset)) => PrinterList::DetailedPage(PrinterPage {
+          total,
+          offset,
+          printers,
+        }),
+        None => PrinterList::Detailed(printers),
+      });
+    }
+
+    let simulated = self
+      .options
+      .simulated_printers()
+      .into_iter()
+      .filter(|name| self.options.access.allows(&client, name));
+    let mut names = printers
+      .iter()
+      .map(|(p, _)| p.name().to_string())
+      .chain(simulated)
+      .collect::<Vec<_>>();
+    names
+      .sort_by_cached_key(|name| printer_order(name, default.as_deref() == Some(OsStr::new(name))));
+    let (printers, page) = paging.apply(names);
+    Response::ok(match page {
+      Some((total, offset)) => PrinterList::NamesPage(PrinterPage {
+        total,
+        offset,
+        printers,
+      }),
+      None => PrinterList::Names(printers),
+    })
+  }
+
+  /// Get printer capabilities
+  ///
+  /// 获取指定打印机能力。纸张名称按请求头 `Accept-Language` 选择：与驱动的显示名称语言相同时使用显示名称，
+  /// 优先英文时使用 PrintSchema 关键字对应的英文名称，如 `ISO A4`；未指定时总是使用显示名称。
+  ///
+  /// 能力从驱动获取，首次获取一台打印机通常需要数百毫秒，部分驱动需要数秒，之后 60 秒内使用缓存。
+  /// 只检查打印机是否存在时指定 `exists_only`，只查询系统的打印机列表及状态，不查询驱动，通常在几毫秒内返回
+  #[oai(path = "/printers/:name", method = "get", operation_id = "getPrinter")]
+  #[allow(clippy::too_many_arguments)]
+  async fn get_printer(
+    &self,
+    name: Path<String>,
+    /// 是否获取各纸张的可打印区域，需要逐个查询驱动，较慢
+    imageable_area: Query<Option<bool>>,
+    /// 是否返回打印机的端口、驱动、位置等信息
+    detailed: Query<Option<bool>>,
+    /// 纸张大小另外以该单位表示，见 `dimensions`
+    units: Query<Option<LengthUnit>>,
+    /// 纸张大小的排序方式，默认按面积排序并去除重复项，`none` 为驱动返回的原始顺序
+    sort: Query<Option<PageSizeSort>>,
+    /// 只检查打印机是否存在，返回打印机的状态，不获取能力
+    exists_only: Query<Option<bool>>,
+    /// 不使用缓存，重新从驱动获取能力
+    refresh: Query<Option<bool>>,
+    req: &Request,
+  ) -> Result<PrinterCapability> {
+    debug!("Getting printer capabilities for {}", name.0);
+    let exists_only = exists_only.0.unwrap_or_default();
+    let languages = Languages::parse(req.header(header::ACCEPT_LANGUAGE));
+
+    if !self.options.access.allows(&request_client(req), &name.0) {
+      return Err(CodedError::forbidden(&name.0).into_error::<PrinterCapability>());
+    }
+
+    if self.options.simulates(&name.0) {
+      if exists_only {
+        return Ok(Response::ok(PrinterCapability {
+          state: Some(simulated_printer_state(&name.0, &self.options.queue_limits)),
+          ..Default::default()
+        }));
+      }
+      let cap = self
+        .options
+        .simulated_capabilities(&name.0)
+        .map_err(InternalServerError)?;
+      let mut pcap = printer_capability(&cap, None, sort.0.unwrap_or_default(), &languages);
+      pcap.page_sizes = with_units(pcap.page_sizes, units.0);
+
+      return Ok(Response::ok(pcap));
+    }
+
+    let printers = PrinterDevice::all().unwrap_or_default();
+    let printer = select_named_printer(&printers, &name.0)
+      .map_err(CodedError::into_error::<PrinterCapability>)?;
+
+    if let Some(printer) = printer {
+      // 不查询驱动，状态由系统打印队列短时间缓存
+      if exists_only {
+        return Ok(Response::ok(PrinterCapability {
+          state: Some(printer_state(&name.0, printer, &self.options.queue_limits)),
+          ..Default::default()
+        }));
+      }
+
+      if refresh.0.unwrap_or_default() {
+        invalidate_capabilities(printer);
+      }
+      let fetching = printer.clone();
+      let record = self.options.record_fixtures.clone();
+      let interchange = self.options.letter_a4_interchange;
+      let cap = tokio::task::spawn_blocking(move || {
+        cached_capabilities(&fetching, record.as_deref(), interchange)
+      })
+      .await
+      .map_err(InternalServerError)?
+      .map_err(InternalServerError)?;
+
+      let mut pcap = printer_capability(
+        &cap,
+        imageable_area.0.unwrap_or_default().then_some(printer),
+        sort.0.unwrap_or_default(),
+        &languages,
+      );
+      pcap.page_sizes = with_units(pcap.page_sizes, units.0);
+      pcap.details = detailed
+        .0
+        .unwrap_or_default()
+        .then(|| printer_entry(printer));
+
+      Ok(Response::ok(pcap))
+    } else {
+      Ok(Response::err("No such printer"))
+    }
+  }
+
+  /// Get capabilities of all printers
+  ///
+  /// 一次获取全部可用打印机的能力，键为打印机名称。各打印机并行获取并短时间缓存，
+  /// 某台打印机获取失败时只在该项返回错误。`fields` 为逗号分隔的字段名，只返回这些字段。
+  /// 纸张名称按请求头 `Accept-Language` 选择，同 `GET /printers/:name`
+  #[oai(
+    path = "/capabilities",
+    method = "get",
+    operation_id = "getCapabilities"
+  )]
+  async fn get_capabilities(
+    &self,
+    /// 只返回这些字段，逗号分隔，可用 `max_copies`、`orientations`、`page_sizes`、`custom_page_size`、
+    /// `driver_features`
+    fields: Query<Option<String>>,
+    req: &Request,
+  ) -> Result<HashMap<String, PrinterCapabilityResult>> {
+    debug!("Getting capabilities of all printers");
+    let languages = Languages::parse(req.header(header::ACCEPT_LANGUAGE));
+
+    let fields: Option<Vec<&str>> = fields.0.as_deref().map(|fields| {
+      fields
+        .split(',')
+        .map(str::trim)
+        .filter(|f| !f.is_empty())
+        .collect()
+    });
+    if let Some(unknown) = fields
+      .iter()
+      .flatten()
+      .find(|f| !PrinterCapability::FIELDS.contains(f))
+    {
+      return Ok(Response::err(format!("Unknown field: {}", unknown)));
+    }
+
+    let client = request_client(req);
+    let printers: Vec<_> = match self.options.replay {
+      Some(_) => Vec::new(),
+      None => PrinterDevice::all().unwrap_or_default(),
+    }
+    .into_iter()
+    .filter(|p| self.options.access.allows(&client, p.name()))
+    .collect();
+
+    let mut results = HashMap::new();
+    for (name, fetched) in self.fetch_capabilities(printers, &languages).await? {
+      let result = match fetched {
+        Ok(capability) => PrinterCapabilityResult {
+          capability: Some(capability),
+          error: None,
+        },
+        Err(e) => {
+          warn!("Failed to get capabilities of printer {}: {}", name, e);
+          PrinterCapabilityResult {
+            capability: None,
+            error: Some(e.to_string()),
+          }
+        }
+      };
+      // 同名的打印机只返回第一台
+      results.entry(name).or_insert(result);
+    }
+
+    for name in self.options.simulated_printers() {
+      if !self.options.access.allows(&client, &name) {
+        continue;
+      }
+      let cap = self
+        .options
+        .simulated_capabilities(&name)
+        .map_err(InternalServerError)?;
+      results.insert(
+        name,
+        PrinterCapabilityResult {
+          capability: Some(printer_capability(
+            &cap,
+            None,
+            PageSizeSort::default(),
+            &languages,
+          )),
+          error: None,
+        },
+      );
+    }
+
+    if let Some(fields) = &fields {
+      for capability in results.values_mut().filter_map(|r| r.capability.as_mut()) {
+        capability.retain(fields);
+      }
+    }
+
+    Ok(Response::ok(results))
+  }
+
+  /// Get printer status
+  ///
+  /// 获取打印机状态，包括暂停状态及系统报告的问题
+  #[oai(
+    path = "/printers/:name/status",
+    method = "get",
+    operation_id = "getPrinterStatus"
+  )]
+  async fn get_printer_status(&self, name: Path<String>, client: Client) -> Result<PrinterState> {
+    debug!("Getting printer status for {}", name.0);
+
+    if !self.options.access.allows(&client, &name.0) {
+      return Err(CodedError::forbidden(&name.0).into_error::<PrinterState>());
+    }
+
+    let printers = PrinterDevice::all().unwrap_or_default();
+
+    let printer =
+      select_named_printer(&printers, &name.0).map_err(CodedError::into_error::<PrinterState>)?;
+    if let Some(printer) = printer {
+      Ok(Response::ok(printer_state(
+        &name.0,
+        printer,
+        &self.options.queue_limits,
+      )))
+    } else {
+      Ok(Response::err("No such printer"))
+    }
+  }
+
+  /// Pause a printer
+  ///
+  /// 暂停向打印机提交任务，新的任务在服务内排队。`spooler` 为 true 时同时暂停系统的打印队列
+  #[oai(
+    path = "/printers/:name/pause",
+    method = "post",
+    operation_id = "pausePrinter"
+  )]
+  async fn pause_printer(
+    &self,
+    name: Path<String>,
+    /// 同时暂停系统的打印队列
+    spooler: Query<Option<bool>>,
+    client: Client,
+  ) -> Result<PrinterState> {
+    debug!("Pausing printer {}", name.0);
+    self
+      .set_printer_paused(&name.0, true, spooler.0.unwrap_or_default(), &client)
+      .await
+  }
+
+  /// Resume a printer
+  ///
+  /// 恢复向打印机提交任务，排队的任务按提交顺序打印。`spooler` 为 true 时同时恢复系统的打印队列
+  #[oai(
+    path = "/printers/:name/resume",
+    method = "post",
+    operation_id = "resumePrinter"
+  )]
+  async fn resume_printer(
+    &self,
+    name: Path<String>,
+    /// 同时恢复系统的打印队列
+    spooler: Query<Option<bool>>,
+    client: Client,
+  ) -> Result<PrinterState> {
+    debug!("Resuming printer {}", name.0);
+    self
+      .set_printer_paused(&name.0, false, spooler.0.unwrap_or_default(), &client)
+      .await
+  }
+
+  /// Get printer pricing
+  ///
+  /// 获取打印机的价格，用于显示费率
+  #[oai(
+    path = "/printers/:name/pricing",
+    method = "get",
+    operation_id = "getPrinterPricing"
+  )]
+  async fn get_printer_pricing(&self, name: Path<String>, client: Client) -> Result<Pricing> {
+    debug!("Getting printer pricing for {}", name.0);
+
+    if !self.options.access.allows(&client, &name.0) {
+      return Err(CodedError::forbidden(&name.0).into_error::<Pricing>());
+    }
+
+    let Some(pricing) = &self.options.pricing else {
+      return Ok(Response::err("Pricing is not configured"));
+    };
+    let Some(prices) = pricing.printers.get(&name.0) else {
+      return Ok(Response::err("No pricing for this printer"));
+    };
+
+    Ok(Response::ok(Pricing {
+      currency: pricing.currency.clone(),
+      mono_page: prices.mono_page,
+      color_page: prices.color_page,
+      sheet: prices.sheet,
+      default_color: pricing.default_color,
+    }))
+  }
+
+  /// Purge a printer
+  ///
+  /// 取消打印机在服务内排队的全部任务，并清空系统的打印队列。需要在 `X-API-Key` 请求头中提供 API 密钥
+  #[oai(
+    path = "/printers/:name/purge",
+    method = "post",
+    operation_id = "purgePrinter",
+    tag = "ApiTag::Admin"
+  )]
+  async fn purge_printer(
+    &self,
+    name: Path<String>,
+    /// API 密钥
+    #[oai(name = "X-API-Key")]
+    api_key: Header<Option<String>>,
+  ) -> Result<PurgeResult> {
+    debug!("Purging printer {}", name.0);
+
+    if !self.authorized(api_key.0) {
+      return Err(CodedError::unauthorized().into_error::<PurgeResult>());
+    }
+
+    let printers = PrinterDevice::all().unwrap_or_default();
+    let Some(printer) =
+      select_named_printer(&printers, &name.0).map_err(CodedError::into_error::<PurgeResult>)?
+    else {
+      return Ok(Response::err("No such printer"));
+    };
+
+    let internal_jobs = schedule::cancel_printer(&name.0, "purged") as u32;
+    let spooler_jobs = match spooler::purge(printer.os_name()) {
+      Ok(jobs) => jobs,
+      Err(e) => {
+        error!("Failed to purge the spooler queue of {}: {}", name.0, e);
+        return Ok(Response::err(format!(
+          "Cancelled {} queued jobs but failed to purge the spooler queue: {}",
+          internal_jobs, e
+        )));
+      }
+    };
+    warn!(
+      "Purged printer {}: {} queued jobs, {} spooler jobs",
+      name.0, internal_jobs, spooler_jobs
+    );
+
+    Ok(Response::ok(PurgeResult {
+      internal_jobs,
+      spooler_jobs,
+    }))
+  }
+
+  /// Connect a shared printer
+  ///
+  /// 连接网络共享打印机，返回连接后的打印机名称，之后即可像本地打印机一样使用。需要在 `X-API-Key` 请求头中提供 API 密钥
+  #[oai(
+    path = "/printers/connect",
+    method = "post",
+    operation_id = "connectPrinter",
+    tag = "ApiTag::Admin"
+  )]
+  async fn connect_printer(
+    &self,
+    payload: Json<PrinterConnectionPayload>,
+    /// API 密钥
+    #[oai(name = "X-API-Key")]
+    api_key: Header<Option<String>>,
+  ) -> Result<String> {
+    debug!("Connecting printer {}", payload.path);
+
+    if !self.authorized(api_key.0) {
+      return Err(CodedError::unauthorized().into_error::<String>());
+    }
+
+    let path = payload.0.path;
+
+    if !is_unc_printer_path(&path) {
+      return Ok(Response::err(r"Printer path must be like \\server\printer"));
+    }
+
+    // 可能需要从服务器下载驱动，较慢
+    let connecting = path.clone();
+    let result = tokio::task::spawn_blocking(move || spooler::connect(OsStr::new(&connecting)))
+      .await
+      .map_err(InternalServerError)?;
+
+    if let Err(e) = result {
+      error!("Failed to connect printer {}: {}", path, e);
+      return Ok(Response::err(format!(
+        "Failed to connect printer: {}",
+        spooler::connection_error(&e).map_or_else(|| e.to_string(), str::to_string)
+      )));
+    }
+
+    // 连接后的打印机名称与路径的大小写可能不同
+    let name = PrinterDevice::all()
+      .unwrap_or_default()
+      .iter()
+      .map(|p| p.name().to_string())
+      .find(|name| name.eq_ignore_ascii_case(&path))
+      .unwrap_or(path);
+    info!("Connected printer {}", name);
+
+    Ok(Response::ok(name))
+  }
+
+  /// Disconnect a shared printer
+  ///
+  /// 断开网络共享打印机的连接。需要在 `X-API-Key` 请求头中提供 API 密钥
+  #[oai(
+    path = "/printers/connect",
+    method = "delete",
+    operation_id = "disconnectPrinter",
+    tag = "ApiTag::Admin"
+  )]
+  async fn disconnect_printer(
+    &self,
+    /// 共享打印机的 UNC 路径
+    path: Query<String>,
+    /// API 密钥
+    #[oai(name = "X-API-Key")]
+    api_key: Header<Option<String>>,
+  ) -> Result<String> {
+    debug!("Disconnecting printer {}", path.0);
+
+    if !self.authorized(api_key.0) {
+      return Err(CodedError::unauthorized().into_error::<String>());
+    }
+
+    if !is_unc_printer_path(&path.0) {
+      return Ok(Response::err(r"Printer path must be like \\server\printer"));
+    }
+
+    if let Err(e) = spooler::disconnect(OsStr::new(&path.0)) {
+      error!("Failed to disconnect printer {}: {}", path.0, e);
+      return Ok(Response::err(format!(
+        "Failed to disconnect printer: {}",
+        spooler::connection_error(&e).map_or_else(|| e.to_string(), str::to_string)
+      )));
+    }
+
+    info!("Disconnected printer {}", path.0);
+    Ok(Response::ok(path.0))
+  }
+
+  /// Get default print settings
+  ///
+  /// 获取默认打印设置
+  #[oai(
+    path = "/settings",
+    method = "get",
+    operation_id = "getDefaultSettings"
+  )]
+  async fn get_default_settings(&self) -> Result<DefaultSettings> {
+    debug!("Getting default settings");
+
+    if let Some(settings) = default_settings() {
+      let validation = SettingsValidation::from(check_settings(&settings, &self.options));
+
+      if self.options.strict_settings && !validation.valid {
+        Ok(Response::err(format!(
+          "Invalid default settings: {}",
+          validation.issues.join("; ")
+        )))
+      } else {
+        Ok(Response::ok(DefaultSettings {
+          settings,
+          validation,
+        }))
+      }
+    } else {
+      Ok(Response::err("No default settings"))
+    }
+  }
+
+  /// Set default print settings
+  ///
+  /// 设置默认打印设置
+  #[oai(
+    path = "/settings",
+    method = "post",
+    operation_id = "setDefaultSettings"
+  )]
+  async fn set_default_settings(&self, payload: Json<PrintSettings>) -> Result<String> {
+    debug!("Setting default settings");
+    self.log_request(&payload.0);
+    let mut payload = payload;
+    payload.0.normalize_units();
+
+    if let Some(filepath) = get_settings_filepath() {
+      if let Err(e) = write_settings(filepath, &payload.0) {
+        error!("Write settings error: {:#?}", e);
+        Ok(Response::err(format!("Failed to write settings: {}", e)))
+      } else {
+        set_default_settings(Some(payload.0));
+        Ok(Response::ok("ok".to_string()))
+      }
+    } else {
+      Ok(Response::err("No default settings"))
+    }
+  }
+
+  /// Validate default print settings
+  ///
+  /// 检查保存的默认打印设置是否适用于当前的打印机
+  #[oai(
+    path = "/settings/validate",
+    method = "post",
+    operation_id = "validateDefaultSettings"
+  )]
+  async fn validate_default_settings(&self) -> Result<SettingsValidation> {
+    debug!("Validating default settings");
+
+    if let Some(settings) = default_settings() {
+      let validation = SettingsValidation::from(check_settings(&settings, &self.options));
+
+      for issue in &validation.issues {
+        warn!("Default settings: {}", issue);
+      }
+
+      Ok(Response::ok(validation))
+    } else {
+      Ok(Response::err("No default settings"))
+    }
+  }
+
+  /// Export configuration
+  ///
+  /// 导出全部保存的配置
+  #[oai(
+    path = "/admin/export",
+    method = "get",
+    operation_id = "exportConfig",
+    tag = "ApiTag::Admin"
+  )]
+  async fn export_config(
+    &self,
+    /// API 密钥
+    #[oai(name = "X-API-Key")]
+    api_key: Header<Option<String>>,
+  ) -> Result<ConfigBundle> {
+    debug!("Exporting configuration");
+
+    if !self.authorized(api_key.0) {
+      return Err(CodedError::unauthorized().into_error::<ConfigBundle>());
+    }
+
+    match export_bundle() {
+      Ok(bundle) => Ok(Response::ok(bundle)),
+      Err(e) => {
+        error!("Export configuration error: {:#?}", e);
+        Ok(Response::err(format!(
+          "Failed to export configuration: {}",
+          e
+        )))
+      }
+    }
+  }
+
+  /// Get diagnostics
+  ///
+  /// 获取诊断信息，包括后台处理程序服务的状态及服务启动后的打印统计。
+  /// 需要在 `X-API-Key` 请求头中提供 API 密钥
+  #[oai(
+    path = "/admin/diagnostics",
+    method = "get",
+    operation_id = "getDiagnostics",
+    tag = "ApiTag::Admin"
+  )]
+  async fn get_diagnostics(
+    &self,
+    /// API 密钥
+    #[oai(name = "X-API-Key")]
+    api_key: Header<Option<String>>,
+  ) -> Result<Diagnostics> {
+    debug!("Getting diagnostics");
+
+    if !self.authorized(api_key.0) {
+      return Err(CodedError::unauthorized().into_error::<Diagnostics>());
+    }
+
+    let (spooler, spooler_error) = match spooler::service_status() {
+      Ok(status) => (Some(SpoolerHealth::from(status)), None),
+      Err(e) => {
+        warn!("Failed to read the print spooler status: {}", e);
+        (None, Some(e.to_string()))
+      }
+    };
+    let (printed, failed) = status::counters();
+
+    Ok(Response::ok(Diagnostics {
+      spooler,
+      spooler_error,
+      printers: PrinterDevice::all().map_or(0, |p| p.len() as u32),
+      paused: status::is_paused(),
+      printed,
+      failed,
+    }))
+  }
+
+  /// Warm up
+  ///
+  /// 加载 Pdfium，枚举打印机并获取、缓存各打印机的能力，使驱动和后台处理程序在第一次打印前加载完成，
+  /// 返回各步骤的耗时及错误。`printers` 为逗号分隔的打印机名称，默认为服务配置的预热打印机，未配置时为全部打印机。
+  /// 需要在 `X-API-Key` 请求头中提供 API 密钥
+  #[oai(
+    path = "/admin/warmup",
+    method = "post",
+    operation_id = "warmUp",
+    tag = "ApiTag::Admin"
+  )]
+  async fn warm_up(
+    &self,
+    /// 只预热这些打印机，逗号分隔
+    printers: Query<Option<String>>,
+    /// 是否为每台打印机生成默认打印票据并由驱动校验，较慢
+    validate_ticket: Query<Option<bool>>,
+    /// API 密钥
+    #[oai(name = "X-API-Key")]
+    api_key: Header<Option<String>>,
+  ) -> Result<WarmupResult> {
+    debug!("Warming up");
+
+    if !self.authorized(api_key.0) {
+      return Err(CodedError::unauthorized().into_error::<WarmupResult>());
+    }
+
+    let options = self.options.clone();
+    let printers: Option<Vec<String>> = printers.0.map(|printers| {
+      printers
+        .split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(str::to_string)
+        .collect()
+    });
+    let validate_ticket = validate_ticket.0.unwrap_or_default();
+    let result =
+      tokio::task::spawn_blocking(move || warm_up(&options, printers.as_deref(), validate_ticket))
+        .await
+        .map_err(InternalServerError)?;
+
+    Ok(Response::ok(result))
+  }
+
+  /// Preview the retention cleanup
+  ///
+  /// 按保留策略列出各类保存的数据（回执、隔离的请求、诊断信息）中下次清理时将删除的数据，不实际删除。
+  /// 服务每小时清理一次，每类最多列出 1000 项。需要在 `X-API-Key` 请求头中提供 API 密钥
+  #[oai(
+    path = "/admin/retention/preview",
+    method = "get",
+    operation_id = "previewRetention",
+    tag = "ApiTag::Admin"
+  )]
+  async fn preview_retention(
+    &self,
+    /// API 密钥
+    #[oai(name = "X-API-Key")]
+    api_key: Header<Option<String>>,
+  ) -> Result<RetentionPreview> {
+    debug!("Previewing the retention cleanup");
+
+    if !self.authorized(api_key.0) {
+      return Err(CodedError::unauthorized().into_error::<RetentionPreview>());
+    }
+    let Some(retention) = self.options.retention.clone() else {
+      return Ok(Response::err("No retention policy is configured"));
+    };
+
+    let previews = tokio::task::spawn_blocking(move || retention.preview())
+      .await
+      .map_err(InternalServerError)?;
+    Ok(Response::ok(RetentionPreview {
+      classes: previews.into_iter().map(Into::into).collect(),
+    }))
+  }
+
+  /// Get a quarantined request
+  ///
+  /// 获取被拒绝而隔离的打印请求，包括请求体、拒绝的原因及文件内容，ID 见被拒绝时响应的 `quarantine_id`。
+  /// 服务需要启用 `--quarantine-dir`，需要在 `X-API-Key` 请求头中提供 API 密钥
+  #[oai(
+    path = "/admin/quarantine/:id",
+    method = "get",
+    operation_id = "getQuarantinedRequest",
+    tag = "ApiTag::Admin"
+  )]
+  async fn get_quarantined(
+    &self,
+    id: Path<String>,
+    /// API 密钥
+    #[oai(name = "X-API-Key")]
+    api_key: Header<Option<String>>,
+  ) -> Result<QuarantineRecord> {
+    debug!("Getting quarantined request {}", id.0);
+
+    if !self.authorized(api_key.0) {
+      return Err(CodedError::unauthorized().into_error::<QuarantineRecord>());
+    }
+
+    let Some(quarantine) = self.options.quarantine.clone() else {
+      return Ok(Response::err("Quarantine is not enabled"));
+    };
+    let entry = tokio::task::spawn_blocking(move || quarantine.get(&id.0))
+      .await
+      .map_err(InternalServerError)?;
+    match entry {
+      Ok(Some(entry)) => Ok(Response::ok(entry.into())),
+      Ok(None) => Ok(Response::err("No such quarantined request")),
+      Err(e) => {
+        warn!("Failed to read the quarantined request: {}", e);
+        Ok(Response::err(format!(
+          "Failed to read the quarantined request: {}",
+          e
+        )))
+      }
+    }
+  }
+
+  /// Pause the service
+  ///
+  /// 暂停接受打印任务，用于维护期间。打印请求返回 `paused` 错误，信息中包括说明及自动恢复的时间，
+  /// 定时及排队的任务推迟到恢复后打印，只读接口仍然可用。暂停状态保存在数据目录中，重启服务后仍然暂停。
+  /// 已暂停时更新说明及自动恢复的时间。需要在 `X-API-Key` 请求头中提供 API 密钥
+  #[oai(
+    path = "/admin/pause",
+    method = "post",
+    operation_id = "pauseService",
+    tag = "ApiTag::Admin"
+  )]
+  async fn pause_service(
+    &self,
+    payload: Json<PausePayload>,
+    /// API 密钥
+    #[oai(name = "X-API-Key")]
+    api_key: Header<Option<String>>,
+  ) -> Result<ServicePause> {
+    debug!("Pausing the service");
+    self.log_request(&payload.0);
+
+    if !self.authorized(api_key.0) {
+      return Err(CodedError::unauthorized().into_error::<ServicePause>());
+    }
+
+    let resume_at = payload.0.resume_at.map(|t| t.to_utc());
+    if resume_at.is_some_and(|t| t <= Utc::now()) {
+      return Ok(Response::err("resume_at must be in the future"));
+    }
+
+    let message = payload.0.message.filter(|m| !m.trim().is_empty());
+    let pause = status::set_pause(message, resume_at);
+    warn!(
+      "Accepting jobs is paused{}",
+      pause
+        .resume_at
+        .map(|t| format!(" until {}", t.to_rfc3339()))
+        .unwrap_or_default()
+    );
+    Ok(Response::ok(Some(pause).into()))
+  }
+
+  /// Resume the service
+  ///
+  /// 恢复接受打印任务，推迟的定时及排队的任务随即打印。需要在 `X-API-Key` 请求头中提供 API 密钥
+  #[oai(
+    path = "/admin/resume",
+    method = "post",
+    operation_id = "resumeService",
+    tag = "ApiTag::Admin"
+  )]
+  async fn resume_service(
+    &self,
+    /// API 密钥
+    #[oai(name = "X-API-Key")]
+    api_key: Header<Option<String>>,
+  ) -> Result<ServicePause> {
+    debug!("Resuming the service");
+
+    if !self.authorized(api_key.0) {
+      return Err(CodedError::unauthorized().into_error::<ServicePause>());
+    }
+
+    status::set_paused(false);
+    schedule::wake();
+    info!("Accepting jobs is resumed");
+    Ok(Response::ok(None.into()))
+  }
+
+  /// Restart the print spooler
+  ///
+  /// 重启 Windows 后台处理程序服务，等待服务重新运行后重新枚举打印机。服务需要以管理员身份运行，需要在 `X-API-Key` 请求头中提供 API 密钥
+  #[oai(
+    path = "/admin/spooler/restart",
+    method = "post",
+    operation_id = "restartSpooler",
+    tag = "ApiTag::Admin"
+  )]
+  async fn restart_spooler(
+    &self,
+    /// API 密钥
+    #[oai(name = "X-API-Key")]
+    api_key: Header<Option<String>>,
+  ) -> Result<SpoolerRestart> {
+    debug!("Restarting the print spooler");
+
+    if !self.authorized(api_key.0) {
+      return Err(CodedError::unauthorized().into_error::<SpoolerRestart>());
+    }
+
+    warn!("Restarting the print spooler");
+    let restarted =
+      tokio::task::spawn_blocking(|| spooler::restart_service(SPOOLER_RESTART_TIMEOUT))
+        .await
+        .map_err(InternalServerError)?;
+
+    let status = match restarted {
+      Ok(status) => status,
+      Err(e) if spooler::is_access_denied(&e) => {
+        let err = CodedError::new(
+          StatusCode::FORBIDDEN,
+          ErrorCode::RequiresAdministrator,
+          "Restarting the print spooler requires administrator privileges",
+        );
+        return Err(err.into_error::<SpoolerRestart>());
+      }
+      Err(e) => {
+        error!("Failed to restart the print spooler: {}", e);
+        return Ok(Response::err(format!(
+          "Failed to restart the print spooler: {}",
+          e
+        )));
+      }
+    };
+
+    let printers = PrinterDevice::all()
+      .unwrap_or_default()
+      .iter()
+      .map(|p| p.name().to_string())
+      .collect::<Vec<_>>();
+    info!(
+      "Print spooler restarted, {} printers available",
+      printers.len()
+    );
+
+    Ok(Response::ok(SpoolerRestart {
+      spooler: status.into(),
+      printers,
+    }))
+  }
+
+  /// Import configuration
+  ///
+  /// 导入配置包，替换全部保存的配置。`merge` 为 true 时只添加缺少的配置
+  #[oai(
+    path = "/admin/import",
+    method = "post",
+    operation_id = "importConfig",
+    tag = "ApiTag::Admin"
+  )]
+  async fn import_config(
+    &self,
+    payload: Json<ConfigBundle>,
+    /// 只添加缺少的配置，不覆盖已有的配置
+    merge: Query<Option<bool>>,
+    /// API 密钥
+    #[oai(name = "X-API-Key")]
+    api_key: Header<Option<String>>,
+  ) -> Result<String> {
+    debug!("Importing configuration");
+    self.log_request(&payload.0);
+
+    if !self.authorized(api_key.0) {
+      return Err(CodedError::unauthorized().into_error::<String>());
+    }
+
+    match import_bundle(payload.0, merge.0.unwrap_or_default()) {
+      Ok(()) => Ok(Response::ok("ok".to_string())),
+      Err(e) => {
+        error!("Import configuration error: {:#?}", e);
+        Ok(Response::err(format!(
+          "Failed to import configuration: {}",
+          e
+        )))
+      }
+    }
+  }
+
+  /// Issue a print token
+  ///
+  /// 签发一次性打印令牌，供浏览器调用打印接口。需要在 `X-API-Key` 请求头中提供 API 密钥
+  #[oai(
+    path = "/tokens",
+    method = "post",
+    operation_id = "issueToken",
+    tag = "ApiTag::Admin"
+  )]
+  async fn issue_token(
+    &self,
+    payload: Json<TokenRequest>,
+    /// API 密钥
+    #[oai(name = "X-API-Key")]
+    api_key: Header<Option<String>>,
+  ) -> Result<PrintToken> {
+    debug!("Issuing a print token");
+    self.log_request(&payload.0);
+
+    if !self.authorized(api_key.0) {
+      return Err(CodedError::unauthorized().into_error::<PrintToken>());
+    }
+
+    let Some(tokens) = &self.options.tokens else {
+      return Ok(Response::err("Print tokens are not enabled"));
+    };
+
+    let TokenRequest {
+      printer,
+      max_pages,
+      expires_in,
+    } = payload.0;
+    let (token, expires_at) = tokens.issue(printer, max_pages, expires_in.unwrap_or(300));
+
+    Ok(Response::ok(PrintToken { token, expires_at }))
+  }
+
+  /// Get jobs
+  ///
+  /// 获取正在分部分打印的任务、等待中的任务（按计划时间排序）及最近过期或取消的任务。
+  /// 已到计划时间的任务包括在打印机队列中的位置及预计的开始时间
+  #[oai(path = "/jobs", method = "get", operation_id = "getJobs")]
+  async fn get_jobs(&self, client: Client) -> Json<Response<Vec<Job>>> {
+    debug!("Getting jobs");
+    let running = schedule::running().into_iter().map(Job::running);
+    let jobs = schedule::list();
+    let printers = jobs.iter().map(|job| job.printer.as_str()).collect();
+    let positions = queue_positions(printers, &self.options);
+    let scheduled = jobs.into_iter().map(|job| {
+      let state = waiting_state(&job);
+      let position = positions.get(&job.id).copied();
+      Job {
+        queue_position: position,
+        estimated_start_seconds: position
+          .and_then(|position| queue_estimate(&job.printer, position, &self.options)),
+        ..Job::new(job, state)
+      }
+    });
+    let finished = schedule::finished()
+      .into_iter()
+      .map(|(job, ending)| Job::finished(job, ending));
+    Response::ok(
+      running
+        .chain(scheduled)
+        .chain(finished)
+        .filter(|job| self.options.access.allows(&client, &job.printer))
+        .collect(),
+    )
+  }
+
+  /// Get a job
+  ///
+  /// 获取任务的当前状态，包括正在打印、等待中及已结束的任务，已结束的任务按回执返回。
+  /// 指定 `wait` 时，任务未结束则等待其状态变化后再返回，最多等待 `wait` 秒（不超过 60 秒），
+  /// 超时时返回当前状态，可用于代替轮询
+  #[oai(path = "/jobs/:id", method = "get", operation_id = "getJob")]
+  async fn get_job(
+    &self,
+    id: Path<String>,
+    /// 任务未结束时最多等待状态变化的秒数
+    wait: Query<Option<u64>>,
+    client: Client,
+  ) -> Result<Job> {
+    debug!("Getting job {}", id.0);
+
+    // 先订阅再查询，避免错过查询后的变化
+    let mut changes = schedule::watch(&id.0);
+    let wait = Duration::from_secs(wait.0.unwrap_or_default()).min(MAX_JOB_WAIT);
+    let deadline = tokio::time::Instant::now() + wait;
+
+    let Some(mut job) = self.find_job(&id.0) else {
+      return Ok(Response::err("No such job"));
+    };
+    if !self.options.access.allows(&client, &job.printer) {
+      return Err(CodedError::forbidden(&job.printer).into_error::<Job>());
+    }
+
+    while !job.state.is_finished() {
+      match tokio::time::timeout_at(deadline, changes.changed()).await {
+        // 任务在各阶段之间移动时可能短暂查询不到，此时继续等待
+        Ok(Ok(())) => job = self.find_job(&id.0).unwrap_or(job),
+        _ => break,
+      }
+    }
+
+    Ok(Response::ok(job))
+  }
+
+  /// Get a job receipt
+  ///
+  /// 获取已结束任务的签名回执，包括立即打印成功的任务及已结束的定时任务。
+  /// 回执由服务保存的 Ed25519 密钥签名，用 `GET /public-key` 返回的公钥验证
+  #[oai(
+    path = "/jobs/:id/receipt",
+    method = "get",
+    operation_id = "getJobReceipt"
+  )]
+  async fn get_job_receipt(&self, id: Path<String>, client: Client) -> Result<JobReceipt> {
+    debug!("Getting the receipt of job {}", id.0);
+
+    let Some(signed) = receipt::get(&id.0) else {
+      return Ok(Response::err("No receipt for this job"));
+    };
+    let receipt = match signed.parse() {
+      Ok(receipt) => receipt,
+      Err(e) => return Ok(Response::err(format!("Invalid receipt: {}", e))),
+    };
+
+    if !self.options.access.allows(&client, &receipt.printer) {
+      return Err(CodedError::forbidden(&receipt.printer).into_error::<JobReceipt>());
+    }
+
+    Ok(Response::ok(JobReceipt {
+      post_print_hook: hook::run_of(&id.0).map(Into::into),
+      ..signed.into()
+    }))
+  }
+
+  /// Download job diagnostics
+  ///
+  /// 下载已结束任务的诊断包，用于排查打印结果不正确的问题。诊断包为 ZIP 文件，包括任务信息及回执、各阶段耗时、
+  /// 提交打印的打印票据 XML、打印使用的打印机能力及打印期间的日志。超过 `--diagnostics-retention-days` 的任务返回 404。
+  /// 需要在 `X-API-Key` 请求头中提供 API 密钥
+  #[oai(
+    path = "/jobs/:id/diagnostics",
+    method = "get",
+    operation_id = "getJobDiagnostics",
+    tag = "ApiTag::Admin"
+  )]
+  async fn get_job_diagnostics(
+    &self,
+    id: Path<String>,
+    /// API 密钥
+    #[oai(name = "X-API-Key")]
+    api_key: Header<Option<String>>,
+  ) -> poem::Result<DiagnosticsResponse> {
+    debug!("Getting the diagnostics of job {}", id.0);
+
+    if !self.authorized(api_key.0) {
+      return Err(CodedError::unauthorized().into_error::<String>());
+    }
+
+    let not_found = |msg: String| {
+      CodedError::new(StatusCode::NOT_FOUND, ErrorCode::NotFound, msg).into_error::<String>()
+    };
+    let Some(diagnostics) = &self.options.job_diagnostics else {
+      return Err(not_found(
+        "Job diagnostics are not kept, set --diagnostics-retention-days to enable them".to_string(),
+      ));
+    };
+
+    if let Some(stream) = diagnostics.stream(&id.0) {
+      return Ok(DiagnosticsResponse::Ok(
+        Binary(Body::from_async_read(stream)),
+        format!("attachment; filename=\"job-{}-diagnostics.zip\"", id.0),
+      ));
+    }
+
+    let retention = diagnostics
+      .retention()
+      .map(|r| TimeDelta::from_std(r).unwrap_or(TimeDelta::MAX));
+    let msg = match receipt::get(&id.0).and_then(|signed| signed.parse().ok()) {
+      Some(receipt)
+        if retention.is_some_and(|retention| receipt.finished_at + retention < Utc::now()) =>
+      {
+        format!(
+          "Job {} finished at {}, its diagnostics are only kept for {} days",
+          id.0,
+          receipt.finished_at.to_rfc3339(),
+          retention.unwrap_or_default().num_days()
+        )
+      }
+      Some(_) => format!("No diagnostics were recorded for job {}", id.0),
+      None
+        if schedule::get(&id.0).is_some() || schedule::running().iter().any(|j| j.id == id.0) =>
+      {
+        format!("Job {} has not finished, try again after it ends", id.0)
+      }
+      None => format!("No such job {}", id.0),
+    };
+    Err(not_found(msg))
+  }
+
+  /// Get printer capability changes
+  ///
+  /// 获取打印机能力最近一次的变化。服务在启动预热及刷新能力缓存时与保存的快照比较，
+  /// 发现驱动更新等导致纸张、布局或最大份数变化时记录，并检查默认打印设置是否引用了已不存在的选项
+  #[oai(
+    path = "/printers/:name/capability-changes",
+    method = "get",
+    operation_id = "getCapabilityChanges"
+  )]
+  async fn get_capability_changes(
+    &self,
+    name: Path<String>,
+    client: Client,
+  ) -> Result<CapabilityChangeSet> {
+    debug!("Getting capability changes of printer {}", name.0);
+
+    if !self.options.access.allows(&client, &name.0) {
+      return Err(CodedError::forbidden(&name.0).into_error::<CapabilityChangeSet>());
+    }
+
+    match drift::last_changes(&name.0) {
+      Some(changes) => Ok(Response::ok(changes.into())),
+      None => Ok(Response::err(format!(
+        "No capability changes recorded for printer {}",
+        name.0
+      ))),
+    }
+  }
+
+  /// Get the daily report
+  ///
+  /// 统计某天结束的任务，按打印机合计任务数、页数、纸张数、失败数及失败最多的错误类型。
+  /// 日期按服务的 `--report-timezone` 划分，没有任务的日期合计为零。只包括客户端可以使用的打印机
+  #[oai(
+    path = "/reports/daily",
+    method = "get",
+    operation_id = "getDailyReport"
+  )]
+  async fn get_daily_report(
+    &self,
+    /// 日期，格式为 `YYYY-MM-DD`，默认为当天
+    date: Query<Option<NaiveDate>>,
+    client: Client,
+  ) -> Result<DailyReport> {
+    let timezone = self.options.report_timezone;
+    let date = date.0.unwrap_or_else(|| timezone.today());
+    debug!("Getting the daily report of {}", date);
+
+    let access = self.options.access.clone();
+    // 读取回执较慢，不阻塞请求处理
+    let summary = tokio::task::spawn_blocking(move || {
+      summary::daily(date, timezone, |printer| access.allows(&client, printer))
+    })
+    .await
+    .map_err(InternalServerError)?;
+
+    Ok(Response::ok(summary.into()))
+  }
+
+  /// Get instance information
+  ///
+  /// 获取服务实例的 ID、计算机名称、版本及使用的目录
+  #[oai(path = "/info", method = "get", operation_id = "getInfo")]
+  async fn get_info(&self) -> Result<InstanceInfo> {
+    debug!("Getting instance information");
+
+    let path = |dir: Option<PathBuf>| dir.map(|dir| dir.display().to_string());
+    // 读取注册表及打印服务器较慢，不阻塞请求处理
+    let (os_version, spool_dir) = tokio::task::spawn_blocking(|| {
+      let spool_dir = spooler::spool_directory()
+        .inspect_err(|e| warn!("Failed to read the spool directory: {}", e))
+        .ok();
+      (instance::os_version(), spool_dir)
+    })
+    .await
+    .map_err(InternalServerError)?;
+
+    Ok(Response::ok(InstanceInfo {
+      instance_id: instance::id().to_string(),
+      hostname: instance::hostname(),
+      os_version,
+      version: env!("CARGO_PKG_VERSION").to_string(),
+      built_at: instance::built_at(),
+      commit: instance::commit().map(str::to_string),
+      features: instance::features()
+        .into_iter()
+        .map(str::to_string)
+        .collect(),
+      data_dir: path(instance::data_dir()),
+      config_dir: path(instance::config_dir()),
+      spool_dir: spool_dir.map(|dir| dir.to_string_lossy().into_owned()),
+      pause: status::pause().map(|pause| Some(pause).into()),
+    }))
+  }
+
+  /// Get the receipt public key
+  ///
+  /// 获取验证任务回执签名的 Ed25519 公钥
+  #[oai(path = "/public-key", method = "get", operation_id = "getPublicKey")]
+  async fn get_public_key(&self) -> Result<PublicKey> {
+    debug!("Getting the receipt public key");
+    let key = match receipt::public_key() {
+      Ok(key) => key,
+      Err(e) => return Ok(Response::err(e)),
+    };
+
+    Ok(Response::ok(PublicKey {
+      algorithm: "Ed25519".to_string(),
+      key,
+    }))
+  }
+
+  /// Cancel a job
+  ///
+  /// 取消定时或暂缓的打印任务。正在分部分打印的任务停止提交剩余的部分，已提交的部分不受影响
+  #[oai(path = "/jobs/:id", method = "delete", operation_id = "cancelJob")]
+  async fn cancel_job(&self, id: Path<String>, client: Client) -> Result<String> {
+    debug!("Cancelling job {}", id.0);
+
+    let scheduled = schedule::get(&id.0);
+    let printer = match &scheduled {
+      Some(job) => job.printer.clone(),
+      None => match schedule::get_running(&id.0) {
+        Some(job) => job.printer,
+        None => return Ok(Response::err("No such job")),
+      },
+    };
+
+    if !self.options.access.allows(&client, &printer) {
+      return Err(CodedError::forbidden(&printer).into_error::<String>());
+    }
+
+    if !schedule::cancel(&id.0, "cancelled") {
+      return Ok(Response::err("No such job"));
+    }
+    info!("Cancelled job {}", id.0);
+
+    // 正在打印的任务由打印流程签发回执并执行打印后命令
+    if let Some(job) = scheduled {
+      if let Some(receipt) = receipt::get(&id.0).and_then(|signed| signed.parse().ok()) {
+        run_post_print_hooks(&receipt, &self.options, skips_hooks(&job.payload));
+      }
+    }
+    Ok(Response::ok("ok".to_string()))
+  }
+
+  /// Release a held job
+  ///
+  /// 立即打印以 `hold` 提交的暂缓任务。释放前再次检查打印机状态，打印机暂停或未就绪时任务仍保持暂缓
+  #[oai(
+    path = "/jobs/:id/release",
+    method = "post",
+    operation_id = "releaseJob"
+  )]
+  async fn release_job(
+    &self,
+    id: Path<String>,
+    client: Client,
+    /// 提交时设置的 `release_pin`
+    #[oai(name = "X-Release-Pin")]
+    pin: Header<Option<String>>,
+  ) -> Result<PrintResult> {
+    debug!("Releasing job {}", id.0);
+
+    let Some(job) = schedule::get(&id.0).filter(|job| job.held) else {
+      return Ok(Response::err("No such held job"));
+    };
+
+    if !self.options.access.allows(&client, &job.printer) {
+      return Err(CodedError::forbidden(&job.printer).into_error::<PrintResult>());
+    }
+
+    if let Some(expected) = &job.release_pin_sha256 {
+      let matched = pin
+        .0
+        .as_deref()
+        .is_some_and(|pin| constant_time_eq(expected, &release_pin_hash(&job.id, pin)));
+      if !matched {
+        warn!("Wrong release PIN for job {}", job.id);
+        let err = CodedError::new(
+          StatusCode::FORBIDDEN,
+          ErrorCode::InvalidReleasePin,
+          "The release PIN is missing or wrong",
+        );
+        return Err(err.into_error::<PrintResult>());
+      }
+    }
+
+    if let Err(e) = check_release(&job) {
+      return Err(e.into_error::<PrintResult>());
+    }
+
+    // 同时释放或取消时只有一个请求取出任务
+    let Some(job) = schedule::release(&id.0) else {
+      return Ok(Response::err("No such held job"));
+    };
+    info!("Releasing held job {} to {}", job.id, job.printer);
+
+    match print_saved(job, &self.options) {
+      Ok(mut result) => {
+        result.job_id = Some(id.0);
+        Ok(Response::ok(result))
+      }
+      Err(e) => match e.downcast::<CodedError>() {
+        Ok(e) => {
+          warn!("Held job {} rejected: {}", id.0, e);
+          Err(e.into_error::<PrintResult>())
+        }
+        Err(e) => {
+          error!("Held job {} failed: {:#}", id.0, e);
+          Ok(Response::err(format!("Failed to print: {}", e)))
+        }
+      },
+    }
+  }
+
+  /// Merge PDF files
+  ///
+  /// 按顺序合并多个 PDF 文件
+  #[oai(
+    path = "/pdf/merge",
+    method = "post",
+    operation_id = "mergePdf",
+    tag = "ApiTag::Pdf"
+  )]
+  async fn merge_pdf(&self, payload: Json<MergePayload>) -> Result<PdfFile> {
+    debug!("Merging {} PDF files", payload.files.len());
+    self.log_request(&payload.0);
+
+    if payload.files.is_empty() {
+      return Ok(Response::err("No files to merge"));
+    }
+
+    let files = payload
+      .files
+      .iter()
+      .map(|f| f.0.as_slice())
+      .collect::<Vec<_>>();
+    let merged = pdf::merge(&files).and_then(|file| Ok((pdf::page_count(&file)?, file)));
+
+    match merged {
+      Ok((pages, file)) => Ok(Response::ok(PdfFile {
+        file: Base64(file),
+        pages,
+      })),
+      Err(e) => Ok(Response::err(format!("Failed to merge: {}", e))),
+    }
+  }
+
+  /// Extract PDF pages
+  ///
+  /// 提取 PDF 文件的指定页面，可用于预览将要打印的内容。请求头 `Accept` 为 `application/pdf` 时直接返回文件
+  #[oai(
+    path = "/pdf/extract",
+    method = "post",
+    operation_id = "extractPdf",
+    tag = "ApiTag::Pdf"
+  )]
+  async fn extract_pdf(&self, payload: Json<ExtractPayload>, req: &Request) -> PdfResponse {
+    debug!("Extracting pages {} from a PDF file", payload.page_range);
+    self.log_request(&payload.0);
+
+    let raw = req
+      .header(header::ACCEPT)
+      .is_some_and(|accept| accept.contains("application/pdf"));
+    let extracted = extract_pages(&payload.file, &payload.page_range);
+
+    let content = match extracted {
+      Ok((_, file)) if raw => PdfContent::Pdf(Binary(file)),
+      Ok((pages, file)) => PdfContent::Json(Response::ok(PdfFile {
+        file: Base64(file),
+        pages,
+      })),
+      Err(e) => PdfContent::Json(Response::err(format!("Failed to extract pages: {}", e))),
+    };
+
+    PdfResponse::Ok(content)
+  }
+
+  /// Render PDF pages
+  ///
+  /// 将 PDF 页面渲染为图片，如用于选择页面的缩略图
+  #[oai(
+    path = "/pdf/render",
+    method = "post",
+    operation_id = "renderPdf",
+    tag = "ApiTag::Pdf"
+  )]
+  async fn render_pdf(&self, payload: Json<RenderPayload>) -> Result<Vec<PageImage>> {
+    debug!("Rendering a PDF file");
+    self.log_request(&payload.0);
+
+    let RenderPayload {
+      file,
+      pages,
+      width,
+      format,
+    } = payload.0;
+
+    if file.0.len() > MAX_PDF_SIZE {
+      return Ok(Response::err(format!(
+        "File is too large, the limit is {} bytes",
+        MAX_PDF_SIZE
+      )));
+    }
+
+    // 渲染较慢，不阻塞请求处理
+    let rendered = tokio::task::spawn_blocking(move || {
+      pdf::render(
+        &file,
+        pages.as_deref(),
+        width.unwrap_or(DEFAULT_RENDER_WIDTH),
+        format.unwrap_or(ImageType::Png).into(),
+        MAX_RENDER_PAGES,
+        MAX_RENDER_PIXELS,
+      )
+    })
+    .await
+    .map_err(InternalServerError)?;
+
+    match rendered {
+      Ok(rendered) => Ok(Response::ok(
+        rendered.into_iter().map(PageImage::from).collect(),
+      )),
+      Err(e) => Ok(Response::err(format!("Failed to render: {}", e))),
+    }
+  }
+
+  /// Estimate ink coverage
+  ///
+  /// 以低分辨率渲染 PDF 页面，估算各页及平均的墨迹覆盖率，有彩色内容时按 CMYK 通道分别估算。
+  /// 只是按屏幕颜色的粗略估计，用于评估耗材，与驱动及打印机实际的用墨不同
+  #[oai(
+    path = "/pdf/coverage",
+    method = "post",
+    operation_id = "estimateCoverage",
+    tag = "ApiTag::Pdf"
+  )]
+  pub async fn estimate_coverage(
+    &self,
+    payload: Json<CoveragePayload>,
+  ) -> Result<CoverageEstimate> {
+    debug!("Estimating the ink coverage of a PDF file");
+    self.log_request(&payload.0);
+
+    let CoveragePayload { file, pages, dpi } = payload.0;
+
+    if file.0.len() > MAX_PDF_SIZE {
+      return Ok(Response::err(format!(
+        "File is too large, the limit is {} bytes",
+        MAX_PDF_SIZE
+      )));
+    }
+
+    // 渲染较慢，不阻塞请求处理
+    let dpi = dpi.unwrap_or(DEFAULT_COVERAGE_DPI);
+    let estimated = tokio::task::spawn_blocking(move || {
+      pdf::coverage(&file, pages.as_deref(), dpi, MAX_COVERAGE_PAGES)
+    })
+    .await
+    .map_err(InternalServerError)?;
+
+    match estimated {
+      Ok(pages) => Ok(Response::ok(CoverageEstimate::new(pages, dpi))),
+      Err(e) => Ok(Response::err(format!("Failed to estimate coverage: {}", e))),
+    }
+  }
+
+  /// Upload a template
+  ///
+  /// 上传带表单的 PDF 模板，供按模板打印。需要在 `X-API-Key` 请求头中提供 API 密钥
+  #[oai(
+    path = "/templates",
+    method = "post",
+    operation_id = "addTemplate",
+    tag = "ApiTag::Admin"
+  )]
+  async fn add_template(
+    &self,
+    payload: Json<TemplatePayload>,
+    /// API 密钥
+    #[oai(name = "X-API-Key")]
+    api_key: Header<Option<String>>,
+  ) -> Result<Template> {
+    debug!("Adding template {}", payload.name);
+    self.log_request(&payload.0);
+
+    if !self.authorized(api_key.0) {
+      return Err(CodedError::unauthorized().into_error::<Template>());
+    }
+
+    let TemplatePayload { name, file } = payload.0;
+
+    if file.0.len() > MAX_PDF_SIZE {
+      return Ok(Response::err(format!(
+        "File is too large, the limit is {} bytes",
+        MAX_PDF_SIZE
+      )));
+    }
+
+    match template::add(name, &file) {
+      Ok(template) => {
+        info!("Added template {} ({})", template.id, template.name);
+        Ok(Response::ok(template.into()))
+      }
+      Err(e) => Ok(Response::err(format!("Failed to add template: {}", e))),
+    }
+  }
+
+  /// Get templates
+  ///
+  /// 获取全部模板及其字段，按上传时间排序
+  #[oai(path = "/templates", method = "get", operation_id = "getTemplates")]
+  async fn get_templates(&self) -> Json<Response<Vec<Template>>> {
+    debug!("Getting templates");
+    Response::ok(template::list().into_iter().map(Template::from).collect())
+  }
+
+  /// Print a template
+  ///
+  /// 填写模板后打印，批量填写时每组变量生成一份文档，合并为一个任务打印。缺少必填变量时不打印，并按组列出缺少的变量
+  #[oai(
+    path = "/templates/:id/print",
+    method = "post",
+    operation_id = "printTemplate"
+  )]
+  async fn print_template(
+    &self,
+    id: Path<String>,
+    payload: Json<TemplatePrintPayload>,
+    client: Client,
+    /// 一次性打印令牌，启用令牌时必须提供
+    #[oai(name = "X-Print-Token")]
+    token: Header<Option<String>>,
+    /// 截止时间，收到请求后多少毫秒内未提交到系统打印队列时取消任务，见 `deadline_ms`
+    #[oai(name = "X-Request-Deadline-Ms")]
+    deadline_ms: Header<Option<u64>>,
+  ) -> Result<PrintResult> {
+    debug!("Printing template {} with {:#?}", id.0, payload.settings);
+    self.log_request(&payload.0);
+    let deadline = deadline_ms.0.and_then(Deadline::after);
+
+    let Some(info) = template::get(&id.0) else {
+      return Ok(Response::err("No such template"));
+    };
+
+    let TemplatePrintPayload {
+      variables,
+      items,
+      settings,
+      queue_if_not_ready,
+      print_at,
+      ttl_seconds,
+    } = payload.0;
+    let items = match (variables, items) {
+      (Some(variables), None) => vec![variables],
+      (None, Some(items)) if !items.is_empty() => items,
+      (Some(_), Some(_)) => return Ok(Response::err("Only one of variables and items can be set")),
+      (None, _) => return Ok(Response::err("No variables to fill")),
+    };
+
+    let missing = items
+      .iter()
+      .enumerate()
+      .filter_map(|(i, variables)| {
+        let names = info
+          .fields
+          .iter()
+          .filter(|f| f.required && variables.get(&f.name).is_none_or(|v| v.is_empty()))
+          .map(|f| f.name.as_str())
+          .collect::<Vec<_>>();
+        (!names.is_empty()).then(|| format!("item {}: {}", i, names.join(", ")))
+      })
+      .collect::<Vec<_>>();
+
+    if !missing.is_empty() {
+      return Ok(Response::err_with(
+        ErrorCode::MissingVariables,
+        format!("Missing required variables: {}", missing.join("; ")),
+      ));
+    }
+
+    // 填写较慢，不阻塞请求处理
+    let filled = tokio::task::spawn_blocking(move || {
+      let file = template::file(&info.id)?;
+      items
+        .iter()
+        .map(|variables| pdf::fill_form(&file, variables))
+        .collect::<anyhow::Result<Vec<_>>>()
+    })
+    .await
+    .map_err(InternalServerError)?;
+
+    let files = match filled {
+      Ok(files) => files,
+      Err(e) => return Ok(Response::err(format!("Failed to fill template: {}", e))),
+    };
+    let payload = PrintPayload {
+      files: Some(files.into_iter().map(Base64).collect()),
+      settings,
+      queue_if_not_ready,
+      print_at,
+      ttl_seconds,
+      ..Default::default()
+    };
+
+    self.submit(payload, client, token.0, deadline).await
+  }
+
+  /// Start an upload
+  ///
+  /// 开始分块上传较大的文件，返回上传 ID 及分块大小。之后按分块大小切分文件，用 `PUT /uploads/{id}/chunks/{n}` 逐块上传，中断后用 `GET /uploads/{id}` 查询缺少的分块续传，全部上传后用 `POST /uploads/{id}/print` 打印
+  /// 启用打印令牌时需要在 `X-Print-Token` 请求头中提供打印令牌（打印上传时才标记为已使用）或在 `X-API-Key` 请求头中提供 API 密钥。
+  /// 进行中的上传数或总大小达到上限时返回 429 或 507
+  #[oai(path = "/uploads", method = "post", operation_id = "createUpload")]
+  async fn create_upload(
+    &self,
+    payload: Json<UploadPayload>,
+    /// 打印令牌，启用令牌且未提供 API 密钥时必须提供
+    #[oai(name = "X-Print-Token")]
+    token: Header<Option<String>>,
+    /// API 密钥
+    #[oai(name = "X-API-Key")]
+    api_key: Header<Option<String>>,
+  ) -> Result<UploadStatus> {
+    debug!("Starting an upload of {} bytes", payload.size);
+    self.log_request(&payload.0);
+    self
+      .authorize_upload(token.0, api_key.0)
+      .map_err(CodedError::into_error::<UploadStatus>)?;
+
+    let UploadPayload { size, sha256 } = payload.0;
+    let quota = self.options.upload_quota;
+    let idle = self.options.upload_idle_timeout;
+    let created = tokio::task::spawn_blocking(move || upload::create(size, sha256, quota, idle))
+      .await
+      .map_err(InternalServerError)?;
+    upload_response(created, idle).map_err(CodedError::into_error::<UploadStatus>)
+  }
+
+  /// Get an upload
+  ///
+  /// 获取上传的进度，包括已收到的分块及缺少的分块。上传不存在或已过期时返回错误。
+  /// 需要的令牌或 API 密钥同 `POST /uploads`
+  #[oai(path = "/uploads/:id", method = "get", operation_id = "getUpload")]
+  async fn get_upload(
+    &self,
+    id: Path<String>,
+    /// 打印令牌，启用令牌且未提供 API 密钥时必须提供
+    #[oai(name = "X-Print-Token")]
+    token: Header<Option<String>>,
+    /// API 密钥
+    #[oai(name = "X-API-Key")]
+    api_key: Header<Option<String>>,
+  ) -> Result<UploadStatus> {
+    debug!("Getting upload {}", id.0);
+    self
+      .authorize_upload(token.0, api_key.0)
+      .map_err(CodedError::into_error::<UploadStatus>)?;
+
+    let idle = self.options.upload_idle_timeout;
+    let upload = tokio::task::spawn_blocking(move || upload::get(&id.0, idle))
+      .await
+      .map_err(InternalServerError)?;
+    upload_response(upload.ok_or(UploadError::NotFound), idle)
+      .map_err(CodedError::into_error::<UploadStatus>)
+  }
+
+  /// Upload a chunk
+  ///
+  /// 上传一个分块，请求体为分块内容，序号从 0 开始。分块大小或 SHA-256 不符时不保存，重复上传时覆盖之前的内容
+  /// 读取请求体时连接中断的分块不保存，可重新上传；不再收到分块的上传在空闲超时（`--upload-idle-timeout`）后删除。
+  /// 需要的令牌或 API 密钥同 `POST /uploads`
+  #[oai(
+    path = "/uploads/:id/chunks/:n",
+    method = "put",
+    operation_id = "putUploadChunk"
+  )]
+  async fn put_upload_chunk(
+    &self,
+    id: Path<String>,
+    /// 分块序号
+    n: Path<u32>,
+    body: Binary<Vec<u8>>,
+    /// 分块内容的 SHA-256（十六进制）
+    #[oai(name = "X-Chunk-SHA256")]
+    sha256: Header<String>,
+    /// 打印令牌，启用令牌且未提供 API 密钥时必须提供
+    #[oai(name = "X-Print-Token")]
+    token: Header<Option<String>>,
+    /// API 密钥
+    #[oai(name = "X-API-Key")]
+    api_key: Header<Option<String>>,
+  ) -> Result<UploadStatus> {
+    debug!("Receiving chunk {} of upload {}", n.0, id.0);
+    self
+      .authorize_upload(token.0, api_key.0)
+      .map_err(CodedError::into_error::<UploadStatus>)?;
+
+    let idle = self.options.upload_idle_timeout;
+    let saved =
+      tokio::task::spawn_blocking(move || upload::put_chunk(&id.0, n.0, &body.0, &sha256.0, idle))
+        .await
+        .map_err(InternalServerError)?;
+    upload_response(saved, idle).map_err(CodedError::into_error::<UploadStatus>)
+  }
+
+  /// Print an upload
+  ///
+  /// 按顺序合并全部分块后打印，与 `POST /print` 相同。缺少分块时不打印，并列出缺少的分块序号。提交成功后删除上传
+  #[oai(
+    path = "/uploads/:id/print",
+    method = "post",
+    operation_id = "printUpload"
+  )]
+  async fn print_upload(
+    &self,
+    id: Path<String>,
+    payload: Json<UploadPrintPayload>,
+    client: Client,
+    /// 一次性打印令牌，启用令牌时必须提供
+    #[oai(name = "X-Print-Token")]
+    token: Header<Option<String>>,
+    /// 截止时间，收到请求后多少毫秒内未提交到系统打印队列时取消任务，见 `deadline_ms`
+    #[oai(name = "X-Request-Deadline-Ms")]
+    deadline_ms: Header<Option<u64>>,
+  ) -> Result<PrintResult> {
+    debug!("Printing upload {} with {:#?}", id.0, payload.settings);
+    self.log_request(&payload.0);
+    let deadline = deadline_ms.0.and_then(Deadline::after);
+
+    let idle = self.options.upload_idle_timeout;
+    let upload_id = id.0.clone();
+    let upload = tokio::task::spawn_blocking(move || upload::get(&upload_id, idle))
+      .await
+      .map_err(InternalServerError)?;
+    let Some(upload) = upload else {
+      return Ok(Response::err(UploadError::NotFound));
+    };
+    let missing = upload.missing();
+    if !missing.is_empty() {
+      let missing = missing.iter().map(u32::to_string).collect::<Vec<_>>();
+      return Ok(Response::err_with(
+        ErrorCode::IncompleteUpload,
+        format!("Missing chunks: {}", missing.join(", ")),
+      ));
+    }
+
+    // 合并较大的文件较慢，不阻塞请求处理
+    let assembled = tokio::task::spawn_blocking(move || upload::assemble(&upload))
+      .await
+      .map_err(InternalServerError)?;
+    let file = match assembled {
+      Ok(file) => file,
+      Err(e) => return Ok(upload_error(e)),
+    };
+
+    let UploadPrintPayload {
+      settings,
+      queue_if_not_ready,
+      print_at,
+      ttl_seconds,
+    } = payload.0;
+    let payload = PrintPayload {
+      file: Some(Base64(file)),
+      settings,
+      queue_if_not_ready,
+      print_at,
+      ttl_seconds,
+      ..Default::default()
+    };
+
+    let resp = self.submit(payload, client, token.0, deadline).await?;
+    // 提交失败时保留上传，客户端可以修改打印设置后重试
+    if resp.code == 0 {
+      upload::remove(&id.0);
+    }
+    Ok(resp)
+  }
+
+  /// Print a table
+  ///
+  /// 将 CSV 或行数据排版为表格后打印，每页重复表头，单元格内容超出列宽时折行。试运行时直接返回生成的 PDF 文件
+  #[oai(path = "/print/table", method = "post", operation_id = "printTable")]
+  async fn print_table(
+    &self,
+    payload: Json<TablePayload>,
+    client: Client,
+    /// 一次性打印令牌，启用令牌时必须提供
+    #[oai(name = "X-Print-Token")]
+    token: Header<Option<String>>,
+    /// 截止时间，收到请求后多少毫秒内未提交到系统打印队列时取消任务，见 `deadline_ms`
+    #[oai(name = "X-Request-Deadline-Ms")]
+    deadline_ms: Header<Option<u64>>,
+  ) -> poem::Result<TableResponse> {
+    debug!("Printing a table with {:#?}", payload.settings);
+    self.log_request(&payload.0);
+    let deadline = deadline_ms.0.and_then(Deadline::after);
+
+    let TablePayload {
+      csv,
+      csv_header,
+      rows,
+      columns,
+      margin,
+      font_size,
+      zebra,
+      dry_run,
+      settings,
+      queue_if_not_ready,
+      print_at,
+      ttl_seconds,
+    } = payload.0;
+    let (columns, rows) = match table_rows(csv, csv_header.unwrap_or_default(), rows, columns) {
+      Ok(table) => table,
+      Err(e) => {
+        let resp = Response::err(format!("Invalid table: {}", e));
+        return Ok(TableResponse::Ok(TableContent::Json(resp)));
+      }
+    };
+
+    // 按打印设置的纸张排版，横向时交换宽高
+    let target = settings.clone().or_else(default_settings);
+    let page_size = target.as_ref().and_then(|s| s.page_size.as_ref());
+    let (width, height) = page_size
+      .map(|size| (size.width, size.height))
+      .unwrap_or(DEFAULT_TABLE_PAGE);
+    // 高度随内容时所有行排在同一页，不交换宽高
+    let auto_height = page_size.is_some_and(PageSize::is_auto_height);
+    let landscape = target.as_ref().is_some_and(|s| {
+      matches!(
+        s.orientation,
+        Some(Orientation::Landscape | Orientation::ReverseLandscape)
+      )
+    });
+    let layout = pdf::TableLayout {
+      page_width: if landscape && !auto_height {
+        height
+      } else {
+        width
+      },
+      page_height: match (auto_height, landscape) {
+        (true, _) => None,
+        (false, true) => Some(width),
+        (false, false) => Some(height),
+      },
+      margin: margin.unwrap_or(DEFAULT_TABLE_MARGIN),
+      font_size: font_size.unwrap_or(DEFAULT_TABLE_FONT_SIZE),
+      zebra: zebra.unwrap_or_default(),
+    };
+    let font = self.options.font.clone();
+
+    // 排版较慢，不阻塞请求处理
+    let generated = tokio::task::spawn_blocking(move || {
+      pdf::table(&columns, &rows, &layout, font.as_deref().map(Vec::as_slice))
+    })
+    .await
+    .map_err(InternalServerError)?;
+
+    let file = match generated {
+      Ok(file) => file,
+      Err(e) => {
+        let resp = Response::err(format!("Failed to lay out the table: {}", e));
+        return Ok(TableResponse::Ok(TableContent::Json(resp)));
+      }
+    };
+
+    if dry_run.unwrap_or_default() {
+      return Ok(TableResponse::Ok(TableContent::Pdf(Binary(file))));
+    }
+
+    let payload = PrintPayload {
+      file: Some(Base64(file)),
+      settings,
+      queue_if_not_ready,
+      print_at,
+      ttl_seconds,
+      ..Default::default()
+    };
+
+    let Json(resp) = self.submit(payload, client, token.0, deadline).await?;
+    let resp = Response {
+      code: resp.code,
+      error: resp.error,
+      msg: resp.msg,
+      data: resp.data.map(Box::new),
+      quarantine_id: resp.quarantine_id,
+    };
+    Ok(TableResponse::Ok(TableContent::Json(Json(resp))))
+  }
+
+  /// Print a label
+  ///
+  /// 按布局生成与标签大小相同的页面后打印，可包括文本、二维码、Code 128 及 EAN-13 条码
+  #[oai(path = "/print/label", method = "post", operation_id = "printLabel")]
+  async fn print_label(
+    &self,
+    payload: Json<LabelPayload>,
+    client: Client,
+    /// 一次性打印令牌，启用令牌时必须提供
+    #[oai(name = "X-Print-Token")]
+    token: Header<Option<String>>,
+    /// 截止时间，收到请求后多少毫秒内未提交到系统打印队列时取消任务，见 `deadline_ms`
+    #[oai(name = "X-Request-Deadline-Ms")]
+    deadline_ms: Header<Option<u64>>,
+  ) -> Result<PrintResult> {
+    debug!("Printing a label with {:#?}", payload.settings);
+    self.log_request(&payload.0);
+    let deadline = deadline_ms.0.and_then(Deadline::after);
+
+    let LabelPayload {
+      width,
+      height,
+      elements,
+      mut settings,
+      queue_if_not_ready,
+      print_at,
+      ttl_seconds,
+    } = payload.0;
+    let elements = match label_elements(&elements, width, height) {
+      Ok(elements) => elements,
+      Err(e) => return Ok(Response::err(e)),
+    };
+    let font = self.options.font.clone();
+    let generated = tokio::task::spawn_blocking(move || {
+      pdf::label(width, height, &elements, font.as_deref().map(Vec::as_slice))
+    })
+    .await
+    .map_err(InternalServerError)?;
+
+    let file = match generated {
+      Ok(file) => file,
+      Err(e) => return Ok(Response::err(format!("Failed to lay out the label: {}", e))),
+    };
+
+    // 纸张默认与标签一样大
+    if let Some(settings) = &mut settings {
+      settings.page_size.get_or_insert_with(|| PageSize {
+        width: (width * 1000.0).round() as u32,
+        height: (height * 1000.0).round() as u32,
+        ..Default::default()
+      });
+    }
+
+    let payload = PrintPayload {
+      file: Some(Base64(file)),
+      settings,
+      queue_if_not_ready,
+      print_at,
+      ttl_seconds,
+      ..Default::default()
+    };
+
+    self.submit(payload, client, token.0, deadline).await
+  }
+
+  /// Print a PDF file
+  ///
+  /// 打印 PDF 文件，指定 `print_at` 时在计划时间打印
+  #[oai(path = "/print", method = "post", operation_id = "print")]
+  async fn print(
+    &self,
+    payload: Json<PrintPayload>,
+    client: Client,
+    /// 一次性打印令牌，启用令牌时必须提供
+    #[oai(name = "X-Print-Token")]
+    token: Header<Option<String>>,
+    /// 截止时间，收到请求后多少毫秒内未提交到系统打印队列时取消任务，见 `deadline_ms`
+    #[oai(name = "X-Request-Deadline-Ms")]
+    deadline_ms: Header<Option<u64>>,
+  ) -> Result<PrintResult> {
+    debug!("Printing with {:#?}", payload.settings);
+    self.log_request(&payload.0);
+    // 请求体中的截止时间优先
+    let deadline = payload
+      .deadline_ms
+      .or(deadline_ms.0)
+      .and_then(Deadline::after);
+    self.submit(payload.0, client, token.0, deadline).await
+  }
+}
+
+/// 解析表格的列和行，行的单元格多于列时忽略多出的部分
+fn table_rows(
+  csv: Option<String>,
+  csv_header: bool,
+  rows: Option<Vec<Vec<String>>>,
+  columns: Option<Vec<TableColumn>>,
+) -> anyhow::Result<(Vec<pdf::TableColumn>, Vec<Vec<String>>)> {
+  let (header, rows) = match (csv, rows) {
+    (Some(csv), None) => {
+      let mut records = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(csv.as_bytes())
+        .records()
+        .map(|record| Ok(record?.iter().map(str::to_string).collect::<Vec<_>>()))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+      let header = if csv_header && !records.is_empty() {
+        Some(records.remove(0))
+      } else {
+        None
+      };
+      (header, records)
+    }
+    (None, Some(rows)) => (None, rows),
+    (Some(_), Some(_)) => bail!("Only one of csv and rows can be set"),
+    (None, None) => bail!("No rows to print"),
+  };
+
+  if rows.len() > MAX_TABLE_ROWS {
+    bail!("Too many rows, the limit is {}", MAX_TABLE_ROWS);
+  }
+
+  let columns = match (columns, header) {
+    (Some(columns), _) => columns
+      .into_iter()
+      .map(|c| pdf::TableColumn {
+        header: c.header,
+        width: c.width,
+        align: c.align.map(pdf::TextAlign::from).unwrap_or_default(),
+      })
+      .collect::<Vec<_>>(),
+    (None, Some(header)) => header
+      .into_iter()
+      .map(|header| pdf::TableColumn {
+        header,
+        width: None,
+        align: pdf::TextAlign::Left,
+      })
+      .collect(),
+    (None, None) => bail!("No columns, set columns or csv_header"),
+  };
+
+  if columns.is_empty() {
+    bail!("No columns");
+  }
+
+  Ok((columns, rows))
+}
+
+/// 解析标签元素并生成条码，出错时指明元素的序号（从 0 开始）
+fn label_elements(
+  elements: &[LabelElement],
+  width: f32,
+  height: f32,
+) -> anyhow::Result<Vec<pdf::LabelElement>> {
+  let element = |element: &LabelElement| -> anyhow::Result<pdf::LabelElement> {
+    let x = parse_length(&element.x, width)?;
+    let y = parse_length(&element.y, height)?;
+    let required = |value: &Option<String>, total, name| match value {
+      Some(value) => parse_length(value, total),
+      None => Err(anyhow!("{} is required", name)),
+    };
+
+    Ok(match element.kind {
+      LabelElementType::Text => pdf::LabelElement::Text {
+        x,
+        y,
+        text: element.data.clone(),
+        font_size: element.font_size.unwrap_or(DEFAULT_LABEL_FONT_SIZE),
+      },
+      LabelElementType::Qr => pdf::LabelElement::Matrix {
+        x,
+        y,
+        size: required(&element.width, width, "width")?,
+        modules: barcode::qr(&element.data)?,
+      },
+      LabelElementType::Code128 | LabelElementType::Ean13 => pdf::LabelElement::Bars {
+        x,
+        y,
+        width: required(&element.width, width, "width")?,
+        height: required(&element.height, height, "height")?,
+        modules: match element.kind {
+          LabelElementType::Code128 => barcode::code128(&element.data)?,
+          _ => barcode::ean13(&element.data)?,
+        },
+      },
+    })
+  };
+
+  elements
+    .iter()
+    .enumerate()
+    .map(|(i, e)| element(e).map_err(|e| anyhow!("Invalid element {}: {}", i, e)))
+    .collect()
+}
+
+/// 解析毫米数或相对于 `total` 毫米的百分比
+fn parse_length(value: &str, total: f32) -> anyhow::Result<f32> {
+  let value = value.trim();
+  let (number, scale) = match value.strip_suffix('%') {
+    Some(percent) => (percent, total / 100.0),
+    None => (value.strip_suffix("mm").unwrap_or(value), 1.0),
+  };
+
+  match number.trim().parse::<f32>() {
+    Ok(n) if n.is_finite() && n >= 0.0 => Ok(n * scale),
+    _ => bail!("Invalid length {}", value),
+  }
+}
+
+/// 上传的错误对应的响应，校验失败时带错误类型
+fn upload_error<T>(e: UploadError) -> Json<Response<T>>
+where
+  T: ParseFromJSON + ToJSON + std::fmt::Debug,
+{
+  match e {
+    UploadError::ChecksumMismatch(_) => Response::err_with(ErrorCode::ChecksumMismatch, e),
+    UploadError::Failed(e) => Response::err(format!("Failed to process the upload: {:#}", e)),
+    e => Response::err(e),
+  }
+}
+
+/// 上传的结果，超过上限时返回带 HTTP 状态的错误
+fn upload_response(
+  result: std::result::Result<Upload, UploadError>,
+  idle: Duration,
+) -> std::result::Result<Json<Response<UploadStatus>>, CodedError> {
+  match result {
+    Ok(upload) => Ok(Response::ok(UploadStatus::new(upload, idle))),
+    Err(UploadError::QuotaExceeded(e)) => Err(e),
+    Err(e) => Ok(upload_error(e)),
+  }
+}
+
+/// 按页码范围提取页面，返回页数及新的文件
+fn extract_pages(file: &[u8], range: &str) -> anyhow::Result<(u32, Vec<u8>)> {
+  if file.len() > MAX_PDF_SIZE {
+    bail!("File is too large, the limit is {} bytes", MAX_PDF_SIZE);
+  }
+
+  let count = pdf::page_count(file)?;
+  let pages = pdf::parse_page_range(range, count)?;
+
+  if pages.is_empty() {
+    bail!("No pages selected");
+  }
+
+  let extracted = pdf::rearrange(file, &pages, |_| pdf::PageTransform {
+    rotate: 0,
+    margins: None,
+  })?;
+
+  Ok((pages.len() as u32, extracted.data))
+}
+
+/// 提交 `POST /print` 请求体格式的打印任务，返回统一响应的 JSON，用于 HTTP 以外的接口，如 MQTT。
+/// 这类客户端没有 IP 地址，设置了访问策略时不能使用任何打印机，启用打印令牌时也无法打印
+#[cfg(feature = "mqtt")]
+pub async fn submit_message(options: &ApiOptions, body: &[u8], source: &'static str) -> Value {
+  debug!("Submitting a print job from {}", source);
+  let envelope = |code: ErrorCode, msg: String| {
+    Response::<PrintResult>::err_with(code, msg)
+      .0
+      .to_json()
+      .unwrap_or_default()
+  };
+
+  let payload = serde_json::from_slice::<Value>(body)
+    .map_err(|e| e.to_string())
+    .and_then(|value| PrintPayload::parse_from_json(Some(value)).map_err(|e| e.into_message()));
+  let payload = match payload {
+    Ok(payload) => payload,
+    Err(e) => {
+      return envelope(
+        ErrorCode::InvalidRequest,
+        format!("Invalid print payload: {}", e),
+      )
+    }
+  };
+
+  let api = Api {
+    options: options.clone(),
+  };
+  api.log_request(&payload);
+  let deadline = payload.deadline_ms.and_then(Deadline::after);
+  let client = Client::default();
+
+  match api.submit(payload, client, None, deadline).await {
+    Ok(resp) => resp.0.to_json().unwrap_or_default(),
+    Err(e) => {
+      // 带状态码的错误已经是统一响应
+      let msg = e.to_string();
+      let body = e.into_response().into_body().into_bytes().await;
+      body
+        .ok()
+        .and_then(|body| serde_json::from_slice(&body).ok())
+        .unwrap_or_else(|| envelope(ErrorCode::InternalError, msg))
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+  };
+
+  use super::{
+    super::{options::CapabilitySource, simulate},
+    *,
+  };
+
+  #[test]
+  fn converts_units_to_microns() {
+    assert_eq!(LengthUnit::Micron.to_micron(210000.0), 210000);
+    assert_eq!(LengthUnit::Mm.to_micron(210.0), 210000);
+    assert_eq!(LengthUnit::Mm.to_micron(99.9996), 100000);
+    assert_eq!(LengthUnit::Inch.to_micron(8.5), 215900);
+    assert_eq!(LengthUnit::Pt.to_micron(72.0), 25400);
+    assert_eq!(LengthUnit::Pt.to_micron(595.0), 209903);
+  }
+
+  #[test]
+  fn converts_microns_to_units() {
+    assert_eq!(LengthUnit::Micron.micron_to_units(215900), 215900.0);
+    assert_eq!(LengthUnit::Mm.micron_to_units(215900), 215.9);
+    assert_eq!(LengthUnit::Inch.micron_to_units(279400), 11.0);
+    assert_eq!(LengthUnit::Pt.micron_to_units(215900), 612.0);
+    assert_eq!(LengthUnit::Inch.micron_to_units(210000), 8.267717);
+  }
+
+  #[test]
+  fn round_trips_letter_and_a4() {
+    let units = [
+      LengthUnit::Micron,
+      LengthUnit::Mm,
+      LengthUnit::Inch,
+      LengthUnit::Pt,
+    ];
+    // Letter、A4
+    for (width, height) in [(215900, 279400), (210000, 297000)] {
+      for units in units {
+        let (w, h) = (units.micron_to_units(width), units.micron_to_units(height));
+        assert_eq!(units.to_micron(w), width, "{:?}", units);
+        assert_eq!(units.to_micron(h), height, "{:?}", units);
+      }
+    }
+  }
+
+  #[test]
+  fn normalizes_page_size_dimensions() {
+    let mut settings = PrintSettings {
+      page_size: Some(PageSize {
+        dimensions: Some(Dimensions {
+          width: 8.5,
+          height: 11.0,
+          units: LengthUnit::Inch,
+        }),
+        ..Default::default()
+      }),
+      ..Default::default()
+    };
+
+    settings.normalize_units();
+    let page_size = settings.page_size.unwrap();
+    assert_eq!((page_size.width, page_size.height), (215900, 279400));
+    assert!(page_size.dimensions.is_none());
+  }
+
+  #[tokio::test]
+  async fn checks_existence_without_fetching_capabilities() {
+    // 任务及暂停状态保存在临时的数据目录中
+    let paths = instance::use_temp_paths();
+    let fetches = Arc::new(AtomicUsize::new(0));
+    let counter = fetches.clone();
+    let api = Api {
+      options: ApiOptions {
+        simulate: paths.data_dir.clone(),
+        capability_source: Some(CapabilitySource(Arc::new(move || {
+          counter.fetch_add(1, Ordering::Relaxed);
+          simulate::capabilities()
+        }))),
+        ..Default::default()
+      },
+    };
+    let req = Request::builder().finish();
+    let get_printer = |exists_only| {
+      api.get_printer(
+        Path(simulate::PRINTER.to_string()),
+        Query(None),
+        Query(None),
+        Query(None),
+        Query(None),
+        Query(Some(exists_only)),
+        Query(None),
+        &req,
+      )
+    };
+
+    let Json(response) = get_printer(true).await.unwrap();
+    assert_eq!(fetches.load(Ordering::Relaxed), 0);
+    let pcap = response.data.unwrap();
+    assert!(pcap.page_sizes.is_none());
+    let state = pcap.state.unwrap();
+    assert!(!state.paused);
+    assert_eq!(state.spooler_paused, Some(false));
+
+    let Json(response) = get_printer(false).await.unwrap();
+    assert_eq!(fetches.load(Ordering::Relaxed), 1);
+    assert!(response.data.unwrap().page_sizes.is_some());
+  }
+}
@@ -0,0 +1,41 @@
+use anyhow::bail;
+use barcoders::sym::{code128::Code128, ean13::EAN13};
+use qrcode::{Color, QrCode};
+
+/// 生成二维码，返回按行排列的模块，`true` 为深色，不含静区
+pub fn qr(data: &str) -> anyhow::Result<Vec<Vec<bool>>> {
+  let code = QrCode::new(data)?;
+  let width = code.width();
+  let modules = code
+    .to_colors()
+    .chunks(width)
+    .map(|row| row.iter().map(|c| *c == Color::Dark).collect())
+    .collect();
+  Ok(modules)
+}
+
+/// 生成 Code 128 条码，返回各模块，`true` 为条。
+/// 未以字符集切换字符（À、Ɓ、Ć）开头时使用字符集 B
+pub fn code128(data: &str) -> anyhow::Result<Vec<bool>> {
+  if data.is_empty() {
+    bail!("Barcode data is empty");
+  }
+
+  let data = if data.starts_with(['À', 'Ɓ', 'Ć']) {
+    data.to_string()
+  } else {
+    format!("Ɓ{}", data)
+  };
+  let bars = Code128::new(data)?.encode();
+  Ok(bars.into_iter().map(|b| b == 1).collect())
+}
+
+/// 生成 EAN-13 条码，`data` 为 12 位数字或带校验位的 13 位数字
+pub fn ean13(data: &str) -> anyhow::Result<Vec<bool>> {
+  if !matches!(data.len(), 12 | 13) || !data.bytes().all(|b| b.is_ascii_digit()) {
+    bail!("EAN-13 data must be 12 or 13 digits");
+  }
+
+  let bars = EAN13::new(data)?.encode();
+  Ok(bars.into_iter().map(|b| b == 1).collect())
+}
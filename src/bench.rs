@@ -0,0 +1,277 @@
+use std::{
+  path::Path,
+  sync::{
+    atomic::{AtomicUsize, Ordering},
+    Mutex,
+  },
+  thread,
+  time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail, Context};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use poem::http::StatusCode;
+use poem_openapi::types::ParseFromJSON;
+use serde::Serialize;
+use serde_json::{json, Value};
+use ureq::Agent;
+
+use crate::api::{
+  error::{CodedError, ErrorCode},
+  options::ApiOptions,
+  print::{self, JobTiming},
+  simulate,
+};
+
+/// 基准测试的目标
+pub enum Target {
+  /// 直接调用打印流程，`simulate` 为 true 时使用模拟打印机，结果保存到临时目录
+  Pipeline { simulate: bool },
+  /// 通过 HTTP API 打印到正在运行的服务，如 `http://127.0.0.1:8080`
+  Http { url: String },
+}
+
+/// 基准测试选项
+pub struct BenchOptions {
+  pub target: Target,
+  /// 打印机名称，使用模拟打印机时可以为空
+  pub printer: Option<String>,
+  pub jobs: usize,
+  pub concurrency: usize,
+}
+
+/// 延迟的分位数，单位为毫秒
+#[derive(Debug, Default, Serialize)]
+pub struct Percentiles {
+  pub p50: u64,
+  pub p95: u64,
+  pub p99: u64,
+  pub max: u64,
+}
+
+impl Percentiles {
+  /// 按最近秩计算，`samples` 为空时均为 0
+  fn of(mut samples: Vec<u64>) -> Self {
+    samples.sort_unstable();
+    let rank = |p: usize| {
+      let index = (samples.len() * p).div_ceil(100).saturating_sub(1);
+      samples.get(index).copied().unwrap_or_default()
+    };
+
+    Self {
+      p50: rank(50),
+      p95: rank(95),
+      p99: rank(99),
+      max: samples.last().copied().unwrap_or_default(),
+    }
+  }
+}
+
+/// 基准测试结果
+#[derive(Debug, Serialize)]
+pub struct BenchReport {
+  pub printer: String,
+  pub jobs: usize,
+  pub concurrency: usize,
+  pub succeeded: usize,
+  pub failed: usize,
+  /// 全部任务的耗时（毫秒）
+  pub elapsed: u64,
+  /// 每分钟完成的任务数，只计成功的任务
+  pub jobs_per_minute: f64,
+  /// 每个任务的延迟，通过 HTTP 时包括网络及请求处理
+  pub latency: Percentiles,
+  /// 合并打印设置并生成打印票据的耗时
+  pub ticket: Percentiles,
+  /// 写入临时文件并提交到打印队列的耗时
+  pub spool: Percentiles,
+  /// 失败任务的错误信息，相同的只保留一条
+  pub errors: Vec<String>,
+  /// 第一个失败任务的错误，用于确定退出码
+  #[serde(skip)]
+  pub first_error: Option<anyhow::Error>,
+}
+
+impl BenchReport {
+  /// 输出为便于阅读的文本
+  pub fn print(&self) {
+    println!(
+      "{} jobs to {} with concurrency {}: {} succeeded, {} failed in {:.1} s",
+      self.jobs,
+      self.printer,
+      self.concurrency,
+      self.succeeded,
+      self.failed,
+      self.elapsed as f64 / 1000.0
+    );
+    println!("Throughput: {:.1} jobs/min", self.jobs_per_minute);
+    println!();
+    println!(
+      "{:<10} {:>8} {:>8} {:>8} {:>8}",
+      "ms", "p50", "p95", "p99", "max"
+    );
+    for (name, p) in [
+      ("latency", &self.latency),
+      ("ticket", &self.ticket),
+      ("spool", &self.spool),
+    ] {
+      println!(
+        "{:<10} {:>8} {:>8} {:>8} {:>8}",
+        name, p.p50, p.p95, p.p99, p.max
+      );
+    }
+
+    if !self.errors.is_empty() {
+      println!();
+      println!("Errors:");
+      for error in &self.errors {
+        println!("  {}", error);
+      }
+    }
+  }
+}
+
+/// 一个任务的结果，延迟及服务端报告的耗时
+type JobOutcome = anyhow::Result<(u64, JobTiming)>;
+
+/// 按 `concurrency` 个并发依次提交 `jobs` 个打印任务，打印 `file` 并统计延迟
+pub fn run(file: &Path, options: BenchOptions) -> anyhow::Result<BenchReport> {
+  let data = std::fs::read(file).with_context(|| format!("Cannot read {}", file.display()))?;
+  let simulated = matches!(options.target, Target::Pipeline { simulate: true });
+  let printer = match &options.printer {
+    Some(printer) => printer.clone(),
+    None if simulated => simulate::PRINTER.to_string(),
+    None => bail!("--printer is required unless --simulate is set"),
+  };
+
+  // 模拟打印的结果保存到临时目录，测试结束后删除
+  let simulate_dir = simulated.then(tempfile::tempdir).transpose()?;
+  let api_options = ApiOptions {
+    simulate: simulate_dir.as_ref().map(|dir| dir.path().to_path_buf()),
+    ..Default::default()
+  };
+  let agent: Agent = Agent::config_builder()
+    .http_status_as_error(false)
+    .build()
+    .into();
+
+  let submit = |data: Vec<u8>| -> JobOutcome {
+    let start = Instant::now();
+    let timing = match &options.target {
+      Target::Pipeline { .. } => print::print_pdf(data, &printer, &api_options)?,
+      Target::Http { url } => print_http(&agent, url, &printer, &data)?,
+    };
+    Ok((start.elapsed().as_millis() as u64, timing))
+  };
+
+  let next = AtomicUsize::new(0);
+  let outcomes = Mutex::new(Vec::with_capacity(options.jobs));
+  let start = Instant::now();
+
+  thread::scope(|scope| {
+    for _ in 0..options.concurrency.clamp(1, options.jobs.max(1)) {
+      scope.spawn(|| {
+        while next.fetch_add(1, Ordering::Relaxed) < options.jobs {
+          let outcome = submit(data.clone());
+          outcomes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(outcome);
+        }
+      });
+    }
+  });
+
+  let elapsed = start.elapsed();
+  let outcomes = outcomes.into_inner().unwrap_or_else(|e| e.into_inner());
+  Ok(report(printer, &options, elapsed, outcomes))
+}
+
+/// 汇总各任务的结果
+fn report(
+  printer: String,
+  options: &BenchOptions,
+  elapsed: Duration,
+  outcomes: Vec<JobOutcome>,
+) -> BenchReport {
+  let mut latency = Vec::new();
+  let mut ticket = Vec::new();
+  let mut spool = Vec::new();
+  let mut errors: Vec<String> = Vec::new();
+  let mut first_error = None;
+  let mut failed = 0;
+
+  for outcome in outcomes {
+    match outcome {
+      Ok((ms, timing)) => {
+        latency.push(ms);
+        ticket.push(timing.ticket);
+        spool.push(timing.spool);
+      }
+      Err(e) => {
+        failed += 1;
+        let msg = format!("{:#}", e);
+        if !errors.contains(&msg) {
+          errors.push(msg);
+        }
+        first_error.get_or_insert(e);
+      }
+    }
+  }
+
+  let succeeded = latency.len();
+  let minutes = elapsed.as_secs_f64() / 60.0;
+
+  BenchReport {
+    printer,
+    jobs: options.jobs,
+    concurrency: options.concurrency,
+    succeeded,
+    failed,
+    elapsed: elapsed.as_millis() as u64,
+    jobs_per_minute: if minutes > 0.0 {
+      succeeded as f64 / minutes
+    } else {
+      0.0
+    },
+    latency: Percentiles::of(latency),
+    ticket: Percentiles::of(ticket),
+    spool: Percentiles::of(spool),
+    errors,
+    first_error,
+  }
+}
+
+/// 通过 `POST /api/v1/print` 打印，返回服务端报告的各阶段耗时
+fn print_http(agent: &Agent, url: &str, printer: &str, data: &[u8]) -> anyhow::Result<JobTiming> {
+  let body = json!({
+    "file": STANDARD.encode(data),
+    "settings": { "printer": printer },
+  });
+  let mut response = agent
+    .post(format!("{}/api/v1/print", url.trim_end_matches('/')))
+    .header("Content-Type", "application/json")
+    .send(body.to_string())?;
+  let status = response.status();
+  let text = response.body_mut().read_to_string()?;
+  let response: Value = serde_json::from_str(&text)
+    .with_context(|| format!("Invalid response with status {}", status))?;
+
+  if response["code"] != 0 {
+    let msg = response["msg"].as_str().unwrap_or("Unknown error");
+    // 保留服务端的错误类型，以便按错误类型确定退出码
+    if let Ok(code) = ErrorCode::parse_from_json(Some(response["error"].clone())) {
+      let status =
+        StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+      return Err(CodedError::new(status, code, format!("{} ({})", msg, status)).into());
+    }
+    return Err(anyhow!("{} ({})", msg, status));
+  }
+
+  let timings = &response["data"]["timings"];
+  let ms = |name: &str| timings[name].as_u64().unwrap_or_default();
+  Ok(JobTiming {
+    ticket: ms("merge") + ms("build"),
+    spool: ms("write") + ms("print"),
+  })
+}
@@ -0,0 +1,428 @@
+use std::{
+  collections::VecDeque,
+  fmt,
+  io::{Read, Write},
+  path::Path,
+  process::{Command, ExitStatus, Stdio},
+  str::FromStr,
+  sync::Mutex,
+  thread,
+  time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+
+/// 命令中替换为文档路径的占位符，命令中没有占位符时路径作为最后一个参数
+const FILE_PLACEHOLDER: &str = "{file}";
+
+/// 错误信息中保留的标准错误输出的最大长度（字节）
+const STDERR_LIMIT: usize = 1024;
+
+/// 记录中保留的命令输出的最大长度（字节）
+const OUTPUT_LIMIT: usize = 4096;
+
+/// 等待命令结束时检查的间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// 保留最近多少个任务的打印后命令结果
+const MAX_RUNS: usize = 200;
+
+/// 打印前对文档或打印后对任务执行的外部命令，如杀毒软件的命令行扫描
+#[derive(Debug, Clone)]
+pub struct Hook {
+  /// 原始的命令模板，用于日志及错误信息
+  template: String,
+  program: String,
+  args: Vec<String>,
+}
+
+impl FromStr for Hook {
+  type Err = String;
+
+  /// 按空白拆分参数，双引号内的空白不拆分，如 `"C:\Program Files\AV\scan.exe" /file={file}`
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut pending = false;
+
+    for c in s.chars() {
+      match c {
+        '"' => {
+          quoted = !quoted;
+          pending = true;
+        }
+        c if c.is_whitespace() && !quoted => {
+          if pending {
+            parts.push(std::mem::take(&mut current));
+            pending = false;
+          }
+        }
+        c => {
+          current.push(c);
+          pending = true;
+        }
+      }
+    }
+
+    if quoted {
+      return Err(format!("Unclosed quote in {}", s));
+    }
+    if pending {
+      parts.push(current);
+    }
+
+    let mut parts = parts.into_iter();
+    let program = parts
+      .next()
+      .filter(|p| !p.is_empty())
+      .ok_or_else(|| "The hook command is empty".to_string())?;
+
+    Ok(Self {
+      template: s.trim().to_string(),
+      program,
+      args: parts.collect(),
+    })
+  }
+}
+
+impl Hook {
+  /// 替换占位符后的参数，没有文档时不替换
+  fn args(&self, file: Option<&Path>) -> Vec<String> {
+    let Some(file) = file else {
+      return self.args.clone();
+    };
+
+    let path = file.to_string_lossy();
+    let mut args: Vec<String> = self
+      .args
+      .iter()
+      .map(|arg| arg.replace(FILE_PLACEHOLDER, &path))
+      .collect();
+
+    let placeholder = self.program.contains(FILE_PLACEHOLDER)
+      || self.args.iter().any(|a| a.contains(FILE_PLACEHOLDER));
+    if !placeholder {
+      args.push(path.into_owned());
+    }
+    args
+  }
+
+  /// 执行命令并等待结束，超时后结束进程。成功时返回命令的输出
+  fn run(&self, input: &HookInput, timeout: Duration) -> Result<String, HookError> {
+    let failed = |e| HookError::Failed {
+      command: self.template.clone(),
+      error: e,
+    };
+
+    let program = match input.file {
+      Some(file) => self
+        .program
+        .replace(FILE_PLACEHOLDER, &file.to_string_lossy()),
+      None => self.program.clone(),
+    };
+    let mut command = Command::new(program);
+    command
+      .args(self.args(input.file))
+      .envs(input.env.iter().map(|(k, v)| (k, v)))
+      .stdin(if input.stdin.is_some() {
+        Stdio::piped()
+      } else {
+        Stdio::null()
+      })
+      .stdout(Stdio::piped())
+      .stderr(Stdio::piped());
+
+    // 服务没有控制台，避免为控制台程序弹出窗口
+    #[cfg(windows)]
+    {
+      use std::os::windows::process::CommandExt;
+      const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+      command.creation_flags(CREATE_NO_WINDOW);
+    }
+
+    let mut child = command.spawn().map_err(failed)?;
+
+    if let (Some(mut stdin), Some(data)) = (child.stdin.take(), input.stdin.clone()) {
+      // 命令不读取输入时写入失败，不影响结果
+      thread::spawn(move || {
+        let _ = stdin.write_all(&data);
+      });
+    }
+
+    // 在单独的线程中读取，避免输出填满管道后命令阻塞
+    let stdout = child.stdout.take().map(read_to_end);
+    let stderr = child.stderr.take().map(read_to_end);
+
+    let start = Instant::now();
+    let status = loop {
+      if let Some(status) = child.try_wait().map_err(failed)? {
+        break Some(status);
+      }
+      if start.elapsed() >= timeout {
+        if let Err(e) = child.kill() {
+          warn!("Failed to kill the timed out hook {}: {}", self.template, e);
+        }
+        let _ = child.wait();
+        break None;
+      }
+      thread::sleep(POLL_INTERVAL);
+    };
+
+    let Some(status) = status else {
+      return Err(HookError::TimedOut {
+        command: self.template.clone(),
+        timeout,
+      });
+    };
+
+    let output = |reader: Option<thread::JoinHandle<Vec<u8>>>| {
+      let buf = reader
+        .and_then(|reader| reader.join().ok())
+        .unwrap_or_default();
+      String::from_utf8_lossy(&buf).trim().to_string()
+    };
+    let stdout = output(stdout);
+    let stderr = output(stderr);
+
+    if status.success() {
+      let output = [stdout, stderr]
+        .into_iter()
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+      return Ok(truncate(&output, OUTPUT_LIMIT));
+    }
+
+    Err(HookError::Rejected {
+      command: self.template.clone(),
+      status,
+      stderr: truncate(&stderr, STDERR_LIMIT),
+    })
+  }
+}
+
+/// 命令的输入
+#[derive(Debug, Default)]
+pub struct HookInput<'a> {
+  /// 替换命令中占位符的文档路径
+  pub file: Option<&'a Path>,
+  /// 写入标准输入的内容
+  pub stdin: Option<Vec<u8>>,
+  /// 额外的环境变量
+  pub env: Vec<(&'static str, String)>,
+}
+
+fn read_to_end(mut reader: impl Read + Send + 'static) -> thread::JoinHandle<Vec<u8>> {
+  thread::spawn(move || {
+    let mut buf = Vec::new();
+    let _ = reader.read_to_end(&mut buf);
+    buf
+  })
+}
+
+/// 截断过长的输出，保留开头部分
+fn truncate(text: &str, limit: usize) -> String {
+  if text.len() <= limit {
+    return text.to_string();
+  }
+
+  let mut end = limit;
+  while !text.is_char_boundary(end) {
+    end -= 1;
+  }
+  format!("{}...", &text[..end])
+}
+
+/// 执行外部命令的错误
+#[derive(Debug)]
+pub enum HookError {
+  /// 命令以非零状态退出，拒绝打印
+  Rejected {
+    command: String,
+    status: ExitStatus,
+    /// 截断后的标准错误输出
+    stderr: String,
+  },
+  /// 超时未结束，进程已被结束
+  TimedOut { command: String, timeout: Duration },
+  /// 无法启动或等待命令
+  Failed {
+    command: String,
+    error: std::io::Error,
+  },
+}
+
+impl fmt::Display for HookError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Rejected {
+        command,
+        status,
+        stderr,
+      } => {
+        write!(
+          f,
+          "The pre-print hook `{}` rejected the document ({})",
+          command, status
+        )?;
+        if !stderr.is_empty() {
+          write!(f, ": {}", stderr)?;
+        }
+        Ok(())
+      }
+      Self::TimedOut { command, timeout } => write!(
+        f,
+        "The pre-print hook `{}` did not finish within {} s",
+        command,
+        timeout.as_secs_f64()
+      ),
+      Self::Failed { command, error } => {
+        write!(f, "Cannot run the pre-print hook `{}`: {}", command, error)
+      }
+    }
+  }
+}
+
+impl std::error::Error for HookError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      Self::Failed { error, .. } => Some(error),
+      _ => None,
+    }
+  }
+}
+
+/// 按顺序执行的命令，任一命令失败时不再执行之后的命令
+#[derive(Debug, Clone)]
+pub struct Hooks {
+  hooks: Vec<Hook>,
+  /// 单个命令的超时时间
+  timeout: Duration,
+}
+
+impl Hooks {
+  /// 没有命令时为空，打印时不写入临时文件也不执行命令
+  pub fn new(hooks: Vec<Hook>, timeout: Duration) -> Option<Self> {
+    (!hooks.is_empty()).then_some(Self { hooks, timeout })
+  }
+
+  /// 依次执行各命令，返回各命令的输出
+  pub fn run(&self, input: &HookInput) -> Result<Vec<String>, HookError> {
+    let mut outputs = Vec::new();
+    for hook in &self.hooks {
+      let start = Instant::now();
+      outputs.push(hook.run(input, self.timeout)?);
+      info!(
+        "Hook `{}` passed in {} ms",
+        hook.template,
+        start.elapsed().as_millis()
+      );
+    }
+    Ok(outputs)
+  }
+}
+
+/// 打印后命令的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookState {
+  Running,
+  Succeeded,
+  /// 命令以非零状态退出或无法启动
+  Failed,
+  TimedOut,
+}
+
+/// 任务的打印后命令的执行结果
+#[derive(Debug, Clone)]
+pub struct HookRun {
+  pub job_id: String,
+  pub state: HookState,
+  pub started_at: DateTime<Utc>,
+  pub finished_at: Option<DateTime<Utc>>,
+  /// 各命令的输出，截断
+  pub output: Vec<String>,
+  pub error: Option<String>,
+}
+
+/// 最近的打印后命令结果，按开始顺序排序
+static RUNS: Mutex<VecDeque<HookRun>> = Mutex::new(VecDeque::new());
+
+fn update_run(run: HookRun) {
+  let mut runs = RUNS.lock().unwrap_or_else(|e| e.into_inner());
+  match runs.iter_mut().find(|r| r.job_id == run.job_id) {
+    Some(existing) => *existing = run,
+    None => {
+      if runs.len() >= MAX_RUNS {
+        runs.pop_front();
+      }
+      runs.push_back(run);
+    }
+  }
+}
+
+/// 任务的打印后命令结果，未执行或已不在记录中时为空
+pub fn run_of(job_id: &str) -> Option<HookRun> {
+  let runs = RUNS.lock().unwrap_or_else(|e| e.into_inner());
+  runs.iter().find(|r| r.job_id == job_id).cloned()
+}
+
+/// 在后台线程中对已结束的任务执行打印后命令，结果不影响任务，可用 [`run_of`] 查询
+pub fn spawn_post_print(hooks: std::sync::Arc<Hooks>, job_id: String, input: HookInput<'static>) {
+  let run = HookRun {
+    job_id: job_id.clone(),
+    state: HookState::Running,
+    started_at: Utc::now(),
+    finished_at: None,
+    output: Vec::new(),
+    error: None,
+  };
+  update_run(run.clone());
+
+  let started = run.clone();
+  let spawned = thread::Builder::new()
+    .name("post-print-hook".to_string())
+    .spawn(move || {
+      let result = hooks.run(&input);
+      let mut run = HookRun {
+        finished_at: Some(Utc::now()),
+        ..started
+      };
+
+      match result {
+        Ok(output) => {
+          for line in output.iter().filter(|o| !o.is_empty()) {
+            info!("Post-print hook output of job {}: {}", job_id, line);
+          }
+          run.state = HookState::Succeeded;
+          run.output = output;
+        }
+        Err(e) => {
+          warn!("Post-print hook of job {} failed: {}", job_id, e);
+          run.state = match e {
+            HookError::TimedOut { .. } => HookState::TimedOut,
+            _ => HookState::Failed,
+          };
+          if let HookError::Rejected { stderr, .. } = &e {
+            run.output = vec![stderr.clone()];
+          }
+          run.error = Some(e.to_string());
+        }
+      }
+      update_run(run);
+    });
+
+  if let Err(e) = spawned {
+    warn!(
+      "Cannot start the post-print hooks of job {}: {}",
+      run.job_id, e
+    );
+    update_run(HookRun {
+      state: HookState::Failed,
+      finished_at: Some(Utc::now()),
+      error: Some(e.to_string()),
+      ..run
+    });
+  }
+}
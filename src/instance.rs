@@ -0,0 +1,298 @@
+use std::{
+  env,
+  fs::{create_dir_all, read_to_string},
+  path::{Path, PathBuf},
+  sync::{LazyLock, OnceLock},
+};
+
+use chrono::{DateTime, Utc};
+use directories::ProjectDirs;
+use log::{error, info};
+use tempfile::NamedTempFile;
+use windows::{
+  core::{w, PCWSTR},
+  Win32::System::Registry::{RegGetValueW, HKEY_LOCAL_MACHINE, RRF_RT_REG_DWORD, RRF_RT_REG_SZ},
+};
+
+use crate::storage::write_atomically;
+
+/// 实例 ID 的文件名，位于数据目录下
+const ID_FILENAME: &str = "instance_id";
+
+/// 持久的实例 ID，首次运行时生成 UUID 并保存在数据目录中，用于关联各处记录的来源
+static INSTANCE_ID: LazyLock<String> = LazyLock::new(|| {
+  let Some(dir) = data_dir() else {
+    error!("No data directory, the instance ID changes on each start");
+    return new_uuid();
+  };
+
+  match load_or_generate_id(&dir.join(ID_FILENAME)) {
+    Ok(id) => id,
+    Err(e) => {
+      error!(
+        "Failed to keep the instance ID, it changes on each start: {:#}",
+        e
+      );
+      new_uuid()
+    }
+  }
+});
+
+/// 实例 ID
+pub fn id() -> &'static str {
+  &INSTANCE_ID
+}
+
+fn load_or_generate_id(path: &Path) -> anyhow::Result<String> {
+  if let Ok(id) = read_to_string(path) {
+    let id = id.trim();
+    if is_uuid(id) {
+      return Ok(id.to_string());
+    }
+    error!(
+      "Invalid instance ID in {}, generating a new one",
+      path.display()
+    );
+  }
+
+  let id = new_uuid();
+  if let Some(dir) = path.parent() {
+    create_dir_all(dir)?;
+  }
+  write_atomically(path, &id)?;
+  info!("Generated instance ID {}", id);
+  Ok(id)
+}
+
+/// 随机的 UUID（版本 4）
+fn new_uuid() -> String {
+  let mut bytes = rand::random::<[u8; 16]>();
+  bytes[6] = (bytes[6] & 0x0f) | 0x40;
+  bytes[8] = (bytes[8] & 0x3f) | 0x80;
+  let hex = bytes
+    .iter()
+    .map(|b| format!("{:02x}", b))
+    .collect::<String>();
+
+  format!(
+    "{}-{}-{}-{}-{}",
+    &hex[..8],
+    &hex[8..12],
+    &hex[12..16],
+    &hex[16..20],
+    &hex[20..]
+  )
+}
+
+fn is_uuid(s: &str) -> bool {
+  s.len() == 36
+    && s.char_indices().all(|(i, c)| match i {
+      8 | 13 | 18 | 23 => c == '-',
+      _ => c.is_ascii_hexdigit(),
+    })
+}
+
+/// 各处保存文件使用的目录，启动时确定，之后不再改变
+#[derive(Debug, Clone)]
+pub struct Paths {
+  /// 数据目录，保存定时任务、模板、回执、日报、日志等
+  pub data_dir: Option<PathBuf>,
+  /// 配置目录，保存默认打印设置
+  pub config_dir: Option<PathBuf>,
+}
+
+impl Paths {
+  /// 指定的目录优先，未指定时为当前用户的应用程序目录。以 LocalSystem 运行时后者位于系统配置文件下
+  pub fn resolve(data_dir: Option<PathBuf>, config_dir: Option<PathBuf>) -> Self {
+    let project = ProjectDirs::from("com", "ubesthelp", env!("CARGO_PKG_NAME"));
+    Self {
+      data_dir: data_dir.or_else(|| project.as_ref().map(|d| d.data_local_dir().to_path_buf())),
+      config_dir: config_dir
+        .or_else(|| project.as_ref().map(|d| d.config_local_dir().to_path_buf())),
+    }
+  }
+
+  /// 创建各目录并检查能否写入
+  pub fn check(&self) -> std::io::Result<()> {
+    let dirs = [
+      ("data", "--data-dir", &self.data_dir),
+      ("configuration", "--config-dir", &self.config_dir),
+    ];
+    for (name, flag, dir) in dirs {
+      let Some(dir) = dir else {
+        return Err(std::io::Error::other(format!(
+          "Cannot determine the {} directory, set it with {}",
+          name, flag
+        )));
+      };
+      create_dir_all(dir)
+        .and_then(|_| NamedTempFile::new_in(dir).map(drop))
+        .map_err(|e| {
+          std::io::Error::new(
+            e.kind(),
+            format!(
+              "The {} directory {} is not writable: {}. Grant the account running the service \
+               write access, or choose another directory with {}",
+              name,
+              dir.display(),
+              e,
+              flag
+            ),
+          )
+        })?;
+    }
+    Ok(())
+  }
+}
+
+static PATHS: OnceLock<Paths> = OnceLock::new();
+
+/// 设置使用的目录，需要在读写任何文件前调用，之后再调用时不生效
+pub fn set_paths(paths: Paths) {
+  if PATHS.set(paths).is_err() {
+    error!("The directories are already in use and cannot be changed");
+  }
+}
+
+/// 使用的目录，未设置时为默认的目录
+pub fn paths() -> &'static Paths {
+  PATHS.get_or_init(|| Paths::resolve(None, None))
+}
+
+/// 测试使用临时的数据及配置目录，不读写用户的目录。同一进程中的测试共用这些目录
+#[cfg(test)]
+pub fn use_temp_paths() -> &'static Paths {
+  static DIR: LazyLock<PathBuf> = LazyLock::new(|| {
+    tempfile::Builder::new()
+      .prefix("direct-printing-test")
+      .tempdir()
+      .expect("Cannot create a temporary directory")
+      .into_path()
+  });
+  let paths = PATHS.get_or_init(|| Paths {
+    data_dir: Some(DIR.join("data")),
+    config_dir: Some(DIR.join("config")),
+  });
+  assert_eq!(
+    paths.data_dir.as_deref(),
+    Some(DIR.join("data").as_path()),
+    "The directories were set before the test"
+  );
+  paths
+}
+
+/// 数据目录，保存定时任务、模板、回执等
+pub fn data_dir() -> Option<PathBuf> {
+  paths().data_dir.clone()
+}
+
+/// 配置目录，保存默认打印设置
+pub fn config_dir() -> Option<PathBuf> {
+  paths().config_dir.clone()
+}
+
+/// 计算机名称
+pub fn hostname() -> String {
+  env::var("COMPUTERNAME")
+    .or_else(|_| env::var("HOSTNAME"))
+    .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// 编译的时间
+pub fn built_at() -> Option<DateTime<Utc>> {
+  env!("BUILD_TIMESTAMP")
+    .parse()
+    .ok()
+    .and_then(|secs| DateTime::from_timestamp(secs, 0))
+}
+
+/// 编译时的 Git 提交，不在 Git 仓库中编译时为空
+pub fn commit() -> Option<&'static str> {
+  Some(env!("BUILD_COMMIT")).filter(|commit| !commit.is_empty())
+}
+
+/// 启用的编译功能
+pub fn features() -> Vec<&'static str> {
+  [
+    ("with-ui", cfg!(feature = "with-ui")),
+    ("tray", cfg!(feature = "tray")),
+    ("error-reporting", cfg!(feature = "error-reporting")),
+    ("otel", cfg!(feature = "otel")),
+  ]
+  .into_iter()
+  .filter_map(|(name, enabled)| enabled.then_some(name))
+  .collect()
+}
+
+/// 操作系统的版本，如 `Windows 10 Pro 22H2 (build 19045.4046)`，无法读取时为空
+pub fn os_version() -> Option<String> {
+  const KEY: PCWSTR = w!(r"SOFTWARE\Microsoft\Windows NT\CurrentVersion");
+
+  let product = registry_string(KEY, w!("ProductName"))?;
+  let mut version = product;
+  if let Some(display) = registry_string(KEY, w!("DisplayVersion")) {
+    version = format!("{} {}", version, display);
+  }
+  if let Some(build) = registry_string(KEY, w!("CurrentBuildNumber")) {
+    version = match registry_dword(KEY, w!("UBR")) {
+      Some(ubr) => format!("{} (build {}.{})", version, build, ubr),
+      None => format!("{} (build {})", version, build),
+    };
+  }
+
+  Some(version)
+}
+
+fn registry_string(key: PCWSTR, value: PCWSTR) -> Option<String> {
+  let mut size = 0u32;
+  unsafe {
+    RegGetValueW(
+      HKEY_LOCAL_MACHINE,
+      key,
+      value,
+      RRF_RT_REG_SZ,
+      None,
+      None,
+      Some(&mut size),
+    )
+    .ok()
+    .ok()?
+  };
+
+  let mut buf = vec![0u16; (size as usize).div_ceil(2)];
+  unsafe {
+    RegGetValueW(
+      HKEY_LOCAL_MACHINE,
+      key,
+      value,
+      RRF_RT_REG_SZ,
+      None,
+      Some(buf.as_mut_ptr().cast()),
+      Some(&mut size),
+    )
+    .ok()
+    .ok()?
+  };
+
+  let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+  Some(String::from_utf16_lossy(&buf[..len]))
+}
+
+fn registry_dword(key: PCWSTR, value: PCWSTR) -> Option<u32> {
+  let mut data = 0u32;
+  let mut size = size_of::<u32>() as u32;
+  unsafe {
+    RegGetValueW(
+      HKEY_LOCAL_MACHINE,
+      key,
+      value,
+      RRF_RT_REG_DWORD,
+      None,
+      Some((&mut data as *mut u32).cast()),
+      Some(&mut size),
+    )
+    .ok()
+    .ok()?
+  };
+  Some(data)
+}
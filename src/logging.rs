@@ -0,0 +1,141 @@
+use std::{
+  fmt::Write,
+  io::{Error, ErrorKind},
+  path::{Path, PathBuf},
+};
+
+use chrono::Local;
+use tracing::{
+  field::{Field, Visit},
+  level_filters::LevelFilter,
+  Event, Subscriber,
+};
+use tracing_appender::{
+  non_blocking::WorkerGuard,
+  rolling::{RollingFileAppender, Rotation},
+};
+use tracing_subscriber::{
+  fmt,
+  layer::{Context, SubscriberExt},
+  util::SubscriberInitExt,
+  EnvFilter, Layer,
+};
+
+use crate::api::diagnostics;
+
+/// 默认的日志文件名
+const LOG_FILENAME: &str = "direct-printing.log";
+
+/// 日志相关的资源，需要保留到程序结束，否则缓冲的日志会丢失
+pub struct LogGuard {
+  _file: Option<WorkerGuard>,
+  #[cfg(feature = "otel")]
+  _telemetry: Option<crate::telemetry::Telemetry>,
+}
+
+/// 初始化日志，`file` 不为空时同时按天滚动写入日志文件。启用 `otel` 功能时按环境变量导出到 OTLP
+pub fn init(file: Option<&Path>, max_files: usize) -> std::io::Result<LogGuard> {
+  let (writer, guard) = match file {
+    Some(file) => {
+      let dir = match file.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+      };
+      let prefix = file
+        .file_name()
+        .map_or(LOG_FILENAME.into(), |name| name.to_string_lossy());
+      let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(prefix)
+        .max_log_files(max_files)
+        .build(dir)
+        .map_err(|e| {
+          Error::other(format!(
+            "Cannot write log files into {}: {}",
+            dir.display(),
+            e
+          ))
+        })?;
+      // 写文件不阻塞请求处理
+      let (writer, guard) = tracing_appender::non_blocking(appender);
+      (Some(writer), Some(guard))
+    }
+    None => (None, None),
+  };
+
+  // 任务的诊断信息不受 `RUST_LOG` 影响，总是记录调试日志
+  let registry = tracing_subscriber::registry()
+    .with(fmt::layer().with_filter(EnvFilter::from_default_env()))
+    .with(writer.map(|writer| {
+      fmt::layer()
+        .with_ansi(false)
+        .with_writer(writer)
+        .with_filter(EnvFilter::from_default_env())
+    }))
+    .with(JobLogLayer.with_filter(LevelFilter::DEBUG));
+
+  #[cfg(feature = "otel")]
+  let (registry, telemetry) = {
+    let (telemetry, layer) = crate::telemetry::init()
+      .map_err(|e| Error::other(format!("Cannot set up OpenTelemetry export: {}", e)))?
+      .unzip();
+    let layer = layer.with_filter(EnvFilter::from_default_env());
+    (registry.with(layer), telemetry)
+  };
+
+  registry.init();
+
+  Ok(LogGuard {
+    _file: guard,
+    #[cfg(feature = "otel")]
+    _telemetry: telemetry,
+  })
+}
+
+/// 将打印任务期间当前线程的日志记录到任务的诊断信息中
+struct JobLogLayer;
+
+impl<S: Subscriber> Layer<S> for JobLogLayer {
+  fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+    if !diagnostics::is_recording() {
+      return;
+    }
+
+    let metadata = event.metadata();
+    let mut line = format!(
+      "{} {:>5} {}:",
+      Local::now().format("%Y-%m-%dT%H:%M:%S%.3f%:z"),
+      metadata.level(),
+      metadata.target()
+    );
+    event.record(&mut LineVisitor(&mut line));
+    diagnostics::log_line(line);
+  }
+}
+
+/// 将日志的各字段追加到一行，消息在前，其他字段为 `名称=值`
+struct LineVisitor<'a>(&'a mut String);
+
+impl Visit for LineVisitor<'_> {
+  fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+    // 转发的 `log` 日志的来源字段与 target 重复
+    match field.name() {
+      "message" => write!(self.0, " {:?}", value),
+      name if name.starts_with("log.") => Ok(()),
+      name => write!(self.0, " {}={:?}", name, value),
+    }
+    .ok();
+  }
+}
+
+/// 默认的日志文件路径，位于数据目录下
+pub fn default_file() -> std::io::Result<PathBuf> {
+  let dir = crate::instance::data_dir().ok_or_else(|| {
+    Error::new(
+      ErrorKind::NotFound,
+      "Cannot determine the data directory for log files, set it with --data-dir",
+    )
+  })?;
+
+  Ok(dir.join("logs").join(LOG_FILENAME))
+}
@@ -1,85 +1,1038 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use std::fs::write;
+use std::{
+  fs::{read, read_to_string, write},
+  io::stdout,
+  path::{Path, PathBuf},
+  sync::Arc,
+  time::Duration,
+};
 
-use api::Api;
-use clap::Parser;
-use log::info;
-use poem::{http::Method, listener::TcpListener, middleware::Cors, EndpointExt, Route, Server};
-use poem_openapi::OpenApiService;
+use clap::{
+  parser::ValueSource, ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum,
+};
+use clap_complete::{generate, Shell};
+use log::{info, warn};
+use poem::{
+  http::Method,
+  listener::{Acceptor, Listener, TcpListener},
+  middleware::{CatchPanic, Cors},
+  EndpointExt, Route, Server,
+};
+use poem_openapi::types::ToJSON;
+use tokio::sync::Semaphore;
+
+use crate::{
+  api::{
+    access::{AccessPolicy, AccessRule, KeyAccessRule},
+    diagnostics::JobDiagnostics,
+    error::{CodedError, ErrorCode},
+    fixture::Fixtures,
+    group::PrinterGroups,
+    pricing::PriceTable,
+    quarantine::Quarantine,
+    receipt::ReceiptStore,
+    retention::{self, Policy, Retention, RetentionConfig},
+    schedule::QueueLimits,
+    summary::ReportTimezone,
+    token::Tokens,
+    upload::UploadQuota,
+  },
+  hook::{Hook, Hooks},
+  pdf::BlankPageDetection,
+  spooler::RetryPolicy,
+  storage::write_atomically,
+};
 
 #[cfg(feature = "with-ui")]
 use poem::middleware::{RequestId, ReuseId, Tracing};
 
 mod api;
+mod barcode;
+mod bench;
+mod hook;
+mod instance;
+#[cfg(feature = "with-ui")]
+mod logging;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+mod pdf;
+#[cfg(feature = "error-reporting")]
+mod report;
+mod spooler;
+mod sqlite;
+mod status;
+mod storage;
+#[cfg(feature = "otel")]
+mod telemetry;
+#[cfg(feature = "tray")]
+mod tray;
+
+/// 命令行的退出码，出错时的退出码不随版本变化
+const EXIT_CODES: &str = "\
+Exit codes:
+  0  Succeeded
+  1  Other errors
+  2  Invalid command-line usage
+  3  The printer was not found, or its name matches several printers
+  4  The printer does not support the print settings
+  5  Printing failed, e.g. the printer or the spooler reported an error";
 
 /// Direct Printing
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, after_help = EXIT_CODES)]
 struct Args {
+  #[command(subcommand)]
+  command: Option<Command>,
+
   /// The host to listen
-  #[arg(long, default_value = "127.0.0.1")]
+  #[arg(long, default_value = "127.0.0.1", env = "DIRECT_PRINTING_HOST")]
   host: String,
 
-  /// The port to listen
-  #[arg(short, long, default_value_t = 63856)]
+  /// The port to listen, 0 to let the system choose one
+  #[arg(short, long, default_value_t = 63856, env = "DIRECT_PRINTING_PORT")]
   port: u16,
 
   /// Save the OpenAPI specification into a JSON file
-  #[arg(long, value_name = "FILE")]
+  #[arg(long, value_name = "FILE", env = "DIRECT_PRINTING_JSON")]
   json: Option<String>,
 
   /// Save the OpenAPI specification into a YAML file
-  #[arg(long, value_name = "FILE")]
+  #[arg(long, value_name = "FILE", env = "DIRECT_PRINTING_YAML")]
   yaml: Option<String>,
+
+  /// Write the actually bound address into a file, useful with `--port 0`
+  #[arg(long, value_name = "FILE", env = "DIRECT_PRINTING_PORT_FILE")]
+  port_file: Option<String>,
+
+  /// Keep scheduled jobs, receipts, templates, reports, logs and other data in this directory
+  /// instead of the user's local application data folder, e.g. when running as LocalSystem
+  #[arg(long, value_name = "DIR", env = "DIRECT_PRINTING_DATA_DIR")]
+  data_dir: Option<PathBuf>,
+
+  /// Keep the default print settings (default.json) in this directory instead of the user's
+  /// local application configuration folder
+  #[arg(long, value_name = "DIR", env = "DIRECT_PRINTING_CONFIG_DIR")]
+  config_dir: Option<PathBuf>,
+
+  /// Also write logs into a file, rotated daily
+  #[cfg(feature = "with-ui")]
+  #[arg(long, value_name = "FILE", env = "DIRECT_PRINTING_LOG_FILE")]
+  log_file: Option<PathBuf>,
+
+  /// Write logs into the default file in the data directory
+  #[cfg(feature = "with-ui")]
+  #[arg(long, env = "DIRECT_PRINTING_LOG_TO_FILE")]
+  log_to_file: bool,
+
+  /// The number of rotated log files to keep
+  #[cfg(feature = "with-ui")]
+  #[arg(long, default_value_t = 7, env = "DIRECT_PRINTING_LOG_MAX_FILES")]
+  log_max_files: usize,
+
+  /// Report panics and print failures to this Sentry DSN
+  #[cfg(feature = "error-reporting")]
+  #[arg(
+    long,
+    value_name = "DSN",
+    env = "DIRECT_PRINTING_ERROR_REPORT_DSN",
+    hide_env_values = true
+  )]
+  error_report_dsn: Option<String>,
+
+  /// Connect to this MQTT broker to accept print jobs and publish job and printer status,
+  /// e.g. `mqtt://broker:1883` or `mqtts://broker:8883`
+  #[cfg(feature = "mqtt")]
+  #[arg(long, value_name = "URL", env = "DIRECT_PRINTING_MQTT_URL")]
+  mqtt_url: Option<String>,
+
+  /// The user name for the MQTT broker
+  #[cfg(feature = "mqtt")]
+  #[arg(long, value_name = "USER", env = "DIRECT_PRINTING_MQTT_USERNAME")]
+  mqtt_username: Option<String>,
+
+  /// The password for the MQTT broker
+  #[cfg(feature = "mqtt")]
+  #[arg(
+    long,
+    value_name = "PASSWORD",
+    env = "DIRECT_PRINTING_MQTT_PASSWORD",
+    hide_env_values = true
+  )]
+  mqtt_password: Option<String>,
+
+  /// The MQTT client ID, derived from the instance ID by default
+  #[cfg(feature = "mqtt")]
+  #[arg(long, value_name = "ID", env = "DIRECT_PRINTING_MQTT_CLIENT_ID")]
+  mqtt_client_id: Option<String>,
+
+  /// Log request bodies with file contents redacted, and response bodies
+  #[arg(long, env = "DIRECT_PRINTING_LOG_BODIES")]
+  log_bodies: bool,
+
+  /// The maximum length of logged response bodies in bytes
+  #[arg(
+    long,
+    value_name = "BYTES",
+    default_value_t = 1024,
+    env = "DIRECT_PRINTING_LOG_BODY_LIMIT"
+  )]
+  log_body_limit: usize,
+
+  /// The maximum size of request bodies in bytes, checked both before and after decompressing
+  /// bodies sent with `Content-Encoding: gzip` or `deflate`
+  #[arg(
+    long,
+    value_name = "BYTES",
+    default_value_t = 64 * 1024 * 1024,
+    env = "DIRECT_PRINTING_MAX_BODY_SIZE"
+  )]
+  max_body_size: usize,
+
+  /// Refuse to use saved default settings that do not fit the current printers
+  #[arg(long, env = "DIRECT_PRINTING_STRICT_SETTINGS")]
+  strict_settings: bool,
+
+  /// Reject print requests and settings containing unknown fields instead of ignoring them.
+  /// Clients can also opt in per request with `"strict": true`
+  #[arg(long, env = "DIRECT_PRINTING_STRICT_REQUESTS")]
+  strict_requests: bool,
+
+  /// Allow clients in a network to use printers matching the patterns,
+  /// e.g. `192.168.1.0/24=Label*,Zebra?`. All printers are allowed if omitted
+  #[arg(
+    long,
+    value_name = "CIDR=PATTERNS",
+    env = "DIRECT_PRINTING_PRINTER_ACCESS",
+    value_delimiter = ';'
+  )]
+  printer_access: Vec<AccessRule>,
+
+  /// Allow requests with an API key in the `X-API-Key` header to use printers matching the
+  /// patterns, e.g. `s3cr3t=Label*,Zebra?`. When set, `--printer-access` no longer applies and
+  /// requests without a matching key cannot use any printer
+  #[arg(
+    long,
+    value_name = "KEY=PATTERNS",
+    env = "DIRECT_PRINTING_PRINTER_KEY_ACCESS",
+    value_delimiter = ';',
+    hide_env_values = true
+  )]
+  printer_key_access: Vec<KeyAccessRule>,
+
+  /// API keys allowed to issue print tokens
+  #[arg(
+    long = "api-key",
+    value_name = "KEY",
+    env = "DIRECT_PRINTING_API_KEYS",
+    value_delimiter = ',',
+    hide_env_values = true
+  )]
+  api_keys: Vec<String>,
+
+  /// The secret for signing one-time print tokens. When set, printing requires a token
+  #[arg(
+    long,
+    value_name = "SECRET",
+    env = "DIRECT_PRINTING_TOKEN_SECRET",
+    hide_env_values = true
+  )]
+  token_secret: Option<String>,
+
+  /// How many times to retry a print after a transient spooler error
+  #[arg(long, default_value_t = 2, env = "DIRECT_PRINTING_PRINT_RETRIES")]
+  print_retries: u32,
+
+  /// The delay before the first retry in milliseconds, doubled for each further retry
+  #[arg(
+    long,
+    value_name = "MS",
+    default_value_t = 1000,
+    env = "DIRECT_PRINTING_PRINT_RETRY_DELAY"
+  )]
+  print_retry_delay: u64,
+
+  /// A JSON price table used to estimate the cost of print jobs
+  #[arg(long, value_name = "FILE", env = "DIRECT_PRINTING_PRICING")]
+  pricing: Option<PathBuf>,
+
+  /// Scheduled jobs not printed within this many seconds after their print time expire
+  /// instead of printing, unless the job sets its own TTL
+  #[arg(long, value_name = "SECONDS", env = "DIRECT_PRINTING_JOB_TTL")]
+  job_ttl: Option<u64>,
+
+  /// Jobs waiting in the service queue move up one priority lane for every this many seconds
+  /// they wait past their print time, so low priority jobs are not starved. 0 disables aging
+  #[arg(
+    long,
+    value_name = "SECONDS",
+    default_value_t = 300,
+    env = "DIRECT_PRINTING_PRIORITY_AGING"
+  )]
+  priority_aging: u64,
+
+  /// How many recent jobs per printer to average when estimating when queued jobs start printing.
+  /// 0 disables the estimates
+  #[arg(
+    long,
+    value_name = "JOBS",
+    default_value_t = 20,
+    env = "DIRECT_PRINTING_ETA_WINDOW"
+  )]
+  eta_window: usize,
+
+  /// Time zone used to split daily reports, `local` or an IANA name such as Asia/Shanghai
+  #[arg(
+    long,
+    value_name = "ZONE",
+    default_value = "local",
+    env = "DIRECT_PRINTING_REPORT_TIMEZONE"
+  )]
+  report_timezone: ReportTimezone,
+
+  /// Write the previous day's report as JSON and CSV to the reports folder of the data
+  /// directory shortly after midnight
+  #[arg(long, env = "DIRECT_PRINTING_DAILY_REPORT")]
+  daily_report: bool,
+
+  /// Also POST the daily report JSON to this URL
+  #[arg(
+    long,
+    value_name = "URL",
+    requires = "daily_report",
+    env = "DIRECT_PRINTING_DAILY_REPORT_WEBHOOK"
+  )]
+  daily_report_webhook: Option<String>,
+
+  /// Documents with more pages than this are printed as several spool jobs of at most this
+  /// many pages each, unless the print settings set `split_every_pages`
+  #[arg(long, value_name = "PAGES", env = "DIRECT_PRINTING_SPLIT_EVERY_PAGES")]
+  split_every_pages: Option<u32>,
+
+  /// Print Letter documents on A4 (and Legal on Folio, and the other way round) scaled to fit
+  /// when the printer lacks the requested size, unless the print settings set
+  /// `allow_letter_a4_interchange`
+  #[arg(long, env = "DIRECT_PRINTING_LETTER_A4_INTERCHANGE")]
+  letter_a4_interchange: bool,
+
+  /// Pages with at most this percentage of inked pixels are blank and skipped when the print
+  /// settings set `skip_blank_pages`
+  #[arg(
+    long,
+    value_name = "PERCENT",
+    default_value_t = 0.1,
+    env = "DIRECT_PRINTING_BLANK_PAGE_COVERAGE"
+  )]
+  blank_page_coverage: f32,
+
+  /// Documents with more pages than this are printed without looking for blank pages
+  #[arg(
+    long,
+    value_name = "PAGES",
+    default_value_t = 500,
+    env = "DIRECT_PRINTING_BLANK_PAGE_MAX_PAGES"
+  )]
+  blank_page_max_pages: u32,
+
+  /// A TrueType font used to lay out printed tables and labels, needed for non-Latin text.
+  /// The built-in Helvetica is used if not set
+  #[arg(long, value_name = "FILE", env = "DIRECT_PRINTING_FONT")]
+  font: Option<PathBuf>,
+
+  /// A JSON file of named printer groups. Print settings may name a group instead of a
+  /// printer, and a member is chosen by the group's routing policy
+  #[arg(long, value_name = "FILE", env = "DIRECT_PRINTING_PRINTER_GROUPS")]
+  printer_groups: Option<PathBuf>,
+
+  /// Simulate printing for testing without a printer: a virtual "Simulated Printer" is listed,
+  /// and jobs sent to it are written to this directory as PDF files with JSON sidecars of the
+  /// resolved settings instead of being printed
+  #[arg(long, value_name = "DIR", env = "DIRECT_PRINTING_SIMULATE")]
+  simulate: Option<PathBuf>,
+
+  /// Save the print capabilities returned by each driver to this directory, as the raw XML and
+  /// a JSON file of the derived capabilities, so that bug reports can be reproduced elsewhere
+  #[arg(long, value_name = "DIR", env = "DIRECT_PRINTING_RECORD_FIXTURES")]
+  record_fixtures: Option<PathBuf>,
+
+  /// Serve the printer list and capabilities from a directory written by --record-fixtures
+  /// instead of the installed printers. Print requests to the recorded printers stop after the
+  /// settings are resolved, or are written to the --simulate directory if set
+  #[arg(
+    long,
+    value_name = "DIR",
+    env = "DIRECT_PRINTING_REPLAY_FIXTURES",
+    conflicts_with = "record_fixtures"
+  )]
+  replay_fixtures: Option<PathBuf>,
+
+  /// Load Pdfium and the capabilities of the printers in the background after starting, so that
+  /// the first print is not slowed down by cold drivers
+  #[arg(long, env = "DIRECT_PRINTING_WARMUP_ON_START")]
+  warmup_on_start: bool,
+
+  /// The printers to warm up, comma separated, all printers if omitted
+  #[arg(
+    long,
+    value_name = "NAMES",
+    value_delimiter = ',',
+    env = "DIRECT_PRINTING_WARMUP_PRINTERS"
+  )]
+  warmup_printers: Vec<String>,
+
+  /// How many printer capability queries may run at once across all requests
+  #[arg(
+    long,
+    default_value_t = 4,
+    env = "DIRECT_PRINTING_CAPABILITY_CONCURRENCY"
+  )]
+  capability_concurrency: usize,
+
+  /// Give up fetching the capabilities of a single printer after this many seconds
+  #[arg(
+    long,
+    value_name = "SECONDS",
+    default_value_t = 10,
+    env = "DIRECT_PRINTING_CAPABILITY_TIMEOUT"
+  )]
+  capability_timeout: u64,
+
+  /// Run a command against each document before it is spooled, e.g. an antivirus scanner.
+  /// `{file}` is replaced by the path of the PDF, which is appended if the placeholder is missing.
+  /// A non-zero exit status rejects the print. Repeat to run several commands in order
+  #[arg(
+    long = "pre-print-hook",
+    value_name = "COMMAND",
+    env = "DIRECT_PRINTING_PRE_PRINT_HOOKS",
+    value_delimiter = ';'
+  )]
+  pre_print_hooks: Vec<Hook>,
+
+  /// Run a command in the background after each job ends, e.g. to kick a cash drawer. The job
+  /// receipt is passed as JSON on stdin, and the job ID, printer, pages and outcome in the
+  /// `DIRECT_PRINTING_JOB_ID`, `DIRECT_PRINTING_PRINTER`, `DIRECT_PRINTING_PAGES` and
+  /// `DIRECT_PRINTING_OUTCOME` environment variables. Jobs can opt out with `"skip_hooks": true`.
+  /// Repeat to run several commands in order
+  #[arg(
+    long = "post-print-hook",
+    value_name = "COMMAND",
+    env = "DIRECT_PRINTING_POST_PRINT_HOOKS",
+    value_delimiter = ';'
+  )]
+  post_print_hooks: Vec<Hook>,
+
+  /// Kill a pre-print or post-print hook if it runs longer than this many seconds
+  #[arg(
+    long,
+    value_name = "SECONDS",
+    default_value_t = 60,
+    env = "DIRECT_PRINTING_HOOK_TIMEOUT"
+  )]
+  hook_timeout: u64,
+
+  /// Keep requests rejected for their document, e.g. not a PDF or refused by a pre-print hook,
+  /// in this directory with a JSON file of the request and the reason. The error response
+  /// carries a `quarantine_id` for fetching it from `GET /admin/quarantine/{id}`
+  #[arg(long, value_name = "DIR", env = "DIRECT_PRINTING_QUARANTINE_DIR")]
+  quarantine_dir: Option<PathBuf>,
+
+  /// Quarantined documents larger than this many bytes in total are not kept, only the request
+  /// and the reason
+  #[arg(
+    long,
+    value_name = "BYTES",
+    default_value_t = 20 * 1024 * 1024,
+    env = "DIRECT_PRINTING_QUARANTINE_MAX_SIZE"
+  )]
+  quarantine_max_size: usize,
+
+  /// How many quarantined requests to keep, the oldest are removed first. Overridden by the
+  /// `quarantine` class of `--retention-policy`
+  #[arg(
+    long,
+    value_name = "COUNT",
+    default_value_t = 100,
+    env = "DIRECT_PRINTING_QUARANTINE_MAX_ENTRIES"
+  )]
+  quarantine_max_entries: usize,
+
+  /// Remove chunked uploads that receive no chunk for this many seconds
+  #[arg(
+    long,
+    value_name = "SECONDS",
+    default_value_t = 3600,
+    env = "DIRECT_PRINTING_UPLOAD_IDLE_TIMEOUT"
+  )]
+  upload_idle_timeout: u64,
+
+  /// Reject new chunked uploads with 429 while this many uploads are in progress
+  #[arg(
+    long,
+    value_name = "COUNT",
+    default_value_t = UploadQuota::default().max_uploads,
+    env = "DIRECT_PRINTING_MAX_UPLOADS"
+  )]
+  max_uploads: u32,
+
+  /// Reject new chunked uploads with 507 once the uploads in progress would exceed this many bytes
+  #[arg(
+    long,
+    value_name = "BYTES",
+    default_value_t = UploadQuota::default().max_bytes,
+    env = "DIRECT_PRINTING_MAX_UPLOAD_BYTES"
+  )]
+  max_upload_bytes: u64,
+
+  /// Keep per-job diagnostics (settings, print ticket, capabilities, timings and log lines) for
+  /// this many days, downloadable from `GET /jobs/{id}/diagnostics`. 0 disables them. Overridden
+  /// by the `diagnostics` class of `--retention-policy`
+  #[arg(
+    long,
+    value_name = "DAYS",
+    default_value_t = 7,
+    env = "DIRECT_PRINTING_DIAGNOSTICS_RETENTION_DAYS"
+  )]
+  diagnostics_retention_days: u64,
+
+  /// Reject new jobs for a printer with `queue_full` once this many jobs wait for it in the
+  /// service, e.g. while it is paused. Unlimited by default
+  #[arg(long, value_name = "JOBS", env = "DIRECT_PRINTING_MAX_QUEUED_JOBS")]
+  max_queued_jobs: Option<u32>,
+
+  /// Reject new jobs for a printer with `queue_full` once the documents waiting for it in the
+  /// service would exceed this many bytes. Unlimited by default
+  #[arg(long, value_name = "BYTES", env = "DIRECT_PRINTING_MAX_QUEUED_BYTES")]
+  max_queued_bytes: Option<u64>,
+
+  /// The maximum page length in millimeters when the page height follows the content
+  /// (`auto_height`), longer documents are rejected
+  #[arg(
+    long,
+    value_name = "MM",
+    default_value_t = 2000,
+    env = "DIRECT_PRINTING_MAX_AUTO_PAGE_HEIGHT"
+  )]
+  max_auto_page_height: u32,
+
+  /// A JSON retention policy for stored receipts, quarantined requests and diagnostics, e.g.
+  /// `{"receipts": {"max_age_days": 365, "max_count": 100000, "max_bytes": 104857600},
+  /// "keep_document_hashes": false}`. Artifacts are removed hourly, preview with
+  /// `GET /admin/retention/preview`. Receipts are kept forever by default
+  #[arg(long, value_name = "FILE", env = "DIRECT_PRINTING_RETENTION_POLICY")]
+  retention_policy: Option<PathBuf>,
+
+  /// Keep queued, held and scheduled jobs in memory only, they are lost when the service stops
+  #[arg(long, env = "DIRECT_PRINTING_NO_PERSISTENT_QUEUE")]
+  no_persistent_queue: bool,
+
+  /// List every printer separately, instead of merging network printers whose port targets the
+  /// same device (e.g. the same queue mapped from two print servers) into one entry with aliases
+  #[arg(long, env = "DIRECT_PRINTING_NO_PRINTER_DEDUPE")]
+  no_printer_dedupe: bool,
+
+  /// The API version of the saved OpenAPI specification
+  #[arg(long, value_enum, default_value_t = SpecVersion::V1, env = "DIRECT_PRINTING_SPEC_VERSION")]
+  spec_version: SpecVersion,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+  /// Print the shell completion script to stdout
+  Completions {
+    /// The shell to generate the script for
+    #[arg(value_enum)]
+    shell: Shell,
+  },
+  /// Export or import the saved configuration
+  Config {
+    #[command(subcommand)]
+    action: ConfigAction,
+  },
+  /// Measure print latency and throughput by submitting the same PDF file repeatedly
+  #[command(after_help = EXIT_CODES)]
+  Bench {
+    /// The printer to print to, the simulated printer if omitted with --simulate
+    #[arg(long, required_unless_present = "simulate")]
+    printer: Option<String>,
+    /// The PDF file to print
+    #[arg(long, value_name = "FILE")]
+    file: PathBuf,
+    /// How many jobs to submit
+    #[arg(long, default_value_t = 100)]
+    jobs: usize,
+    /// How many jobs to submit at once
+    #[arg(long, default_value_t = 4)]
+    concurrency: usize,
+    /// Print to the simulated printer instead of a real one, so that the benchmark runs without
+    /// a printer, e.g. in CI
+    #[arg(long, conflicts_with = "url")]
+    simulate: bool,
+    /// Submit the jobs through the HTTP API of a running instance, e.g. http://127.0.0.1:8080,
+    /// instead of calling the print pipeline directly
+    #[arg(long)]
+    url: Option<String>,
+    /// Write the report as JSON
+    #[arg(long)]
+    json: bool,
+  },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigAction {
+  /// Write all saved configuration as a JSON bundle
+  Export {
+    /// The file to write, stdout if omitted
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<String>,
+  },
+  /// Replace the saved configuration with a JSON bundle
+  Import {
+    /// The bundle file to read
+    #[arg(value_name = "FILE")]
+    file: String,
+    /// Only add missing entries instead of replacing everything
+    #[arg(long)]
+    merge: bool,
+  },
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum SpecVersion {
+  V1,
 }
 
 #[tokio::main]
 async fn main() -> tokio::io::Result<()> {
-  let args = Args::parse();
+  let matches = Args::command().get_matches();
+  let args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+  // 在读写任何文件前确定目录
+  instance::set_paths(instance::Paths::resolve(
+    args.data_dir.clone(),
+    args.config_dir.clone(),
+  ));
+  api::schedule::set_persistent(!args.no_persistent_queue);
+
+  match args.command {
+    Some(Command::Completions { shell }) => {
+      let mut cmd = Args::command();
+      let name = cmd.get_name().to_string();
+      generate(shell, &mut cmd, name, &mut stdout());
+      return Ok(());
+    }
+    Some(Command::Config { action }) => {
+      return match action {
+        ConfigAction::Export { output } => {
+          let json = api::settings::export_config().map_err(std::io::Error::other)?;
+          match output {
+            Some(output) => write(output, json),
+            None => {
+              println!("{}", json);
+              Ok(())
+            }
+          }
+        }
+        ConfigAction::Import { file, merge } => {
+          let json = read_to_string(file)?;
+          api::settings::import_config(&json, merge).map_err(std::io::Error::other)
+        }
+      };
+    }
+    Some(Command::Bench {
+      printer,
+      file,
+      jobs,
+      concurrency,
+      simulate,
+      url,
+      json,
+    }) => {
+      let target = match url {
+        Some(url) => bench::Target::Http { url },
+        None => bench::Target::Pipeline { simulate },
+      };
+      let options = bench::BenchOptions {
+        target,
+        printer,
+        jobs,
+        concurrency,
+      };
+      let report = bench::run(&file, options).unwrap_or_else(|e| exit_with(e, 1));
+      if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+      } else {
+        report.print();
+      }
+      // 有任务失败时按第一个失败任务的错误退出
+      if let Some(e) = report.first_error {
+        exit_with(e, 5);
+      }
+      return Ok(());
+    }
+    None => {}
+  }
 
   #[cfg(feature = "with-ui")]
-  tracing_subscriber::fmt::init();
+  let _log_guard = {
+    let log_file = match &args.log_file {
+      Some(file) => Some(file.clone()),
+      None if args.log_to_file => Some(logging::default_file()?),
+      None => None,
+    };
+    logging::init(log_file.as_deref(), args.log_max_files)?
+  };
+
+  log_config(&matches);
 
-  let addr = format!("{}:{}", args.host, args.port);
-  let server = format!("http://{}/api", addr);
+  let paths = instance::paths();
+  let show = |dir: &Option<PathBuf>| {
+    dir
+      .as_ref()
+      .map_or("none".into(), |d| d.display().to_string())
+  };
+  info!("Data directory: {}", show(&paths.data_dir));
+  info!("Configuration directory: {}", show(&paths.config_dir));
 
-  let api_service = OpenApiService::new(Api, "Direct Printing", "0.1")
-    .description("可从 web 直接调用的打印 API。")
-    .server(&server);
+  #[cfg(feature = "error-reporting")]
+  let _report_guard = args.error_report_dsn.as_deref().map(report::init);
 
-  if let Some(json) = args.json {
-    write(json, api_service.spec())
-  } else if let Some(yaml) = args.yaml {
-    write(yaml, api_service.spec_yaml())
+  // 导出规范时不监听端口，使用指定的地址
+  let spec_service = || {
+    let server = format!("http://{}:{}/api/v1", args.host, args.port);
+    api::v1::service(&server, api::options::ApiOptions::default())
+  };
+
+  if let Some(json) = &args.json {
+    let spec = match args.spec_version {
+      SpecVersion::V1 => spec_service().spec(),
+    };
+    write(json, spec)
+  } else if let Some(yaml) = &args.yaml {
+    let spec = match args.spec_version {
+      SpecVersion::V1 => spec_service().spec_yaml(),
+    };
+    write(yaml, spec)
   } else {
+    // 目录不可写时各功能会静默失效，启动时直接报错
+    paths.check()?;
+
+    // 先绑定端口，端口为 0 时由系统分配，之后的地址都以实际端口为准
+    let acceptor = TcpListener::bind(format!("{}:{}", args.host, args.port))
+      .into_acceptor()
+      .await?;
+    let port = acceptor
+      .local_addr()
+      .first()
+      .and_then(|addr| addr.as_socket_addr())
+      .map_or(args.port, |addr| addr.port());
+    let addr = format!("{}:{}", args.host, port);
+    let server = format!("http://{}/api/v1", addr);
+
+    // 输出实际监听的地址，供启动本程序的进程读取
+    let listening = format!(
+      r#"{{"host":{},"port":{}}}"#,
+      args.host.to_json_string(),
+      port
+    );
+    println!("LISTENING {}", listening);
+    if let Some(port_file) = &args.port_file {
+      write_atomically(Path::new(port_file), &listening)?;
+    }
+
+    let pricing = args
+      .pricing
+      .as_deref()
+      .map(PriceTable::load)
+      .transpose()
+      .map_err(std::io::Error::other)?;
+    let groups = args
+      .printer_groups
+      .as_deref()
+      .map(PrinterGroups::load)
+      .transpose()
+      .map_err(std::io::Error::other)?;
+    let replay = args
+      .replay_fixtures
+      .as_deref()
+      .map(Fixtures::load)
+      .transpose()
+      .map_err(std::io::Error::other)?;
+    let font = args.font.as_deref().map(read).transpose()?;
+
+    // 各类保存的数据统一按保留策略删除
+    let retention_config = args
+      .retention_policy
+      .as_deref()
+      .map(RetentionConfig::load)
+      .transpose()
+      .map_err(std::io::Error::other)?
+      .unwrap_or_default();
+    api::receipt::set_keep_document_hashes(retention_config.keep_document_hashes);
+    let mut retention = Retention::new(retention_config);
+    retention.register(
+      retention::RECEIPTS,
+      Arc::new(ReceiptStore),
+      Policy::default(),
+    );
+    let quarantine = args.quarantine_dir.clone().map(|dir| {
+      let quarantine = Arc::new(Quarantine::new(dir, args.quarantine_max_size));
+      let policy = Policy {
+        max_count: Some(args.quarantine_max_entries),
+        ..Default::default()
+      };
+      retention.register(retention::QUARANTINE, quarantine.clone(), policy);
+      quarantine
+    });
+    let job_diagnostics = instance::data_dir()
+      .filter(|_| args.diagnostics_retention_days > 0)
+      .map(|dir| {
+        let policy = Policy {
+          max_age_days: Some(args.diagnostics_retention_days),
+          ..Default::default()
+        };
+        let policy = retention.config().policy(retention::DIAGNOSTICS, policy);
+        let diagnostics = Arc::new(JobDiagnostics::new(
+          dir.join("diagnostics"),
+          policy.max_age(),
+        ));
+        retention.register(retention::DIAGNOSTICS, diagnostics.clone(), policy);
+        diagnostics
+      });
+    let retention = Arc::new(retention);
+    let options = api::options::ApiOptions {
+      log_bodies: args.log_bodies,
+      strict_settings: args.strict_settings,
+      access: AccessPolicy::new(args.printer_access.clone(), args.printer_key_access.clone()),
+      api_keys: args.api_keys.clone(),
+      tokens: args
+        .token_secret
+        .as_ref()
+        .map(|secret| Arc::new(Tokens::new(secret.as_bytes()))),
+      retry: RetryPolicy {
+        retries: args.print_retries,
+        delay: Duration::from_millis(args.print_retry_delay),
+      },
+      job_ttl: args.job_ttl,
+      priority_aging: (args.priority_aging > 0).then(|| Duration::from_secs(args.priority_aging)),
+      eta_window: args.eta_window,
+      report_timezone: args.report_timezone,
+      pricing: pricing.map(Arc::new),
+      font: font.map(Arc::new),
+      groups: groups.map(Arc::new),
+      simulate: args.simulate.clone(),
+      capability_source: None,
+      record_fixtures: args.record_fixtures.clone(),
+      replay: replay.map(Arc::new),
+      warmup_printers: args.warmup_printers.clone(),
+      capability_permits: Some(Arc::new(Semaphore::new(args.capability_concurrency.max(1)))),
+      capability_timeout: Duration::from_secs(args.capability_timeout),
+      split_every_pages: args.split_every_pages,
+      letter_a4_interchange: args.letter_a4_interchange,
+      blank_pages: BlankPageDetection {
+        max_coverage: args.blank_page_coverage / 100.0,
+        max_pages: args.blank_page_max_pages,
+      },
+      hooks: Hooks::new(
+        args.pre_print_hooks.clone(),
+        Duration::from_secs(args.hook_timeout),
+      )
+      .map(Arc::new),
+      post_print_hooks: Hooks::new(
+        args.post_print_hooks.clone(),
+        Duration::from_secs(args.hook_timeout),
+      )
+      .map(Arc::new),
+      quarantine,
+      upload_idle_timeout: Duration::from_secs(args.upload_idle_timeout),
+      upload_quota: UploadQuota {
+        max_uploads: args.max_uploads,
+        max_bytes: args.max_upload_bytes,
+      },
+      job_diagnostics,
+      queue_limits: QueueLimits {
+        max_jobs: args.max_queued_jobs,
+        max_bytes: args.max_queued_bytes,
+      },
+      max_auto_page_height: Some(args.max_auto_page_height.saturating_mul(1000)),
+      retention: Some(retention.clone()),
+      dedupe_printers: !args.no_printer_dedupe,
+    };
+
+    // 检查默认打印设置，查询打印机较慢，不阻塞启动
+    let check_options = options.clone();
+    tokio::task::spawn_blocking(move || api::settings::check_default_settings(&check_options));
+
+    if args.warmup_on_start {
+      let warmup_options = options.clone();
+      tokio::task::spawn_blocking(move || api::capability::warm_up_on_start(&warmup_options));
+    }
+
+    // 先恢复暂停状态，再执行定时打印任务，包括服务停止期间错过的任务
+    status::restore();
+    api::dispatch::spawn_scheduler(options.clone());
+    // 跟踪已提交到系统打印队列的任务的打印进度
+    api::dispatch::spawn_progress_tracker();
+    // 删除过期的上传，包括服务停止期间过期的
+    api::upload::spawn_cleanup(options.upload_idle_timeout);
+    // 按保留策略删除回执、隔离的请求及诊断信息
+    retention.spawn_cleanup();
+    if args.daily_report {
+      api::summary::spawn_daily(args.report_timezone, args.daily_report_webhook.clone());
+    }
+
+    #[cfg(feature = "mqtt")]
+    let mqtt_bridge = match &args.mqtt_url {
+      Some(url) => {
+        let config = mqtt::MqttConfig {
+          url: url.clone(),
+          username: args.mqtt_username.clone(),
+          password: args.mqtt_password.clone(),
+          client_id: args.mqtt_client_id.clone(),
+          max_message_size: args.max_body_size,
+        };
+        Some(mqtt::spawn(config, options.clone()).map_err(std::io::Error::other)?)
+      }
+      None => None,
+    };
+
+    // 设置文件变化时自动重新加载
+    let _settings_watcher = api::settings::watch_settings()
+      .inspect_err(|e| warn!("Failed to watch the settings file: {}", e))
+      .ok();
+    let api_service = api::v1::service(&server, options.clone());
+    let body_limit = args.log_bodies.then_some(args.log_body_limit);
+    let strict_requests = args.strict_requests;
+    let max_body_size = args.max_body_size;
     #[cfg(feature = "with-ui")]
     let ui = api_service.swagger_ui();
-    #[cfg(feature = "with-ui")]
     let spec = api_service.spec_endpoint_yaml();
+    let spec_json = api_service.spec_endpoint();
+    let spec_yaml = api_service.spec_endpoint_yaml();
+
+    // 未带版本号的路径是 v1 的别名，仅为兼容旧客户端保留
+    let app = Route::new()
+      .at("/spec", spec)
+      .at("/spec.yaml", spec_yaml)
+      .at("/spec.json", spec_json)
+      .nest(
+        "/api/v1",
+        api_service
+          .around(move |ep, req| api::middleware::reject_unknown_fields(ep, req, strict_requests))
+          .around(move |ep, req| api::log_response(ep, req, body_limit))
+          .around(move |ep, req| api::middleware::content_encoding(ep, req, max_body_size)),
+      )
+      .nest(
+        "/api",
+        api::v1::service(&server, options)
+          .around(move |ep, req| api::middleware::reject_unknown_fields(ep, req, strict_requests))
+          .around(move |ep, req| api::log_response(ep, req, body_limit))
+          .around(move |ep, req| api::middleware::content_encoding(ep, req, max_body_size))
+          .with(api::deprecated("/api/v1")),
+      );
+
+    #[cfg(feature = "with-ui")]
+    let app = app.nest("/", ui);
 
-    let app = Route::new().nest("/api", api_service);
+    // 在请求的 span 内关联调用方的 trace
+    #[cfg(feature = "otel")]
+    let app = app.around(telemetry::propagate);
 
     #[cfg(feature = "with-ui")]
     let app = app
-      .nest("/", ui)
-      .nest("/spec", spec)
       .with(Tracing)
       .with(RequestId::default().reuse_id(ReuseId::Use));
 
-    let app = app.with(
-      Cors::new()
-        .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
-        .allow_credentials(false),
-    );
+    let app = app
+      .with(CatchPanic::new().with_handler(api::error::panic_response))
+      .catch_all_error(api::error::error_response)
+      .with(
+        Cors::new()
+          .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::DELETE,
+            Method::OPTIONS,
+          ])
+          .allow_credentials(false),
+      );
 
+    info!("Instance {} on {}", instance::id(), instance::hostname());
     info!("The API is served on {}", server);
+    info!(
+      "The specification is served on http://{0}/spec.json and http://{0}/spec.yaml",
+      addr
+    );
     #[cfg(feature = "with-ui")]
-    {
-      info!("The documentation is served on http://{}/", addr);
-      info!("The specification is served on http://{}/spec", addr);
+    info!("The documentation is served on http://{}/", addr);
+
+    let http = Server::new_with_acceptor(acceptor);
+
+    // 从托盘退出或按 Ctrl+C 时等待正在处理的请求完成
+    #[cfg(feature = "tray")]
+    let result = {
+      let quit = tray::spawn(addr);
+      http
+        .run_with_graceful_shutdown(
+          app,
+          async move { quit.notified().await },
+          Some(Duration::from_secs(30)),
+        )
+        .await
+    };
+
+    #[cfg(not(feature = "tray"))]
+    let result = http
+      .run_with_graceful_shutdown(
+        app,
+        async {
+          let _ = tokio::signal::ctrl_c().await;
+        },
+        Some(Duration::from_secs(30)),
+      )
+      .await;
+
+    #[cfg(feature = "mqtt")]
+    if let Some(bridge) = mqtt_bridge {
+      bridge.shutdown().await;
     }
 
-    Server::new(TcpListener::bind(addr)).run(app).await
+    result
+  }
+}
+
+/// 输出生效的配置及其来源，默认值不输出，隐藏环境变量值的选项视为机密
+/// 输出错误并按错误类型以对应的退出码退出，见 [`EXIT_CODES`]，其他错误使用 `fallback`
+fn exit_with(e: anyhow::Error, fallback: i32) -> ! {
+  eprintln!("Error: {:#}", e);
+  let code = match e.downcast_ref::<CodedError>().map(|e| &e.code) {
+    Some(ErrorCode::PrinterNotFound | ErrorCode::AmbiguousPrinter) => 3,
+    Some(ErrorCode::UnsupportedSettings) => 4,
+    Some(_) => 5,
+    None => fallback,
+  };
+  std::process::exit(code)
+}
+
+fn log_config(matches: &ArgMatches) {
+  for arg in Args::command().get_arguments() {
+    let id = arg.get_id().as_str();
+    let source = match matches.value_source(id) {
+      Some(ValueSource::CommandLine) => "command line",
+      Some(ValueSource::EnvVariable) => "environment",
+      _ => continue,
+    };
+    let value = if arg.is_hide_env_values_set() {
+      "<redacted>".to_string()
+    } else {
+      matches
+        .get_raw(id)
+        .into_iter()
+        .flatten()
+        .map(|v| v.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(",")
+    };
+
+    info!("Option {} = {} (from {})", id, value, source);
   }
 }
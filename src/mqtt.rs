@@ -0,0 +1,318 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{anyhow, bail};
+use log::{debug, info, warn};
+use rumqttc::{
+  AsyncClient, Event, EventLoop, LastWill, MqttOptions, Packet, QoS, TlsConfiguration, Transport,
+};
+use serde::Serialize;
+use tokio::{
+  sync::{broadcast::error::RecvError, Notify},
+  task::JoinHandle,
+  time::{interval, sleep, timeout, MissedTickBehavior},
+};
+use winprint::printer::PrinterDevice;
+
+use crate::{
+  api::{events, options::ApiOptions, v1},
+  instance, spooler, status,
+};
+
+/// 首次重连前等待的时间，之后每次加倍
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// 重连前等待的最长时间
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// 检查打印机状态的间隔，有变化时才发布
+const AVAILABILITY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// 停止时等待断开连接的时间
+const DISCONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 请求队列的容量
+const REQUEST_CAPACITY: usize = 64;
+
+/// MQTT 桥接的配置
+#[derive(Debug, Clone)]
+pub struct MqttConfig {
+  /// 代理地址，如 `mqtt://broker:1883` 或 `mqtts://broker:8883`
+  pub url: String,
+  pub username: Option<String>,
+  pub password: Option<String>,
+  /// 客户端 ID，默认由实例 ID 生成
+  pub client_id: Option<String>,
+  /// 接收的消息的最大长度（字节）
+  pub max_message_size: usize,
+}
+
+/// 主题，均以 `direct-printing/<实例 ID>` 开头
+struct Topics {
+  prefix: String,
+}
+
+impl Topics {
+  fn new() -> Self {
+    Self {
+      prefix: format!("direct-printing/{}", instance::id()),
+    }
+  }
+
+  /// 提交打印任务，`print/<请求 ID>` 的结果发布到 `responses/<请求 ID>`
+  fn print(&self) -> String {
+    format!("{}/print", self.prefix)
+  }
+
+  fn print_with_id(&self) -> String {
+    format!("{}/print/+", self.prefix)
+  }
+
+  /// 打印请求的结果，统一响应的 JSON
+  fn response(&self, topic: &str) -> Option<String> {
+    let rest = topic.strip_prefix(&self.print())?;
+    match rest.strip_prefix('/') {
+      None if rest.is_empty() => Some(format!("{}/responses", self.prefix)),
+      Some(id) if !id.is_empty() => Some(format!("{}/responses/{}", self.prefix, id)),
+      _ => None,
+    }
+  }
+
+  /// 任务状态的变化
+  fn job(&self, id: &str) -> String {
+    format!("{}/jobs/{}", self.prefix, id)
+  }
+
+  /// 打印机的可用状态，保留消息
+  fn printers(&self) -> String {
+    format!("{}/printers", self.prefix)
+  }
+
+  /// 桥接的在线状态，保留消息，连接断开时由代理发布 `offline`
+  fn status(&self) -> String {
+    format!("{}/status", self.prefix)
+  }
+}
+
+/// 运行中的 MQTT 桥接
+pub struct MqttBridge {
+  shutdown: Arc<Notify>,
+  task: JoinHandle<()>,
+}
+
+impl MqttBridge {
+  /// 发布离线状态并断开连接
+  pub async fn shutdown(self) {
+    self.shutdown.notify_one();
+    if let Err(e) = self.task.await {
+      warn!("The MQTT bridge stopped abnormally: {}", e);
+    }
+  }
+}
+
+/// 连接代理并在后台运行桥接，连接断开后自动重连
+pub fn spawn(config: MqttConfig, options: ApiOptions) -> anyhow::Result<MqttBridge> {
+  let topics = Topics::new();
+  let mut mqtt = mqtt_options(&config)?;
+  mqtt.set_last_will(LastWill::new(
+    topics.status(),
+    "offline",
+    QoS::AtLeastOnce,
+    true,
+  ));
+
+  let (client, eventloop) = AsyncClient::new(mqtt, REQUEST_CAPACITY);
+  let shutdown = Arc::new(Notify::new());
+  let task = tokio::spawn(run(client, eventloop, options, topics, shutdown.clone()));
+
+  info!("MQTT bridge connecting to {}", config.url);
+  Ok(MqttBridge { shutdown, task })
+}
+
+/// 解析代理地址，格式为 `mqtt://主机[:端口]` 或 `mqtts://主机[:端口]`
+fn mqtt_options(config: &MqttConfig) -> anyhow::Result<MqttOptions> {
+  let (scheme, rest) = config
+    .url
+    .split_once("://")
+    .ok_or_else(|| anyhow!("Invalid MQTT URL {}, expected mqtt://host:port", config.url))?;
+  let (transport, default_port) = match scheme {
+    "mqtt" | "tcp" => (Transport::Tcp, 1883),
+    "mqtts" | "ssl" => (Transport::tls_with_config(TlsConfiguration::Native), 8883),
+    _ => bail!("Unsupported MQTT URL scheme {}", scheme),
+  };
+
+  let authority = rest.trim_end_matches('/');
+  // IPv6 地址写在方括号内，如 `mqtt://[::1]:1883`
+  let (host, port) = match authority.rsplit_once(':') {
+    Some((host, port)) if !port.contains(']') => {
+      let port = port
+        .parse()
+        .map_err(|_| anyhow!("Invalid port in MQTT URL {}", config.url))?;
+      (host, port)
+    }
+    _ => (authority, default_port),
+  };
+  let host = host.trim_start_matches('[').trim_end_matches(']');
+  if host.is_empty() {
+    bail!("No host in MQTT URL {}", config.url);
+  }
+
+  let client_id = config
+    .client_id
+    .clone()
+    .unwrap_or_else(|| format!("direct-printing-{}", instance::id()));
+  let mut options = MqttOptions::new(client_id, host, port);
+  options
+    .set_transport(transport)
+    .set_keep_alive(Duration::from_secs(30))
+    .set_max_packet_size(config.max_message_size, config.max_message_size);
+
+  if let Some(username) = &config.username {
+    options.set_credentials(username, config.password.clone().unwrap_or_default());
+  }
+
+  Ok(options)
+}
+
+async fn run(
+  client: AsyncClient,
+  mut eventloop: EventLoop,
+  options: ApiOptions,
+  topics: Topics,
+  shutdown: Arc<Notify>,
+) {
+  let mut events = events::subscribe();
+  let mut backoff = INITIAL_BACKOFF;
+  let mut availability = interval(AVAILABILITY_INTERVAL);
+  availability.set_missed_tick_behavior(MissedTickBehavior::Delay);
+  let mut published: Option<String> = None;
+
+  loop {
+    tokio::select! {
+      _ = shutdown.notified() => break,
+      event = eventloop.poll() => match event {
+        Ok(Event::Incoming(Packet::ConnAck(_))) => {
+          info!("MQTT bridge connected, listening on {}", topics.print());
+          backoff = INITIAL_BACKOFF;
+          // 不保留会话，每次连接后重新订阅并发布状态
+          published = None;
+          let subscribed = client
+            .try_subscribe(topics.print(), QoS::AtLeastOnce)
+            .and_then(|_| client.try_subscribe(topics.print_with_id(), QoS::AtLeastOnce))
+            .and_then(|_| client.try_publish(topics.status(), QoS::AtLeastOnce, true, "online"));
+          if let Err(e) = subscribed {
+            warn!("Failed to subscribe to MQTT topics: {}", e);
+          }
+        }
+        Ok(Event::Incoming(Packet::Publish(publish))) => {
+          let Some(response) = topics.response(&publish.topic) else {
+            continue;
+          };
+          debug!("MQTT print request on {}", publish.topic);
+          let client = client.clone();
+          let options = options.clone();
+          // 打印可能较慢，不阻塞收发消息
+          tokio::spawn(async move {
+            let result = v1::submit_message(&options, &publish.payload, "mqtt").await;
+            if let Err(e) = client
+              .publish(response, QoS::AtLeastOnce, false, result.to_string())
+              .await
+            {
+              warn!("Failed to publish the MQTT print response: {}", e);
+            }
+          });
+        }
+        Ok(_) => {}
+        Err(e) => {
+          warn!(
+            "MQTT connection failed, retrying in {} s: {}",
+            backoff.as_secs(),
+            e
+          );
+          tokio::select! {
+            _ = sleep(backoff) => {}
+            _ = shutdown.notified() => break,
+          }
+          backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+      },
+      event = events.recv() => match event {
+        Ok(event) => {
+          let payload = serde_json::to_string(&event).unwrap_or_default();
+          let topic = topics.job(&event.job_id);
+          if let Err(e) = client.try_publish(topic, QoS::AtLeastOnce, false, payload) {
+            debug!("Failed to publish the status of job {}: {}", event.job_id, e);
+          }
+        }
+        Err(RecvError::Lagged(skipped)) => {
+          warn!("MQTT bridge skipped {} job status changes", skipped);
+        }
+        Err(RecvError::Closed) => {}
+      },
+      _ = availability.tick() => {
+        let printers = match tokio::task::spawn_blocking(availability_snapshot).await {
+          Ok(printers) => printers,
+          Err(_) => continue,
+        };
+        let payload = serde_json::to_string(&printers).unwrap_or_default();
+        if published.as_ref() != Some(&payload) {
+          match client.try_publish(topics.printers(), QoS::AtLeastOnce, true, payload.clone()) {
+            Ok(()) => published = Some(payload),
+            Err(e) => debug!("Failed to publish printer availability: {}", e),
+          }
+        }
+      }
+    }
+  }
+
+  // 正常停止时代理不发布遗嘱消息，自行发布离线状态
+  let _ = client.try_publish(topics.status(), QoS::AtLeastOnce, true, "offline");
+  let _ = client.try_disconnect();
+  let drained = timeout(DISCONNECT_TIMEOUT, async {
+    while eventloop.poll().await.is_ok() {}
+  })
+  .await;
+  if drained.is_err() {
+    warn!("Timed out disconnecting from the MQTT broker");
+  }
+  info!("MQTT bridge stopped");
+}
+
+/// 打印机的可用状态
+#[derive(Debug, Serialize)]
+struct PrinterAvailability {
+  name: String,
+  /// 打印机就绪且服务未暂停该打印机
+  available: bool,
+  /// 服务暂停了该打印机，任务在服务内排队
+  paused: bool,
+  /// 导致无法打印的问题
+  problems: Vec<&'static str>,
+}
+
+fn availability_snapshot() -> Vec<PrinterAvailability> {
+  let printers = match PrinterDevice::all() {
+    Ok(printers) => printers,
+    Err(e) => {
+      warn!("Failed to list printers for MQTT: {}", e);
+      return Vec::new();
+    }
+  };
+
+  printers
+    .iter()
+    .map(|printer| {
+      let name = printer.name().to_string();
+      let problems = match spooler::printer_status(printer.os_name()) {
+        Ok(status) => status.problems(),
+        Err(_) => vec!["unknown"],
+      };
+      let paused = status::is_printer_paused(&name);
+      PrinterAvailability {
+        available: problems.is_empty() && !paused,
+        name,
+        paused,
+        problems,
+      }
+    })
+    .collect()
+}
@@ -0,0 +1,1168 @@
+use std::{
+  collections::HashMap,
+  io::Cursor,
+  sync::{Mutex, MutexGuard, OnceLock},
+};
+
+use anyhow::{anyhow, bail};
+use chrono::{DateTime, FixedOffset, NaiveDate, TimeZone};
+use image::{DynamicImage, ImageFormat};
+use pdfium_render::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// 获取 Pdfium 实例，与 winprint 共用同一个 pdfium.dll
+fn pdfium() -> anyhow::Result<&'static Pdfium> {
+  static PDFIUM: OnceLock<Result<Pdfium, String>> = OnceLock::new();
+
+  PDFIUM
+    .get_or_init(|| {
+      Pdfium::bind_to_system_library()
+        .map(Pdfium::new)
+        .map_err(|e| e.to_string())
+    })
+    .as_ref()
+    .map_err(|e| anyhow!("Failed to load pdfium: {}", e))
+}
+
+/// 预先加载 Pdfium，避免第一次处理文档时较慢
+pub fn init() -> anyhow::Result<()> {
+  pdfium().map(|_| ())
+}
+
+/// Pdfium 不是线程安全的，处理文档和打印时都需要持有此锁
+pub fn lock() -> MutexGuard<'static, ()> {
+  static LOCK: Mutex<()> = Mutex::new(());
+  LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 获取 PDF 文件的页数
+pub fn page_count(file: &[u8]) -> anyhow::Result<u32> {
+  let _guard = lock();
+  let doc = pdfium()?.load_pdf_from_byte_slice(file, None)?;
+  Ok(doc.pages().len() as u32)
+}
+
+/// 各页按宽度等比缩放到 `width` 后最高一页的高度，单位同 `width`，向上取整
+pub fn fitted_height(file: &[u8], width: u32) -> anyhow::Result<u32> {
+  let _guard = lock();
+  let doc = pdfium()?.load_pdf_from_byte_slice(file, None)?;
+  let height = doc
+    .pages()
+    .iter()
+    .map(|page| page.height().value as f64 * width as f64 / page.width().value as f64)
+    .fold(0.0, f64::max);
+  Ok(height.ceil() as u32)
+}
+
+/// 文档信息字典中的元数据，缺少、为空或无法解码的项为空
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DocumentMetadata {
+  pub title: Option<String>,
+  pub author: Option<String>,
+  pub producer: Option<String>,
+  pub creation_date: Option<DateTime<FixedOffset>>,
+}
+
+/// 打开文档后获取的信息
+#[derive(Debug, Clone)]
+pub struct DocumentSummary {
+  pub pages: u32,
+  pub metadata: DocumentMetadata,
+}
+
+/// 获取 PDF 文件的页数及元数据
+pub fn inspect(file: &[u8]) -> anyhow::Result<DocumentSummary> {
+  let _guard = lock();
+  let doc = pdfium()?.load_pdf_from_byte_slice(file, None)?;
+  let metadata = doc.metadata();
+  let tag = |tag| {
+    metadata
+      .get(tag)
+      .map(|t| t.value().trim().to_string())
+      .filter(|v| !v.is_empty())
+  };
+
+  Ok(DocumentSummary {
+    pages: doc.pages().len() as u32,
+    metadata: DocumentMetadata {
+      title: tag(PdfDocumentMetadataTagType::Title),
+      author: tag(PdfDocumentMetadataTagType::Author),
+      producer: tag(PdfDocumentMetadataTagType::Producer),
+      creation_date: tag(PdfDocumentMetadataTagType::CreationDate)
+        .as_deref()
+        .and_then(parse_date),
+    },
+  })
+}
+
+/// 解析 PDF 的日期，格式为 `D:YYYYMMDDHHmmSSOHH'mm'`，年份之后的各部分均可省略，没有时区时为 UTC
+fn parse_date(date: &str) -> Option<DateTime<FixedOffset>> {
+  let date = date.strip_prefix("D:").unwrap_or(date);
+  let digits = date.chars().take_while(char::is_ascii_digit).count();
+  let part = |start: usize, default: u32| match date.get(start..start + 2) {
+    Some(part) if start + 2 <= digits => part.parse().ok(),
+    _ => Some(default),
+  };
+
+  let year = date.get(..4).filter(|_| digits >= 4)?.parse().ok()?;
+  let time = NaiveDate::from_ymd_opt(year, part(4, 1)?, part(6, 1)?)?.and_hms_opt(
+    part(8, 0)?,
+    part(10, 0)?,
+    part(12, 0)?,
+  )?;
+
+  let zone = &date[digits..];
+  let offset = match zone.chars().next() {
+    Some(sign @ ('+' | '-')) => {
+      let zone: String = zone.chars().filter(char::is_ascii_digit).collect();
+      let hours: i32 = zone.get(..2)?.parse().ok()?;
+      let minutes: i32 = zone.get(2..4).map_or(Some(0), |m| m.parse().ok())?;
+      let seconds = (hours * 60 + minutes) * 60;
+      FixedOffset::east_opt(if sign == '-' { -seconds } else { seconds })?
+    }
+    _ => FixedOffset::east_opt(0)?,
+  };
+  offset.from_local_datetime(&time).single()
+}
+
+/// 按顺序合并多个 PDF 文件，出错时指明文件的序号（从 0 开始）
+pub fn merge(files: &[&[u8]]) -> anyhow::Result<Vec<u8>> {
+  let _guard = lock();
+  let pdfium = pdfium()?;
+  let mut dest = pdfium.create_new_pdf()?;
+
+  for (i, file) in files.iter().enumerate() {
+    // 加密且需要密码的文档无法打开
+    let src = pdfium
+      .load_pdf_from_byte_slice(file, None)
+      .map_err(|e| anyhow!("Cannot open file {}: {}", i, e))?;
+    dest
+      .pages_mut()
+      .append(&src)
+      .map_err(|e| anyhow!("Cannot merge file {}: {}", i, e))?;
+  }
+
+  Ok(dest.save_to_bytes()?)
+}
+
+/// 按顺序将 PDF 文件拆分为每份 `every` 页，最后一份可能不足 `every` 页
+pub fn split(file: &[u8], every: u32) -> anyhow::Result<Vec<Vec<u8>>> {
+  if every == 0 {
+    bail!("Cannot split into chunks of 0 pages");
+  }
+
+  let _guard = lock();
+  let pdfium = pdfium()?;
+  let src = pdfium.load_pdf_from_byte_slice(file, None)?;
+  let count = src.pages().len();
+  let mut chunks = Vec::new();
+
+  for start in (0..count).step_by(every as usize) {
+    let end = (start + every as PdfPageIndex).min(count) - 1;
+    let mut dest = pdfium.create_new_pdf()?;
+    dest
+      .pages_mut()
+      .copy_page_range_from_document(&src, start..=end, 0)?;
+    chunks.push(dest.save_to_bytes()?);
+  }
+
+  Ok(chunks)
+}
+
+/// 表单字段
+#[derive(Debug, Clone)]
+pub struct FormField {
+  pub name: String,
+  /// 是否必填
+  pub required: bool,
+  /// 是否为复选框，否则为文本框
+  pub checkbox: bool,
+}
+
+/// 获取 PDF 文件中可填写的文本框和复选框，同名字段只返回一次
+pub fn form_fields(file: &[u8]) -> anyhow::Result<Vec<FormField>> {
+  let _guard = lock();
+  let doc = pdfium()?.load_pdf_from_byte_slice(file, None)?;
+  let mut fields = Vec::<FormField>::new();
+
+  for page in doc.pages().iter() {
+    for annotation in page.annotations().iter() {
+      let Some(field) = annotation.as_form_field() else {
+        continue;
+      };
+      let checkbox = match field.field_type() {
+        PdfFormFieldType::Text => false,
+        PdfFormFieldType::Checkbox => true,
+        _ => continue,
+      };
+      let Some(name) = field.name().filter(|_| !field.is_read_only()) else {
+        continue;
+      };
+
+      match fields.iter_mut().find(|f| f.name == name) {
+        Some(f) => f.required |= field.is_required(),
+        None => fields.push(FormField {
+          name,
+          required: field.is_required(),
+          checkbox,
+        }),
+      }
+    }
+  }
+
+  Ok(fields)
+}
+
+/// 按字段名称填写表单并固化到页面内容中，复选框的值为 `true`、`yes`、`on` 或 `1` 时勾选
+pub fn fill_form(file: &[u8], values: &HashMap<String, String>) -> anyhow::Result<Vec<u8>> {
+  let _guard = lock();
+  let doc = pdfium()?.load_pdf_from_byte_slice(file, None)?;
+
+  for mut page in doc.pages().iter() {
+    for mut annotation in page.annotations().iter() {
+      let Some(field) = annotation.as_form_field_mut() else {
+        continue;
+      };
+      let Some(value) = field.name().and_then(|name| values.get(&name)) else {
+        continue;
+      };
+
+      if let Some(text) = field.as_text_field_mut() {
+        text.set_value(value)?;
+      } else if let Some(checkbox) = field.as_checkbox_field_mut() {
+        let checked = matches!(
+          value.to_ascii_lowercase().as_str(),
+          "true" | "yes" | "on" | "1"
+        );
+        checkbox.set_checked(checked)?;
+      }
+    }
+
+    // 固化后打印时不依赖表单的外观流
+    page.flatten()?;
+  }
+
+  Ok(doc.save_to_bytes()?)
+}
+
+/// 文本对齐方式
+#[derive(Debug, Clone, Copy, Default)]
+pub enum TextAlign {
+  #[default]
+  Left,
+  Center,
+  Right,
+}
+
+/// 表格列
+#[derive(Debug, Clone)]
+pub struct TableColumn {
+  pub header: String,
+  /// 宽度（微米），为空时平分剩余宽度
+  pub width: Option<u32>,
+  pub align: TextAlign,
+}
+
+/// 表格的页面布局，长度单位为微米
+#[derive(Debug, Clone, Copy)]
+pub struct TableLayout {
+  pub page_width: u32,
+  /// 为空时高度随内容，所有行在同一页
+  pub page_height: Option<u32>,
+  pub margin: u32,
+  /// 字号（磅）
+  pub font_size: f32,
+  /// 是否隔行填充底色
+  pub zebra: bool,
+}
+
+/// 折行后的单元格，每行为文本及其宽度（磅）
+type Cell = Vec<(String, f32)>;
+
+/// 将行数据排版为表格，每页重复表头，单元格内容超出列宽时折行，超过一页的行被截断。
+/// `font` 为 TrueType 字体，为空时使用只支持拉丁字母的内置 Helvetica
+pub fn table(
+  columns: &[TableColumn],
+  rows: &[Vec<String>],
+  layout: &TableLayout,
+  font: Option<&[u8]>,
+) -> anyhow::Result<Vec<u8>> {
+  let _guard = lock();
+  let mut doc = pdfium()?.create_new_pdf()?;
+  let (font, bold) = match font {
+    Some(data) => {
+      let font = doc.fonts_mut().load_true_type_from_bytes(data, true)?;
+      (font, font)
+    }
+    None => (
+      doc.fonts_mut().helvetica(),
+      doc.fonts_mut().helvetica_bold(),
+    ),
+  };
+
+  let pt = |micron: u32| micron as f32 * 72.0 / 25400.0;
+  let page_width = pt(layout.page_width);
+  let margin = pt(layout.margin);
+  let size = layout.font_size;
+  let padding = size * 0.3;
+  let line_height = size * 1.2;
+
+  // 列宽
+  let available = page_width - margin * 2.0;
+  let fixed = columns.iter().filter_map(|c| c.width).map(pt).sum::<f32>();
+  let auto = columns.iter().filter(|c| c.width.is_none()).count();
+  let auto_width = if auto > 0 {
+    (available - fixed) / auto as f32
+  } else {
+    0.0
+  };
+  let widths = columns
+    .iter()
+    .map(|c| c.width.map(pt).unwrap_or(auto_width))
+    .collect::<Vec<_>>();
+
+  if fixed > available || widths.iter().any(|w| *w <= padding * 2.0 + size) {
+    bail!("Columns do not fit in the page width");
+  }
+
+  // 先折行，再生成页面
+  let mut widths_cache = HashMap::new();
+  let mut measure = |c: char, font: PdfFontToken| -> anyhow::Result<f32> {
+    if let Some(width) = widths_cache.get(&(c, font)) {
+      return Ok(*width);
+    }
+
+    // 空白字符没有轮廓，夹在两个字符间测量
+    let text_width = |text: String| -> anyhow::Result<f32> {
+      let object = PdfPageTextObject::new(&doc, text, font, PdfPoints::new(size))?;
+      Ok(object.width()?.value)
+    };
+    let width = text_width(format!("|{}|", c))? - text_width("||".to_string())?;
+    widths_cache.insert((c, font), width);
+    Ok(width)
+  };
+  let header = columns
+    .iter()
+    .zip(&widths)
+    .map(|(c, w)| wrap(&c.header, w - padding * 2.0, |ch| measure(ch, bold)))
+    .collect::<anyhow::Result<Vec<_>>>()?;
+  let mut cells = rows
+    .iter()
+    .map(|row| {
+      widths
+        .iter()
+        .enumerate()
+        .map(|(i, w)| {
+          let text = row.get(i).map(String::as_str).unwrap_or_default();
+          wrap(text, w - padding * 2.0, |ch| measure(ch, font))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()
+    })
+    .collect::<anyhow::Result<Vec<_>>>()?;
+
+  let row_height = |row: &[Cell]| {
+    let lines = row.iter().map(Vec::len).max().unwrap_or_default().max(1);
+    lines as f32 * line_height + padding * 2.0
+  };
+  let header_height = row_height(&header);
+  let page_height = match layout.page_height {
+    Some(height) => pt(height),
+    // 向上取整，避免累加的舍入误差使最后一行换页
+    None => {
+      (margin * 2.0 + header_height + cells.iter().map(|row| row_height(row)).sum::<f32>()).ceil()
+    }
+  };
+  let (top, bottom) = (page_height - margin, margin);
+  let max_lines = ((top - bottom - header_height - padding * 2.0) / line_height).floor();
+
+  if max_lines < 1.0 {
+    bail!("The page is too small for the table");
+  }
+
+  let grid = PdfColor::new(128, 128, 128, 255);
+  let stripe = PdfColor::new(235, 235, 235, 255);
+  let draw = |page: &mut PdfPage, row: &[Cell], y: f32, height: f32, font, fill| {
+    let objects = page.objects_mut();
+    let right = margin + widths.iter().sum::<f32>();
+
+    if let Some(fill) = fill {
+      let rect = PdfRect::new_from_values(y - height, margin, y, right);
+      objects.create_path_object_rect(rect, None, None, Some(fill))?;
+    }
+
+    let mut x = margin;
+
+    for ((cell, width), column) in row.iter().zip(&widths).zip(columns) {
+      for (i, (text, text_width)) in cell.iter().enumerate() {
+        let left = match column.align {
+          TextAlign::Left => x + padding,
+          TextAlign::Center => x + (width - text_width) / 2.0,
+          TextAlign::Right => x + width - padding - text_width,
+        };
+        let baseline = y - padding - i as f32 * line_height - size;
+        objects.create_text_object(
+          PdfPoints::new(left),
+          PdfPoints::new(baseline),
+          text,
+          font,
+          PdfPoints::new(size),
+        )?;
+      }
+
+      x += width;
+    }
+
+    let line = PdfPoints::new(y - height);
+    objects.create_path_object_line(
+      PdfPoints::new(margin),
+      line,
+      PdfPoints::new(right),
+      line,
+      grid,
+      PdfPoints::new(0.5),
+    )?;
+    anyhow::Ok(())
+  };
+
+  let paper =
+    PdfPagePaperSize::from_points(PdfPoints::new(page_width), PdfPoints::new(page_height));
+  let mut page: Option<PdfPage> = None;
+  let mut y = top;
+
+  for (i, row) in cells.iter_mut().enumerate() {
+    for cell in row.iter_mut() {
+      cell.truncate(max_lines as usize);
+    }
+
+    let height = row_height(row);
+
+    if page.is_none() || y - height < bottom {
+      let mut new = doc.pages_mut().create_page_at_end(paper)?;
+      new.set_content_regeneration_strategy(PdfPageContentRegenerationStrategy::AutomaticOnDrop);
+      draw(&mut new, &header, top, header_height, bold, None)?;
+      y = top - header_height;
+      page = Some(new);
+    }
+
+    let fill = (layout.zebra && i % 2 == 1).then_some(stripe);
+    draw(page.as_mut().unwrap(), row, y, height, font, fill)?;
+    y -= height;
+  }
+
+  // 没有数据时只有表头
+  if page.is_none() {
+    let mut new = doc.pages_mut().create_page_at_end(paper)?;
+    draw(&mut new, &header, top, header_height, bold, None)?;
+  }
+
+  drop(page);
+  Ok(doc.save_to_bytes()?)
+}
+
+/// 标签元素，坐标和尺寸为距标签左上角的毫米数
+#[derive(Debug, Clone)]
+pub enum LabelElement {
+  /// 文本，`y` 为首行顶部，按换行符分行
+  Text {
+    x: f32,
+    y: f32,
+    text: String,
+    /// 字号（磅）
+    font_size: f32,
+  },
+  /// 一维条码，`modules` 为各模块是否为条
+  Bars {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    modules: Vec<bool>,
+  },
+  /// 二维码，`modules` 为按行排列的模块是否为深色
+  Matrix {
+    x: f32,
+    y: f32,
+    size: f32,
+    modules: Vec<Vec<bool>>,
+  },
+}
+
+/// 生成一页与标签大小（毫米）相同的 PDF 文件。`font` 同 [`table`]
+pub fn label(
+  width: f32,
+  height: f32,
+  elements: &[LabelElement],
+  font: Option<&[u8]>,
+) -> anyhow::Result<Vec<u8>> {
+  let _guard = lock();
+  let mut doc = pdfium()?.create_new_pdf()?;
+  let font = match font {
+    Some(data) => doc.fonts_mut().load_true_type_from_bytes(data, true)?,
+    None => doc.fonts_mut().helvetica(),
+  };
+
+  let pt = |mm: f32| mm * 72.0 / 25.4;
+  let top = pt(height);
+  let paper = PdfPagePaperSize::from_points(PdfPoints::new(pt(width)), PdfPoints::new(top));
+  let mut page = doc.pages_mut().create_page_at_end(paper)?;
+  page.set_content_regeneration_strategy(PdfPageContentRegenerationStrategy::AutomaticOnDrop);
+  let objects = page.objects_mut();
+  let black = PdfColor::new(0, 0, 0, 255);
+
+  // 连续的深色模块合并为一个矩形
+  let bars =
+    |objects: &mut PdfPageObjects, x: f32, y: f32, module: f32, height: f32, modules: &[bool]| {
+      let mut i = 0;
+
+      while i < modules.len() {
+        let run = modules[i..]
+          .iter()
+          .take_while(|m| **m == modules[i])
+          .count();
+
+        if modules[i] {
+          let left = x + module * i as f32;
+          let rect = PdfRect::new_from_values(
+            top - pt(y + height),
+            pt(left),
+            top - pt(y),
+            pt(left + module * run as f32),
+          );
+          objects.create_path_object_rect(rect, None, None, Some(black))?;
+        }
+
+        i += run;
+      }
+
+      anyhow::Ok(())
+    };
+
+  for element in elements {
+    match element {
+      LabelElement::Text {
+        x,
+        y,
+        text,
+        font_size,
+      } => {
+        for (i, line) in text.lines().enumerate() {
+          let baseline = top - pt(*y) - font_size * (1.2 * i as f32 + 1.0);
+          objects.create_text_object(
+            PdfPoints::new(pt(*x)),
+            PdfPoints::new(baseline),
+            line,
+            font,
+            PdfPoints::new(*font_size),
+          )?;
+        }
+      }
+      LabelElement::Bars {
+        x,
+        y,
+        width,
+        height,
+        modules,
+      } => {
+        let module = width / modules.len() as f32;
+        bars(objects, *x, *y, module, *height, modules)?;
+      }
+      LabelElement::Matrix {
+        x,
+        y,
+        size,
+        modules,
+      } => {
+        let module = size / modules.len() as f32;
+
+        for (i, row) in modules.iter().enumerate() {
+          bars(objects, *x, y + module * i as f32, module, module, row)?;
+        }
+      }
+    }
+  }
+
+  drop(page);
+  Ok(doc.save_to_bytes()?)
+}
+
+/// 按宽度（磅）折行，优先在空白处断开，没有空白时按字符断开
+fn wrap(
+  text: &str,
+  width: f32,
+  mut measure: impl FnMut(char) -> anyhow::Result<f32>,
+) -> anyhow::Result<Cell> {
+  let mut lines = Vec::new();
+
+  for paragraph in text.lines() {
+    let mut line = String::new();
+    let mut line_width = 0.0;
+    // 最近的空白字符在行内的位置及其之前的宽度
+    let mut breakable: Option<(usize, f32)> = None;
+
+    for c in paragraph.chars() {
+      let w = measure(c)?;
+
+      while line_width + w > width && !line.is_empty() {
+        match breakable.take() {
+          Some((at, before)) => {
+            let rest = line.split_off(at);
+            let mut chars = rest.chars();
+            let space = chars
+              .next()
+              .map(&mut measure)
+              .transpose()?
+              .unwrap_or_default();
+            lines.push((line, before));
+            line = chars.as_str().to_string();
+            line_width -= before + space;
+          }
+          None => {
+            lines.push((std::mem::take(&mut line), line_width));
+            line_width = 0.0;
+          }
+        }
+      }
+
+      if c.is_whitespace() {
+        breakable = Some((line.len(), line_width));
+      }
+
+      line.push(c);
+      line_width += w;
+    }
+
+    lines.push((line, line_width));
+  }
+
+  Ok(lines)
+}
+
+/// 渲染的页面图片
+pub struct RenderedPage {
+  /// 页码，从 1 开始
+  pub page: u32,
+  /// 图片宽度（像素）
+  pub width: u32,
+  /// 图片高度（像素）
+  pub height: u32,
+  /// 页面宽度（微米）
+  pub page_width: u32,
+  /// 页面高度（微米）
+  pub page_height: u32,
+  /// 编码后的图片
+  pub data: Vec<u8>,
+}
+
+/// 以 `width` 像素宽渲染指定页面（从 1 开始），`pages` 为空时渲染全部页面。
+/// 页数超过 `max_pages`，或渲染前按页面比例估算的像素总数超过 `max_pixels` 时报错
+pub fn render(
+  file: &[u8],
+  pages: Option<&[u32]>,
+  width: u32,
+  format: ImageFormat,
+  max_pages: usize,
+  max_pixels: u64,
+) -> anyhow::Result<Vec<RenderedPage>> {
+  let _guard = lock();
+  let doc = pdfium()?.load_pdf_from_byte_slice(file, None)?;
+  let count = doc.pages().len() as u32;
+  let pages = match pages {
+    Some(pages) => pages.to_vec(),
+    None => (1..=count).collect(),
+  };
+
+  if let Some(page) = pages.iter().find(|p| **p == 0 || **p > count) {
+    bail!(
+      "Page {} is out of range, the document has {} pages",
+      page,
+      count
+    );
+  }
+
+  if pages.len() > max_pages {
+    bail!(
+      "Too many pages, at most {} pages can be rendered at once",
+      max_pages
+    );
+  }
+
+  let mut sizes = Vec::with_capacity(pages.len());
+  let mut pixels = 0u64;
+
+  for page in &pages {
+    let size = doc.pages().page_size(*page as PdfPageIndex - 1)?;
+    let height = (width as f32 * size.height().value / size.width().value).ceil() as u32;
+    pixels += width as u64 * height as u64;
+    sizes.push(size);
+  }
+
+  if pixels > max_pixels {
+    bail!(
+      "Rendering would produce {} pixels, the limit is {}",
+      pixels,
+      max_pixels
+    );
+  }
+
+  let micron = |pt: PdfPoints| (pt.value * 25400.0 / 72.0).round() as u32;
+  let config = PdfRenderConfig::new().set_target_width(width as Pixels);
+  let mut rendered = Vec::with_capacity(pages.len());
+
+  for (page, size) in pages.into_iter().zip(sizes) {
+    let image = doc
+      .pages()
+      .get(page as PdfPageIndex - 1)?
+      .render_with_config(&config)?
+      .as_image()?;
+    // JPEG 不支持透明通道
+    let image = if format == ImageFormat::Jpeg {
+      image.into_rgb8().into()
+    } else {
+      image
+    };
+    let mut data = Vec::new();
+    image.write_to(&mut Cursor::new(&mut data), format)?;
+
+    rendered.push(RenderedPage {
+      page,
+      width: image.width(),
+      height: image.height(),
+      page_width: micron(size.width()),
+      page_height: micron(size.height()),
+      data,
+    });
+  }
+
+  Ok(rendered)
+}
+
+/// 空白页检测
+#[derive(Debug, Clone, Copy)]
+pub struct BlankPageDetection {
+  /// 有墨迹的像素占比不超过此值时视为空白页，0 到 1
+  pub max_coverage: f32,
+  /// 页数超过此值的文档不检测
+  pub max_pages: u32,
+}
+
+impl Default for BlankPageDetection {
+  fn default() -> Self {
+    Self {
+      max_coverage: 0.001,
+      max_pages: 500,
+    }
+  }
+}
+
+/// 检测空白页时渲染的宽度（像素）
+const BLANK_RENDER_WIDTH: Pixels = 200;
+/// 灰度低于此值的像素视为有墨迹，忽略扫描件等的浅色底纹
+const INK_LUMA: u8 = 230;
+
+/// 渲染页面用于估算墨迹，背景填充为白色，透明的页面视为白纸
+fn render_ink(
+  doc: &PdfDocument,
+  page: u32,
+  config: &PdfRenderConfig,
+) -> anyhow::Result<DynamicImage> {
+  Ok(
+    doc
+      .pages()
+      .get(page as PdfPageIndex - 1)?
+      .render_with_config(config)?
+      .as_image()?,
+  )
+}
+
+/// 以低分辨率渲染指定页面（从 1 开始），返回有墨迹的像素占比不超过 `max_coverage` 的页码
+pub fn blank_pages(file: &[u8], pages: &[u32], max_coverage: f32) -> anyhow::Result<Vec<u32>> {
+  let _guard = lock();
+  let doc = pdfium()?.load_pdf_from_byte_slice(file, None)?;
+  let config = PdfRenderConfig::new().set_target_width(BLANK_RENDER_WIDTH);
+  let mut blank = Vec::new();
+
+  for &page in pages {
+    let image = render_ink(&doc, page, &config)?.into_luma8();
+    let total = image.width() as usize * image.height() as usize;
+    let ink = image.pixels().filter(|p| p.0[0] < INK_LUMA).count();
+
+    if total == 0 || ink as f32 <= total as f32 * max_coverage {
+      blank.push(page);
+    }
+  }
+
+  Ok(blank)
+}
+
+/// 估算墨迹覆盖率的最高分辨率（DPI）
+pub const MAX_COVERAGE_DPI: u32 = 72;
+/// RGB 通道最大差值超过此值的像素视为彩色
+const COLOR_CHROMA: f32 = 0.1;
+
+/// 页面的墨迹覆盖率，均为 0 到 1
+#[derive(Debug, Clone)]
+pub struct PageCoverage {
+  pub page: u32,
+  /// 按灰度计算的覆盖率，全黑为 1
+  pub coverage: f32,
+  /// 是否有彩色内容
+  pub color: bool,
+  /// 青、品红、黄、黑各通道的覆盖率，由 RGB 简单换算
+  pub cmyk: [f32; 4],
+}
+
+/// 以 `dpi` 渲染指定页面（从 1 开始），`pages` 为空时为全部页面，估算各页的墨迹覆盖率。
+/// 按屏幕颜色估算，不考虑驱动的色彩管理及省墨设置。页数超过 `max_pages` 时报错
+pub fn coverage(
+  file: &[u8],
+  pages: Option<&[u32]>,
+  dpi: u32,
+  max_pages: usize,
+) -> anyhow::Result<Vec<PageCoverage>> {
+  let _guard = lock();
+  let doc = pdfium()?.load_pdf_from_byte_slice(file, None)?;
+  let count = doc.pages().len() as u32;
+  let pages = match pages {
+    Some(pages) => pages.to_vec(),
+    None => (1..=count).collect(),
+  };
+
+  if let Some(page) = pages.iter().find(|p| **p == 0 || **p > count) {
+    bail!(
+      "Page {} is out of range, the document has {} pages",
+      page,
+      count
+    );
+  }
+
+  if pages.len() > max_pages {
+    bail!(
+      "Too many pages, the coverage of at most {} pages can be estimated at once",
+      max_pages
+    );
+  }
+
+  let scale = dpi.clamp(1, MAX_COVERAGE_DPI) as f32 / 72.0;
+  let config = PdfRenderConfig::new().scale_page_by_factor(scale);
+  let mut estimated = Vec::with_capacity(pages.len());
+
+  for page in pages {
+    let image = render_ink(&doc, page, &config)?.into_rgb8();
+    let mut gray = 0.0;
+    let mut cmyk = [0.0; 4];
+    let mut color = false;
+
+    for pixel in image.pixels() {
+      let [r, g, b] = pixel.0.map(|v| v as f32 / 255.0);
+      let max = r.max(g).max(b);
+      let min = r.min(g).min(b);
+      color |= max - min > COLOR_CHROMA;
+      gray += 1.0 - (0.299 * r + 0.587 * g + 0.114 * b);
+
+      // 黑色为 1 - max，其余为去掉黑色后的比例
+      cmyk[3] += 1.0 - max;
+      if max > 0.0 {
+        cmyk[0] += (max - r) / max;
+        cmyk[1] += (max - g) / max;
+        cmyk[2] += (max - b) / max;
+      }
+    }
+
+    let pixels = (image.width() as usize * image.height() as usize).max(1) as f32;
+    estimated.push(PageCoverage {
+      page,
+      coverage: gray / pixels,
+      color,
+      cmyk: cmyk.map(|v| v / pixels),
+    });
+  }
+
+  Ok(estimated)
+}
+
+/// 页边距，单位为微米，以纸张上的上下左右为准，正值向内收缩，负值向外扩展
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PageMargins {
+  pub top: i32,
+  pub right: i32,
+  pub bottom: i32,
+  pub left: i32,
+  /// 是否将内容缩放到页边距以内，否则只平移内容
+  pub fit: bool,
+}
+
+impl PageMargins {
+  /// 将纸张上的页边距转换到页面未旋转时的坐标系
+  fn unrotate(self, degrees: u16) -> Self {
+    let Self {
+      top,
+      right,
+      bottom,
+      left,
+      fit,
+    } = self;
+    let (top, right, bottom, left) = match degrees % 360 {
+      90 => (right, bottom, left, top),
+      180 => (bottom, left, top, right),
+      270 => (left, top, right, bottom),
+      _ => (top, right, bottom, left),
+    };
+
+    Self {
+      top,
+      right,
+      bottom,
+      left,
+      fit,
+    }
+  }
+
+  /// 计算内容的变换矩阵
+  fn matrix(&self, media: &PdfRect) -> anyhow::Result<PdfMatrix> {
+    let pt = |micron: i32| micron as f32 * 72.0 / 25400.0;
+    let (left, bottom) = (media.left().value, media.bottom().value);
+    let (right, top) = (media.right().value, media.top().value);
+
+    if self.fit {
+      let width = right - left - pt(self.left) - pt(self.right);
+      let height = top - bottom - pt(self.top) - pt(self.bottom);
+
+      if width <= 0.0 || height <= 0.0 {
+        bail!("Margins leave no room for the page content");
+      }
+
+      // 等比缩放，内容靠左上角对齐
+      let scale = f32::min(width / (right - left), height / (top - bottom));
+      let x = left + pt(self.left) - left * scale;
+      let y = top - pt(self.top) - top * scale;
+      Ok(PdfMatrix::new(scale, 0.0, 0.0, scale, x, y))
+    } else {
+      let x = pt(self.left) - pt(self.right);
+      let y = pt(self.bottom) - pt(self.top);
+      Ok(PdfMatrix::new(1.0, 0.0, 0.0, 1.0, x, y))
+    }
+  }
+}
+
+/// 单页的处理方式
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PageTransform {
+  /// 需要追加的顺时针旋转角度
+  pub rotate: u16,
+  /// 页边距，在旋转后应用
+  pub margins: Option<PageMargins>,
+}
+
+/// 处理后的 PDF 文件
+pub struct Rearranged {
+  pub data: Vec<u8>,
+  /// 内容被完全移出页面的页码
+  pub off_page: Vec<u32>,
+}
+
+/// 按指定页码（从 1 开始）及顺序重新组织 PDF 文件，`transform` 返回各原页码的处理方式
+pub fn rearrange(
+  file: &[u8],
+  pages: &[u32],
+  transform: impl Fn(u32) -> PageTransform,
+) -> anyhow::Result<Rearranged> {
+  let _guard = lock();
+  let pdfium = pdfium()?;
+  let src = pdfium.load_pdf_from_byte_slice(file, None)?;
+  let mut dest = pdfium.create_new_pdf()?;
+  let mut off_page = Vec::new();
+
+  for (i, page) in pages.iter().enumerate() {
+    let index = i as PdfPageIndex;
+    dest
+      .pages_mut()
+      .copy_page_from_document(&src, *page as PdfPageIndex - 1, index)?;
+
+    let transform = transform(*page);
+    let mut copied = dest.pages().get(index)?;
+    let mut degrees = rotation_to_degrees(copied.rotation()?);
+
+    if !transform.rotate.is_multiple_of(360) {
+      degrees = (degrees + transform.rotate) % 360;
+      copied.set_rotation(degrees_to_rotation(degrees));
+    }
+
+    if let Some(margins) = transform.margins {
+      let media = copied.boundaries().media()?.bounds;
+      let matrix = margins.unrotate(degrees).matrix(&media)?;
+      copied.apply_matrix(matrix)?;
+
+      if !media.transform(matrix).does_overlap(&media) {
+        off_page.push(*page);
+      }
+    }
+  }
+
+  Ok(Rearranged {
+    data: dest.save_to_bytes()?,
+    off_page,
+  })
+}
+
+/// 拼版后的小册子
+pub struct Booklet {
+  pub data: Vec<u8>,
+  /// 每份使用的纸张数
+  pub sheets: u32,
+}
+
+/// 实际使用的纸张数。`pages` 为每份的页数（包括补的空白页），`pages_per_side` 为每面拼版的页数
+pub fn sheet_count(pages: u32, copies: u32, duplex: bool, pages_per_side: u32) -> u32 {
+  let sides = pages.div_ceil(pages_per_side.max(1));
+  let sheets = if duplex { sides.div_ceil(2) } else { sides };
+  sheets * copies
+}
+
+/// 骑马钉小册子的页面顺序，`count` 必须是 4 的倍数，每张纸依次为正面左右两页、背面左右两页
+pub fn booklet_order(count: u32) -> Vec<u32> {
+  (0..count / 4)
+    .flat_map(|i| {
+      let (front, back) = (2 * i + 1, count - 2 * i);
+      [back, front, front + 1, back - 1]
+    })
+    .collect()
+}
+
+/// 将页面按骑马钉顺序排列，并拼版为每面两页，不足 4 的倍数时补空白页。
+/// `media` 为输出纸张大小（微米），为空时使用首页宽度的两倍
+pub fn impose_booklet(file: &[u8], media: Option<(u32, u32)>) -> anyhow::Result<Booklet> {
+  let _guard = lock();
+  let pdfium = pdfium()?;
+  let src = pdfium.load_pdf_from_byte_slice(file, None)?;
+  let count = src.pages().len() as u32;
+
+  if count == 0 {
+    bail!("No pages to make a booklet");
+  }
+
+  let first = src.pages().page_size(0)?;
+  let blank = PdfPagePaperSize::from_points(first.width(), first.height());
+  let padded = count.div_ceil(4) * 4;
+  let mut ordered = pdfium.create_new_pdf()?;
+
+  for (i, page) in booklet_order(padded).into_iter().enumerate() {
+    let index = i as PdfPageIndex;
+
+    if page <= count {
+      ordered
+        .pages_mut()
+        .copy_page_from_document(&src, page as PdfPageIndex - 1, index)?;
+    } else {
+      ordered.pages_mut().create_page_at_index(blank, index)?;
+    }
+  }
+
+  // 输出页面总是横向
+  let size = match media {
+    Some((width, height)) => {
+      let pt = |micron: u32| PdfPoints::new(micron as f32 * 72.0 / 25400.0);
+      PdfPagePaperSize::from_points(pt(width.max(height)), pt(width.min(height)))
+    }
+    None => PdfPagePaperSize::from_points(first.width() * 2.0, first.height()),
+  };
+  let imposed = ordered.pages().tile_into_new_document(1, 2, size)?;
+
+  Ok(Booklet {
+    data: imposed.save_to_bytes()?,
+    sheets: padded / 4,
+  })
+}
+
+fn rotation_to_degrees(rotation: PdfPageRenderRotation) -> u16 {
+  match rotation {
+    PdfPageRenderRotation::None => 0,
+    PdfPageRenderRotation::Degrees90 => 90,
+    PdfPageRenderRotation::Degrees180 => 180,
+    PdfPageRenderRotation::Degrees270 => 270,
+  }
+}
+
+fn degrees_to_rotation(degrees: u16) -> PdfPageRenderRotation {
+  match degrees % 360 {
+    90 => PdfPageRenderRotation::Degrees90,
+    180 => PdfPageRenderRotation::Degrees180,
+    270 => PdfPageRenderRotation::Degrees270,
+    _ => PdfPageRenderRotation::None,
+  }
+}
+
+/// 解析页码范围，如 `1-3,5,8-`，返回从 1 开始的页码列表
+pub fn parse_page_range(range: &str, count: u32) -> anyhow::Result<Vec<u32>> {
+  let mut pages = Vec::new();
+
+  for part in range.split(',').map(str::trim).filter(|x| !x.is_empty()) {
+    let (start, end) = match part.split_once('-') {
+      Some((start, end)) => (start.trim(), end.trim()),
+      None => (part, part),
+    };
+    let parse = |s: &str, default: u32| -> anyhow::Result<u32> {
+      if s.is_empty() {
+        Ok(default)
+      } else {
+        s.parse()
+          .map_err(|_| anyhow!("Invalid page range: {}", part))
+      }
+    };
+    let start = parse(start, 1)?;
+    let end = parse(end, count)?;
+
+    if start == 0 || start > end {
+      bail!("Invalid page range: {}", part);
+    }
+    if end > count {
+      bail!(
+        "Page {} out of range, the document has {} pages",
+        end,
+        count
+      );
+    }
+
+    pages.extend(start..=end);
+  }
+
+  if pages.is_empty() {
+    bail!("Empty page range");
+  }
+
+  Ok(pages)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn counts_sheets() {
+    // 10 页，2 份，双面，每面 2 页
+    assert_eq!(sheet_count(10, 2, true, 2), 6);
+    assert_eq!(sheet_count(10, 1, false, 1), 10);
+    assert_eq!(sheet_count(10, 1, true, 1), 5);
+    assert_eq!(sheet_count(10, 3, false, 2), 15);
+    assert_eq!(sheet_count(10, 1, true, 4), 2);
+  }
+
+  #[test]
+  fn counts_partial_sheets() {
+    // 单数页的双面打印及未排满的拼版也使用整张纸
+    assert_eq!(sheet_count(9, 1, true, 1), 5);
+    assert_eq!(sheet_count(5, 2, false, 4), 4);
+    assert_eq!(sheet_count(1, 1, true, 2), 1);
+    assert_eq!(sheet_count(0, 1, false, 1), 0);
+  }
+
+  #[test]
+  fn treats_zero_pages_per_side_as_one() {
+    assert_eq!(sheet_count(3, 1, false, 0), 3);
+  }
+
+  #[test]
+  fn orders_booklet_pages() {
+    assert_eq!(booklet_order(4), vec![4, 1, 2, 3]);
+    assert_eq!(booklet_order(8), vec![8, 1, 2, 7, 6, 3, 4, 5]);
+  }
+}
@@ -0,0 +1,42 @@
+use std::{
+  sync::{Arc, Mutex},
+  time::{Duration, Instant},
+};
+
+use sentry::{integrations::anyhow::capture_anyhow, ClientInitGuard, ClientOptions};
+
+/// 每个时间窗口内最多上报的事件数
+const MAX_EVENTS: u32 = 10;
+/// 限流的时间窗口
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// 初始化错误上报，返回的 guard 需要保留到程序结束，以便发送剩余的事件
+pub fn init(dsn: &str) -> ClientInitGuard {
+  let window = Mutex::new((Instant::now(), 0));
+
+  let mut options = ClientOptions::default();
+  options.release = sentry::release_name!();
+  options.before_send = Some(Arc::new(move |event| {
+    let mut window = window.lock().unwrap_or_else(|e| e.into_inner());
+
+    if window.0.elapsed() >= WINDOW {
+      *window = (Instant::now(), 0);
+    }
+    if window.1 >= MAX_EVENTS {
+      return None;
+    }
+
+    window.1 += 1;
+    Some(event)
+  }));
+
+  sentry::init((dsn, options))
+}
+
+/// 上报打印失败，只包括打印机名称和错误链，不包括文档内容
+pub fn print_failure(printer: Option<&str>, error: &anyhow::Error) {
+  sentry::with_scope(
+    |scope| scope.set_tag("printer", printer.unwrap_or("<default>")),
+    || capture_anyhow(error),
+  );
+}
@@ -0,0 +1,667 @@
+use std::{
+  collections::HashMap,
+  ffi::{OsStr, OsString},
+  iter::once,
+  os::windows::ffi::{OsStrExt, OsStringExt},
+  sync::{LazyLock, Mutex},
+  thread,
+  time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use windows::{
+  core::{w, PCWSTR, PWSTR},
+  Win32::{
+    Foundation::{CloseHandle, ERROR_SERVICE_REQUEST_TIMEOUT, FILETIME, HANDLE, WIN32_ERROR},
+    Graphics::Printing::*,
+    System::{
+      Services::*,
+      Threading::{GetProcessTimes, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION},
+    },
+  },
+};
+use winprint::{printer::PdfiumPrinterError, ticket::ToDevModeError};
+
+/// 暂时性错误的 Win32 错误码，打印机从休眠中唤醒或网络抖动时会出现
+const TRANSIENT_ERRORS: &[u32] = &[
+  53,   // ERROR_BAD_NETPATH
+  59,   // ERROR_UNEXP_NET_ERR
+  64,   // ERROR_NETNAME_DELETED
+  121,  // ERROR_SEM_TIMEOUT
+  170,  // ERROR_BUSY
+  1722, // RPC_S_SERVER_UNAVAILABLE
+  1723, // RPC_S_SERVER_TOO_BUSY
+  1726, // RPC_S_CALL_FAILED
+  1727, // RPC_S_CALL_FAILED_DNE
+];
+
+/// 打印失败时的重试策略
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+  /// 最多重试次数
+  pub retries: u32,
+  /// 首次重试前的等待时间，之后每次加倍
+  pub delay: Duration,
+}
+
+impl Default for RetryPolicy {
+  fn default() -> Self {
+    Self {
+      retries: 2,
+      delay: Duration::from_secs(1),
+    }
+  }
+}
+
+impl RetryPolicy {
+  /// 第 `attempt` 次尝试失败后的等待时间，从 1 开始
+  pub fn backoff(&self, attempt: u32) -> Duration {
+    self.delay * 2u32.saturating_pow(attempt.saturating_sub(1))
+  }
+}
+
+/// 是否是重试可能成功的暂时性错误。打印票据和文件错误都是永久性的
+pub fn is_transient(e: &PdfiumPrinterError) -> bool {
+  match e {
+    PdfiumPrinterError::FailedToOpenPrinter => true,
+    PdfiumPrinterError::PrintTicketError(
+      ToDevModeError::OpenProviderFailed(e) | ToDevModeError::FailedToOpenPrinter(e),
+    ) => {
+      // HRESULT_FROM_WIN32 的低 16 位是 Win32 错误码
+      let code = e.code().0 as u32;
+      code & 0xffff_0000 == 0x8007_0000 && TRANSIENT_ERRORS.contains(&(code & 0xffff))
+    }
+    _ => false,
+  }
+}
+
+/// 打印机状态的缓存时间，连续打印时不必每次都查询后台处理程序
+const STATUS_TTL: Duration = Duration::from_secs(5);
+
+/// 打印机状态缓存，键为系统中的打印机名称
+static STATUS_CACHE: LazyLock<Mutex<HashMap<OsString, (Instant, PrinterStatus)>>> =
+  LazyLock::new(Default::default);
+
+/// 导致打印机无法打印的状态标志及其描述
+const NOT_READY: &[(u32, &str)] = &[
+  (PRINTER_STATUS_OFFLINE, "offline"),
+  (PRINTER_STATUS_ERROR, "error"),
+  (PRINTER_STATUS_PAPER_OUT, "paper out"),
+  (PRINTER_STATUS_PAPER_JAM, "paper jam"),
+  (PRINTER_STATUS_PAPER_PROBLEM, "paper problem"),
+  (PRINTER_STATUS_NO_TONER, "no toner"),
+  (PRINTER_STATUS_DOOR_OPEN, "door open"),
+  (PRINTER_STATUS_OUTPUT_BIN_FULL, "output bin full"),
+  (PRINTER_STATUS_NOT_AVAILABLE, "not available"),
+  (
+    PRINTER_STATUS_USER_INTERVENTION,
+    "user intervention required",
+  ),
+  (PRINTER_STATUS_SERVER_OFFLINE, "server offline"),
+];
+
+/// 后台处理程序报告的打印机状态
+#[derive(Debug, Clone, Copy)]
+pub struct PrinterStatus {
+  /// `PRINTER_STATUS_*` 标志
+  pub status: u32,
+  /// `PRINTER_ATTRIBUTE_*` 标志
+  pub attributes: u32,
+  /// 系统打印队列中的任务数
+  pub jobs: u32,
+}
+
+impl PrinterStatus {
+  /// 导致无法打印的问题，为空表示就绪
+  pub fn problems(&self) -> Vec<&'static str> {
+    let mut problems = NOT_READY
+      .iter()
+      .filter(|(flag, _)| self.status & flag != 0)
+      .map(|(_, desc)| *desc)
+      .collect::<Vec<_>>();
+
+    // 设置为“脱机使用打印机”时，状态标志中不一定有 OFFLINE
+    if self.attributes & PRINTER_ATTRIBUTE_WORK_OFFLINE != 0
+      && self.status & PRINTER_STATUS_OFFLINE == 0
+    {
+      problems.push("offline");
+    }
+
+    problems
+  }
+
+  /// 系统打印队列是否暂停
+  pub fn is_paused(&self) -> bool {
+    self.status & PRINTER_STATUS_PAUSED != 0
+  }
+}
+
+/// 读取打印机状态，`name` 为系统中的打印机名称。短时间内重复读取时使用缓存
+pub fn printer_status(name: &OsStr) -> windows::core::Result<PrinterStatus> {
+  {
+    let cache = STATUS_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+
+    if let Some((time, status)) = cache.get(name) {
+      if time.elapsed() < STATUS_TTL {
+        return Ok(*status);
+      }
+    }
+  }
+
+  let status = fetch_status(name)?;
+  STATUS_CACHE
+    .lock()
+    .unwrap_or_else(|e| e.into_inner())
+    .insert(name.to_os_string(), (Instant::now(), status));
+
+  Ok(status)
+}
+
+/// 暂停或恢复系统打印队列，需要管理打印机的权限
+pub fn set_paused(name: &OsStr, paused: bool) -> windows::core::Result<()> {
+  let command = if paused {
+    PRINTER_CONTROL_PAUSE
+  } else {
+    PRINTER_CONTROL_RESUME
+  };
+
+  control(name, command)
+}
+
+/// 清空系统打印队列，返回清空前的任务数
+pub fn purge(name: &OsStr) -> windows::core::Result<u32> {
+  let jobs = fetch_status(name)?.jobs;
+  control(name, PRINTER_CONTROL_PURGE)?;
+  Ok(jobs)
+}
+
+/// 系统打印队列中的任务
+#[derive(Debug, Clone)]
+pub struct SpoolerJob {
+  /// 文档名称
+  pub document: String,
+  /// `JOB_STATUS_*` 标志
+  pub status: u32,
+  /// 总页数，驱动未报告时为 0
+  pub total_pages: u32,
+  /// 已打印的页数，驱动未报告时为 0
+  pub pages_printed: u32,
+}
+
+impl SpoolerJob {
+  /// 已打印的页数，驱动未报告页数时为空
+  pub fn pages_printed(&self) -> Option<u32> {
+    (self.total_pages > 0 || self.pages_printed > 0).then_some(self.pages_printed)
+  }
+
+  /// 总页数，驱动未报告时为空
+  pub fn total_pages(&self) -> Option<u32> {
+    (self.total_pages > 0).then_some(self.total_pages)
+  }
+
+  /// 是否已打印完成或被删除，保留打印过的文档的打印机中仍会列出
+  pub fn is_done(&self) -> bool {
+    self.status & (JOB_STATUS_PRINTED | JOB_STATUS_COMPLETE | JOB_STATUS_DELETED) != 0
+  }
+}
+
+/// 读取系统打印队列中的全部任务
+pub fn jobs(name: &OsStr) -> windows::core::Result<Vec<SpoolerJob>> {
+  let printer = PrinterHandle::open(name, None)?;
+  let mut needed = 0;
+  let mut returned = 0;
+
+  // 第一次调用只获取需要的缓冲区大小
+  let _ = unsafe { EnumJobsW(printer.0, 0, u32::MAX, 1, None, &mut needed, &mut returned) };
+
+  // JOB_INFO_1W 包含指针，缓冲区需要按 8 字节对齐
+  let mut buf = vec![0u64; (needed as usize).div_ceil(8)];
+  let bytes =
+    unsafe { std::slice::from_raw_parts_mut(buf.as_mut_ptr().cast::<u8>(), buf.len() * 8) };
+  unsafe {
+    EnumJobsW(
+      printer.0,
+      0,
+      u32::MAX,
+      1,
+      Some(bytes),
+      &mut needed,
+      &mut returned,
+    )?
+  };
+
+  let infos =
+    unsafe { std::slice::from_raw_parts(buf.as_ptr().cast::<JOB_INFO_1W>(), returned as usize) };
+  Ok(
+    infos
+      .iter()
+      .map(|info| SpoolerJob {
+        document: match info.pDocument.is_null() {
+          true => String::new(),
+          false => unsafe { info.pDocument.to_string() }.unwrap_or_default(),
+        },
+        status: info.Status,
+        total_pages: info.TotalPages,
+        pages_printed: info.PagesPrinted,
+      })
+      .collect(),
+  )
+}
+
+/// 连接打印机时常见错误的 Win32 错误码及其说明
+const CONNECTION_ERRORS: &[(u32, &str)] = &[
+  (5, "access to the shared printer is denied"), // ERROR_ACCESS_DENIED
+  (53, "the print server is not found"),         // ERROR_BAD_NETPATH
+  (67, "the shared printer is not found"),       // ERROR_BAD_NET_NAME
+  (1801, "the printer name is invalid"),         // ERROR_INVALID_PRINTER_NAME
+  (3014, "the printer driver is blocked"),       // ERROR_PRINTER_DRIVER_BLOCKED
+  (
+    3019, // ERROR_PRINTER_DRIVER_DOWNLOAD_NEEDED
+    "the printer driver must be installed by an administrator first",
+  ),
+];
+
+/// 连接网络共享打印机，`path` 为 `\\server\printer` 形式的 UNC 路径，连接后即为系统中的打印机名称
+pub fn connect(path: &OsStr) -> windows::core::Result<()> {
+  let path = path.encode_wide().chain(once(0)).collect::<Vec<_>>();
+  unsafe { AddPrinterConnectionW(PCWSTR(path.as_ptr())).ok() }
+}
+
+/// 断开网络共享打印机的连接
+pub fn disconnect(path: &OsStr) -> windows::core::Result<()> {
+  let path = path.encode_wide().chain(once(0)).collect::<Vec<_>>();
+  unsafe { DeletePrinterConnectionW(PCWSTR(path.as_ptr())).ok() }
+}
+
+/// 连接或断开打印机失败的原因，未知错误时为空
+pub fn connection_error(e: &windows::core::Error) -> Option<&'static str> {
+  let code = e.code().0 as u32;
+
+  if code & 0xffff_0000 != 0x8007_0000 {
+    return None;
+  }
+
+  CONNECTION_ERRORS
+    .iter()
+    .find(|(c, _)| *c == code & 0xffff)
+    .map(|(_, desc)| *desc)
+}
+
+/// 向打印机发送控制命令，之后重新读取状态
+fn control(name: &OsStr, command: u32) -> windows::core::Result<()> {
+  let printer = PrinterHandle::open(name, Some(PRINTER_ACCESS_ADMINISTER))?;
+
+  unsafe { SetPrinterW(printer.0, 0, None, command)? };
+
+  STATUS_CACHE
+    .lock()
+    .unwrap_or_else(|e| e.into_inner())
+    .remove(name);
+  Ok(())
+}
+
+/// 系统打印队列保存假脱机文件的目录
+pub fn spool_directory() -> windows::core::Result<OsString> {
+  let mut handle = HANDLE::default();
+  // 打印机名称为空时打开本机的打印服务器
+  unsafe { OpenPrinterW(PCWSTR::null(), &mut handle, None)? };
+  let server = PrinterHandle(handle);
+
+  let mut needed = 0;
+  let _ = unsafe {
+    GetPrinterDataW(
+      server.0,
+      SPLREG_DEFAULT_SPOOL_DIRECTORY,
+      None,
+      None,
+      &mut needed,
+    )
+  };
+
+  let mut buf = vec![0u16; (needed as usize).div_ceil(2)];
+  let bytes =
+    unsafe { std::slice::from_raw_parts_mut(buf.as_mut_ptr().cast::<u8>(), buf.len() * 2) };
+  let status = unsafe {
+    GetPrinterDataW(
+      server.0,
+      SPLREG_DEFAULT_SPOOL_DIRECTORY,
+      None,
+      Some(bytes),
+      &mut needed,
+    )
+  };
+  WIN32_ERROR(status).ok()?;
+
+  let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+  Ok(OsString::from_wide(&buf[..len]))
+}
+
+/// 当前用户的默认打印机名称，没有默认打印机时为空
+pub fn default_printer() -> Option<OsString> {
+  let mut len = 0;
+  let _ = unsafe { GetDefaultPrinterW(PWSTR::null(), &mut len) };
+  if len == 0 {
+    return None;
+  }
+
+  let mut buf = vec![0u16; len as usize];
+  unsafe { GetDefaultPrinterW(PWSTR(buf.as_mut_ptr()), &mut len) }
+    .ok()
+    .ok()?;
+  let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+  Some(OsString::from_wide(&buf[..len]))
+}
+
+/// 打印机句柄，释放时关闭
+struct PrinterHandle(HANDLE);
+
+impl PrinterHandle {
+  /// 打开打印机，`access` 为空时使用默认权限
+  fn open(name: &OsStr, access: Option<PRINTER_ACCESS_RIGHTS>) -> windows::core::Result<Self> {
+    let name = name.encode_wide().chain(once(0)).collect::<Vec<_>>();
+    let mut handle = HANDLE::default();
+    let defaults = access.map(|access| PRINTER_DEFAULTSW {
+      DesiredAccess: access,
+      ..Default::default()
+    });
+
+    unsafe {
+      OpenPrinterW(
+        PCWSTR(name.as_ptr()),
+        &mut handle,
+        defaults.as_ref().map(|d| d as *const _),
+      )?
+    };
+
+    Ok(Self(handle))
+  }
+}
+
+impl Drop for PrinterHandle {
+  fn drop(&mut self) {
+    unsafe {
+      let _ = ClosePrinter(self.0);
+    }
+  }
+}
+
+fn fetch_status(name: &OsStr) -> windows::core::Result<PrinterStatus> {
+  with_printer_info(name, |info| PrinterStatus {
+    status: info.Status,
+    attributes: info.Attributes,
+    jobs: info.cJobs,
+  })
+}
+
+/// 打印机的端口、驱动等信息
+#[derive(Debug, Clone, Default)]
+pub struct PrinterInfo {
+  /// 端口名称，如 `USB001`、`WSD-...`
+  pub port: String,
+  /// 驱动名称
+  pub driver: String,
+  /// 位置
+  pub location: String,
+  /// 备注
+  pub comment: String,
+  /// 是否共享
+  pub shared: bool,
+  /// 共享名称
+  pub share_name: String,
+  /// 打印处理器接受的数据类型，如 `RAW`、`NT EMF 1.008`、`XPS2GDI`，无法读取时为空
+  pub datatypes: Vec<String>,
+}
+
+/// 打印机信息的缓存时间，这些信息很少变化
+const INFO_TTL: Duration = Duration::from_secs(60);
+
+/// 打印机信息缓存，键为系统中的打印机名称
+static INFO_CACHE: LazyLock<Mutex<HashMap<OsString, (Instant, PrinterInfo)>>> =
+  LazyLock::new(Default::default);
+
+/// 读取打印机的端口、驱动等信息，`name` 为系统中的打印机名称。短时间内重复读取时使用缓存
+pub fn printer_info(name: &OsStr) -> windows::core::Result<PrinterInfo> {
+  {
+    let cache = INFO_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+
+    if let Some((time, info)) = cache.get(name) {
+      if time.elapsed() < INFO_TTL {
+        return Ok(info.clone());
+      }
+    }
+  }
+
+  let info = with_printer_info(name, |info| unsafe {
+    PrinterInfo {
+      port: info.pPortName.to_string().unwrap_or_default(),
+      driver: info.pDriverName.to_string().unwrap_or_default(),
+      location: info.pLocation.to_string().unwrap_or_default(),
+      comment: info.pComment.to_string().unwrap_or_default(),
+      shared: info.Attributes & PRINTER_ATTRIBUTE_SHARED != 0,
+      share_name: info.pShareName.to_string().unwrap_or_default(),
+      datatypes: print_processor_datatypes(
+        PCWSTR(info.pServerName.0),
+        PCWSTR(info.pPrintProcessor.0),
+      )
+      .unwrap_or_default(),
+    }
+  })?;
+  INFO_CACHE
+    .lock()
+    .unwrap_or_else(|e| e.into_inner())
+    .insert(name.to_os_string(), (Instant::now(), info.clone()));
+
+  Ok(info)
+}
+
+/// 打印处理器接受的数据类型，`server` 为空时为本机
+fn print_processor_datatypes(
+  server: PCWSTR,
+  processor: PCWSTR,
+) -> windows::core::Result<Vec<String>> {
+  let mut needed = 0;
+  let mut returned = 0;
+
+  // 第一次调用只获取需要的缓冲区大小
+  let _ =
+    unsafe { EnumPrintProcessorDatatypesW(server, processor, 1, None, &mut needed, &mut returned) };
+
+  // DATATYPES_INFO_1W 包含指针，缓冲区需要按 8 字节对齐
+  let mut buf = vec![0u64; (needed as usize).div_ceil(8)];
+  let bytes =
+    unsafe { std::slice::from_raw_parts_mut(buf.as_mut_ptr().cast::<u8>(), buf.len() * 8) };
+  unsafe {
+    EnumPrintProcessorDatatypesW(
+      server,
+      processor,
+      1,
+      Some(bytes),
+      &mut needed,
+      &mut returned,
+    )
+    .ok()?
+  };
+
+  let infos = unsafe {
+    std::slice::from_raw_parts(buf.as_ptr().cast::<DATATYPES_INFO_1W>(), returned as usize)
+  };
+  Ok(
+    infos
+      .iter()
+      .filter_map(|info| unsafe { info.pName.to_string().ok() })
+      .collect(),
+  )
+}
+
+/// 读取打印机的 PRINTER_INFO_2W，在缓冲区释放前调用 `f`
+fn with_printer_info<T>(
+  name: &OsStr,
+  f: impl FnOnce(&PRINTER_INFO_2W) -> T,
+) -> windows::core::Result<T> {
+  let printer = PrinterHandle::open(name, None)?;
+  let mut needed = 0;
+
+  // 第一次调用只获取需要的缓冲区大小
+  let _ = unsafe { GetPrinterW(printer.0, 2, None, &mut needed) };
+
+  // PRINTER_INFO_2W 包含指针，缓冲区需要按 8 字节对齐
+  let mut buf = vec![0u64; (needed as usize).div_ceil(8)];
+  let bytes =
+    unsafe { std::slice::from_raw_parts_mut(buf.as_mut_ptr().cast::<u8>(), buf.len() * 8) };
+
+  unsafe { GetPrinterW(printer.0, 2, Some(bytes), &mut needed)? };
+
+  let info = unsafe { &*buf.as_ptr().cast::<PRINTER_INFO_2W>() };
+  Ok(f(info))
+}
+
+/// 后台处理程序服务的状态
+#[derive(Debug, Clone, Copy)]
+pub struct ServiceStatus {
+  /// `SERVICE_*` 状态
+  pub state: u32,
+  /// 服务进程的启动时间，未运行或无法读取时为空
+  pub started_at: Option<DateTime<Utc>>,
+}
+
+impl ServiceStatus {
+  pub fn is_running(&self) -> bool {
+    self.state == SERVICE_RUNNING.0
+  }
+
+  /// 状态名称
+  pub fn state_name(&self) -> &'static str {
+    match SERVICE_STATUS_CURRENT_STATE(self.state) {
+      SERVICE_STOPPED => "stopped",
+      SERVICE_START_PENDING => "start_pending",
+      SERVICE_STOP_PENDING => "stop_pending",
+      SERVICE_RUNNING => "running",
+      SERVICE_CONTINUE_PENDING => "continue_pending",
+      SERVICE_PAUSE_PENDING => "pause_pending",
+      SERVICE_PAUSED => "paused",
+      _ => "unknown",
+    }
+  }
+}
+
+/// 等待服务状态变化时的查询间隔
+const SERVICE_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// 读取后台处理程序服务的状态
+pub fn service_status() -> windows::core::Result<ServiceStatus> {
+  let service = ServiceHandle::open_spooler(SERVICE_QUERY_STATUS)?;
+  service.status()
+}
+
+/// 重启后台处理程序服务，等待服务重新运行，超过 `timeout` 时返回超时错误。需要管理员权限
+pub fn restart_service(timeout: Duration) -> windows::core::Result<ServiceStatus> {
+  let service = ServiceHandle::open_spooler(SERVICE_STOP | SERVICE_START | SERVICE_QUERY_STATUS)?;
+  let deadline = Instant::now() + timeout;
+
+  if service.status()?.state != SERVICE_STOPPED.0 {
+    let mut status = SERVICE_STATUS::default();
+    unsafe { ControlService(service.0, SERVICE_CONTROL_STOP, &mut status)? };
+    service.wait_for(SERVICE_STOPPED, deadline)?;
+  }
+
+  unsafe { StartServiceW(service.0, None)? };
+  let status = service.wait_for(SERVICE_RUNNING, deadline)?;
+
+  // 打印机句柄及状态都已失效
+  STATUS_CACHE
+    .lock()
+    .unwrap_or_else(|e| e.into_inner())
+    .clear();
+  INFO_CACHE.lock().unwrap_or_else(|e| e.into_inner()).clear();
+  Ok(status)
+}
+
+/// 是否为缺少权限的错误
+pub fn is_access_denied(e: &windows::core::Error) -> bool {
+  e.code().0 as u32 == 0x8007_0005
+}
+
+/// 服务句柄，释放时关闭
+struct ServiceHandle(SC_HANDLE);
+
+impl ServiceHandle {
+  fn open_spooler(access: u32) -> windows::core::Result<Self> {
+    let manager = Self(unsafe { OpenSCManagerW(None, None, SC_MANAGER_CONNECT)? });
+    let service = unsafe { OpenServiceW(manager.0, w!("Spooler"), access)? };
+    Ok(Self(service))
+  }
+
+  fn status(&self) -> windows::core::Result<ServiceStatus> {
+    let mut buf = [0u8; size_of::<SERVICE_STATUS_PROCESS>()];
+    let mut needed = 0;
+
+    unsafe { QueryServiceStatusEx(self.0, SC_STATUS_PROCESS_INFO, Some(&mut buf), &mut needed)? };
+
+    let info = unsafe {
+      buf
+        .as_ptr()
+        .cast::<SERVICE_STATUS_PROCESS>()
+        .read_unaligned()
+    };
+    let started_at = (info.dwCurrentState == SERVICE_RUNNING)
+      .then(|| process_started_at(info.dwProcessId))
+      .flatten();
+
+    Ok(ServiceStatus {
+      state: info.dwCurrentState.0,
+      started_at,
+    })
+  }
+
+  /// 等待服务进入 `state`
+  fn wait_for(
+    &self,
+    state: SERVICE_STATUS_CURRENT_STATE,
+    deadline: Instant,
+  ) -> windows::core::Result<ServiceStatus> {
+    loop {
+      let status = self.status()?;
+
+      if status.state == state.0 {
+        return Ok(status);
+      }
+
+      if Instant::now() >= deadline {
+        return Err(ERROR_SERVICE_REQUEST_TIMEOUT.to_hresult().into());
+      }
+
+      thread::sleep(SERVICE_POLL_INTERVAL);
+    }
+  }
+}
+
+impl Drop for ServiceHandle {
+  fn drop(&mut self) {
+    unsafe {
+      let _ = CloseServiceHandle(self.0);
+    }
+  }
+}
+
+/// 进程的启动时间
+fn process_started_at(pid: u32) -> Option<DateTime<Utc>> {
+  let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid).ok()? };
+  let (mut created, mut exited, mut kernel, mut user) = Default::default();
+  let result =
+    unsafe { GetProcessTimes(process, &mut created, &mut exited, &mut kernel, &mut user) };
+
+  unsafe {
+    let _ = CloseHandle(process);
+  }
+
+  result.ok()?;
+  filetime_to_datetime(created)
+}
+
+/// FILETIME 为从 1601 年起的 100 纳秒数
+fn filetime_to_datetime(time: FILETIME) -> Option<DateTime<Utc>> {
+  let ticks = ((time.dwHighDateTime as i64) << 32) | time.dwLowDateTime as i64;
+  let secs = ticks / 10_000_000 - 11_644_473_600;
+  let nanos = (ticks % 10_000_000) as u32 * 100;
+  DateTime::from_timestamp(secs, nanos)
+}
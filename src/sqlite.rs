@@ -0,0 +1,225 @@
+use std::{
+  ffi::{c_char, c_int, c_void, CStr, CString},
+  path::Path,
+  ptr,
+};
+
+use anyhow::{anyhow, bail};
+
+#[repr(C)]
+struct Sqlite3 {
+  _private: [u8; 0],
+}
+
+#[repr(C)]
+struct Sqlite3Stmt {
+  _private: [u8; 0],
+}
+
+const SQLITE_OK: c_int = 0;
+const SQLITE_ROW: c_int = 100;
+const SQLITE_DONE: c_int = 101;
+const SQLITE_OPEN_READWRITE: c_int = 0x2;
+const SQLITE_OPEN_CREATE: c_int = 0x4;
+const SQLITE_OPEN_FULLMUTEX: c_int = 0x10000;
+/// 绑定参数时让 SQLite 复制内容
+const SQLITE_TRANSIENT: isize = -1;
+
+/// 等待其他连接释放锁的时间（毫秒）
+const BUSY_TIMEOUT: c_int = 5000;
+
+#[link(name = "winsqlite3", kind = "raw-dylib")]
+extern "system" {
+  fn sqlite3_open_v2(
+    filename: *const c_char,
+    db: *mut *mut Sqlite3,
+    flags: c_int,
+    vfs: *const c_char,
+  ) -> c_int;
+  fn sqlite3_close(db: *mut Sqlite3) -> c_int;
+  fn sqlite3_errmsg(db: *mut Sqlite3) -> *const c_char;
+  fn sqlite3_busy_timeout(db: *mut Sqlite3, ms: c_int) -> c_int;
+  fn sqlite3_prepare_v2(
+    db: *mut Sqlite3,
+    sql: *const c_char,
+    bytes: c_int,
+    stmt: *mut *mut Sqlite3Stmt,
+    tail: *mut *const c_char,
+  ) -> c_int;
+  fn sqlite3_bind_text(
+    stmt: *mut Sqlite3Stmt,
+    index: c_int,
+    text: *const c_char,
+    bytes: c_int,
+    destructor: isize,
+  ) -> c_int;
+  fn sqlite3_step(stmt: *mut Sqlite3Stmt) -> c_int;
+  fn sqlite3_column_count(stmt: *mut Sqlite3Stmt) -> c_int;
+  fn sqlite3_column_text(stmt: *mut Sqlite3Stmt, column: c_int) -> *const c_void;
+  fn sqlite3_column_bytes(stmt: *mut Sqlite3Stmt, column: c_int) -> c_int;
+  fn sqlite3_finalize(stmt: *mut Sqlite3Stmt) -> c_int;
+}
+
+/// 系统自带的 SQLite（`winsqlite3.dll`，Windows 10 起提供）的数据库连接，释放时关闭。
+/// 只支持文本参数及文本列
+pub struct Connection(*mut Sqlite3);
+
+// 以 SQLITE_OPEN_FULLMUTEX 打开，连接可以在线程间使用
+unsafe impl Send for Connection {}
+
+impl Connection {
+  /// 打开数据库文件，不存在时创建
+  pub fn open(path: &Path) -> anyhow::Result<Self> {
+    let filename = path
+      .to_str()
+      .ok_or_else(|| anyhow!("Invalid database path {}", path.display()))?;
+    let filename = CString::new(filename)?;
+    let mut db = ptr::null_mut();
+    let flags = SQLITE_OPEN_READWRITE | SQLITE_OPEN_CREATE | SQLITE_OPEN_FULLMUTEX;
+    let rc = unsafe { sqlite3_open_v2(filename.as_ptr(), &mut db, flags, ptr::null()) };
+    // 打开失败时也可能分配了连接，需要关闭
+    let connection = Self(db);
+    if rc != SQLITE_OK {
+      bail!(
+        "Failed to open {}: {}",
+        path.display(),
+        connection.error_message()
+      );
+    }
+
+    unsafe { sqlite3_busy_timeout(connection.0, BUSY_TIMEOUT) };
+    Ok(connection)
+  }
+
+  /// 执行一条语句，忽略返回的行
+  pub fn execute(&self, sql: &str, params: &[&str]) -> anyhow::Result<()> {
+    self.run(sql, params, |_| ())
+  }
+
+  /// 执行一条查询，返回各行的文本列，`NULL` 为空字符串
+  pub fn query(&self, sql: &str, params: &[&str]) -> anyhow::Result<Vec<Vec<String>>> {
+    let mut rows = Vec::new();
+    self.run(sql, params, |row| rows.push(row))?;
+    Ok(rows)
+  }
+
+  fn run(
+    &self,
+    sql: &str,
+    params: &[&str],
+    mut on_row: impl FnMut(Vec<String>),
+  ) -> anyhow::Result<()> {
+    let statement = Statement::prepare(self, sql)?;
+    for (i, param) in params.iter().enumerate() {
+      let rc = unsafe {
+        sqlite3_bind_text(
+          statement.0,
+          i as c_int + 1,
+          param.as_ptr().cast(),
+          param.len() as c_int,
+          SQLITE_TRANSIENT,
+        )
+      };
+      self.check(rc)?;
+    }
+
+    loop {
+      match unsafe { sqlite3_step(statement.0) } {
+        SQLITE_ROW => on_row(statement.row()),
+        SQLITE_DONE => return Ok(()),
+        rc => return self.check(rc),
+      }
+    }
+  }
+
+  fn check(&self, rc: c_int) -> anyhow::Result<()> {
+    match rc {
+      SQLITE_OK => Ok(()),
+      rc => bail!("SQLite error {}: {}", rc, self.error_message()),
+    }
+  }
+
+  fn error_message(&self) -> String {
+    if self.0.is_null() {
+      return "out of memory".to_string();
+    }
+    unsafe { CStr::from_ptr(sqlite3_errmsg(self.0)) }
+      .to_string_lossy()
+      .into_owned()
+  }
+}
+
+impl Drop for Connection {
+  fn drop(&mut self) {
+    unsafe { sqlite3_close(self.0) };
+  }
+}
+
+/// 编译后的语句，释放时销毁
+struct Statement(*mut Sqlite3Stmt);
+
+impl Statement {
+  fn prepare(connection: &Connection, sql: &str) -> anyhow::Result<Self> {
+    let mut stmt = ptr::null_mut();
+    let rc = unsafe {
+      sqlite3_prepare_v2(
+        connection.0,
+        sql.as_ptr().cast(),
+        sql.len() as c_int,
+        &mut stmt,
+        ptr::null_mut(),
+      )
+    };
+    let statement = Self(stmt);
+    connection.check(rc)?;
+    if statement.0.is_null() {
+      bail!("Empty SQL statement");
+    }
+    Ok(statement)
+  }
+
+  fn row(&self) -> Vec<String> {
+    let count = unsafe { sqlite3_column_count(self.0) };
+    (0..count)
+      .map(|i| unsafe {
+        let text = sqlite3_column_text(self.0, i);
+        if text.is_null() {
+          return String::new();
+        }
+        let len = sqlite3_column_bytes(self.0, i) as usize;
+        String::from_utf8_lossy(std::slice::from_raw_parts(text.cast::<u8>(), len)).into_owned()
+      })
+      .collect()
+  }
+}
+
+impl Drop for Statement {
+  fn drop(&mut self) {
+    unsafe { sqlite3_finalize(self.0) };
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn stores_and_queries_text() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("test.db");
+    let db = Connection::open(&path).unwrap();
+    db.execute("CREATE TABLE t (k TEXT PRIMARY KEY, v TEXT)", &[])
+      .unwrap();
+    db.execute("INSERT INTO t VALUES (?, ?)", &["a", "甲"])
+      .unwrap();
+    db.execute("INSERT INTO t (k) VALUES (?)", &["b"]).unwrap();
+    drop(db);
+
+    let db = Connection::open(&path).unwrap();
+    let rows = db.query("SELECT k, v FROM t ORDER BY k", &[]).unwrap();
+    assert_eq!(rows, vec![vec!["a", "甲"], vec!["b", ""]]);
+    assert!(db
+      .execute("INSERT INTO t VALUES (?, ?)", &["a", "x"])
+      .is_err());
+  }
+}
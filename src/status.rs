@@ -0,0 +1,260 @@
+use std::{
+  collections::{HashMap, HashSet, VecDeque},
+  fs::{create_dir_all, read_to_string, remove_file},
+  path::{Path, PathBuf},
+  sync::{
+    atomic::{AtomicU64, Ordering},
+    LazyLock, Mutex, MutexGuard,
+  },
+  time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+
+use crate::{instance, storage::write_atomically};
+
+/// 暂停接受打印任务的状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pause {
+  pub paused_at: DateTime<Utc>,
+  /// 返回给客户端的说明，如维护的原因
+  pub message: Option<String>,
+  /// 自动恢复的时间，为空时需手动恢复
+  pub resume_at: Option<DateTime<Utc>>,
+}
+
+/// 暂停接受打印任务的状态，保存在数据目录中，重启服务后仍然暂停
+static PAUSE: LazyLock<Mutex<Option<Pause>>> = LazyLock::new(|| {
+  let pause = pause_filepath()
+    .and_then(|path| read_to_string(path).ok())
+    .and_then(|json| {
+      serde_json::from_str::<Pause>(&json)
+        .inspect_err(|e| error!("Failed to load the pause state: {}", e))
+        .ok()
+    });
+  if pause.is_some() {
+    info!("Accepting jobs stays paused after the restart");
+  }
+  Mutex::new(pause)
+});
+/// 暂停提交任务的打印机，保存在数据目录中，重启服务后仍然暂停
+static PAUSED_PRINTERS: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(|| {
+  let printers = paused_printers_filepath()
+    .map(|path| load_paused_printers(&path))
+    .unwrap_or_default();
+  if !printers.is_empty() {
+    info!(
+      "Submitting jobs to {} printers stays paused after the restart",
+      printers.len()
+    );
+  }
+  Mutex::new(printers)
+});
+/// 打印成功的任务数
+static PRINTED: AtomicU64 = AtomicU64::new(0);
+/// 打印失败的任务数
+static FAILED: AtomicU64 = AtomicU64::new(0);
+/// 各打印机最近打印任务的耗时，用于估算排队任务的开始时间，重启服务后清空
+static DURATIONS: LazyLock<Mutex<HashMap<String, VecDeque<Duration>>>> =
+  LazyLock::new(Default::default);
+
+fn pause_filepath() -> Option<PathBuf> {
+  instance::data_dir().map(|dir| dir.join("pause.json"))
+}
+
+fn paused_printers_filepath() -> Option<PathBuf> {
+  instance::data_dir().map(|dir| dir.join("paused-printers.json"))
+}
+
+fn load_paused_printers(path: &Path) -> HashSet<String> {
+  let Ok(json) = read_to_string(path) else {
+    return HashSet::new();
+  };
+  serde_json::from_str(&json)
+    .inspect_err(|e| error!("Failed to load the paused printers: {}", e))
+    .unwrap_or_default()
+}
+
+/// 保存暂停的打印机，全部恢复时删除
+fn save_paused_printers(path: &Path, printers: &HashSet<String>) -> anyhow::Result<()> {
+  if printers.is_empty() {
+    return match remove_file(path) {
+      Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+      _ => Ok(()),
+    };
+  }
+
+  let mut printers: Vec<_> = printers.iter().collect();
+  printers.sort();
+  create_dir_all(path.parent().unwrap_or(path))?;
+  write_atomically(path, serde_json::to_string(&printers)?)?;
+  Ok(())
+}
+
+/// 加载保存的暂停状态，在开始调度排队的任务前调用，以免重启后先打印暂停的打印机的任务
+pub fn restore() {
+  LazyLock::force(&PAUSE);
+  LazyLock::force(&PAUSED_PRINTERS);
+}
+
+fn pause_lock() -> MutexGuard<'static, Option<Pause>> {
+  PAUSE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 保存暂停状态，恢复时删除。失败时只记录错误，暂停仍然生效
+fn save_pause(pause: Option<&Pause>) {
+  let Some(path) = pause_filepath() else {
+    return;
+  };
+  let saved = match pause {
+    Some(pause) => (|| {
+      create_dir_all(path.parent().unwrap_or(&path))?;
+      write_atomically(&path, serde_json::to_string(pause)?)?;
+      anyhow::Ok(())
+    })(),
+    None => match remove_file(&path) {
+      Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+      _ => Ok(()),
+    },
+  };
+  if let Err(e) = saved {
+    error!("Failed to save the pause state: {:#}", e);
+  }
+}
+
+/// 是否暂停接受打印任务，暂停时只读接口仍然可用
+pub fn is_paused() -> bool {
+  pause().is_some()
+}
+
+/// 暂停接受打印任务的状态，到达自动恢复的时间后恢复
+pub fn pause() -> Option<Pause> {
+  let mut pause = pause_lock();
+  if pause
+    .as_ref()
+    .and_then(|p| p.resume_at)
+    .is_some_and(|at| at <= Utc::now())
+  {
+    info!("Accepting jobs is resumed as scheduled");
+    *pause = None;
+    save_pause(None);
+  }
+  pause.clone()
+}
+
+/// 暂停接受打印任务，已暂停时更新说明及自动恢复的时间
+pub fn set_pause(message: Option<String>, resume_at: Option<DateTime<Utc>>) -> Pause {
+  let mut pause = pause_lock();
+  let state = Pause {
+    paused_at: pause.as_ref().map_or_else(Utc::now, |p| p.paused_at),
+    message,
+    resume_at,
+  };
+  save_pause(Some(&state));
+  *pause = Some(state.clone());
+  state
+}
+
+/// 暂停或恢复接受打印任务
+pub fn set_paused(paused: bool) {
+  if paused {
+    set_pause(None, None);
+  } else {
+    *pause_lock() = None;
+    save_pause(None);
+  }
+}
+
+/// 是否暂停向打印机提交任务，暂停时新任务在服务内排队
+pub fn is_printer_paused(printer: &str) -> bool {
+  PAUSED_PRINTERS
+    .lock()
+    .unwrap_or_else(|e| e.into_inner())
+    .contains(printer)
+}
+
+/// 暂停或恢复向打印机提交任务。保存失败时只记录错误，暂停仍然生效
+pub fn set_printer_paused(printer: &str, paused: bool) {
+  let mut printers = PAUSED_PRINTERS.lock().unwrap_or_else(|e| e.into_inner());
+
+  let changed = if paused {
+    printers.insert(printer.to_string())
+  } else {
+    printers.remove(printer)
+  };
+  if changed {
+    if let Some(path) = paused_printers_filepath() {
+      if let Err(e) = save_paused_printers(&path, &printers) {
+        error!("Failed to save the paused printers: {:#}", e);
+      }
+    }
+  }
+}
+
+/// 记录一个打印任务的结果
+pub fn record(success: bool) {
+  let counter = if success { &PRINTED } else { &FAILED };
+  counter.fetch_add(1, Ordering::Relaxed);
+}
+
+/// 获取打印成功及失败的任务数
+pub fn counters() -> (u64, u64) {
+  (
+    PRINTED.load(Ordering::Relaxed),
+    FAILED.load(Ordering::Relaxed),
+  )
+}
+
+/// 记录打印机打印一个任务的耗时，每台打印机只保留最近 `window` 个
+pub fn record_duration(printer: &str, duration: Duration, window: usize) {
+  if window == 0 {
+    return;
+  }
+
+  let mut durations = DURATIONS.lock().unwrap_or_else(|e| e.into_inner());
+  let samples = durations.entry(printer.to_string()).or_default();
+  while samples.len() >= window {
+    samples.pop_front();
+  }
+  samples.push_back(duration);
+}
+
+/// 打印机最近打印任务的平均耗时，记录少于 `min_samples` 个时为空
+pub fn average_duration(printer: &str, min_samples: usize) -> Option<Duration> {
+  let durations = DURATIONS.lock().unwrap_or_else(|e| e.into_inner());
+  let samples = durations.get(printer)?;
+  if samples.is_empty() || samples.len() < min_samples {
+    return None;
+  }
+  Some(samples.iter().sum::<Duration>() / samples.len() as u32)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn keeps_paused_printers_after_a_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("paused-printers.json");
+    let printers = HashSet::from(["Label".to_string(), "Office".to_string()]);
+
+    save_paused_printers(&path, &printers).unwrap();
+    assert_eq!(load_paused_printers(&path), printers);
+
+    save_paused_printers(&path, &HashSet::new()).unwrap();
+    assert!(!path.exists());
+    assert!(load_paused_printers(&path).is_empty());
+  }
+
+  #[test]
+  fn ignores_invalid_paused_printers() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("paused-printers.json");
+    std::fs::write(&path, "{").unwrap();
+
+    assert!(load_paused_printers(&path).is_empty());
+  }
+}
@@ -0,0 +1,205 @@
+use std::{
+  io::{self, Write},
+  path::Path,
+};
+
+use icu_normalizer::ComposingNormalizer;
+use sha2::{Digest, Sha256};
+use tempfile::NamedTempFile;
+
+/// 编码后文件名的最大长度（字节），为扩展名及所在目录的路径留出余量
+const MAX_FILE_STEM_BYTES: usize = 120;
+
+/// 截断的文件名后附加的哈希长度（十六进制字符）
+const HASH_CHARS: usize = 16;
+
+/// Windows 保留的设备名，不区分大小写，带扩展名时也不能使用
+const RESERVED_NAMES: &[&str] = &[
+  "CON", "PRN", "AUX", "NUL", "CONIN$", "CONOUT$", "COM0", "COM1", "COM2", "COM3", "COM4", "COM5",
+  "COM6", "COM7", "COM8", "COM9", "COM¹", "COM²", "COM³", "LPT0", "LPT1", "LPT2", "LPT3", "LPT4",
+  "LPT5", "LPT6", "LPT7", "LPT8", "LPT9", "LPT¹", "LPT²", "LPT³",
+];
+
+/// 将打印机名称等任意名称编码为文件名（不含扩展名），名称到文件的映射都应使用此函数。
+///
+/// 先规范化为 NFC，组合字符的不同写法对应同一个文件；再将文件名中不能使用的字符、`%`、`~`
+/// 及控制字符编码为 `%XX`，不同的名称不会得到相同的文件名，如 `a/b` 为 `a%2Fb`，`a_b` 不变。
+/// 结尾的点和空格、`.` 和 `..` 以及 `CON`、`NUL`、`COM1` 等设备名的首字符也会被编码。
+/// 超过长度限制时截断，并附加 `~` 和完整名称的哈希
+pub fn file_stem(name: &str) -> String {
+  let name = ComposingNormalizer::new_nfc().normalize(name);
+  let mut stem = String::with_capacity(name.len());
+
+  for c in name.chars() {
+    match c {
+      '\\' | '/' | ':' | '*' | '?' | '"' | '<' | '>' | '|' | '%' | '~' => {
+        push_encoded(&mut stem, c)
+      }
+      c if c.is_control() => push_encoded(&mut stem, c),
+      c => stem.push(c),
+    }
+  }
+
+  // 结尾的点和空格会被 Windows 去掉，`.`、`..` 也因此全部编码
+  let kept = stem.trim_end_matches(['.', ' ']).len();
+  let trailing = stem.split_off(kept);
+  for c in trailing.chars() {
+    push_encoded(&mut stem, c);
+  }
+
+  if is_reserved_name(&stem) {
+    let first = stem.remove(0);
+    let mut encoded = String::new();
+    push_encoded(&mut encoded, first);
+    stem.insert_str(0, &encoded);
+  }
+
+  if stem.is_empty() {
+    return "%".to_string();
+  }
+
+  if stem.len() > MAX_FILE_STEM_BYTES {
+    let hash = Sha256::digest(name.as_bytes());
+    let hash = hash
+      .iter()
+      .map(|b| format!("{:02x}", b))
+      .collect::<String>();
+    // 在字符或 `%XX` 的边界截断，原有的 `%` 都已编码，`%` 一定是 `%XX` 的开始
+    let limit = MAX_FILE_STEM_BYTES - HASH_CHARS - 1;
+    let mut end = 0;
+    while let Some(c) = stem[end..].chars().next() {
+      let len = if c == '%' { 3 } else { c.len_utf8() };
+      if end + len > limit {
+        break;
+      }
+      end += len;
+    }
+    stem.truncate(end);
+    return format!("{}~{}", stem, &hash[..HASH_CHARS]);
+  }
+
+  stem
+}
+
+/// 是否为 Windows 保留的设备名，如 `CON`、`nul.txt`
+pub fn is_reserved_name(name: &str) -> bool {
+  let device = name.split('.').next().unwrap_or_default().trim_end();
+  RESERVED_NAMES
+    .iter()
+    .any(|r| r.eq_ignore_ascii_case(device))
+}
+
+/// 先写入同一目录中的临时文件再替换 `path`，读取时不会得到写入一半的文件。目录需已存在
+pub fn write_atomically(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+  let dir = match path.parent() {
+    Some(dir) if !dir.as_os_str().is_empty() => dir,
+    _ => Path::new("."),
+  };
+  let mut file = NamedTempFile::new_in(dir)?;
+  file.write_all(contents.as_ref())?;
+  file.persist(path)?;
+  Ok(())
+}
+
+fn push_encoded(stem: &mut String, c: char) {
+  let mut buf = [0; 4];
+  for b in c.encode_utf8(&mut buf).bytes() {
+    stem.push_str(&format!("%{:02X}", b));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn keeps_ordinary_names() {
+    assert_eq!(file_stem("HP LaserJet M404"), "HP LaserJet M404");
+    assert_eq!(file_stem("标签打印机"), "标签打印机");
+    assert_eq!(file_stem("Zebra_ZD420 (300 dpi)"), "Zebra_ZD420 (300 dpi)");
+  }
+
+  #[test]
+  fn encodes_path_traversal() {
+    assert_eq!(file_stem(".."), "%2E%2E");
+    assert_eq!(file_stem("."), "%2E");
+    assert_eq!(file_stem("../etc/passwd"), "..%2Fetc%2Fpasswd");
+    assert_eq!(file_stem(r"..\..\Windows"), "..%5C..%5CWindows");
+    assert_eq!(file_stem(r"\\server\printer"), "%5C%5Cserver%5Cprinter");
+    assert_eq!(file_stem("C:printer"), "C%3Aprinter");
+  }
+
+  #[test]
+  fn encodes_reserved_device_names() {
+    assert_eq!(file_stem("CON"), "%43ON");
+    assert_eq!(file_stem("nul"), "%6Eul");
+    assert_eq!(file_stem("COM1"), "%43OM1");
+    assert_eq!(file_stem("LPT9.txt"), "%4CPT9.txt");
+    assert_eq!(file_stem("CON "), "CON%20");
+    assert_eq!(file_stem("CONSOLE"), "CONSOLE");
+    assert_eq!(file_stem("COM10"), "COM10");
+  }
+
+  #[test]
+  fn encodes_trailing_dots_and_spaces() {
+    assert_eq!(file_stem("printer."), "printer%2E");
+    assert_eq!(file_stem("printer. ."), "printer%2E%20%2E");
+    assert_eq!(file_stem("printer "), "printer%20");
+    assert_eq!(file_stem(" printer.v2"), " printer.v2");
+  }
+
+  #[test]
+  fn different_names_do_not_collide() {
+    let names = ["a/b", "a_b", "a%2Fb", "a:b", "a~b", "", "%", "a.", "a"];
+    let stems = names.map(file_stem);
+
+    for (i, a) in stems.iter().enumerate() {
+      for b in &stems[i + 1..] {
+        assert_ne!(a, b);
+      }
+    }
+  }
+
+  #[test]
+  fn normalizes_unicode() {
+    // 预组合的 é 和 e 加组合重音符
+    assert_eq!(file_stem("Caf\u{e9}"), file_stem("Cafe\u{301}"));
+    assert_eq!(file_stem("Cafe\u{301}"), "Caf\u{e9}");
+  }
+
+  #[test]
+  fn encodes_control_characters() {
+    assert_eq!(file_stem("a\tb\n"), "a%09b%0A");
+  }
+
+  #[test]
+  fn truncates_long_names_with_hash() {
+    let long = "x".repeat(300);
+    let stem = file_stem(&long);
+    assert_eq!(stem.len(), MAX_FILE_STEM_BYTES);
+    assert!(stem.starts_with("xxx"));
+    assert_ne!(stem, file_stem(&"x".repeat(301)));
+
+    // 不在 `%XX` 或多字节字符中间截断
+    let stem = file_stem(&"/".repeat(100));
+    let (head, _) = stem.split_once('~').unwrap();
+    assert!(head.len() % 3 == 0 && head.chars().all(|c| c == '%' || c == '2' || c == 'F'));
+    assert!(file_stem(&"打".repeat(100)).len() <= MAX_FILE_STEM_BYTES);
+  }
+
+  #[test]
+  fn replaces_files_atomically() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("job.json");
+
+    write_atomically(&path, "first").unwrap();
+    write_atomically(&path, b"second").unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "second");
+
+    // 临时文件已重命名，目录中只有目标文件
+    let files = std::fs::read_dir(dir.path()).unwrap().count();
+    assert_eq!(files, 1);
+
+    assert!(write_atomically(&dir.path().join("missing/job.json"), "").is_err());
+  }
+}
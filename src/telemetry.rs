@@ -0,0 +1,114 @@
+use std::{env, process, sync::Arc};
+
+use log::warn;
+use opentelemetry::{
+  global, metrics::MeterProvider as _, propagation::Extractor, trace::TracerProvider as _, KeyValue,
+};
+use opentelemetry_otlp::{MetricExporter, SpanExporter};
+use opentelemetry_sdk::{
+  metrics::SdkMeterProvider,
+  propagation::TraceContextPropagator,
+  trace::{SdkTracer, SdkTracerProvider},
+  Resource,
+};
+use poem::{http::HeaderMap, Endpoint, IntoResponse, Request, Response};
+use tracing::Subscriber;
+use tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::{instance, status};
+
+/// 上报的服务名称
+const SERVICE_NAME: &str = "direct-printing";
+
+/// 设置任一环境变量时启用 OTLP 导出，导出的地址等也由标准的 `OTEL_*` 环境变量配置
+const ENDPOINT_VARS: &[&str] = &[
+  "OTEL_EXPORTER_OTLP_ENDPOINT",
+  "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT",
+  "OTEL_EXPORTER_OTLP_METRICS_ENDPOINT",
+];
+
+/// OTLP 导出，需要保留到程序结束，以便发送剩余的数据
+pub struct Telemetry {
+  tracer: SdkTracerProvider,
+  meter: SdkMeterProvider,
+}
+
+impl Drop for Telemetry {
+  fn drop(&mut self) {
+    if let Err(e) = self.tracer.force_flush() {
+      eprintln!("Failed to flush traces: {}", e);
+    }
+    let _ = self.tracer.shutdown();
+    let _ = self.meter.shutdown();
+  }
+}
+
+/// 初始化 OTLP 导出，未配置导出地址或设置了 `OTEL_SDK_DISABLED=true` 时为空。
+/// 返回的层将 tracing 的 span 导出为 trace，打印任务数作为指标定期导出
+pub fn init<S>() -> anyhow::Result<Option<(Telemetry, OpenTelemetryLayer<S, SdkTracer>)>>
+where
+  S: Subscriber + for<'span> LookupSpan<'span>,
+{
+  let disabled = env::var("OTEL_SDK_DISABLED").is_ok_and(|v| v.eq_ignore_ascii_case("true"));
+  if disabled || !ENDPOINT_VARS.iter().any(|var| env::var_os(var).is_some()) {
+    return Ok(None);
+  }
+
+  // `OTEL_RESOURCE_ATTRIBUTES` 中的属性优先。实例 ID 与 `GET /info` 及回执中的相同
+  let resource = Resource::builder()
+    .with_service_name(SERVICE_NAME)
+    .with_attribute(KeyValue::new("service.instance.id", instance::id()))
+    .with_attribute(KeyValue::new("service.version", env!("CARGO_PKG_VERSION")))
+    .with_attribute(KeyValue::new("host.name", instance::hostname()))
+    .with_attribute(KeyValue::new("process.pid", process::id() as i64))
+    .build();
+
+  let tracer = SdkTracerProvider::builder()
+    .with_batch_exporter(SpanExporter::builder().with_http().build()?)
+    .with_resource(resource.clone())
+    .build();
+  global::set_text_map_propagator(TraceContextPropagator::new());
+
+  let meter = SdkMeterProvider::builder()
+    .with_periodic_exporter(MetricExporter::builder().with_http().build()?)
+    .with_resource(resource)
+    .build();
+  meter
+    .meter(SERVICE_NAME)
+    .u64_observable_counter("direct_printing.jobs")
+    .with_description("Print jobs handed to the spooler, by outcome")
+    .with_callback(|observer| {
+      let (printed, failed) = status::counters();
+      observer.observe(printed, &[KeyValue::new("outcome", "printed")]);
+      observer.observe(failed, &[KeyValue::new("outcome", "failed")]);
+    })
+    .build();
+
+  let layer = tracing_opentelemetry::layer().with_tracer(tracer.tracer(SERVICE_NAME));
+  Ok(Some((Telemetry { tracer, meter }, layer)))
+}
+
+/// 从请求头读取 W3C Trace Context
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+  fn get(&self, key: &str) -> Option<&str> {
+    self.0.get(key).and_then(|v| v.to_str().ok())
+  }
+
+  fn keys(&self) -> Vec<&str> {
+    self.0.keys().map(|k| k.as_str()).collect()
+  }
+}
+
+/// 将请求的 span 关联到请求头 `traceparent` 中的 trace，需要在 `Tracing` 中间件内使用
+pub async fn propagate<E: Endpoint>(ep: Arc<E>, req: Request) -> poem::Result<Response> {
+  let parent = global::get_text_map_propagator(|p| p.extract(&HeaderExtractor(req.headers())));
+
+  if let Err(e) = tracing::Span::current().set_parent(parent) {
+    warn!("Cannot attach the request to the incoming trace: {}", e);
+  }
+
+  Ok(ep.call(req).await?.into_response())
+}
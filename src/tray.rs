@@ -0,0 +1,172 @@
+use std::{
+  process::Command,
+  sync::Arc,
+  thread,
+  time::{Duration, Instant},
+};
+
+use log::{error, info};
+use tao::{
+  event::Event,
+  event_loop::{ControlFlow, EventLoopBuilder},
+  platform::{run_return::EventLoopExtRunReturn, windows::EventLoopBuilderExtWindows},
+};
+use tokio::sync::Notify;
+use tray_icon::{
+  menu::{CheckMenuItem, Menu, MenuEvent, MenuItem, PredefinedMenuItem},
+  Icon, TrayIcon, TrayIconBuilder,
+};
+
+use crate::{api, status};
+
+/// 图标边长
+const ICON_SIZE: u32 = 32;
+/// 刷新提示信息的间隔
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 在独立线程中显示托盘图标，返回的 `Notify` 在用户选择退出时触发
+pub fn spawn(addr: String) -> Arc<Notify> {
+  let quit = Arc::new(Notify::new());
+  let notify = quit.clone();
+
+  thread::spawn(move || {
+    if let Err(e) = run(&addr, &notify) {
+      error!("Failed to show the tray icon: {}", e);
+    }
+  });
+
+  quit
+}
+
+fn run(addr: &str, quit: &Notify) -> anyhow::Result<()> {
+  let mut event_loop = EventLoopBuilder::new().with_any_thread(true).build();
+
+  let menu = Menu::new();
+  let docs = MenuItem::new("Open documentation", cfg!(feature = "with-ui"), None);
+  // 重启前暂停的仍然暂停
+  let mut paused = status::is_paused();
+  let pause = CheckMenuItem::new("Pause accepting jobs", true, paused, None);
+  let exit = MenuItem::new("Quit", true, None);
+  menu.append(&docs)?;
+  menu.append(&pause)?;
+  menu.append(&PredefinedMenuItem::separator())?;
+  menu.append(&exit)?;
+
+  let tray = TrayIconBuilder::new()
+    .with_menu(Box::new(menu))
+    .with_tooltip(tooltip(addr))
+    .with_icon(icon(paused)?)
+    .build()?;
+
+  event_loop.run_return(|event, _, control_flow| {
+    if *control_flow == ControlFlow::Exit {
+      return;
+    }
+    *control_flow = ControlFlow::WaitUntil(Instant::now() + REFRESH_INTERVAL);
+
+    while let Ok(event) = MenuEvent::receiver().try_recv() {
+      if event.id == docs.id() {
+        open(&format!("http://{}/", addr));
+      } else if event.id == pause.id() {
+        status::set_paused(pause.is_checked());
+        if !pause.is_checked() {
+          api::schedule::wake();
+        }
+        info!(
+          "Accepting jobs is {} from the tray",
+          if pause.is_checked() {
+            "paused"
+          } else {
+            "resumed"
+          }
+        );
+      } else if event.id == exit.id() {
+        info!("Quitting from the tray");
+        quit.notify_one();
+        *control_flow = ControlFlow::Exit;
+      }
+    }
+
+    if let Event::NewEvents(_) = event {
+      if let Err(e) = refresh(&tray, &pause, addr, &mut paused) {
+        error!("Failed to update the tray icon: {}", e);
+      }
+    }
+  });
+
+  Ok(())
+}
+
+/// 更新图标和提示信息
+fn refresh(
+  tray: &TrayIcon,
+  pause: &CheckMenuItem,
+  addr: &str,
+  paused: &mut bool,
+) -> anyhow::Result<()> {
+  // 也可以通过接口暂停或恢复，或到时自动恢复
+  if status::is_paused() != *paused {
+    *paused = status::is_paused();
+    tray.set_icon(Some(icon(*paused)?))?;
+    pause.set_checked(*paused);
+  }
+
+  tray.set_tooltip(Some(tooltip(addr)))?;
+  Ok(())
+}
+
+/// 提示信息，包括监听地址和任务数
+fn tooltip(addr: &str) -> String {
+  let (printed, failed) = status::counters();
+  let state = match status::pause() {
+    Some(pause) => {
+      let mut state = "paused".to_string();
+      if let Some(resume_at) = pause.resume_at {
+        let local = resume_at.with_timezone(&chrono::Local);
+        state.push_str(&format!(" until {}", local.format("%H:%M")));
+      }
+      if let Some(message) = pause.message {
+        // 提示信息的长度有限
+        let message = message.chars().take(40).collect::<String>();
+        state.push_str(&format!(": {}", message));
+      }
+      state
+    }
+    None => "running".to_string(),
+  };
+
+  format!(
+    "Direct Printing ({})\n{}\nPrinted: {}, failed: {}",
+    state, addr, printed, failed
+  )
+}
+
+/// 圆形图标，运行中为绿色，暂停时为红色
+fn icon(paused: bool) -> anyhow::Result<Icon> {
+  let color = if paused {
+    [0xd9, 0x30, 0x25]
+  } else {
+    [0x1e, 0x8e, 0x3e]
+  };
+  let center = (ICON_SIZE as f32 - 1.0) / 2.0;
+  let radius = ICON_SIZE as f32 / 2.0 - 1.0;
+  let mut rgba = Vec::with_capacity((ICON_SIZE * ICON_SIZE * 4) as usize);
+
+  for y in 0..ICON_SIZE {
+    for x in 0..ICON_SIZE {
+      let distance = f32::hypot(x as f32 - center, y as f32 - center);
+      let alpha = (radius + 0.5 - distance).clamp(0.0, 1.0);
+      rgba.extend(color);
+      rgba.push((alpha * 255.0) as u8);
+    }
+  }
+
+  Ok(Icon::from_rgba(rgba, ICON_SIZE, ICON_SIZE)?)
+}
+
+/// 用默认浏览器打开网址
+fn open(url: &str) {
+  if let Err(e) = Command::new("explorer").arg(url).spawn() {
+    error!("Failed to open {}: {}", url, e);
+  }
+}